@@ -1,13 +1,44 @@
+use crate::validation::{Diagnostic, ValidationOptions};
+
+/// Represents a location in the parsed input, given as a range of byte offsets and
+/// the line (starting from 1) at which it begins. Spans of nodes created by hand are empty,
+/// with the line set to 0.
+///
+/// Spans never affect comparison or hashing of the nodes containing them, so that
+/// the same protocol parsed from differently formatted inputs is still considered equal.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
+impl std::hash::Hash for Span {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
 /// Represents an identifier, which is a name used to refer to types, fields, etc.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Identifier {
     pub name: String,
+    pub span: Span,
 }
 
 impl Identifier {
     pub fn new(name: &str) -> Self {
         Identifier {
             name: name.to_string(),
+            span: Span::default(),
         }
     }
 }
@@ -15,6 +46,7 @@ impl Identifier {
 /// Represents a type identifier, which can be a built-in type, a user-defined type,
 /// a static array, or a dynamic array.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TypeIdentifier {
     Integer8,
     Integer16,
@@ -28,153 +60,1764 @@ pub enum TypeIdentifier {
     Float64,
     Bit,
     Byte,
+    /// Single character of text, written as a byte. Text is an array of characters, given as
+    /// `string` or `string[size]` in meklang.
+    Char,
+    /// Type carrying no data, e.g. of union variants identified only by their discriminator.
+    Void,
     UserDefined(Identifier),
     StaticArray {
         r#type: Box<TypeIdentifier>,
         size: u64,
+        /// Constant the size was given by, if any, e.g. `MAX_LEN` in `byte[MAX_LEN]`. The size
+        /// holds its value once the constants of the protocol are resolved.
+        size_constant: Option<Identifier>,
     },
     DynamicArray {
         r#type: Box<TypeIdentifier>,
     },
 }
 
+impl TypeIdentifier {
+    /// Returns the natural size in bits of a built-in type, or `None` for user-defined
+    /// types and arrays, whose size depends on other definitions or on runtime data.
+    pub fn builtin_size_in_bits(&self) -> Option<u64> {
+        match self {
+            TypeIdentifier::Integer8 | TypeIdentifier::UnsignedInteger8 => Some(8),
+            TypeIdentifier::Integer16 | TypeIdentifier::UnsignedInteger16 => Some(16),
+            TypeIdentifier::Integer32 | TypeIdentifier::UnsignedInteger32 => Some(32),
+            TypeIdentifier::Integer64 | TypeIdentifier::UnsignedInteger64 => Some(64),
+            TypeIdentifier::Float32 => Some(32),
+            TypeIdentifier::Float64 => Some(64),
+            TypeIdentifier::Bit => Some(1),
+            TypeIdentifier::Byte | TypeIdentifier::Char => Some(8),
+            TypeIdentifier::Void => Some(0),
+            TypeIdentifier::UserDefined(_)
+            | TypeIdentifier::StaticArray { .. }
+            | TypeIdentifier::DynamicArray { .. } => None,
+        }
+    }
+}
+
+/// Keywords of the built-in types, in the order they are listed in the grammar.
+pub(crate) const BUILTIN_TYPES: &[(&str, TypeIdentifier)] = &[
+    ("int8", TypeIdentifier::Integer8),
+    ("int16", TypeIdentifier::Integer16),
+    ("int32", TypeIdentifier::Integer32),
+    ("int64", TypeIdentifier::Integer64),
+    ("uint8", TypeIdentifier::UnsignedInteger8),
+    ("uint16", TypeIdentifier::UnsignedInteger16),
+    ("uint32", TypeIdentifier::UnsignedInteger32),
+    ("uint64", TypeIdentifier::UnsignedInteger64),
+    ("float32", TypeIdentifier::Float32),
+    ("float64", TypeIdentifier::Float64),
+    ("bit", TypeIdentifier::Bit),
+    ("byte", TypeIdentifier::Byte),
+    ("char", TypeIdentifier::Char),
+    ("void", TypeIdentifier::Void),
+];
+
+/// Returns the keyword of a built-in type, or `None` for user-defined types and arrays.
+pub(crate) fn builtin_type_keyword(type_identifier: &TypeIdentifier) -> Option<&'static str> {
+    BUILTIN_TYPES
+        .iter()
+        .find(|(_, builtin)| builtin == type_identifier)
+        .map(|(name, _)| *name)
+}
+
+/// Returns the type identifier as written in meklang, e.g. `int32[4][N]` or `Bytes[]`.
+pub(crate) fn type_identifier_name(type_identifier: &TypeIdentifier) -> String {
+    let mut dimensions = String::new();
+    let mut element = type_identifier;
+    loop {
+        match element {
+            TypeIdentifier::StaticArray {
+                r#type,
+                size,
+                size_constant,
+            } => {
+                match size_constant {
+                    Some(constant) => dimensions.push_str(&format!("[{}]", constant.name)),
+                    None => dimensions.push_str(&format!("[{size}]")),
+                }
+                element = r#type;
+            }
+            TypeIdentifier::DynamicArray { r#type } => {
+                dimensions.push_str("[]");
+                element = r#type;
+            }
+            TypeIdentifier::UserDefined(identifier) => {
+                return format!("{}{dimensions}", identifier.name);
+            }
+            builtin => {
+                return format!(
+                    "{}{dimensions}",
+                    builtin_type_keyword(builtin).unwrap_or("?")
+                );
+            }
+        }
+    }
+}
+
+/// Returns the keyword and natural size in bits of every built-in type, e.g. for documentation.
+pub fn builtin_types_table() -> Vec<(&'static str, u64)> {
+    BUILTIN_TYPES
+        .iter()
+        .filter_map(|(name, type_identifier)| {
+            Some((*name, type_identifier.builtin_size_in_bits()?))
+        })
+        .collect()
+}
+
+/// Renders built-in types in the language of a smith, which lets users replace the default
+/// mapping, e.g. to represent floats with a fixed-point type of their platform.
+pub trait TypeMapper: std::fmt::Debug + Send + Sync {
+    /// Returns the type of the target language representing the given built-in type.
+    /// Never called with user-defined types or arrays, which smiths render themselves.
+    fn map_builtin(&self, type_identifier: &TypeIdentifier) -> String;
+}
+
 /// Represents a single field in an enumeration, which can either be a single value
 /// or a range of values. Each field has a name and either a single value or a start
 /// and end value for the range.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EnumerationField {
     SingleValue {
         name: Identifier,
-        value: u64,
+        value: i128,
+        /// Text of the `#` comment block directly preceding the field, if any.
+        doc: Option<String>,
     },
     RangeOfValues {
         name: Identifier,
-        start: u64,
-        end: u64,
+        start: i128,
+        end: i128,
+        /// Text of the `#` comment block directly preceding the field, if any.
+        doc: Option<String>,
     },
 }
 
+impl EnumerationField {
+    /// Returns the comment block documenting the field, if it has one.
+    pub fn doc(&self) -> Option<&str> {
+        match self {
+            EnumerationField::SingleValue { doc, .. }
+            | EnumerationField::RangeOfValues { doc, .. } => doc.as_deref(),
+        }
+    }
+}
+
+/// Returns the name of the member generated for a single value of a range of enumeration
+/// values, e.g. `reserved_3`, or `reserved_minus_3` for a negative value, as `-` cannot
+/// appear in identifiers.
+pub fn range_member_name(name: &str, value: i128) -> String {
+    if value < 0 {
+        format!("{name}_minus_{}", value.unsigned_abs())
+    } else {
+        format!("{name}_{value}")
+    }
+}
+
 /// Represents an enumeration, which is a user-defined type that consists of
 /// a set of named values, each of which can be a single value or a range of values.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumerationDefinition {
     pub name: Identifier,
+    /// Integer type the values are stored in on the wire, given as `enum Name : type`.
+    /// When not given, it is inferred from the values, see [`Self::size_in_bits`].
+    pub base: Option<TypeIdentifier>,
     pub fields: Vec<EnumerationField>,
+    pub attributes: Vec<Attribute>,
+    /// Text of the `#` comment block directly preceding the definition, if any.
+    pub doc: Option<String>,
 }
 
-/// Represents a single attribute of a field in a structure or union.
+impl EnumerationDefinition {
+    /// Returns the largest value that any field of the enumeration can take,
+    /// including the ends of ranges.
+    pub fn max_value(&self) -> i128 {
+        self.fields
+            .iter()
+            .map(|field| match field {
+                EnumerationField::SingleValue { value, .. } => *value,
+                EnumerationField::RangeOfValues { end, .. } => *end,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the smallest value that any field of the enumeration can take,
+    /// including the starts of ranges.
+    pub fn min_value(&self) -> i128 {
+        self.fields
+            .iter()
+            .map(|field| match field {
+                EnumerationField::SingleValue { value, .. } => *value,
+                EnumerationField::RangeOfValues { start, .. } => *start,
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Returns whether the values of the enumeration are signed integers (in two's complement
+    /// on the wire), which is the case for signed base types and, without a base type, for
+    /// enumerations with negative values.
+    pub fn is_signed(&self) -> bool {
+        match &self.base {
+            Some(base) => matches!(
+                base,
+                TypeIdentifier::Integer8
+                    | TypeIdentifier::Integer16
+                    | TypeIdentifier::Integer32
+                    | TypeIdentifier::Integer64
+            ),
+            None => self.min_value() < 0,
+        }
+    }
+
+    /// Returns whether any field of the enumeration has the given value.
+    pub fn contains_value(&self, value: i128) -> bool {
+        self.fields.iter().any(|field| match field {
+            EnumerationField::SingleValue { value: v, .. } => *v == value,
+            EnumerationField::RangeOfValues { start, end, .. } => (*start..=*end).contains(&value),
+        })
+    }
+
+    /// Returns the size in bits of the base type of the enumeration or, without one, of
+    /// the smallest integer (8, 16, 32 or 64 bits) able to hold every value of the enumeration,
+    /// which is signed if [`Self::is_signed`].
+    pub fn size_in_bits(&self) -> u64 {
+        if let Some(size) = self
+            .base
+            .as_ref()
+            .and_then(TypeIdentifier::builtin_size_in_bits)
+        {
+            return size;
+        }
+        if self.is_signed() {
+            let (min, max) = (self.min_value(), self.max_value());
+            return [8, 16, 32]
+                .into_iter()
+                .find(|bits| min >= -(1 << (bits - 1)) && max < 1 << (bits - 1))
+                .unwrap_or(64);
+        }
+        match self.max_value() {
+            0..=0xFF => 8,
+            0x100..=0xFFFF => 16,
+            0x1_0000..=0xFFFF_FFFF => 32,
+            _ => 64,
+        }
+    }
+}
+
+/// Memory representation of a structure generated by the Rust smith, given by its `repr`
+/// attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Repr {
+    /// `#[repr(C)]`, laying out fields in order with C alignment.
+    C,
+    /// `#[repr(C, packed)]`, laying out fields in order without any padding.
+    CPacked,
+    /// `#[repr(transparent)]`, laying out the structure as its only field.
+    Transparent,
+}
+
+/// Represents a single attribute of a field in a structure or union, or of a whole definition.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Attribute {
-    DiscriminatedBy { field: Identifier },
-    BitsSize { size: u64 },
-    BytesSize { size: u64 },
+    DiscriminatedBy {
+        field: Identifier,
+    },
+    BitsSize {
+        size: u64,
+    },
+    BytesSize {
+        size: u64,
+    },
+    /// Stable numeric identifier of a definition on the wire, independent of declaration order.
+    Id {
+        value: u64,
+    },
+    /// Type used for the field in the generated C code instead of the one mapped from its
+    /// meklang type. The size of the field on the wire is still given by the meklang type.
+    CType {
+        name: String,
+    },
+    /// Memory representation of a structure in the generated Rust code.
+    Repr {
+        repr: Repr,
+    },
+    /// Constraint checked by the generated decoders after reading the field, which is
+    /// available to the expression as `value`. The expression is emitted verbatim.
+    Assert {
+        expression: String,
+    },
+    /// Links an integer field to the dynamic array field of the same structure following it,
+    /// declaring that the field holds the number of its elements, so that the array has no
+    /// length prefix on the wire.
+    LengthOf {
+        field: Identifier,
+    },
+    /// Links a dynamic array field to the integer field of the same structure preceding it,
+    /// which holds the number of its elements. The reverse of `length_of`.
+    Length {
+        field: Identifier,
+    },
+}
+
+impl Attribute {
+    /// Returns the name of the attribute as written in meklang.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Attribute::DiscriminatedBy { .. } => "discriminated_by",
+            Attribute::BitsSize { .. } => "bits",
+            Attribute::BytesSize { .. } => "bytes",
+            Attribute::Id { .. } => "id",
+            Attribute::CType { .. } => "c_type",
+            Attribute::Repr { .. } => "repr",
+            Attribute::Assert { .. } => "assert",
+            Attribute::LengthOf { .. } => "length_of",
+            Attribute::Length { .. } => "length",
+        }
+    }
 }
 
 /// Represents a single field in a structure, which consists of an attribute list, name and a type.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructureField {
     pub name: Identifier,
     pub r#type: TypeIdentifier,
     pub attributes: Vec<Attribute>,
+    /// Text of the `#` comment block directly preceding the field, if any.
+    pub doc: Option<String>,
+    /// Whether the field only reserves space on the wire, which is the case for fields whose
+    /// names start with `_` (see [`StructureField::is_reserved_name`]). Such a field is still
+    /// stored, but always encoded as zeros, skipped when decoded and ignored when compared.
+    /// Its size on the wire is the one of its type, unless narrowed by a `bits` or `bytes`
+    /// attribute, e.g. `[bits=3] _padding: uint8;` reserves 3 bits.
+    pub reserved: bool,
+}
+
+impl StructureField {
+    /// Returns whether a field with the given name is reserved, see [`StructureField::reserved`].
+    pub fn is_reserved_name(name: &str) -> bool {
+        name.starts_with('_')
+    }
 }
 
 /// Represents a structure, which is a user-defined type that consists of
 /// a collection of fields, each with a name and a type.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructureDefinition {
     pub name: Identifier,
     pub fields: Vec<StructureField>,
+    pub attributes: Vec<Attribute>,
+    /// Text of the `#` comment block directly preceding the definition, if any.
+    pub doc: Option<String>,
+}
+
+/// Represents a single field in a union, which consists of a name, type, and
+/// a discriminator value that identifies which type the field holds.
+/// The discriminator is an integer value that is unique for each field in the union.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnionField {
+    SingleValue {
+        name: Identifier,
+        r#type: TypeIdentifier,
+        discriminator: u64,
+    },
+    RangeOfValues {
+        name: Identifier,
+        r#type: TypeIdentifier,
+        start_discriminator: u64,
+        end_discriminator: u64,
+    },
+}
+
+/// Represents a union, which is a user-defined type that can hold one of several
+/// values, each identified by a discriminator.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnionDefinition {
+    pub name: Identifier,
+    pub fields: Vec<UnionField>,
+    pub attributes: Vec<Attribute>,
+    /// Text of the `#` comment block directly preceding the definition, if any.
+    pub doc: Option<String>,
+}
+
+/// Represents a type definition, which is a user-defined type that can be
+/// an alias for a built-in type or another user-defined type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypeDefinition {
+    pub new_type: Identifier,
+    pub r#type: TypeIdentifier,
+    pub attributes: Vec<Attribute>,
+    /// Text of the `#` comment block directly preceding the definition, if any.
+    pub doc: Option<String>,
+}
+
+/// Represents a single flag of a set of flags, which consists of a name and its mask.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlagsField {
+    pub name: Identifier,
+    pub value: u64,
+}
+
+/// Represents a set of flags, which is an unsigned integer whose bits are described
+/// by named masks. Unlike values of an enumeration, the masks can be combined.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlagsDefinition {
+    pub name: Identifier,
+    pub r#type: TypeIdentifier,
+    pub fields: Vec<FlagsField>,
+    pub attributes: Vec<Attribute>,
+    /// Text of the `#` comment block directly preceding the definition, if any.
+    pub doc: Option<String>,
+}
+
+/// Represents a named unsigned integer, which can be used as the size of static arrays.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstantDefinition {
+    pub name: Identifier,
+    pub value: u64,
+    /// Text of the `#` comment block directly preceding the definition, if any.
+    pub doc: Option<String>,
+}
+
+/// Represents a single definition in the protocol, which can be an [`EnumerationDefinition`],
+/// [`StructureDefinition`], [`UnionDefinition`], [`TypeDefinition`], [`FlagsDefinition`]
+/// or [`ConstantDefinition`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Definition {
+    Enumeration(EnumerationDefinition),
+    Structure(StructureDefinition),
+    Union(UnionDefinition),
+    Type(TypeDefinition),
+    Flags(FlagsDefinition),
+    Constant(ConstantDefinition),
+}
+
+/// Kind of a [`Definition`], telling which variant it is without its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DefinitionKind {
+    Enumeration,
+    Structure,
+    Union,
+    Type,
+    Flags,
+    Constant,
+}
+
+impl Definition {
+    /// Returns the kind of the definition.
+    pub fn kind(&self) -> DefinitionKind {
+        match self {
+            Definition::Enumeration(_) => DefinitionKind::Enumeration,
+            Definition::Structure(_) => DefinitionKind::Structure,
+            Definition::Union(_) => DefinitionKind::Union,
+            Definition::Type(_) => DefinitionKind::Type,
+            Definition::Flags(_) => DefinitionKind::Flags,
+            Definition::Constant(_) => DefinitionKind::Constant,
+        }
+    }
+
+    /// Returns the attributes attached to the definition itself (not to its fields).
+    pub fn attributes(&self) -> &[Attribute] {
+        match self {
+            Definition::Enumeration(enumeration_def) => &enumeration_def.attributes,
+            Definition::Structure(structure_def) => &structure_def.attributes,
+            Definition::Union(union_def) => &union_def.attributes,
+            Definition::Type(type_def) => &type_def.attributes,
+            Definition::Flags(flags_def) => &flags_def.attributes,
+            Definition::Constant(_) => &[],
+        }
+    }
+
+    /// Returns the name of the defined type.
+    pub fn name(&self) -> &Identifier {
+        match self {
+            Definition::Enumeration(enumeration_def) => &enumeration_def.name,
+            Definition::Structure(structure_def) => &structure_def.name,
+            Definition::Union(union_def) => &union_def.name,
+            Definition::Type(type_def) => &type_def.new_type,
+            Definition::Flags(flags_def) => &flags_def.name,
+            Definition::Constant(constant_def) => &constant_def.name,
+        }
+    }
+
+    /// Returns the comment block documenting the definition, if it has one.
+    pub fn doc(&self) -> Option<&str> {
+        match self {
+            Definition::Enumeration(enumeration_def) => enumeration_def.doc.as_deref(),
+            Definition::Structure(structure_def) => structure_def.doc.as_deref(),
+            Definition::Union(union_def) => union_def.doc.as_deref(),
+            Definition::Type(type_def) => type_def.doc.as_deref(),
+            Definition::Flags(flags_def) => flags_def.doc.as_deref(),
+            Definition::Constant(constant_def) => constant_def.doc.as_deref(),
+        }
+    }
+
+    /// Returns the value of the `id` attribute of the definition, if it has one.
+    pub fn id(&self) -> Option<u64> {
+        self.attributes()
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::Id { value } => Some(*value),
+                _ => None,
+            })
+    }
+}
+
+/// Represents the entire protocol, which consists of multiple definitions.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Protocol {
+    /// Name of the module given by the `module` header, if present.
+    pub name: Option<String>,
+    /// Version of the protocol given by the `module` header, if present.
+    pub version: Option<u64>,
+    pub definitions: Vec<Definition>,
+}
+
+/// Looks up definitions by their names, so that type aliases can be resolved
+/// while generating encoders and parsers.
+pub(crate) struct DefinitionLookup<'a> {
+    definitions: std::collections::HashMap<&'a str, &'a Definition>,
+}
+
+impl<'a> DefinitionLookup<'a> {
+    pub(crate) fn new(protocol: &'a Protocol) -> Self {
+        let definitions = protocol
+            .definitions
+            .iter()
+            .map(|definition| (definition.name().name.as_str(), definition))
+            .collect();
+        DefinitionLookup { definitions }
+    }
+
+    /// Follows type aliases until a type that is not an alias is found. Sets of flags
+    /// are resolved to their underlying integer type.
+    pub(crate) fn resolve(&self, type_identifier: &'a TypeIdentifier) -> &'a TypeIdentifier {
+        match type_identifier {
+            TypeIdentifier::UserDefined(identifier) => {
+                match self.definitions.get(identifier.name.as_str()) {
+                    Some(Definition::Type(type_definition)) => {
+                        self.resolve(&type_definition.r#type)
+                    }
+                    Some(Definition::Flags(flags)) => &flags.r#type,
+                    _ => type_identifier,
+                }
+            }
+            _ => type_identifier,
+        }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&'a Definition> {
+        self.definitions.get(name).copied()
+    }
+
+    /// See [`is_memcpy_safe`].
+    pub(crate) fn is_memcpy_safe(&self, type_identifier: &TypeIdentifier) -> bool {
+        self.is_memcpy_safe_visiting(type_identifier, &mut std::collections::HashSet::new())
+    }
+
+    fn is_memcpy_safe_visiting(
+        &self,
+        type_identifier: &TypeIdentifier,
+        visiting: &mut std::collections::HashSet<String>,
+    ) -> bool {
+        match type_identifier {
+            TypeIdentifier::DynamicArray { .. } => false,
+            TypeIdentifier::StaticArray { r#type, .. } => {
+                self.is_memcpy_safe_visiting(r#type, visiting)
+            }
+            TypeIdentifier::UserDefined(identifier) => {
+                if !visiting.insert(identifier.name.clone()) {
+                    return false;
+                }
+                let is_safe = match self.get(&identifier.name) {
+                    Some(Definition::Structure(structure_def)) => structure_def
+                        .fields
+                        .iter()
+                        .all(|field| self.is_memcpy_safe_visiting(&field.r#type, visiting)),
+                    Some(Definition::Union(union_def)) => {
+                        union_def.fields.iter().all(|field| match field {
+                            UnionField::SingleValue { r#type, .. }
+                            | UnionField::RangeOfValues { r#type, .. } => {
+                                self.is_memcpy_safe_visiting(r#type, visiting)
+                            }
+                        })
+                    }
+                    Some(Definition::Type(type_def)) => {
+                        self.is_memcpy_safe_visiting(&type_def.r#type, visiting)
+                    }
+                    Some(_) => true,
+                    None => false,
+                };
+                visiting.remove(&identifier.name);
+                is_safe
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Returns whether the in-memory representation of the named type contains no pointers,
+/// which is not the case for types containing dynamic arrays (directly or through other
+/// definitions). Only such types have a fixed size in memory, although it may still differ
+/// from their size on the wire. Undefined and recursive types are never considered safe.
+pub fn is_memcpy_safe(protocol: &Protocol, type_name: &str) -> bool {
+    DefinitionLookup::new(protocol)
+        .is_memcpy_safe(&TypeIdentifier::UserDefined(Identifier::new(type_name)))
+}
+
+/// Returns the size in bits forced on a field by its `bits` or `bytes` attribute.
+pub(crate) fn field_size_in_bits_from_attributes(attributes: &[Attribute]) -> Option<u64> {
+    attributes.iter().find_map(|attribute| match attribute {
+        Attribute::BitsSize { size } => Some(*size),
+        Attribute::BytesSize { size } => Some(size.saturating_mul(8)),
+        _ => None,
+    })
+}
+
+/// Options affecting the positions of fields on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayoutOptions {
+    /// Inserts padding bits before every field with the `bytes` attribute, so that it starts
+    /// on a byte boundary. Off by default, as meklang does not pad fields.
+    pub align_bytes_fields_to_byte: bool,
+}
+
+/// Position and size of a single field of a structure on the wire. Both are `None` when they
+/// depend on runtime data, e.g. the length of a preceding dynamic array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldLayout {
+    pub name: String,
+    pub offset_in_bits: Option<u64>,
+    pub size_in_bits: Option<u64>,
+}
+
+impl DefinitionLookup<'_> {
+    /// Returns the number of padding bits inserted before a field starting at `offset`.
+    fn padding_in_bits(attributes: &[Attribute], offset: u64, options: &LayoutOptions) -> u64 {
+        let has_bytes_size = attributes
+            .iter()
+            .any(|attribute| matches!(attribute, Attribute::BytesSize { .. }));
+        if options.align_bytes_fields_to_byte && has_bytes_size {
+            offset.next_multiple_of(8) - offset
+        } else {
+            0
+        }
+    }
+
+    /// Returns the layout of the fields of a structure starting at the given offset, as the
+    /// padding inserted by [`LayoutOptions`] depends on the absolute position of a field.
+    /// Fails if an offset or a size does not fit in 64 bits.
+    pub(crate) fn structure_layout(
+        &self,
+        structure: &StructureDefinition,
+        start_offset: Option<u64>,
+        options: &LayoutOptions,
+    ) -> Result<Vec<FieldLayout>, String> {
+        let overflow = || {
+            format!(
+                "Size of structure {} does not fit in 64 bits",
+                structure.name.name
+            )
+        };
+        let mut offset = start_offset;
+        let mut layout = Vec::new();
+        for field in &structure.fields {
+            let field_offset = offset
+                .map(|offset| {
+                    offset
+                        .checked_add(Self::padding_in_bits(&field.attributes, offset, options))
+                        .ok_or_else(overflow)
+                })
+                .transpose()?;
+            let size_in_bits = match field_size_in_bits_from_attributes(&field.attributes) {
+                Some(size) => Some(size),
+                None => self.type_size_in_bits(&field.r#type, field_offset, options)?,
+            };
+            layout.push(FieldLayout {
+                name: field.name.name.clone(),
+                offset_in_bits: field_offset,
+                size_in_bits,
+            });
+            offset = field_offset
+                .zip(size_in_bits)
+                .map(|(offset, size)| offset.checked_add(size).ok_or_else(overflow))
+                .transpose()?;
+        }
+        Ok(layout)
+    }
+
+    /// See [`union_max_size_in_bits`].
+    fn union_max_size_in_bits(&self, union: &UnionDefinition) -> Result<Option<u64>, String> {
+        let mut max_size = 0;
+        for field in &union.fields {
+            let r#type = match field {
+                UnionField::SingleValue { r#type, .. }
+                | UnionField::RangeOfValues { r#type, .. } => r#type,
+            };
+            let Some(size) = self.type_size_in_bits(r#type, Some(0), &LayoutOptions::default())?
+            else {
+                return Ok(None);
+            };
+            max_size = max_size.max(size);
+        }
+        Ok(Some(max_size))
+    }
+
+    /// Returns the size in bits of a value of the given type starting at the given offset,
+    /// or `None` if the size is only known at runtime. Fails if the size does not fit in
+    /// 64 bits.
+    fn type_size_in_bits(
+        &self,
+        type_identifier: &TypeIdentifier,
+        offset: Option<u64>,
+        options: &LayoutOptions,
+    ) -> Result<Option<u64>, String> {
+        match self.resolve(type_identifier) {
+            TypeIdentifier::DynamicArray { .. } => Ok(None),
+            TypeIdentifier::StaticArray { r#type, size, .. } => {
+                let overflow =
+                    || format!("Size of an array of {size} elements does not fit in 64 bits");
+                if *size == 0 {
+                    return Ok(Some(0));
+                }
+                let Some(first) = self.type_size_in_bits(r#type, offset, options)? else {
+                    return Ok(None);
+                };
+                // Elements take the same size when it does not depend on where they start,
+                // which padding only changes for elements not ending on a byte boundary
+                let Some(start) =
+                    offset.filter(|_| options.align_bytes_fields_to_byte && first % 8 != 0)
+                else {
+                    return first.checked_mul(*size).map(Some).ok_or_else(overflow);
+                };
+                let mut total = first;
+                for _ in 1..*size {
+                    let element_offset = start.checked_add(total).ok_or_else(overflow)?;
+                    let Some(element) =
+                        self.type_size_in_bits(r#type, Some(element_offset), options)?
+                    else {
+                        return Ok(None);
+                    };
+                    total = total.checked_add(element).ok_or_else(overflow)?;
+                }
+                Ok(Some(total))
+            }
+            TypeIdentifier::UserDefined(identifier) => match self.get(&identifier.name) {
+                Some(Definition::Enumeration(enumeration)) => Ok(Some(enumeration.size_in_bits())),
+                Some(Definition::Structure(structure)) => {
+                    // Without padding, the size does not depend on where the structure starts
+                    let Some(start) = offset.or((!options.align_bytes_fields_to_byte).then_some(0))
+                    else {
+                        return Ok(None);
+                    };
+                    let layout = self.structure_layout(structure, Some(start), options)?;
+                    let Some(last) = layout.last() else {
+                        return Ok(Some(0));
+                    };
+                    Ok(last
+                        .offset_in_bits
+                        .zip(last.size_in_bits)
+                        .map(|(offset, size)| offset + size - start))
+                }
+                _ => Ok(None),
+            },
+            builtin => Ok(builtin.builtin_size_in_bits()),
+        }
+    }
+}
+
+/// Returns the offsets and sizes of the fields of the named structure on the wire.
+pub fn structure_layout(
+    protocol: &Protocol,
+    structure_name: &str,
+    options: &LayoutOptions,
+) -> Result<Vec<FieldLayout>, String> {
+    let lookup = DefinitionLookup::new(protocol);
+    match lookup.get(structure_name) {
+        Some(Definition::Structure(structure)) => {
+            lookup.structure_layout(structure, Some(0), options)
+        }
+        _ => Err(format!("Structure {structure_name} is not defined")),
+    }
+}
+
+/// Returns the number of bits needed to hold any variant of the named union, i.e. the size of
+/// its largest variant, or `None` if the size of any variant is only known at runtime.
+pub fn union_max_size_in_bits(
+    protocol: &Protocol,
+    union_name: &str,
+) -> Result<Option<u64>, String> {
+    let lookup = DefinitionLookup::new(protocol);
+    match lookup.get(union_name) {
+        Some(Definition::Union(union)) => lookup.union_max_size_in_bits(union),
+        _ => Err(format!("Union {union_name} is not defined")),
+    }
+}
+
+/// Returns the size in bits of the named definition on the wire, or `None` if it is only known
+/// at runtime. Like in [`union_max_size_in_bits`], a union takes the size of its largest variant.
+pub fn definition_size_in_bits(protocol: &Protocol, name: &str) -> Result<Option<u64>, String> {
+    let lookup = DefinitionLookup::new(protocol);
+    if lookup.get(name).is_none() {
+        return Err(format!("Definition {name} is not defined"));
+    }
+    let user_defined = TypeIdentifier::UserDefined(Identifier::new(name));
+    let type_identifier = lookup.resolve(&user_defined);
+    if let TypeIdentifier::UserDefined(identifier) = type_identifier {
+        match lookup.get(&identifier.name) {
+            Some(Definition::Union(union)) => return lookup.union_max_size_in_bits(union),
+            Some(Definition::Flags(flags)) => return Ok(flags.r#type.builtin_size_in_bits()),
+            _ => {}
+        }
+    }
+    lookup.type_size_in_bits(type_identifier, Some(0), &LayoutOptions::default())
+}
+
+/// Returns the field reached by following the dotted `path` of field names from the named
+/// structure, e.g. `["header", "kind"]` for `header.kind`. Every segment but the last one
+/// must name a field whose type is a structure (possibly through type aliases).
+pub fn resolve_field_path<'a>(
+    protocol: &'a Protocol,
+    root_type: &str,
+    path: &[&str],
+) -> Result<&'a StructureField, String> {
+    let lookup = DefinitionLookup::new(protocol);
+    let Some(Definition::Structure(root)) = lookup.get(root_type) else {
+        return Err(format!("Structure {root_type} is not defined"));
+    };
+    let Some((last, segments)) = path.split_last() else {
+        return Err(format!("Field path in {root_type} is empty"));
+    };
+
+    let mut structure = root;
+    for segment in segments {
+        let field = find_structure_field(structure, segment)?;
+        structure = match lookup.resolve(&field.r#type) {
+            TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name) {
+                Some(Definition::Structure(nested)) => Some(nested),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| {
+            format!(
+                "Field {segment} in {} is not a structure",
+                structure.name.name
+            )
+        })?;
+    }
+    find_structure_field(structure, last)
+}
+
+fn find_structure_field<'a>(
+    structure: &'a StructureDefinition,
+    name: &str,
+) -> Result<&'a StructureField, String> {
+    structure
+        .fields
+        .iter()
+        .find(|field| field.name.name == name)
+        .ok_or_else(|| format!("Field {name} is not defined in {}", structure.name.name))
+}
+
+/// Returns the representation given by a `repr` attribute, if present.
+pub(crate) fn repr(attributes: &[Attribute]) -> Option<Repr> {
+    attributes.iter().find_map(|attribute| match attribute {
+        Attribute::Repr { repr } => Some(*repr),
+        _ => None,
+    })
+}
+
+/// Returns the C type forced on a field by its `c_type` attribute, if present.
+pub(crate) fn c_type_override(attributes: &[Attribute]) -> Option<&str> {
+    attributes.iter().find_map(|attribute| match attribute {
+        Attribute::CType { name } => Some(name.as_str()),
+        _ => None,
+    })
+}
+
+/// Returns the expression of the `assert` attribute, if present.
+pub(crate) fn assertion(attributes: &[Attribute]) -> Option<&str> {
+    attributes.iter().find_map(|attribute| match attribute {
+        Attribute::Assert { expression } => Some(expression.as_str()),
+        _ => None,
+    })
+}
+
+/// Returns the name of the field referenced by the `discriminated_by` attribute, if present.
+pub(crate) fn discriminated_by(attributes: &[Attribute]) -> Option<&str> {
+    attributes.iter().find_map(|attribute| match attribute {
+        Attribute::DiscriminatedBy { field } => Some(field.name.as_str()),
+        _ => None,
+    })
+}
+
+/// Returns the name of the field of the structure holding the number of elements of the given
+/// dynamic array field, which is linked to it with either `length` or `length_of`.
+pub(crate) fn length_field<'a>(
+    structure: &'a StructureDefinition,
+    field: &'a StructureField,
+) -> Option<&'a str> {
+    field
+        .attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            Attribute::Length { field } => Some(field.name.as_str()),
+            _ => None,
+        })
+        .or_else(|| {
+            structure.fields.iter().find_map(|other| {
+                other
+                    .attributes
+                    .iter()
+                    .find_map(|attribute| match attribute {
+                        Attribute::LengthOf { field: target } if *target == field.name => {
+                            Some(other.name.name.as_str())
+                        }
+                        _ => None,
+                    })
+            })
+        })
+}
+
+/// Extracts the name of a custom type identifier from a [`TypeIdentifier`].
+/// If the type identifier is a user-defined type, it returns the name.
+/// If it is a static or dynamic array, it recursively extracts the name from the contained type.
+/// If it is a built-in type, it returns `None`.
+pub(crate) fn extract_custom_type_identifier_name(
+    type_identifier: &TypeIdentifier,
+) -> Option<String> {
+    match type_identifier {
+        TypeIdentifier::UserDefined(id) => Some(id.name.clone()),
+        TypeIdentifier::StaticArray { r#type, .. } => extract_custom_type_identifier_name(r#type),
+        TypeIdentifier::DynamicArray { r#type } => extract_custom_type_identifier_name(r#type),
+        _ => None,
+    }
+}
+
+/// Returns the constants giving the sizes of a static array and of the arrays nested in it,
+/// starting from the outermost one.
+pub(crate) fn array_size_constants(type_identifier: &TypeIdentifier) -> Vec<&Identifier> {
+    match type_identifier {
+        TypeIdentifier::StaticArray {
+            r#type,
+            size_constant,
+            ..
+        } => size_constant
+            .iter()
+            .chain(array_size_constants(r#type))
+            .collect(),
+        TypeIdentifier::DynamicArray { r#type } => array_size_constants(r#type),
+        _ => vec![],
+    }
+}
+
+/// Extracts the names of all definitions a type identifier depends on, i.e. the custom type
+/// (see [`extract_custom_type_identifier_name`]) and the constants giving the array sizes.
+fn extract_type_identifier_dependencies(type_identifier: &TypeIdentifier) -> Vec<String> {
+    extract_custom_type_identifier_name(type_identifier)
+        .into_iter()
+        .chain(
+            array_size_constants(type_identifier)
+                .into_iter()
+                .map(|constant| constant.name.clone()),
+        )
+        .collect()
+}
+
+/// Extracts the names of all custom type identifiers and constants from a structure definition.
+fn extract_structure_subtypes(structure_def: &StructureDefinition) -> Vec<String> {
+    structure_def
+        .fields
+        .iter()
+        .flat_map(|field| extract_type_identifier_dependencies(&field.r#type))
+        .collect()
+}
+
+/// Extracts the names of all custom type identifiers and constants from a union definition.
+fn extract_union_subtypes(union_def: &UnionDefinition) -> Vec<String> {
+    union_def
+        .fields
+        .iter()
+        .flat_map(|field| match field {
+            UnionField::SingleValue { r#type, .. } | UnionField::RangeOfValues { r#type, .. } => {
+                extract_type_identifier_dependencies(r#type)
+            }
+        })
+        .collect()
+}
+
+/// Returns the names of all user-defined types and constants a definition refers to.
+pub(crate) fn extract_definition_subtypes(definition: &Definition) -> Vec<String> {
+    match definition {
+        Definition::Enumeration(_) | Definition::Flags(_) | Definition::Constant(_) => vec![],
+        Definition::Structure(structure_def) => extract_structure_subtypes(structure_def),
+        Definition::Union(union_def) => extract_union_subtypes(union_def),
+        Definition::Type(type_def) => extract_type_identifier_dependencies(&type_def.r#type),
+    }
+}
+
+/// Returns the structure a protocol is read from, which is the first structure not used by any
+/// other definition, e.g. the root type of a Kaitai Struct specification.
+pub(crate) fn root_structure(protocol: &Protocol) -> Option<&StructureDefinition> {
+    let used: std::collections::HashSet<String> = protocol
+        .definitions
+        .iter()
+        .flat_map(extract_definition_subtypes)
+        .collect();
+    protocol
+        .definitions
+        .iter()
+        .find_map(|definition| match definition {
+            Definition::Structure(structure) if !used.contains(&structure.name.name) => {
+                Some(structure)
+            }
+            _ => None,
+        })
+}
+
+/// Sets the size of every static array sized by a constant to the value of that constant.
+/// Sizes referring to undefined constants are left as they are, for the validation to report.
+pub(crate) fn resolve_array_size_constants(protocol: &mut Protocol) {
+    fn resolve(type_identifier: &mut TypeIdentifier, constants: &HashMap<String, u64>) {
+        match type_identifier {
+            TypeIdentifier::StaticArray {
+                r#type,
+                size,
+                size_constant,
+            } => {
+                if let Some(value) = size_constant
+                    .as_ref()
+                    .and_then(|constant| constants.get(&constant.name))
+                {
+                    *size = *value;
+                }
+                resolve(r#type, constants);
+            }
+            TypeIdentifier::DynamicArray { r#type } => resolve(r#type, constants),
+            _ => {}
+        }
+    }
+
+    use std::collections::HashMap;
+
+    let constants: HashMap<String, u64> = protocol
+        .definitions
+        .iter()
+        .filter_map(|definition| match definition {
+            Definition::Constant(constant_def) => {
+                Some((constant_def.name.name.clone(), constant_def.value))
+            }
+            _ => None,
+        })
+        .collect();
+    for definition in &mut protocol.definitions {
+        match definition {
+            Definition::Structure(structure_def) => {
+                for field in &mut structure_def.fields {
+                    resolve(&mut field.r#type, &constants);
+                }
+            }
+            Definition::Union(union_def) => {
+                for field in &mut union_def.fields {
+                    match field {
+                        UnionField::SingleValue { r#type, .. }
+                        | UnionField::RangeOfValues { r#type, .. } => resolve(r#type, &constants),
+                    }
+                }
+            }
+            Definition::Type(type_def) => resolve(&mut type_def.r#type, &constants),
+            Definition::Enumeration(_) | Definition::Flags(_) | Definition::Constant(_) => {}
+        }
+    }
+}
+
+/// Returns mutable references to all identifiers of the protocol: names of definitions and
+/// fields, referenced user-defined types and fields referenced by attributes.
+pub(crate) fn identifiers_mut(protocol: &mut Protocol) -> Vec<&mut Identifier> {
+    fn type_identifiers_mut(type_identifier: &mut TypeIdentifier) -> Vec<&mut Identifier> {
+        match type_identifier {
+            TypeIdentifier::UserDefined(identifier) => vec![identifier],
+            TypeIdentifier::StaticArray {
+                r#type,
+                size_constant,
+                ..
+            } => {
+                let mut identifiers = type_identifiers_mut(r#type);
+                identifiers.extend(size_constant.as_mut());
+                identifiers
+            }
+            TypeIdentifier::DynamicArray { r#type } => type_identifiers_mut(r#type),
+            _ => vec![],
+        }
+    }
+
+    fn attribute_identifiers_mut(attributes: &mut [Attribute]) -> Vec<&mut Identifier> {
+        attributes
+            .iter_mut()
+            .filter_map(|attribute| match attribute {
+                Attribute::DiscriminatedBy { field }
+                | Attribute::LengthOf { field }
+                | Attribute::Length { field } => Some(field),
+                _ => None,
+            })
+            .collect()
+    }
+
+    let mut identifiers = Vec::new();
+    for definition in &mut protocol.definitions {
+        match definition {
+            Definition::Enumeration(enumeration_def) => {
+                identifiers.push(&mut enumeration_def.name);
+                for field in &mut enumeration_def.fields {
+                    match field {
+                        EnumerationField::SingleValue { name, .. }
+                        | EnumerationField::RangeOfValues { name, .. } => identifiers.push(name),
+                    }
+                }
+            }
+            Definition::Structure(structure_def) => {
+                identifiers.push(&mut structure_def.name);
+                for field in &mut structure_def.fields {
+                    identifiers.extend(attribute_identifiers_mut(&mut field.attributes));
+                    identifiers.push(&mut field.name);
+                    identifiers.extend(type_identifiers_mut(&mut field.r#type));
+                }
+            }
+            Definition::Union(union_def) => {
+                identifiers.push(&mut union_def.name);
+                for field in &mut union_def.fields {
+                    match field {
+                        UnionField::SingleValue { name, r#type, .. }
+                        | UnionField::RangeOfValues { name, r#type, .. } => {
+                            identifiers.push(name);
+                            identifiers.extend(type_identifiers_mut(r#type));
+                        }
+                    }
+                }
+            }
+            Definition::Type(type_def) => {
+                identifiers.push(&mut type_def.new_type);
+                identifiers.extend(type_identifiers_mut(&mut type_def.r#type));
+            }
+            Definition::Flags(flags_def) => {
+                identifiers.push(&mut flags_def.name);
+                for field in &mut flags_def.fields {
+                    identifiers.push(&mut field.name);
+                }
+            }
+            Definition::Constant(constant_def) => identifiers.push(&mut constant_def.name),
+        }
+    }
+    identifiers
+}
+
+/// Hasher implementing the 64-bit FNV-1a algorithm. Unlike the default hasher of the standard
+/// library, its output is guaranteed not to change between Rust releases, and integers are
+/// written as fixed-width little-endian bytes, so that it does not depend on the platform
+/// either.
+struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Fnv1aHasher(0xcbf29ce484222325)
+    }
+}
+
+impl std::hash::Hasher for Fnv1aHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_i16(&mut self, value: i16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, value: i32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_i64(&mut self, value: i64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_i128(&mut self, value: i128) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_isize(&mut self, value: isize) {
+        self.write_i64(value as i64);
+    }
+}
+
+pub(crate) fn fnv1a_hash<T: std::hash::Hash>(value: &T) -> u64 {
+    use std::hash::Hasher;
+
+    let mut hasher = Fnv1aHasher::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Writes the meaning of a protocol to a [`Fnv1aHasher`] in a canonical form, independent of
+/// the derived `Hash` implementations: integers as fixed-width little-endian bytes, strings and
+/// lists prefixed with their length as a `u64`, and variants as one-byte tags. Doc comments
+/// and spans are left out.
+#[derive(Default)]
+struct CanonicalEncoder(Fnv1aHasher);
+
+impl CanonicalEncoder {
+    fn finish(&self) -> u64 {
+        std::hash::Hasher::finish(&self.0)
+    }
+
+    fn tag(&mut self, tag: u8) {
+        std::hash::Hasher::write(&mut self.0, &[tag]);
+    }
+
+    fn u64(&mut self, value: u64) {
+        std::hash::Hasher::write(&mut self.0, &value.to_le_bytes());
+    }
+
+    fn i128(&mut self, value: i128) {
+        std::hash::Hasher::write(&mut self.0, &value.to_le_bytes());
+    }
+
+    fn length(&mut self, length: usize) {
+        self.u64(length as u64);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.length(value.len());
+        std::hash::Hasher::write(&mut self.0, value.as_bytes());
+    }
+
+    fn identifier(&mut self, identifier: &Identifier) {
+        self.str(&identifier.name);
+    }
+
+    fn option<T>(&mut self, value: Option<T>, encode: impl FnOnce(&mut Self, T)) {
+        match value {
+            None => self.tag(0),
+            Some(value) => {
+                self.tag(1);
+                encode(self, value);
+            }
+        }
+    }
+
+    fn type_identifier(&mut self, type_identifier: &TypeIdentifier) {
+        match type_identifier {
+            TypeIdentifier::Integer8 => self.tag(0),
+            TypeIdentifier::Integer16 => self.tag(1),
+            TypeIdentifier::Integer32 => self.tag(2),
+            TypeIdentifier::Integer64 => self.tag(3),
+            TypeIdentifier::UnsignedInteger8 => self.tag(4),
+            TypeIdentifier::UnsignedInteger16 => self.tag(5),
+            TypeIdentifier::UnsignedInteger32 => self.tag(6),
+            TypeIdentifier::UnsignedInteger64 => self.tag(7),
+            TypeIdentifier::Float32 => self.tag(8),
+            TypeIdentifier::Float64 => self.tag(9),
+            TypeIdentifier::Bit => self.tag(10),
+            TypeIdentifier::Byte => self.tag(11),
+            TypeIdentifier::Char => self.tag(12),
+            TypeIdentifier::Void => self.tag(13),
+            TypeIdentifier::UserDefined(identifier) => {
+                self.tag(14);
+                self.identifier(identifier);
+            }
+            TypeIdentifier::StaticArray {
+                r#type,
+                size,
+                size_constant,
+            } => {
+                self.tag(15);
+                self.type_identifier(r#type);
+                self.u64(*size);
+                self.option(size_constant.as_ref(), Self::identifier);
+            }
+            TypeIdentifier::DynamicArray { r#type } => {
+                self.tag(16);
+                self.type_identifier(r#type);
+            }
+        }
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute]) {
+        self.length(attributes.len());
+        for attribute in attributes {
+            match attribute {
+                Attribute::DiscriminatedBy { field } => {
+                    self.tag(0);
+                    self.identifier(field);
+                }
+                Attribute::BitsSize { size } => {
+                    self.tag(1);
+                    self.u64(*size);
+                }
+                Attribute::BytesSize { size } => {
+                    self.tag(2);
+                    self.u64(*size);
+                }
+                Attribute::Id { value } => {
+                    self.tag(3);
+                    self.u64(*value);
+                }
+                Attribute::CType { name } => {
+                    self.tag(4);
+                    self.str(name);
+                }
+                Attribute::Repr { repr } => {
+                    self.tag(5);
+                    self.tag(match repr {
+                        Repr::C => 0,
+                        Repr::CPacked => 1,
+                        Repr::Transparent => 2,
+                    });
+                }
+                Attribute::Assert { expression } => {
+                    self.tag(6);
+                    self.str(expression);
+                }
+                Attribute::LengthOf { field } => {
+                    self.tag(7);
+                    self.identifier(field);
+                }
+                Attribute::Length { field } => {
+                    self.tag(8);
+                    self.identifier(field);
+                }
+            }
+        }
+    }
+
+    fn definition(&mut self, definition: &Definition) {
+        match definition {
+            Definition::Enumeration(enumeration) => {
+                self.tag(0);
+                self.identifier(&enumeration.name);
+                self.option(enumeration.base.as_ref(), Self::type_identifier);
+                self.length(enumeration.fields.len());
+                for field in &enumeration.fields {
+                    match field {
+                        EnumerationField::SingleValue { name, value, .. } => {
+                            self.tag(0);
+                            self.identifier(name);
+                            self.i128(*value);
+                        }
+                        EnumerationField::RangeOfValues {
+                            name, start, end, ..
+                        } => {
+                            self.tag(1);
+                            self.identifier(name);
+                            self.i128(*start);
+                            self.i128(*end);
+                        }
+                    }
+                }
+                self.attributes(&enumeration.attributes);
+            }
+            Definition::Structure(structure) => {
+                self.tag(1);
+                self.identifier(&structure.name);
+                self.length(structure.fields.len());
+                for field in &structure.fields {
+                    self.identifier(&field.name);
+                    self.type_identifier(&field.r#type);
+                    self.attributes(&field.attributes);
+                    self.tag(u8::from(field.reserved));
+                }
+                self.attributes(&structure.attributes);
+            }
+            Definition::Union(union) => {
+                self.tag(2);
+                self.identifier(&union.name);
+                self.length(union.fields.len());
+                for field in &union.fields {
+                    match field {
+                        UnionField::SingleValue {
+                            name,
+                            r#type,
+                            discriminator,
+                        } => {
+                            self.tag(0);
+                            self.identifier(name);
+                            self.type_identifier(r#type);
+                            self.u64(*discriminator);
+                        }
+                        UnionField::RangeOfValues {
+                            name,
+                            r#type,
+                            start_discriminator,
+                            end_discriminator,
+                        } => {
+                            self.tag(1);
+                            self.identifier(name);
+                            self.type_identifier(r#type);
+                            self.u64(*start_discriminator);
+                            self.u64(*end_discriminator);
+                        }
+                    }
+                }
+                self.attributes(&union.attributes);
+            }
+            Definition::Type(type_definition) => {
+                self.tag(3);
+                self.identifier(&type_definition.new_type);
+                self.type_identifier(&type_definition.r#type);
+                self.attributes(&type_definition.attributes);
+            }
+            Definition::Flags(flags) => {
+                self.tag(4);
+                self.identifier(&flags.name);
+                self.type_identifier(&flags.r#type);
+                self.length(flags.fields.len());
+                for field in &flags.fields {
+                    self.identifier(&field.name);
+                    self.u64(field.value);
+                }
+                self.attributes(&flags.attributes);
+            }
+            Definition::Constant(constant) => {
+                self.tag(5);
+                self.identifier(&constant.name);
+                self.u64(constant.value);
+            }
+        }
+    }
 }
 
-/// Represents a single field in a union, which consists of a name, type, and
-/// a discriminator value that identifies which type the field holds.
-/// The discriminator is an integer value that is unique for each field in the union.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum UnionField {
-    SingleValue {
-        name: Identifier,
-        r#type: TypeIdentifier,
-        discriminator: u64,
-    },
-    RangeOfValues {
-        name: Identifier,
-        r#type: TypeIdentifier,
-        start_discriminator: u64,
-        end_discriminator: u64,
-    },
+/// Computes a content hash of the protocol, including its module name and version, which can be
+/// used for change detection. It hashes a canonical encoding of the meaning of the protocol, so
+/// it is the same on every platform and stays the same across releases, as long as the syntax
+/// tree does not change. The order of definitions does not affect the hash, as it has no meaning
+/// in meklang, while the order of fields inside a definition does. Doc comments and formatting
+/// do not affect it either.
+pub fn protocol_hash(protocol: &Protocol) -> u64 {
+    let mut definition_hashes: Vec<u64> = protocol
+        .definitions
+        .iter()
+        .map(|definition| {
+            let mut encoder = CanonicalEncoder::default();
+            encoder.definition(definition);
+            encoder.finish()
+        })
+        .collect();
+    definition_hashes.sort_unstable();
+    let mut encoder = CanonicalEncoder::default();
+    encoder.option(protocol.name.as_deref(), CanonicalEncoder::str);
+    encoder.option(protocol.version, CanonicalEncoder::u64);
+    encoder.length(definition_hashes.len());
+    for hash in definition_hashes {
+        encoder.u64(hash);
+    }
+    encoder.finish()
 }
 
-/// Represents a union, which is a user-defined type that can hold one of several
-/// values, each identified by a discriminator.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct UnionDefinition {
-    pub name: Identifier,
-    pub fields: Vec<UnionField>,
+/// Returns the protocol with every range of enumeration values or union discriminators whose
+/// start equals its end, such as `2..2`, turned into a single value, so that the smiths need
+/// not tell them apart, e.g. when naming the generated members.
+pub fn canonicalize_singleton_ranges(protocol: &Protocol) -> Protocol {
+    let mut protocol = protocol.clone();
+    for definition in &mut protocol.definitions {
+        match definition {
+            Definition::Enumeration(enumeration) => {
+                for field in &mut enumeration.fields {
+                    if let EnumerationField::RangeOfValues {
+                        name,
+                        start,
+                        end,
+                        doc,
+                    } = field
+                        && start == end
+                    {
+                        *field = EnumerationField::SingleValue {
+                            name: name.clone(),
+                            value: *start,
+                            doc: doc.take(),
+                        };
+                    }
+                }
+            }
+            Definition::Union(union) => {
+                for field in &mut union.fields {
+                    if let UnionField::RangeOfValues {
+                        name,
+                        r#type,
+                        start_discriminator,
+                        end_discriminator,
+                    } = field
+                        && start_discriminator == end_discriminator
+                    {
+                        *field = UnionField::SingleValue {
+                            name: name.clone(),
+                            r#type: r#type.clone(),
+                            discriminator: *start_discriminator,
+                        };
+                    }
+                }
+            }
+            Definition::Structure(_)
+            | Definition::Type(_)
+            | Definition::Flags(_)
+            | Definition::Constant(_) => {}
+        }
+    }
+    protocol
 }
 
-/// Represents a type definition, which is a user-defined type that can be
-/// an alias for a built-in type or another user-defined type.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct TypeDefinition {
-    pub new_type: Identifier,
-    pub r#type: TypeIdentifier,
+/// Returns the members of `new` missing in `old`, failing with the given message unless all
+/// members of `old` are kept unchanged, as told by `same`. With `append_only`, members can only
+/// be added after the existing ones.
+fn added_members<T: Clone>(
+    old: &[T],
+    new: &[T],
+    same: impl Fn(&T, &T) -> bool,
+    append_only: bool,
+    error: impl FnOnce() -> String,
+) -> Result<Vec<T>, String> {
+    let kept = |member: &T| new.iter().any(|other| same(member, other));
+    let keeps_members = if append_only {
+        new.len() >= old.len()
+            && old
+                .iter()
+                .zip(new)
+                .all(|(member, other)| same(member, other))
+    } else {
+        old.iter().all(kept)
+    };
+    if !keeps_members {
+        return Err(error());
+    }
+    Ok(new
+        .iter()
+        .filter(|member| !old.iter().any(|other| same(other, member)))
+        .cloned()
+        .collect())
 }
 
-/// Represents a single definition in the protocol, which can be an [`EnumerationDefinition`],
-/// [`StructureDefinition`], [`UnionDefinition`], or [`TypeDefinition`].
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum Definition {
-    Enumeration(EnumerationDefinition),
-    Structure(StructureDefinition),
-    Union(UnionDefinition),
-    Type(TypeDefinition),
+/// Returns the enumeration field without its doc comment, so that fields can be compared
+/// regardless of their documentation.
+fn without_doc(field: &EnumerationField) -> EnumerationField {
+    let mut field = field.clone();
+    match &mut field {
+        EnumerationField::SingleValue { doc, .. } | EnumerationField::RangeOfValues { doc, .. } => {
+            *doc = None;
+        }
+    }
+    field
 }
 
-/// Represents the entire protocol, which consists of multiple definitions.
-#[derive(Debug, Clone, PartialEq)]
-pub struct Protocol {
-    pub definitions: Vec<Definition>,
+/// Returns the additions made by `new` to `old` as a patch: a protocol holding every added
+/// definition and, for every extended definition, a definition of the same name holding only
+/// the added fields, enumeration values, union members or flags. Fails if the change is not
+/// backward-compatible, i.e. if a definition is removed or changes its kind, attributes or
+/// existing members, if a type alias or a constant changes its type or value, or if fields are not appended at the end of a structure, as they would
+/// move the fields following them on the wire. Doc comments are not compared.
+pub fn generate_additive_patch(old: &Protocol, new: &Protocol) -> Result<Protocol, String> {
+    let new_lookup = DefinitionLookup::new(new);
+    if let Some(removed) = old
+        .definitions
+        .iter()
+        .find(|definition| new_lookup.get(&definition.name().name).is_none())
+    {
+        return Err(format!("Definition {} is removed", removed.name().name));
+    }
+
+    let old_lookup = DefinitionLookup::new(old);
+    let mut definitions = Vec::new();
+    for definition in &new.definitions {
+        let name = &definition.name().name;
+        let Some(old_definition) = old_lookup.get(name) else {
+            definitions.push(definition.clone());
+            continue;
+        };
+        if old_definition.attributes() != definition.attributes() {
+            return Err(format!("Definition {name} changes its attributes"));
+        }
+        let patch = match (old_definition, definition) {
+            (Definition::Structure(old_structure), Definition::Structure(structure)) => {
+                let fields = added_members(
+                    &old_structure.fields,
+                    &structure.fields,
+                    |a, b| {
+                        (&a.name, &a.r#type, &a.attributes) == (&b.name, &b.r#type, &b.attributes)
+                    },
+                    true,
+                    || {
+                        format!(
+                            "Structure {name} changes or removes fields, or adds fields before \
+                             existing ones"
+                        )
+                    },
+                )?;
+                (!fields.is_empty()).then(|| {
+                    Definition::Structure(StructureDefinition {
+                        fields,
+                        ..structure.clone()
+                    })
+                })
+            }
+            (Definition::Enumeration(old_enumeration), Definition::Enumeration(enumeration)) => {
+                if old_enumeration.base != enumeration.base {
+                    return Err(format!("Enumeration {name} changes its base type"));
+                }
+                let fields = added_members(
+                    &old_enumeration.fields,
+                    &enumeration.fields,
+                    |a, b| without_doc(a) == without_doc(b),
+                    false,
+                    || format!("Enumeration {name} changes or removes values"),
+                )?;
+                (!fields.is_empty()).then(|| {
+                    Definition::Enumeration(EnumerationDefinition {
+                        fields,
+                        ..enumeration.clone()
+                    })
+                })
+            }
+            (Definition::Union(old_union), Definition::Union(union)) => {
+                let fields = added_members(
+                    &old_union.fields,
+                    &union.fields,
+                    PartialEq::eq,
+                    false,
+                    || format!("Union {name} changes or removes members"),
+                )?;
+                (!fields.is_empty()).then(|| {
+                    Definition::Union(UnionDefinition {
+                        fields,
+                        ..union.clone()
+                    })
+                })
+            }
+            (Definition::Flags(old_flags), Definition::Flags(flags)) => {
+                if old_flags.r#type != flags.r#type {
+                    return Err(format!("Flags {name} change their underlying type"));
+                }
+                let fields = added_members(
+                    &old_flags.fields,
+                    &flags.fields,
+                    PartialEq::eq,
+                    false,
+                    || format!("Flags {name} change or remove flags"),
+                )?;
+                (!fields.is_empty()).then(|| {
+                    Definition::Flags(FlagsDefinition {
+                        fields,
+                        ..flags.clone()
+                    })
+                })
+            }
+            (Definition::Type(old_type), Definition::Type(type_def)) => {
+                if old_type.r#type != type_def.r#type {
+                    return Err(format!("Type {name} changes its aliased type"));
+                }
+                None
+            }
+            (Definition::Constant(old_constant), Definition::Constant(constant)) => {
+                if old_constant.value != constant.value {
+                    return Err(format!("Constant {name} changes its value"));
+                }
+                None
+            }
+            _ => return Err(format!("Definition {name} changes its kind")),
+        };
+        definitions.extend(patch);
+    }
+    Ok(Protocol {
+        name: new.name.clone(),
+        version: new.version,
+        definitions,
+    })
 }
 
-/// Extracts the name of a custom type identifier from a [`TypeIdentifier`].
-/// If the type identifier is a user-defined type, it returns the name.
-/// If it is a static or dynamic array, it recursively extracts the name from the contained type.
-/// If it is a built-in type, it returns `None`.
-fn extract_custom_type_identifier_name(type_identifier: &TypeIdentifier) -> Option<String> {
-    match type_identifier {
-        TypeIdentifier::UserDefined(id) => Some(id.name.clone()),
-        TypeIdentifier::StaticArray { r#type, .. } => extract_custom_type_identifier_name(r#type),
-        TypeIdentifier::DynamicArray { r#type } => extract_custom_type_identifier_name(r#type),
-        _ => None,
+/// Runs all validators on the protocol and returns it sorted by dependencies and with
+/// [`canonicalize_singleton_ranges`] applied, ready to be passed to any of the smiths.
+/// On failure, all collected diagnostics are returned.
+pub fn prepare(protocol: &Protocol) -> Result<Protocol, Vec<Diagnostic>> {
+    let diagnostics = crate::validation::collect_diagnostics(protocol);
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return Err(diagnostics);
     }
+    sort_protocol_by_dependencies(protocol)
+        .map(|sorted| canonicalize_singleton_ranges(&sorted))
+        .map_err(|error| vec![Diagnostic::error(error.to_string())])
 }
 
-/// Extracts the names of all custom type identifiers from a structure definition.
-fn extract_structure_subtypes(structure_def: &StructureDefinition) -> Vec<String> {
-    structure_def
-        .fields
-        .iter()
-        .filter_map(|field| extract_custom_type_identifier_name(&field.r#type))
-        .collect()
+/// Same as [`prepare`], but keeps the warnings of a valid protocol and fails with
+/// a [`crate::MeksmithError`]. Used by the `generate_*_from_string` functions of the smiths.
+pub(crate) fn prepare_with_warnings(
+    protocol: &Protocol,
+) -> Result<(Protocol, Vec<Diagnostic>), crate::MeksmithError> {
+    prepare_with_warnings_and_options(protocol, &ValidationOptions::default())
 }
 
-/// Extracts the names of all custom type identifiers from a union definition.
-fn extract_union_subtypes(union_def: &UnionDefinition) -> Vec<String> {
-    union_def
-        .fields
-        .iter()
-        .filter_map(|field| match field {
-            UnionField::SingleValue { r#type, .. } => extract_custom_type_identifier_name(r#type),
-            UnionField::RangeOfValues { r#type, .. } => extract_custom_type_identifier_name(r#type),
-        })
-        .collect()
+/// Same as [`prepare_with_warnings`], validating the protocol with the given options.
+pub(crate) fn prepare_with_warnings_and_options(
+    protocol: &Protocol,
+    options: &ValidationOptions,
+) -> Result<(Protocol, Vec<Diagnostic>), crate::MeksmithError> {
+    let warnings = crate::validation::collect_warnings_or_errors(protocol, options)?;
+    let sorted = sort_protocol_by_dependencies(protocol)?;
+    Ok((canonicalize_singleton_ranges(&sorted), warnings))
 }
 
 /// Sorts the protocol definitions using their dependencies, meaning that if
 /// a type `A` depends on type `B`, then `B` should appear before `A` in the sorted list.
 /// This function returns a new `Protocol` with the definitions sorted accordingly.
 /// If a circular dependency is detected, it returns an error.
-pub(crate) fn sort_protocol_by_dependencies(protocol: &Protocol) -> Result<Protocol, String> {
+pub(crate) fn sort_protocol_by_dependencies(
+    protocol: &Protocol,
+) -> Result<Protocol, crate::MeksmithError> {
     use std::collections::{HashMap, HashSet};
 
     let mut sorted_definitions = Vec::new();
@@ -187,16 +1830,11 @@ pub(crate) fn sort_protocol_by_dependencies(protocol: &Protocol) -> Result<Proto
         temp_mark: &mut HashSet<String>,
         sorted_definitions: &mut Vec<Definition>,
         definitions_map: &HashMap<String, Definition>,
-    ) -> Result<(), String> {
-        let name = match def {
-            Definition::Enumeration(enumeration_def) => enumeration_def.name.name.clone(),
-            Definition::Structure(structure_def) => structure_def.name.name.clone(),
-            Definition::Union(union_def) => union_def.name.name.clone(),
-            Definition::Type(type_def) => type_def.new_type.name.clone(),
-        };
+    ) -> Result<(), crate::MeksmithError> {
+        let name = def.name().name.clone();
 
         if temp_mark.contains(&name) {
-            return Err(format!("Circular dependency detected for {name}"));
+            return Err(crate::MeksmithError::CircularDependency(name));
         }
         if visited.contains(&name) {
             return Ok(());
@@ -205,7 +1843,7 @@ pub(crate) fn sort_protocol_by_dependencies(protocol: &Protocol) -> Result<Proto
         temp_mark.insert(name.clone());
 
         match def {
-            Definition::Enumeration(_) => {}
+            Definition::Enumeration(_) | Definition::Flags(_) | Definition::Constant(_) => {}
             Definition::Structure(structure_def) => {
                 for subtype in extract_structure_subtypes(structure_def) {
                     if let Some(subtype_def) = definitions_map.get(&subtype) {
@@ -233,16 +1871,16 @@ pub(crate) fn sort_protocol_by_dependencies(protocol: &Protocol) -> Result<Proto
                 }
             }
             Definition::Type(type_def) => {
-                if let Some(type_name) = extract_custom_type_identifier_name(&type_def.r#type)
-                    && let Some(type_def_ref) = definitions_map.get(&type_name)
-                {
-                    visit(
-                        type_def_ref,
-                        visited,
-                        temp_mark,
-                        sorted_definitions,
-                        definitions_map,
-                    )?;
+                for type_name in extract_type_identifier_dependencies(&type_def.r#type) {
+                    if let Some(type_def_ref) = definitions_map.get(&type_name) {
+                        visit(
+                            type_def_ref,
+                            visited,
+                            temp_mark,
+                            sorted_definitions,
+                            definitions_map,
+                        )?;
+                    }
                 }
             }
         }
@@ -258,32 +1896,11 @@ pub(crate) fn sort_protocol_by_dependencies(protocol: &Protocol) -> Result<Proto
         .definitions
         .iter()
         .cloned()
-        .map(|def| match def {
-            Definition::Enumeration(enumeration_def) => (
-                enumeration_def.name.name.clone(),
-                Definition::Enumeration(enumeration_def),
-            ),
-            Definition::Structure(structure_def) => (
-                structure_def.name.name.clone(),
-                Definition::Structure(structure_def),
-            ),
-            Definition::Union(union_def) => {
-                (union_def.name.name.clone(), Definition::Union(union_def))
-            }
-            Definition::Type(type_def) => {
-                (type_def.new_type.name.clone(), Definition::Type(type_def))
-            }
-        })
+        .map(|def| (def.name().name.clone(), def))
         .collect();
 
     for def in &protocol.definitions {
-        let name = match def {
-            Definition::Enumeration(enumeration_def) => enumeration_def.name.name.clone(),
-            Definition::Structure(structure_def) => structure_def.name.name.clone(),
-            Definition::Union(union_def) => union_def.name.name.clone(),
-            Definition::Type(type_def) => type_def.new_type.name.clone(),
-        };
-        if !visited.contains(&name) {
+        if !visited.contains(&def.name().name) {
             visit(
                 def,
                 &mut visited,
@@ -295,6 +1912,8 @@ pub(crate) fn sort_protocol_by_dependencies(protocol: &Protocol) -> Result<Proto
     }
 
     Ok(Protocol {
+        name: protocol.name.clone(),
+        version: protocol.version,
         definitions: sorted_definitions,
     })
 }
@@ -305,6 +1924,22 @@ mod tests {
     use crate::parse_protocol_to_ast;
     use rstest::rstest;
 
+    #[rstest]
+    #[case::enumeration("enum Kind { a = 0; };", "Kind", DefinitionKind::Enumeration)]
+    #[case::structure("struct Message { a: uint8; };", "Message", DefinitionKind::Structure)]
+    #[case::union("union Payload { 0 => a: uint8; };", "Payload", DefinitionKind::Union)]
+    #[case::type_definition("using Bytes = byte[];", "Bytes", DefinitionKind::Type)]
+    #[case::flags("flags Mode : uint8 { a = 1; };", "Mode", DefinitionKind::Flags)]
+    fn test_definition_name_and_kind(
+        #[case] input: &str,
+        #[case] name: &str,
+        #[case] kind: DefinitionKind,
+    ) {
+        let protocol = parse_protocol_to_ast(input).unwrap();
+        assert_eq!(protocol.definitions[0].name().name, name);
+        assert_eq!(protocol.definitions[0].kind(), kind);
+    }
+
     #[test]
     fn test_extract_custom_type_identifier_name_user_defined() {
         let type_id = TypeIdentifier::UserDefined(Identifier::new("CustomType"));
@@ -319,6 +1954,7 @@ mod tests {
         let type_id = TypeIdentifier::StaticArray {
             r#type: Box::new(TypeIdentifier::UserDefined(Identifier::new("CustomType"))),
             size: 10,
+            size_constant: None,
         };
         assert_eq!(
             extract_custom_type_identifier_name(&type_id),
@@ -337,6 +1973,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extract_definition_subtypes_of_nested_arrays() {
+        let protocol = crate::parse_protocol_to_ast(
+            "const N = 2; struct Cell { value: uint8; }; using Grid = Cell[][4][N];",
+        )
+        .unwrap();
+        let Definition::Type(grid) = &protocol.definitions[2] else {
+            panic!("expected a type definition");
+        };
+        assert_eq!(
+            extract_definition_subtypes(&protocol.definitions[2]),
+            vec!["Cell".to_string(), "N".to_string()]
+        );
+        assert_eq!(type_identifier_name(&grid.r#type), "Cell[][4][N]");
+        let TypeIdentifier::DynamicArray { r#type: rows } = &grid.r#type else {
+            panic!("expected a dynamic array");
+        };
+        let TypeIdentifier::StaticArray { r#type: row, .. } = rows.as_ref() else {
+            panic!("expected a static array");
+        };
+        assert!(matches!(
+            row.as_ref(),
+            TypeIdentifier::StaticArray { size: 2, .. }
+        ));
+    }
+
     #[rstest]
     #[case(TypeIdentifier::Integer8)]
     #[case(TypeIdentifier::Integer16)]
@@ -354,27 +2016,125 @@ mod tests {
         assert_eq!(extract_custom_type_identifier_name(&type_id), None);
     }
 
+    #[rstest]
+    #[case(TypeIdentifier::Integer8, Some(8))]
+    #[case(TypeIdentifier::UnsignedInteger16, Some(16))]
+    #[case(TypeIdentifier::Float32, Some(32))]
+    #[case(TypeIdentifier::Integer64, Some(64))]
+    #[case(TypeIdentifier::Bit, Some(1))]
+    #[case(TypeIdentifier::Byte, Some(8))]
+    #[case(TypeIdentifier::UserDefined(Identifier::new("CustomType")), None)]
+    #[case(TypeIdentifier::DynamicArray { r#type: Box::new(TypeIdentifier::Byte) }, None)]
+    fn test_builtin_size_in_bits(#[case] type_id: TypeIdentifier, #[case] expected: Option<u64>) {
+        assert_eq!(type_id.builtin_size_in_bits(), expected);
+    }
+
+    #[test]
+    fn test_builtin_types_table() {
+        let table = builtin_types_table();
+        assert_eq!(table.len(), BUILTIN_TYPES.len());
+        assert!(table.contains(&("int32", 32)));
+        assert!(table.contains(&("bit", 1)));
+        assert!(table.contains(&("byte", 8)));
+    }
+
+    #[rstest]
+    #[case(1, 8)]
+    #[case(0xFF, 8)]
+    #[case(0x100, 16)]
+    #[case(0x1FFFF, 32)]
+    #[case(0x1_0000_0000, 64)]
+    #[case(u64::MAX.into(), 64)]
+    fn test_enumeration_size_in_bits(#[case] end: i128, #[case] expected: u64) {
+        let enumeration = EnumerationDefinition {
+            attributes: vec![],
+            name: Identifier::new("MyEnum"),
+            base: None,
+            fields: vec![
+                EnumerationField::SingleValue {
+                    name: Identifier::new("first"),
+                    value: 0,
+                    doc: None,
+                },
+                EnumerationField::RangeOfValues {
+                    name: Identifier::new("rest"),
+                    start: 1,
+                    end,
+                    doc: None,
+                },
+            ],
+            doc: None,
+        };
+        assert_eq!(enumeration.max_value(), end);
+        assert_eq!(enumeration.size_in_bits(), expected);
+    }
+
+    #[rstest]
+    #[case(-1, 1, 8)]
+    #[case(-128, 127, 8)]
+    #[case(-129, 0, 16)]
+    #[case(-1, 128, 16)]
+    #[case(-40000, 0, 32)]
+    #[case(-1, 0x8000_0000, 64)]
+    fn test_signed_enumeration_size_in_bits(
+        #[case] start: i128,
+        #[case] end: i128,
+        #[case] expected: u64,
+    ) {
+        let enumeration = EnumerationDefinition {
+            attributes: vec![],
+            name: Identifier::new("MyEnum"),
+            base: None,
+            fields: vec![EnumerationField::RangeOfValues {
+                name: Identifier::new("values"),
+                start,
+                end,
+                doc: None,
+            }],
+            doc: None,
+        };
+        assert!(enumeration.is_signed());
+        assert_eq!(enumeration.min_value(), start);
+        assert_eq!(enumeration.size_in_bits(), expected);
+    }
+
+    #[rstest]
+    #[case(3, "reserved_3")]
+    #[case(-3, "reserved_minus_3")]
+    #[case(i64::MIN.into(), "reserved_minus_9223372036854775808")]
+    fn test_range_member_name(#[case] value: i128, #[case] expected: &str) {
+        assert_eq!(range_member_name("reserved", value), expected);
+    }
+
     #[test]
     fn test_extract_structure_subtypes() {
         let structure_def = StructureDefinition {
+            attributes: vec![],
             name: Identifier::new("TestStructure"),
             fields: vec![
                 StructureField {
                     name: Identifier::new("field1"),
                     r#type: TypeIdentifier::UserDefined(Identifier::new("SubType1")),
                     attributes: vec![],
+                    doc: None,
+                    reserved: false,
                 },
                 StructureField {
                     name: Identifier::new("field2"),
                     r#type: TypeIdentifier::Integer32,
                     attributes: vec![],
+                    doc: None,
+                    reserved: false,
                 },
                 StructureField {
                     name: Identifier::new("field3"),
                     r#type: TypeIdentifier::UserDefined(Identifier::new("SubType2")),
                     attributes: vec![],
+                    doc: None,
+                    reserved: false,
                 },
             ],
+            doc: None,
         };
 
         let subtypes = extract_structure_subtypes(&structure_def);
@@ -387,6 +2147,7 @@ mod tests {
     #[test]
     fn test_extract_union_subtypes() {
         let union_def = UnionDefinition {
+            attributes: vec![],
             name: Identifier::new("TestUnion"),
             fields: vec![
                 UnionField::SingleValue {
@@ -406,6 +2167,7 @@ mod tests {
                     discriminator: 6,
                 },
             ],
+            doc: None,
         };
 
         let subtypes = extract_union_subtypes(&union_def);
@@ -419,12 +2181,7 @@ mod tests {
         protocol
             .definitions
             .iter()
-            .position(|def| match def {
-                Definition::Enumeration(enumeration_def) => enumeration_def.name.name == name,
-                Definition::Structure(structure_def) => structure_def.name.name == name,
-                Definition::Union(union_def) => union_def.name.name == name,
-                Definition::Type(type_def) => type_def.new_type.name == name,
-            })
+            .position(|def| def.name().name == name)
             .expect("Definition not found")
     }
 
@@ -462,6 +2219,23 @@ mod tests {
         assert_def_is_before_another_def(&sorted, "PingPong", "Message");
     }
 
+    #[test]
+    fn test_sort_protocol_by_dependencies_with_constants() {
+        let code = r#"
+struct Message { data: Buffer; names: Name[MAX_NAMES]; };
+using Buffer = byte[MAX_LEN];
+struct Name { text: byte[MAX_LEN]; };
+const MAX_NAMES = 4;
+const MAX_LEN = 256;
+"#;
+        let parsed = parse_protocol_to_ast(code).expect("Parsing failed");
+        let sorted = sort_protocol_by_dependencies(&parsed).expect("Sorting failed");
+
+        assert_def_is_before_another_def(&sorted, "MAX_LEN", "Buffer");
+        assert_def_is_before_another_def(&sorted, "MAX_LEN", "Name");
+        assert_def_is_before_another_def(&sorted, "MAX_NAMES", "Message");
+    }
+
     #[test]
     fn test_sort_protocol_by_dependencies_with_circular_dependency() {
         let code = r#"
@@ -476,7 +2250,408 @@ struct B {
         let parsed = parse_protocol_to_ast(code).expect("Parsing failed");
         let sorted = sort_protocol_by_dependencies(&parsed);
 
-        assert!(sorted.is_err(), "Failed to detect circular dependency");
-        assert_eq!(sorted.err().unwrap(), "Circular dependency detected for A");
+        assert!(
+            matches!(sorted, Err(crate::MeksmithError::CircularDependency(name)) if name == "A"),
+            "Failed to detect circular dependency"
+        );
+    }
+
+    #[rstest]
+    #[case::fixed_structure("Fixed", true)]
+    #[case::dynamic_array("WithBytes", false)]
+    #[case::nested_dynamic_array("Nested", false)]
+    #[case::alias_of_dynamic_array("Bytes", false)]
+    #[case::union_with_dynamic_array("Payload", false)]
+    #[case::enumeration("Kind", true)]
+    #[case::undefined_type("Missing", false)]
+    fn test_is_memcpy_safe(#[case] type_name: &str, #[case] expected: bool) {
+        let protocol = parse_protocol_to_ast(
+            r#"
+enum Kind { a = 0; };
+using Bytes = byte[];
+struct Fixed { kind: Kind; values: int32[4]; };
+struct WithBytes { data: byte[]; };
+struct Nested { fixed: Fixed; inner: WithBytes[2]; };
+union Payload { 0 => fixed: Fixed; 1 => bytes: Bytes; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(is_memcpy_safe(&protocol, type_name), expected);
+    }
+
+    fn field_layout(
+        name: &str,
+        offset_in_bits: Option<u64>,
+        size_in_bits: Option<u64>,
+    ) -> FieldLayout {
+        FieldLayout {
+            name: name.to_string(),
+            offset_in_bits,
+            size_in_bits,
+        }
+    }
+
+    #[rstest]
+    #[case::without_alignment(false, 3)]
+    #[case::with_alignment(true, 8)]
+    fn test_structure_layout_aligns_bytes_fields(
+        #[case] align_bytes_fields_to_byte: bool,
+        #[case] aligned_offset: u64,
+    ) {
+        let protocol = parse_protocol_to_ast(
+            "struct Header { [bits=3] version: uint8; [bytes=1] flags: uint8; length: uint16; };",
+        )
+        .unwrap();
+        let options = LayoutOptions {
+            align_bytes_fields_to_byte,
+        };
+        assert_eq!(
+            structure_layout(&protocol, "Header", &options),
+            Ok(vec![
+                field_layout("version", Some(0), Some(3)),
+                field_layout("flags", Some(aligned_offset), Some(8)),
+                field_layout("length", Some(aligned_offset + 8), Some(16)),
+            ])
+        );
+    }
+
+    #[rstest]
+    #[case::fixed_variants("Fixed", Ok(Some(40)))]
+    #[case::dynamic_array_variant("Dynamic", Ok(None))]
+    #[case::void_variants("Signal", Ok(Some(8)))]
+    #[case::only_void_variants("Empty", Ok(Some(0)))]
+    #[case::not_a_union("Pair", Err("Union Pair is not defined".to_string()))]
+    fn test_union_max_size_in_bits(
+        #[case] name: &str,
+        #[case] expected: Result<Option<u64>, String>,
+    ) {
+        let protocol = parse_protocol_to_ast(
+            r#"
+struct Pair { first: uint32; second: uint8; };
+union Fixed { 0 => small: uint8; 1 => pair: Pair; 2..3 => flag: bit; };
+union Dynamic { 0 => small: uint8; 1 => data: byte[]; };
+union Signal { 0 => none: void; 1 => value: uint8; 2..3 => reserved: void; };
+union Empty { 0 => none: void; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(union_max_size_in_bits(&protocol, name), expected);
+    }
+
+    #[rstest]
+    #[case::enumeration("Kind", Ok(Some(8)))]
+    #[case::structure("Pair", Ok(Some(40)))]
+    #[case::union("Choice", Ok(Some(40)))]
+    #[case::alias_of_union("Alias", Ok(Some(40)))]
+    #[case::flags("Permissions", Ok(Some(16)))]
+    #[case::dynamic("Data", Ok(None))]
+    #[case::large_array("Large", Ok(Some(800_000_000)))]
+    #[case::overflowing_array(
+        "Overflowing",
+        Err("Size of an array of 18446744073709551615 elements does not fit in 64 bits".to_string())
+    )]
+    #[case::overflowing_structure(
+        "Wide",
+        Err("Size of structure Wide does not fit in 64 bits".to_string())
+    )]
+    #[case::undefined("Missing", Err("Definition Missing is not defined".to_string()))]
+    fn test_definition_size_in_bits(
+        #[case] name: &str,
+        #[case] expected: Result<Option<u64>, String>,
+    ) {
+        let protocol = parse_protocol_to_ast(
+            r#"
+enum Kind { a = 0; b = 3; };
+struct Pair { first: uint32; second: uint8; };
+union Choice { 0 => small: uint8; 1 => pair: Pair; };
+using Alias = Choice;
+flags Permissions : uint16 { read = 1; };
+using Data = byte[];
+using Large = byte[100000000];
+using Overflowing = uint64[18446744073709551615];
+struct Wide { first: byte[2305843009213693951]; second: uint16; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(definition_size_in_bits(&protocol, name), expected);
+    }
+
+    #[rstest]
+    #[case::one_level(&["header"], Ok("header"))]
+    #[case::two_levels(&["header", "kind"], Ok("kind"))]
+    #[case::through_alias(&["tail", "kind"], Ok("kind"))]
+    #[case::nonexistent_segment(&["header", "missing"], Err("Field missing is not defined in Header"))]
+    #[case::not_a_structure(&["length", "kind"], Err("Field length in Packet is not a structure"))]
+    #[case::empty(&[], Err("Field path in Packet is empty"))]
+    fn test_resolve_field_path(#[case] path: &[&str], #[case] expected: Result<&str, &str>) {
+        let protocol = parse_protocol_to_ast(
+            r#"
+struct Header { kind: uint8; };
+using Trailer = Header;
+struct Packet { header: Header; length: uint16; tail: Trailer; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            resolve_field_path(&protocol, "Packet", path).map(|field| field.name.name.as_str()),
+            expected.map_err(str::to_string)
+        );
+    }
+
+    #[test]
+    fn test_structure_layout_with_nested_and_variable_size_fields() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+enum Kind { a = 0; b = 0x1FF; };
+struct Inner { [bits=1] flag: bit; [bytes=2] value: uint32; };
+struct Outer { inner: Inner[2]; kind: Kind; data: byte[]; tail: uint8; };
+struct Odd { [bytes=1] value: uint8; [bits=1] flag: bit; };
+struct Padded { odd: Odd[3]; tail: uint8; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            structure_layout(&protocol, "Outer", &LayoutOptions::default()),
+            Ok(vec![
+                field_layout("inner", Some(0), Some(34)),
+                field_layout("kind", Some(34), Some(16)),
+                field_layout("data", Some(50), None),
+                field_layout("tail", None, Some(8)),
+            ])
+        );
+        let options = LayoutOptions {
+            align_bytes_fields_to_byte: true,
+        };
+        assert_eq!(
+            structure_layout(&protocol, "Outer", &options).unwrap()[0],
+            field_layout("inner", Some(0), Some(48))
+        );
+        assert_eq!(
+            structure_layout(&protocol, "Padded", &options),
+            Ok(vec![
+                field_layout("odd", Some(0), Some(41)),
+                field_layout("tail", Some(41), Some(8)),
+            ])
+        );
+        assert_eq!(
+            structure_layout(&protocol, "Missing", &options),
+            Err("Structure Missing is not defined".to_string())
+        );
+    }
+
+    #[test]
+    fn test_prepare_with_undefined_type() {
+        let parsed = parse_protocol_to_ast("struct A { field1: Missing; };").unwrap();
+        assert_eq!(
+            prepare(&parsed),
+            Err(vec![Diagnostic::error(
+                "Type Missing of field field1 in A is not defined".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    fn test_prepare_with_valid_protocol() {
+        let ping_pong = include_str!("../examples/data/ping-pong.mek");
+        let parsed = parse_protocol_to_ast(ping_pong).unwrap();
+        let prepared = prepare(&parsed).expect("Failed to prepare protocol");
+        assert_eq!(prepared, sort_protocol_by_dependencies(&parsed).unwrap());
+    }
+
+    #[test]
+    fn test_prepare_with_circular_dependency() {
+        let parsed =
+            parse_protocol_to_ast("struct A { field1: B; }; struct B { field1: A; };").unwrap();
+        assert_eq!(
+            prepare(&parsed),
+            Err(vec![Diagnostic::error(
+                "Circular dependency detected for A".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_singleton_ranges() {
+        let protocol = parse_protocol_to_ast(
+            "enum Kind { a = 0..1; x = 6..6; }; union Body { 0..1 => a: uint8; 6..6 => x: uint16; };",
+        )
+        .unwrap();
+        let canonical = canonicalize_singleton_ranges(&protocol);
+        let Definition::Enumeration(enumeration) = &canonical.definitions[0] else {
+            panic!("expected an enumeration");
+        };
+        assert!(matches!(
+            enumeration.fields[0],
+            EnumerationField::RangeOfValues {
+                start: 0,
+                end: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            enumeration.fields[1],
+            EnumerationField::SingleValue {
+                name: Identifier::new("x"),
+                value: 6,
+                doc: None,
+            }
+        );
+        let Definition::Union(union) = &canonical.definitions[1] else {
+            panic!("expected a union");
+        };
+        assert!(matches!(union.fields[0], UnionField::RangeOfValues { .. }));
+        assert_eq!(
+            union.fields[1],
+            UnionField::SingleValue {
+                name: Identifier::new("x"),
+                r#type: TypeIdentifier::UnsignedInteger16,
+                discriminator: 6,
+            }
+        );
+    }
+
+    static PATCH_BASE: &str = "enum Kind { a = 0; }; struct Message { kind: Kind; };";
+
+    #[rstest]
+    #[case::added_definition(
+        "enum Kind { a = 0; }; struct Message { kind: Kind; }; struct Extra { value: uint8; };",
+        "struct Extra { value: uint8; };"
+    )]
+    #[case::added_members(
+        "enum Kind { b = 1; a = 0; }; struct Message { kind: Kind; value: uint16; };",
+        "enum Kind { b = 1; }; struct Message { value: uint16; };"
+    )]
+    #[case::unchanged(PATCH_BASE, "")]
+    #[case::documented(
+        "enum Kind {\n# First\na = 0; };\nstruct Message {\n# The kind\nkind: Kind; };",
+        ""
+    )]
+    fn test_generate_additive_patch(#[case] new: &str, #[case] expected: &str) {
+        let old = parse_protocol_to_ast(PATCH_BASE).unwrap();
+        let new = parse_protocol_to_ast(new).unwrap();
+        assert_eq!(
+            generate_additive_patch(&old, &new),
+            Ok(parse_protocol_to_ast(expected).unwrap())
+        );
+    }
+
+    #[rstest]
+    #[case::unchanged("const MAX_LEN = 4; struct Message { data: byte[MAX_LEN]; };", Ok(""))]
+    #[case::added_field(
+        "# The limit\nconst MAX_LEN = 4; struct Message { data: byte[MAX_LEN]; tail: uint8; };",
+        Ok("struct Message { tail: uint8; };")
+    )]
+    #[case::changed_value(
+        "const MAX_LEN = 8; struct Message { data: byte[MAX_LEN]; };",
+        Err("Constant MAX_LEN changes its value")
+    )]
+    fn test_generate_additive_patch_with_constant(
+        #[case] new: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let old =
+            parse_protocol_to_ast("const MAX_LEN = 4; struct Message { data: byte[MAX_LEN]; };")
+                .unwrap();
+        let new = parse_protocol_to_ast(new).unwrap();
+        assert_eq!(
+            generate_additive_patch(&old, &new),
+            expected
+                .map(|expected| parse_protocol_to_ast(expected).unwrap())
+                .map_err(str::to_string)
+        );
+    }
+
+    #[rstest]
+    #[case::removed_definition("struct Message { kind: uint8; };", "Definition Kind is removed")]
+    #[case::inserted_field(
+        "enum Kind { a = 0; }; struct Message { value: uint16; kind: Kind; };",
+        "Structure Message changes or removes fields, or adds fields before existing ones"
+    )]
+    #[case::changed_value(
+        "enum Kind { a = 1; }; struct Message { kind: Kind; };",
+        "Enumeration Kind changes or removes values"
+    )]
+    #[case::changed_kind(
+        "using Kind = uint8; struct Message { kind: Kind; };",
+        "Definition Kind changes its kind"
+    )]
+    #[case::changed_attributes(
+        "enum Kind { a = 0; }; [id=1] struct Message { kind: Kind; };",
+        "Definition Message changes its attributes"
+    )]
+    fn test_generate_additive_patch_with_breaking_change(
+        #[case] new: &str,
+        #[case] expected: &str,
+    ) {
+        let old = parse_protocol_to_ast(PATCH_BASE).unwrap();
+        let new = parse_protocol_to_ast(new).unwrap();
+        assert_eq!(
+            generate_additive_patch(&old, &new),
+            Err(expected.to_string())
+        );
+    }
+
+    #[test]
+    fn test_protocol_hash_does_not_depend_on_definition_order() {
+        let first = parse_protocol_to_ast(
+            "enum Kind { a = 1; }; struct Message { kind: Kind; value: uint8; };",
+        )
+        .unwrap();
+        let second = parse_protocol_to_ast(
+            "struct Message { kind: Kind; value: uint8; }; enum Kind { a = 1; };",
+        )
+        .unwrap();
+        assert_eq!(protocol_hash(&first), protocol_hash(&second));
+    }
+
+    #[test]
+    fn test_protocol_hash_does_not_depend_on_doc_comments() {
+        let original = parse_protocol_to_ast(
+            "enum Kind { a = 1; }; struct Message { kind: Kind; value: uint8; };",
+        )
+        .unwrap();
+        let documented = parse_protocol_to_ast(
+            "# Kinds\nenum Kind {\n# First\na = 1; };\nstruct Message {\n# The value\nkind: Kind; value: uint8; };",
+        )
+        .unwrap();
+        assert_eq!(protocol_hash(&original), protocol_hash(&documented));
+    }
+
+    #[test]
+    fn test_protocol_hash_is_stable() {
+        let protocol = parse_protocol_to_ast(
+            "module Pinned version 3; enum Kind { a = 1; }; struct Message { kind: Kind; [bits=4] value: uint8; };",
+        )
+        .unwrap();
+        assert_eq!(protocol_hash(&protocol), 0x5fdf516d34eed555);
+    }
+
+    #[rstest]
+    #[case::field_type("enum Kind { a = 1; }; struct Message { kind: Kind; value: uint16; };")]
+    #[case::field_order("enum Kind { a = 1; }; struct Message { value: uint8; kind: Kind; };")]
+    #[case::enum_value("enum Kind { a = 2; }; struct Message { kind: Kind; value: uint8; };")]
+    #[case::attribute(
+        "enum Kind { a = 1; }; struct Message { kind: Kind; [bits=4] value: uint8; };"
+    )]
+    fn test_protocol_hash_changes_with_content(#[case] changed: &str) {
+        let original = parse_protocol_to_ast(
+            "enum Kind { a = 1; }; struct Message { kind: Kind; value: uint8; };",
+        )
+        .unwrap();
+        let changed = parse_protocol_to_ast(changed).unwrap();
+        assert_ne!(protocol_hash(&original), protocol_hash(&changed));
+    }
+
+    #[rstest]
+    #[case::version("module Telemetry version 2; struct Message { value: uint8; };")]
+    #[case::without_version("module Telemetry; struct Message { value: uint8; };")]
+    #[case::name("module Metrics version 1; struct Message { value: uint8; };")]
+    #[case::without_module("struct Message { value: uint8; };")]
+    fn test_protocol_hash_changes_with_module_header(#[case] changed: &str) {
+        let original =
+            parse_protocol_to_ast("module Telemetry version 1; struct Message { value: uint8; };")
+                .unwrap();
+        let changed = parse_protocol_to_ast(changed).unwrap();
+        assert_ne!(protocol_hash(&original), protocol_hash(&changed));
     }
 }
@@ -0,0 +1,170 @@
+//! Language server for meklang, talking the Language Server Protocol over stdio. It publishes
+//! the diagnostics of every opened document on change and shows sizes of types on hover.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as LspNotification, PublishDiagnostics,
+};
+use lsp_types::request::{HoverRequest, Request as LspRequest};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    MarkupContent, MarkupKind, Position, PublishDiagnosticsParams, Range, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+};
+use meksmith::lsp::DocumentDiagnostic;
+use meksmith::validation::Severity;
+
+fn to_lsp_position(position: meksmith::lsp::Position) -> Position {
+    Position::new(position.line, position.character)
+}
+
+fn to_lsp_diagnostic(diagnostic: DocumentDiagnostic) -> Diagnostic {
+    Diagnostic {
+        range: Range::new(
+            to_lsp_position(diagnostic.start),
+            to_lsp_position(diagnostic.end),
+        ),
+        severity: Some(match diagnostic.severity {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+        }),
+        source: Some("meksmith".to_string()),
+        message: diagnostic.message,
+        ..Default::default()
+    }
+}
+
+fn publish_diagnostics(
+    connection: &Connection,
+    uri: Uri,
+    text: &str,
+) -> Result<(), Box<dyn Error>> {
+    let params = PublishDiagnosticsParams {
+        uri,
+        diagnostics: meksmith::lsp::document_diagnostics(text)
+            .into_iter()
+            .map(to_lsp_diagnostic)
+            .collect(),
+        version: None,
+    };
+    connection
+        .sender
+        .send(Message::Notification(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            params,
+        )))?;
+    Ok(())
+}
+
+fn handle_notification(
+    connection: &Connection,
+    documents: &mut HashMap<String, String>,
+    notification: Notification,
+) -> Result<(), Box<dyn Error>> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: <DidOpenTextDocument as LspNotification>::Params =
+                serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            publish_diagnostics(connection, uri.clone(), &params.text_document.text)?;
+            documents.insert(uri.as_str().to_string(), params.text_document.text);
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: <DidChangeTextDocument as LspNotification>::Params =
+                serde_json::from_value(notification.params)?;
+            // The full text is synchronized, so the last change holds the whole document
+            if let Some(change) = params.content_changes.into_iter().last() {
+                let uri = params.text_document.uri;
+                publish_diagnostics(connection, uri.clone(), &change.text)?;
+                documents.insert(uri.as_str().to_string(), change.text);
+            }
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: <DidCloseTextDocument as LspNotification>::Params =
+                serde_json::from_value(notification.params)?;
+            documents.remove(params.text_document.uri.as_str());
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_request(
+    connection: &Connection,
+    documents: &HashMap<String, String>,
+    request: Request,
+) -> Result<(), Box<dyn Error>> {
+    let response = match request.method.as_str() {
+        HoverRequest::METHOD => {
+            let params: HoverParams = serde_json::from_value(request.params)?;
+            let position = params.text_document_position_params.position;
+            let hover = documents
+                .get(
+                    params
+                        .text_document_position_params
+                        .text_document
+                        .uri
+                        .as_str(),
+                )
+                .and_then(|text| {
+                    meksmith::lsp::hover(
+                        text,
+                        meksmith::lsp::Position {
+                            line: position.line,
+                            character: position.character,
+                        },
+                    )
+                })
+                .map(|value| Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::PlainText,
+                        value,
+                    }),
+                    range: None,
+                });
+            Response::new_ok(request.id, hover)
+        }
+        _ => Response::new_err(
+            request.id,
+            lsp_server::ErrorCode::MethodNotFound as i32,
+            format!("Unsupported request {}", request.method),
+        ),
+    };
+    connection.sender.send(Message::Response(response))?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    // Keyed by the text of the URI, as the URI itself caches its parts in interior mutable cells
+    let mut documents = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                handle_request(&connection, &documents, request)?;
+            }
+            Message::Notification(notification) => {
+                handle_notification(&connection, &mut documents, notification)?;
+            }
+            Message::Response(_) => {}
+        }
+    }
+    // The writer thread finishes only once the connection is dropped
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
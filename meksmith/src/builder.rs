@@ -0,0 +1,398 @@
+use crate::ast::{
+    Attribute, Definition, EnumerationDefinition, EnumerationField, FlagsDefinition, FlagsField,
+    Identifier, Protocol, StructureDefinition, StructureField, TypeDefinition, TypeIdentifier,
+    UnionDefinition, UnionField,
+};
+
+/// Builds a [`Protocol`] programmatically, without nesting the AST structures by hand:
+///
+/// ```
+/// use meksmith::ast::{Identifier, TypeIdentifier};
+/// use meksmith::builder::ProtocolBuilder;
+///
+/// let protocol = ProtocolBuilder::new()
+///     .enum_("Kind", |e| e.value("ping", 0).value("pong", 1))
+///     .struct_("Message", |s| {
+///         s.field("kind", TypeIdentifier::UserDefined(Identifier::new("Kind")))
+///             .field("payload", TypeIdentifier::DynamicArray {
+///                 r#type: Box::new(TypeIdentifier::Byte),
+///             })
+///     })
+///     .build()
+///     .unwrap();
+/// assert_eq!(protocol.definitions.len(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProtocolBuilder {
+    name: Option<String>,
+    version: Option<u64>,
+    definitions: Vec<Definition>,
+}
+
+impl ProtocolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names the module of the protocol and optionally gives its version, like the `module`
+    /// header of meklang.
+    pub fn module(mut self, name: &str, version: Option<u64>) -> Self {
+        self.name = Some(name.to_string());
+        self.version = version;
+        self
+    }
+
+    pub fn struct_(
+        mut self,
+        name: &str,
+        build: impl FnOnce(StructureBuilder) -> StructureBuilder,
+    ) -> Self {
+        let builder = build(StructureBuilder {
+            definition: StructureDefinition {
+                name: Identifier::new(name),
+                fields: vec![],
+                attributes: vec![],
+                doc: None,
+            },
+        });
+        self.definitions
+            .push(Definition::Structure(builder.definition));
+        self
+    }
+
+    pub fn enum_(
+        mut self,
+        name: &str,
+        build: impl FnOnce(EnumerationBuilder) -> EnumerationBuilder,
+    ) -> Self {
+        let builder = build(EnumerationBuilder {
+            definition: EnumerationDefinition {
+                name: Identifier::new(name),
+                base: None,
+                fields: vec![],
+                attributes: vec![],
+                doc: None,
+            },
+        });
+        self.definitions
+            .push(Definition::Enumeration(builder.definition));
+        self
+    }
+
+    pub fn union(mut self, name: &str, build: impl FnOnce(UnionBuilder) -> UnionBuilder) -> Self {
+        let builder = build(UnionBuilder {
+            definition: UnionDefinition {
+                name: Identifier::new(name),
+                fields: vec![],
+                attributes: vec![],
+                doc: None,
+            },
+        });
+        self.definitions.push(Definition::Union(builder.definition));
+        self
+    }
+
+    pub fn flags(
+        mut self,
+        name: &str,
+        r#type: TypeIdentifier,
+        build: impl FnOnce(FlagsBuilder) -> FlagsBuilder,
+    ) -> Self {
+        let builder = build(FlagsBuilder {
+            definition: FlagsDefinition {
+                name: Identifier::new(name),
+                r#type,
+                fields: vec![],
+                attributes: vec![],
+                doc: None,
+            },
+        });
+        self.definitions.push(Definition::Flags(builder.definition));
+        self
+    }
+
+    /// Adds a type definition, i.e. `using <name> = <type>;`.
+    pub fn type_(mut self, name: &str, r#type: TypeIdentifier) -> Self {
+        self.definitions.push(Definition::Type(TypeDefinition {
+            new_type: Identifier::new(name),
+            r#type,
+            attributes: vec![],
+            doc: None,
+        }));
+        self
+    }
+
+    /// Adds an already constructed definition.
+    pub fn definition(mut self, definition: Definition) -> Self {
+        self.definitions.push(definition);
+        self
+    }
+
+    /// Returns the built protocol, after checking that all names are valid meklang identifiers,
+    /// that no two definitions share a name, and that the protocol passes
+    /// [`crate::validation::validate_protocol`]. All found errors are returned at once.
+    pub fn build(self) -> Result<Protocol, Vec<String>> {
+        let mut protocol = Protocol {
+            name: self.name,
+            version: self.version,
+            definitions: self.definitions,
+        };
+        crate::ast::resolve_array_size_constants(&mut protocol);
+
+        let module_name = protocol.name.clone();
+        let mut errors: Vec<String> = module_name
+            .iter()
+            .chain(
+                crate::ast::identifiers_mut(&mut protocol)
+                    .into_iter()
+                    .map(|identifier| &identifier.name),
+            )
+            .filter(|name| !is_valid_identifier(name))
+            .map(|name| format!("{name:?} is not a valid identifier"))
+            .collect();
+
+        if let Err(validation_errors) = crate::validation::validate_protocol(&protocol) {
+            errors.extend(validation_errors);
+        }
+        if errors.is_empty() {
+            Ok(protocol)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Returns whether the name would be parsed as an identifier, i.e. `[a-zA-Z_][a-zA-Z0-9_]*`.
+fn is_valid_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone)]
+pub struct StructureBuilder {
+    definition: StructureDefinition,
+}
+
+impl StructureBuilder {
+    pub fn field(self, name: &str, r#type: TypeIdentifier) -> Self {
+        self.field_with_attributes(name, r#type, vec![])
+    }
+
+    pub fn field_with_attributes(
+        mut self,
+        name: &str,
+        r#type: TypeIdentifier,
+        attributes: Vec<Attribute>,
+    ) -> Self {
+        self.definition.fields.push(StructureField {
+            name: Identifier::new(name),
+            r#type,
+            attributes,
+            doc: None,
+            reserved: StructureField::is_reserved_name(name),
+        });
+        self
+    }
+
+    /// Adds an attribute to the structure itself.
+    pub fn attribute(mut self, attribute: Attribute) -> Self {
+        self.definition.attributes.push(attribute);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumerationBuilder {
+    definition: EnumerationDefinition,
+}
+
+impl EnumerationBuilder {
+    pub fn value(mut self, name: &str, value: i128) -> Self {
+        self.definition.fields.push(EnumerationField::SingleValue {
+            name: Identifier::new(name),
+            value,
+            doc: None,
+        });
+        self
+    }
+
+    pub fn range(mut self, name: &str, start: i128, end: i128) -> Self {
+        self.definition
+            .fields
+            .push(EnumerationField::RangeOfValues {
+                name: Identifier::new(name),
+                start,
+                end,
+                doc: None,
+            });
+        self
+    }
+
+    /// Declares the type the values are stored in, i.e. `enum <name> : <type>`.
+    pub fn base(mut self, r#type: TypeIdentifier) -> Self {
+        self.definition.base = Some(r#type);
+        self
+    }
+
+    /// Adds an attribute to the enumeration itself.
+    pub fn attribute(mut self, attribute: Attribute) -> Self {
+        self.definition.attributes.push(attribute);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnionBuilder {
+    definition: UnionDefinition,
+}
+
+impl UnionBuilder {
+    pub fn variant(mut self, discriminator: u64, name: &str, r#type: TypeIdentifier) -> Self {
+        self.definition.fields.push(UnionField::SingleValue {
+            name: Identifier::new(name),
+            r#type,
+            discriminator,
+        });
+        self
+    }
+
+    pub fn range(
+        mut self,
+        start_discriminator: u64,
+        end_discriminator: u64,
+        name: &str,
+        r#type: TypeIdentifier,
+    ) -> Self {
+        self.definition.fields.push(UnionField::RangeOfValues {
+            name: Identifier::new(name),
+            r#type,
+            start_discriminator,
+            end_discriminator,
+        });
+        self
+    }
+
+    /// Adds an attribute to the union itself.
+    pub fn attribute(mut self, attribute: Attribute) -> Self {
+        self.definition.attributes.push(attribute);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FlagsBuilder {
+    definition: FlagsDefinition,
+}
+
+impl FlagsBuilder {
+    pub fn flag(mut self, name: &str, value: u64) -> Self {
+        self.definition.fields.push(FlagsField {
+            name: Identifier::new(name),
+            value,
+        });
+        self
+    }
+
+    /// Adds an attribute to the flags themselves.
+    pub fn attribute(mut self, attribute: Attribute) -> Self {
+        self.definition.attributes.push(attribute);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_defined(name: &str) -> TypeIdentifier {
+        TypeIdentifier::UserDefined(Identifier::new(name))
+    }
+
+    #[test]
+    fn test_build_equals_parsed_protocol() {
+        let protocol = ProtocolBuilder::new()
+            .module("files", Some(2))
+            .struct_("Message", |s| {
+                s.field_with_attributes(
+                    "message_type",
+                    user_defined("MessageType"),
+                    vec![Attribute::BitsSize { size: 1 }],
+                )
+                .field_with_attributes(
+                    "message",
+                    user_defined("PingPong"),
+                    vec![Attribute::DiscriminatedBy {
+                        field: Identifier::new("message_type"),
+                    }],
+                )
+            })
+            .enum_("MessageType", |e| e.value("ping", 0).range("pong", 1, 3))
+            .union("PingPong", |u| {
+                u.variant(0, "ping", user_defined("Ping")).range(
+                    1,
+                    3,
+                    "pong",
+                    TypeIdentifier::UnsignedInteger32,
+                )
+            })
+            .type_(
+                "Ping",
+                TypeIdentifier::StaticArray {
+                    r#type: Box::new(TypeIdentifier::Byte),
+                    size: 4,
+                    size_constant: None,
+                },
+            )
+            .flags("Permissions", TypeIdentifier::UnsignedInteger8, |f| {
+                f.flag("read", 1).flag("write", 2)
+            })
+            .struct_("File", |s| {
+                s.attribute(Attribute::Id { value: 7 })
+                    .field("permissions", user_defined("Permissions"))
+            })
+            .build()
+            .unwrap();
+
+        let parsed = crate::parse_protocol_to_ast(
+            r#"
+module files version 2;
+struct Message {
+    [bits=1] message_type: MessageType;
+    [discriminated_by=message_type] message: PingPong;
+};
+enum MessageType { ping = 0; pong = 1..3; };
+union PingPong { 0 => ping: Ping; 1..3 => pong: uint32; };
+using Ping = byte[4];
+flags Permissions : uint8 { read = 1; write = 2; };
+[id=7] struct File { permissions: Permissions; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(protocol, parsed);
+    }
+
+    #[test]
+    fn test_build_reports_all_errors() {
+        let result = ProtocolBuilder::new()
+            .module("ping-pong", None)
+            .struct_("Message", |s| {
+                s.field("1st", TypeIdentifier::Bit)
+                    .field("payload", user_defined("Missing"))
+            })
+            .type_("Message", TypeIdentifier::Byte)
+            .build();
+        assert_eq!(
+            result,
+            Err(vec![
+                "\"ping-pong\" is not a valid identifier".to_string(),
+                "\"1st\" is not a valid identifier".to_string(),
+                "Definition Message is defined more than once, as a structure and as a type"
+                    .to_string(),
+                "Type Missing of field payload in Message is not defined".to_string(),
+            ])
+        );
+    }
+}
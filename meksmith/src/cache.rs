@@ -0,0 +1,430 @@
+use crate::MeksmithError;
+use crate::ast::Protocol;
+use crate::pipeline::{Backend, CompileOptions};
+use crate::smith_c::CCodegenOptions;
+use crate::smith_kaitai::KaitaiCodegenOptions;
+use crate::smith_kotlin::KotlinCodegenOptions;
+use crate::smith_rust::RustCodegenOptions;
+use crate::smith_wireshark::WiresharkCodegenOptions;
+use crate::validation::ValidationOptions;
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counter making the names of temporary files unique between threads of the process.
+static TEMPORARY_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the options the output of the backend depends on as `name=value` entries, listing
+/// each option explicitly so that the entries do not change along with their debug
+/// representation. The type mapper is written as the C types it maps the built-in types to.
+fn options_key(backend: Backend, options: &CompileOptions) -> Vec<String> {
+    let CompileOptions {
+        c,
+        rust,
+        kotlin,
+        kotlin_package,
+        kaitai,
+        wireshark,
+        validation,
+    } = options;
+    let ValidationOptions {
+        warn_shadowing,
+        strict,
+    } = validation;
+    let mut key = vec![
+        format!("warn_shadowing={warn_shadowing}"),
+        format!("strict={strict}"),
+    ];
+    match backend {
+        Backend::C => key.extend(c_options_key(c)),
+        Backend::Rust => {
+            let RustCodegenOptions {
+                emit_parsers,
+                parser_crate,
+                endianness,
+            } = rust;
+            key.push(format!("emit_parsers={emit_parsers}"));
+            key.push(format!("parser_crate={parser_crate:?}"));
+            key.push(format!("endianness={endianness:?}"));
+        }
+        Backend::Kotlin => {
+            let KotlinCodegenOptions { endianness } = kotlin;
+            key.push(format!("kotlin_package={kotlin_package}"));
+            key.push(format!("endianness={endianness:?}"));
+        }
+        Backend::Kaitai => {
+            let KaitaiCodegenOptions { endianness } = kaitai;
+            key.push(format!("endianness={endianness:?}"));
+        }
+        Backend::Wireshark => {
+            let WiresharkCodegenOptions { endianness } = wireshark;
+            key.push(format!("endianness={endianness:?}"));
+        }
+        Backend::Swift | Backend::Python | Backend::Protobuf | Backend::AsciiDoc => {}
+    }
+    key
+}
+
+fn c_options_key(options: &CCodegenOptions) -> Vec<String> {
+    let CCodegenOptions {
+        emit_encoders,
+        emit_decoders,
+        emit_field_getters,
+        emit_streaming_decoders,
+        fat_pointers,
+        emit_generic_macros,
+        length_prefix,
+        bit_order,
+        endianness,
+        emit_enum_tables,
+        emit_union_setters,
+        emit_source_refs,
+        emit_doc_comments,
+        align_bytes_fields_to_byte,
+        emit_init,
+        type_mapper,
+        omit_stdint_include,
+        use_restrict,
+        emit_json_io,
+        emit_wire_size,
+        emit_destructors,
+        emit_equals,
+        packed,
+        prefix,
+    } = options;
+    let type_mapper = match type_mapper {
+        Some(type_mapper) => crate::ast::BUILTIN_TYPES
+            .iter()
+            .map(|(name, type_identifier)| {
+                format!("{name}:{}", type_mapper.map_builtin(type_identifier))
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        None => "default".to_string(),
+    };
+    vec![
+        format!("emit_encoders={emit_encoders}"),
+        format!("emit_decoders={emit_decoders}"),
+        format!("emit_field_getters={emit_field_getters}"),
+        format!("emit_streaming_decoders={emit_streaming_decoders}"),
+        format!("fat_pointers={fat_pointers}"),
+        format!("emit_generic_macros={emit_generic_macros}"),
+        format!("length_prefix={length_prefix:?}"),
+        format!("bit_order={bit_order:?}"),
+        format!("endianness={endianness:?}"),
+        format!("emit_enum_tables={emit_enum_tables}"),
+        format!("emit_union_setters={emit_union_setters}"),
+        format!("emit_source_refs={emit_source_refs}"),
+        format!("emit_doc_comments={emit_doc_comments}"),
+        format!("align_bytes_fields_to_byte={align_bytes_fields_to_byte}"),
+        format!("emit_init={emit_init}"),
+        format!("type_mapper={type_mapper}"),
+        format!("omit_stdint_include={omit_stdint_include}"),
+        format!("use_restrict={use_restrict}"),
+        format!("emit_json_io={emit_json_io}"),
+        format!("emit_wire_size={emit_wire_size}"),
+        format!("emit_destructors={emit_destructors}"),
+        format!("emit_equals={emit_equals}"),
+        format!("packed={packed}"),
+        format!("prefix={prefix:?}"),
+    ]
+}
+
+/// Returns the path of the cached output for the protocol compiled with the given backend and
+/// options by this version of the library. The protocol is keyed as a whole rather than by
+/// [`crate::ast::protocol_hash`], as the outputs also follow the order of the definitions, e.g.
+/// to pick the root of a Kaitai Struct specification, and their doc comments. The lines of the
+/// definitions are keyed too when the C code refers to them.
+fn cache_path(
+    protocol: &Protocol,
+    backend: Backend,
+    options: &CompileOptions,
+    cache_dir: &Path,
+) -> PathBuf {
+    let lines: Vec<usize> = match backend {
+        Backend::C if options.c.emit_source_refs => protocol
+            .definitions
+            .iter()
+            .map(|definition| definition.name().span.line)
+            .collect(),
+        _ => Vec::new(),
+    };
+    let key = crate::ast::fnv1a_hash(&(
+        &protocol.name,
+        protocol.version,
+        &protocol.definitions,
+        lines,
+        env!("CARGO_PKG_VERSION"),
+        backend.language(),
+        options_key(backend, options),
+    ));
+    let extension = backend.smith(options).file_extension().to_string();
+    cache_dir.join(format!("{key:016x}.{extension}"))
+}
+
+/// Writes the code to a temporary file next to `path` and renames it into place, so that
+/// concurrent readers never see a partially written output.
+fn write_atomically(path: &Path, code: &str) -> Result<(), MeksmithError> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temporary_path = path.with_file_name(format!(
+        ".{file_name}.{}-{}.tmp",
+        std::process::id(),
+        TEMPORARY_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&temporary_path, code)
+        .map_err(|e| MeksmithError::io("Failed to write to file", e))?;
+    std::fs::rename(&temporary_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temporary_path);
+        MeksmithError::io("Failed to rename file", e)
+    })
+}
+
+/// Works like [`crate::pipeline::compile`], but stores the generated code in `cache_dir` and
+/// reuses it on later calls with the same protocol, backend and options. The input is always
+/// parsed, while only failed lookups validate and generate the code, in which case warnings
+/// are not reported.
+pub fn compile_cached(
+    input: &str,
+    backend: Backend,
+    options: &CompileOptions,
+    cache_dir: &Path,
+) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let path = cache_path(&protocol, backend, options, cache_dir);
+    if let Ok(code) = std::fs::read_to_string(&path) {
+        return Ok(code);
+    }
+
+    let output = crate::pipeline::compile_protocol(&protocol, backend, options)?;
+    std::fs::create_dir_all(cache_dir)
+        .map_err(|e| MeksmithError::io("Failed to create directory", e))?;
+    write_atomically(&path, &output.code)?;
+    Ok(output.code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{TypeIdentifier, TypeMapper};
+    use crate::smith_c::DefaultCMapper;
+    use rstest::rstest;
+    use std::sync::Arc;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    fn cached_files(cache_dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(cache_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_compile_cached_hit_does_not_regenerate() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let options = CompileOptions::default();
+
+        let code = compile_cached(PING_PONG, Backend::C, &options, cache_dir.path()).unwrap();
+        assert_eq!(
+            code,
+            crate::smith_c::generate_c_code_from_string_with_options(
+                PING_PONG,
+                &crate::smith_c::CCodegenOptions::default()
+            )
+            .unwrap()
+        );
+        let files = cached_files(cache_dir.path());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "h");
+
+        // A regenerated output would overwrite the marker.
+        std::fs::write(&files[0], "cached").unwrap();
+        let code = compile_cached(PING_PONG, Backend::C, &options, cache_dir.path()).unwrap();
+        assert_eq!(code, "cached");
+    }
+
+    #[test]
+    fn test_compile_cached_miss_after_changing_input() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let options = CompileOptions::default();
+
+        compile_cached(PING_PONG, Backend::Rust, &options, cache_dir.path()).unwrap();
+        let changed = PING_PONG.replace("sequence_number", "sequence_id");
+        let code = compile_cached(&changed, Backend::Rust, &options, cache_dir.path()).unwrap();
+        assert!(code.contains("pub sequence_id: u32,"));
+        assert_eq!(cached_files(cache_dir.path()).len(), 2);
+
+        compile_cached(PING_PONG, Backend::Swift, &options, cache_dir.path()).unwrap();
+        assert_eq!(cached_files(cache_dir.path()).len(), 3);
+    }
+
+    #[rstest]
+    #[case(Backend::Python, "py")]
+    #[case(Backend::Protobuf, "proto")]
+    #[case(Backend::Kaitai, "ksy")]
+    #[case(Backend::Wireshark, "lua")]
+    #[case(Backend::AsciiDoc, "adoc")]
+    fn test_compile_cached_uses_extension_of_smith(
+        #[case] backend: Backend,
+        #[case] extension: &str,
+    ) {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let code = compile_cached(
+            PING_PONG,
+            backend,
+            &CompileOptions::default(),
+            cache_dir.path(),
+        )
+        .unwrap();
+        let files = cached_files(cache_dir.path());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), extension);
+        assert_eq!(std::fs::read_to_string(&files[0]).unwrap(), code);
+    }
+
+    #[test]
+    fn test_compile_cached_miss_after_changing_version_or_lines() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut options = CompileOptions::default();
+        options.c.emit_source_refs = true;
+
+        let input = "module Telemetry version 1;\nstruct Message { field: uint8; };";
+        compile_cached(input, Backend::C, &options, cache_dir.path()).unwrap();
+        let code = compile_cached(
+            &input.replace("version 1", "version 2"),
+            Backend::C,
+            &options,
+            cache_dir.path(),
+        )
+        .unwrap();
+        assert!(code.contains("#define PROTOCOL_VERSION 2\n"));
+
+        let moved = input.replace(";\nstruct", ";\n\n\nstruct");
+        let code = compile_cached(&moved, Backend::C, &options, cache_dir.path()).unwrap();
+        assert!(code.contains("/* meklang: Message (input line 4) */\n"));
+        assert_eq!(cached_files(cache_dir.path()).len(), 3);
+    }
+
+    /// Mapper whose debug representation does not show the type it maps `float32` to.
+    struct Float32Mapper(&'static str);
+
+    impl std::fmt::Debug for Float32Mapper {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("Float32Mapper")
+        }
+    }
+
+    impl TypeMapper for Float32Mapper {
+        fn map_builtin(&self, type_identifier: &TypeIdentifier) -> String {
+            match type_identifier {
+                TypeIdentifier::Float32 => self.0.to_string(),
+                _ => DefaultCMapper.map_builtin(type_identifier),
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_cached_miss_after_changing_type_mapper() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let input = "struct Message { value: float32; };";
+        let mut options = CompileOptions::default();
+
+        options.c.type_mapper = Some(Arc::new(Float32Mapper("fixed16_16_t")));
+        let code = compile_cached(input, Backend::C, &options, cache_dir.path()).unwrap();
+        assert!(code.contains("    fixed16_16_t value;\n"));
+
+        options.c.type_mapper = Some(Arc::new(Float32Mapper("q15_16_t")));
+        let code = compile_cached(input, Backend::C, &options, cache_dir.path()).unwrap();
+        assert!(code.contains("    q15_16_t value;\n"));
+        assert_eq!(cached_files(cache_dir.path()).len(), 2);
+    }
+
+    #[rstest]
+    #[case::rust(Backend::Rust)]
+    #[case::kaitai(Backend::Kaitai)]
+    fn test_compile_cached_miss_after_reordering_definitions(#[case] backend: Backend) {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let options = CompileOptions::default();
+
+        let input = "struct First { field: uint8; };\nstruct Second { field: uint8; };";
+        compile_cached(input, backend, &options, cache_dir.path()).unwrap();
+        let reordered = "struct Second { field: uint8; };\n\nstruct First { field: uint8; };";
+        let code = compile_cached(reordered, backend, &options, cache_dir.path()).unwrap();
+        assert_eq!(
+            code,
+            crate::pipeline::compile(reordered, backend, &options)
+                .unwrap()
+                .code
+        );
+        assert_eq!(cached_files(cache_dir.path()).len(), 2);
+    }
+
+    #[test]
+    fn test_compile_cached_miss_after_changing_doc_comment() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut options = CompileOptions::default();
+        options.c.emit_doc_comments = true;
+
+        compile_cached(
+            "# First revision\nstruct Message { field: uint8; };",
+            Backend::C,
+            &options,
+            cache_dir.path(),
+        )
+        .unwrap();
+        let code = compile_cached(
+            "# Second revision\nstruct Message { field: uint8; };",
+            Backend::C,
+            &options,
+            cache_dir.path(),
+        )
+        .unwrap();
+        assert!(code.contains("Second revision"));
+        assert_eq!(cached_files(cache_dir.path()).len(), 2);
+    }
+
+    #[test]
+    fn test_compile_cached_hit_after_reformatting() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let options = CompileOptions::default();
+
+        let input = "struct First { field: uint8; };\nstruct Second { field: uint8; };";
+        compile_cached(input, Backend::Kaitai, &options, cache_dir.path()).unwrap();
+        let reformatted =
+            "struct First {\n    field: uint8;\n};\n\nstruct Second { field: uint8; };";
+        compile_cached(reformatted, Backend::Kaitai, &options, cache_dir.path()).unwrap();
+        assert_eq!(cached_files(cache_dir.path()).len(), 1);
+    }
+
+    #[test]
+    fn test_compile_cached_leaves_no_temporary_files() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let options = CompileOptions::default();
+
+        let code = compile_cached(PING_PONG, Backend::C, &options, cache_dir.path()).unwrap();
+        let files = cached_files(cache_dir.path());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "h");
+        assert_eq!(std::fs::read_to_string(&files[0]).unwrap(), code);
+    }
+
+    #[test]
+    fn test_compile_cached_does_not_store_errors() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        assert!(
+            compile_cached(
+                "struct Message { field: Missing; };",
+                Backend::C,
+                &CompileOptions::default(),
+                cache_dir.path(),
+            )
+            .is_err()
+        );
+        assert!(cached_files(cache_dir.path()).is_empty());
+    }
+}
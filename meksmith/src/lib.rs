@@ -1,64 +1,223 @@
-mod ast;
+pub mod ast;
+pub mod builder;
+pub mod cache;
+pub mod lsp;
 mod parser;
+pub mod pipeline;
+pub mod smith;
+pub mod smith_asciidoc;
 pub mod smith_c;
+pub mod smith_kaitai;
+pub mod smith_kotlin;
+pub mod smith_protobuf;
+pub mod smith_python;
+pub mod smith_rust;
+pub mod smith_swift;
+pub mod smith_wireshark;
+pub mod validation;
 
 use crate::ast::*;
 use crate::parser::protocol;
 
 use chumsky::Parser;
 
-/// Based on the provided input, returns the line and column number of the error encountered during parsing.
-fn get_error_location(input: &str, error: crate::parser::RichError) -> (usize, usize) {
-    let mut line = 1;
-    let mut column = 1;
+/// Byte order of multi-byte values on the wire, used by the smiths generating codecs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endianness {
+    /// Byte order of the host running the generated code, which is decided when it is built
+    /// or run, so that values keep the layout they have in its memory.
+    #[default]
+    Native,
+    Big,
+    Little,
+}
+
+/// Returns the line and column, both starting from 1, at which an editor shows the given byte
+/// offset of the input, e.g. the start of an error. Columns count characters rather than bytes,
+/// and an offset at a newline is located right after the last character of its line.
+fn get_error_location(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset.min(input.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
 
-    for (i, c) in input.char_indices() {
-        if i >= error.span().start && i < error.span().end {
-            return (line, column);
+/// Fills in the line numbers of spans of all identifiers, as the parser only knows their offsets.
+fn assign_lines_to_spans(input: &str, protocol: &mut Protocol) {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(input.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    for identifier in identifiers_mut(protocol) {
+        identifier.span.line = line_starts.partition_point(|&start| start <= identifier.span.start);
+    }
+}
+
+/// Parses a protocol from a string input and returns the resulting AST.
+pub fn parse_protocol_to_ast(input: &str) -> Result<Protocol, MeksmithError> {
+    parse_protocol(input, None)
+}
+
+/// Same as [`parse_protocol_to_ast`], but names the source of the input in errors the way
+/// compilers do, e.g. `protocol.mek:3:5: found ';' expected ...`.
+pub fn parse_protocol_to_ast_named(
+    input: &str,
+    source_name: &str,
+) -> Result<Protocol, MeksmithError> {
+    parse_protocol(input, Some(source_name))
+}
+
+/// Error encountered while parsing a protocol, located in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    /// Line at which the error begins, starting from 1.
+    pub line: usize,
+    /// Column at which the error begins, starting from 1.
+    pub column: usize,
+    /// Range of byte offsets of the input covered by the error.
+    pub span: std::ops::Range<usize>,
+    /// Name of the source of the input, e.g. its file, if it was given.
+    pub source_name: Option<String>,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source_name {
+            Some(source_name) => write!(
+                f,
+                "{source_name}:{}:{}: {}",
+                self.line, self.column, self.message
+            ),
+            None => write!(f, "{} in {}:{}", self.message, self.line, self.column),
         }
-        if c == '\n' {
-            line += 1;
-            column = 1;
-        } else {
-            column += 1;
+    }
+}
+
+/// Error returned by the public functions of the library, e.g. [`parse_protocol_to_ast`] and
+/// the `generate_*_from_string` functions of the smiths.
+#[derive(Debug)]
+pub enum MeksmithError {
+    /// Reading or writing a file failed. The context describes the operation.
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+    /// The input is not a valid protocol, with every error found in it.
+    Parse(Vec<ParseError>),
+    /// The named definition depends on itself, directly or through other definitions.
+    CircularDependency(String),
+    /// The protocol or the options of a smith are invalid, with all error messages.
+    Validation(Vec<String>),
+}
+
+impl MeksmithError {
+    /// Wraps an I/O error with a description of the operation which failed.
+    pub(crate) fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        MeksmithError::Io {
+            context: context.into(),
+            source,
         }
     }
+}
 
-    (line, column)
+impl std::fmt::Display for MeksmithError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeksmithError::Io { context, source } => write!(f, "{context}: {source}"),
+            MeksmithError::Parse(errors) => {
+                let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                write!(f, "Parsing failed. Errors: {}", errors.join(", "))
+            }
+            MeksmithError::CircularDependency(name) => {
+                write!(f, "Circular dependency detected for {name}")
+            }
+            MeksmithError::Validation(errors) => {
+                write!(f, "{}", crate::validation::format_validation_errors(errors))
+            }
+        }
+    }
 }
 
-/// Parses a protocol from a string input and returns the resulting AST.
-pub fn parse_protocol_to_ast(input: &str) -> Result<Protocol, String> {
+impl std::error::Error for MeksmithError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MeksmithError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Same as [`parse_protocol_to_ast`], but returns every error as a [`ParseError`], in the
+/// order they appear in the input. After an error, parsing resumes after the end of the
+/// definition containing it, so that errors of the following definitions are reported too.
+pub fn parse_protocol_detailed(input: &str) -> Result<Protocol, Vec<ParseError>> {
     let result = protocol().parse(input);
 
     match result.into_result() {
-        Ok(ast) => Ok(ast),
+        Ok(mut ast) => {
+            assign_lines_to_spans(input, &mut ast);
+            resolve_array_size_constants(&mut ast);
+            Ok(ast)
+        }
         Err(errors) => {
-            let error_messages: Vec<String> = errors
+            let mut errors: Vec<ParseError> = errors
                 .into_iter()
                 .map(|e| {
-                    let (line, column) = get_error_location(input, e.clone());
-                    e.to_string()
-                        + " in "
-                        + line.to_string().as_str()
-                        + ":"
-                        + column.to_string().as_str()
+                    let (line, column) = get_error_location(input, e.span().start);
+                    ParseError {
+                        message: e.to_string(),
+                        line,
+                        column,
+                        span: e.span().start..e.span().end,
+                        source_name: None,
+                    }
                 })
                 .collect();
-            Err(format!(
-                "Parsing failed. Errors: {}",
-                error_messages.join(", ")
-            ))
+            errors.sort_by_key(|error| error.span.start);
+            Err(errors)
         }
     }
 }
 
+fn parse_protocol(input: &str, source_name: Option<&str>) -> Result<Protocol, MeksmithError> {
+    parse_protocol_detailed(input).map_err(|errors| {
+        MeksmithError::Parse(
+            errors
+                .into_iter()
+                .map(|error| ParseError {
+                    source_name: source_name.map(str::to_string),
+                    ..error
+                })
+                .collect(),
+        )
+    })
+}
+
 /// Parses a protocol from a file and returns the resulting AST. Similar to `parse_protocol_to_ast`,
 /// but reads the input from a file instead of a string.
-pub fn parse_protocol_from_file_to_ast(file_path: &str) -> Result<Protocol, String> {
-    let input =
-        std::fs::read_to_string(file_path).map_err(|e| format!("Failed to read file: {e}"))?;
-    parse_protocol_to_ast(&input)
+pub fn parse_protocol_from_file_to_ast(file_path: &str) -> Result<Protocol, MeksmithError> {
+    let input = std::fs::read_to_string(file_path)
+        .map_err(|e| MeksmithError::io("Failed to read file", e))?;
+    parse_protocol_to_ast_named(&input, file_path)
+}
+
+/// Allows parsing a protocol with [`str::parse`], e.g. `let protocol: Protocol = input.parse()?;`.
+/// It is equivalent to calling [`parse_protocol_to_ast`].
+impl std::str::FromStr for Protocol {
+    type Err = MeksmithError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        parse_protocol_to_ast(input)
+    }
+}
+
+/// Serializes a protocol to JSON, so that tools can consume its definitions without linking
+/// the parser. The JSON can be read back with `serde_json::from_str::<Protocol>`, giving a
+/// protocol equal to the serialized one.
+#[cfg(feature = "serde")]
+pub fn protocol_to_json(protocol: &Protocol) -> String {
+    serde_json::to_string(protocol).expect("protocols always serialize to JSON")
 }
 
 #[cfg(test)]
@@ -82,6 +241,7 @@ using MyType = int32[10];
                 TypeIdentifier::StaticArray {
                     r#type: Box::new(TypeIdentifier::Integer32),
                     size: 10,
+                    size_constant: None,
                 }
             );
         } else {
@@ -100,10 +260,44 @@ using MyType = int32[10;
         assert!(
             result
                 .unwrap_err()
+                .to_string()
                 .contains("Parsing failed. Errors: found ';' expected digit, or right bracket")
         );
     }
 
+    #[test]
+    fn test_parse_protocol_to_ast_named_with_errors() {
+        let input = "\nusing MyType = int32[10;\n";
+        assert_eq!(
+            parse_protocol_to_ast_named(input, "my_protocol.mek")
+                .map_err(|error| error.to_string()),
+            Err("Parsing failed. Errors: my_protocol.mek:2:24: \
+                 found ';' expected digit, or right bracket (])"
+                .to_string())
+        );
+        assert_eq!(
+            parse_protocol_to_ast(input).map_err(|error| error.to_string()),
+            Err(
+                "Parsing failed. Errors: found ';' expected digit, or right bracket (]) in 2:24"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_protocol_from_str() -> Result<(), MeksmithError> {
+        let protocol: Protocol = "using MyType = int32[10];".parse()?;
+        assert_eq!(protocol.definitions.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_protocol_from_str_with_errors() {
+        let result = "using MyType = int32[10;".parse::<Protocol>();
+        assert!(result.is_err());
+        assert!(matches!(result, Err(MeksmithError::Parse(errors)) if errors.len() == 1));
+    }
+
     #[test]
     fn test_parse_protocol_from_file_to_ast() {
         let file_path = "test_protocol.txt";
@@ -131,6 +325,7 @@ using MyType = int32[10];
                 TypeIdentifier::StaticArray {
                     r#type: Box::new(TypeIdentifier::Integer32),
                     size: 10,
+                    size_constant: None,
                 }
             );
         } else {
@@ -138,4 +333,164 @@ using MyType = int32[10];
         }
         std::fs::remove_file(file_path).expect("Failure in removing test file");
     }
+
+    #[test]
+    fn test_parse_protocol_from_missing_file_to_ast() {
+        let error = parse_protocol_from_file_to_ast("missing_protocol.mek").unwrap_err();
+        let MeksmithError::Io { context, source } = &error else {
+            panic!("Expected an I/O error, got {error:?}");
+        };
+        assert_eq!(context, "Failed to read file");
+        assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(error.to_string(), format!("Failed to read file: {source}"));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn test_meksmith_error_display() {
+        let error = MeksmithError::CircularDependency("A".to_string());
+        assert_eq!(error.to_string(), "Circular dependency detected for A");
+        assert!(std::error::Error::source(&error).is_none());
+        assert_eq!(
+            MeksmithError::Validation(vec!["first".to_string(), "second".to_string()]).to_string(),
+            "Validation failed. Errors: first, second"
+        );
+        assert_eq!(
+            MeksmithError::Parse(vec![]).to_string(),
+            "Parsing failed. Errors: "
+        );
+    }
+
+    #[test]
+    fn test_parse_protocol_to_ast_resolves_array_size_constants() {
+        let protocol =
+            parse_protocol_to_ast("struct Message { data: byte[MAX_LEN]; };\nconst MAX_LEN = 16;")
+                .unwrap();
+        let Definition::Structure(structure) = &protocol.definitions[0] else {
+            panic!("expected a structure");
+        };
+        assert_eq!(
+            structure.fields[0].r#type,
+            TypeIdentifier::StaticArray {
+                r#type: Box::new(TypeIdentifier::Byte),
+                size: 16,
+                size_constant: Some(Identifier::new("MAX_LEN")),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_protocol_to_ast_assigns_spans() {
+        let input = "enum Kind { a = 1; };\n\nstruct Message {\n    kind: Kind;\n};\n";
+        let protocol = parse_protocol_to_ast(input).unwrap();
+        let Definition::Structure(structure) = &protocol.definitions[1] else {
+            panic!("expected a structure");
+        };
+        assert_eq!(structure.name.span.line, 3);
+        assert_eq!(
+            &input[structure.name.span.start..structure.name.span.end],
+            "Message"
+        );
+        assert_eq!(structure.fields[0].name.span.line, 4);
+        let TypeIdentifier::UserDefined(kind) = &structure.fields[0].r#type else {
+            panic!("expected a user-defined type");
+        };
+        assert_eq!(&input[kind.span.start..kind.span.end], "Kind");
+    }
+
+    #[rstest::rstest]
+    #[case::start(0, (1, 1))]
+    #[case::first_line(3, (1, 4))]
+    #[case::newline(5, (1, 6))]
+    #[case::line_start(6, (2, 1))]
+    #[case::multi_byte_characters(16, (3, 4))]
+    #[case::end_of_input(19, (4, 1))]
+    #[case::past_end_of_input(100, (4, 1))]
+    fn test_get_error_location(#[case] offset: usize, #[case] expected: (usize, usize)) {
+        assert_eq!(get_error_location("ab\tcd\nefg\nżółw;\n", offset), expected);
+    }
+
+    #[rstest::rstest]
+    #[case::third_line(
+        "enum A { a = 0; };\n\nstruct B { b: uint8 };\n",
+        "found '}' expected '[', or ';' in 3:21"
+    )]
+    #[case::end_of_input(
+        "enum A { a = 0; };\nstruct B {\n    b: uint8;\n",
+        "found end of input expected structure field, or right brace (}) in 4:1"
+    )]
+    fn test_parse_protocol_to_ast_error_locations(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(
+            parse_protocol_to_ast(input).map_err(|error| error.to_string()),
+            Err(format!("Parsing failed. Errors: {expected}"))
+        );
+    }
+
+    #[test]
+    fn test_parse_protocol_detailed_reports_every_error() {
+        let input = "struct A {\n    a: uint8\n};\nenum B { x = ; };\nstruct C { c: uint8; };\nusing D = byte[;\n";
+        let errors = parse_protocol_detailed(input).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                ParseError {
+                    message: "found '}' expected '[', or ';'".to_string(),
+                    line: 3,
+                    column: 1,
+                    span: 24..25,
+                    source_name: None,
+                },
+                ParseError {
+                    message: "found ';' expected signed integer, or range".to_string(),
+                    line: 4,
+                    column: 14,
+                    span: 40..41,
+                    source_name: None,
+                },
+                ParseError {
+                    message:
+                        "found ';' expected 'b', unsigned_integer, identifier, or right bracket (])"
+                            .to_string(),
+                    line: 6,
+                    column: 16,
+                    span: 84..85,
+                    source_name: None,
+                },
+            ]
+        );
+        assert!(matches!(
+            parse_protocol_to_ast(input),
+            Err(MeksmithError::Parse(parse_errors)) if parse_errors == errors
+        ));
+        assert!(parse_protocol_detailed("struct C { c: uint8; };").is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest::rstest]
+    #[case(include_str!("../examples/data/ping-pong.mek"))]
+    #[case(include_str!("../examples/data/can-bus.mek"))]
+    #[case(include_str!("../examples/data/ecpri.mek"))]
+    #[case(
+        r#"module Telemetry version 3;
+const MAX = 4;
+flags Mode: uint8 { fast = 0x1; safe = 0x2; };
+enum Kind: int16 { low = -2..-1; high = 7; };
+union Body { 0 => none: void; 1..2 => values: int32[MAX][2]; };
+[id=5, repr=c_packed]
+struct Sample {
+    [bits=3, assert="value < 6"] kind: Kind;
+    [discriminated_by=kind] body: Body;
+    [length_of=data] count: uint16;
+    data: char[];
+    [bytes=2, c_type="short"] level: uint32;
+    _padding: uint8;
+    mode: Mode;
+    ratio: float64;
+};"#
+    )]
+    fn test_protocol_to_json_round_trip(#[case] input: &str) {
+        let protocol = parse_protocol_to_ast(input).unwrap();
+        let json = protocol_to_json(&protocol);
+        assert_eq!(serde_json::from_str::<Protocol>(&json).unwrap(), protocol);
+    }
 }
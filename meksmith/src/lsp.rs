@@ -0,0 +1,256 @@
+//! Editor support independent of the transport, which is provided by the `meksmith-lsp`
+//! binary built with the `lsp` feature.
+
+use crate::ast::Protocol;
+use crate::validation::{Diagnostic, Severity};
+
+/// Position in a document, with lines and characters counted from 0 and characters counted
+/// in UTF-16 code units, as in the Language Server Protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// Problem found in a document, located at the range from `start` to `end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentDiagnostic {
+    pub start: Position,
+    pub end: Position,
+    pub severity: Severity,
+    pub message: String,
+}
+
+fn offset_to_position(text: &str, offset: usize) -> Position {
+    let mut position = Position::default();
+    for (i, c) in text.char_indices() {
+        if i >= offset {
+            break;
+        }
+        if c == '\n' {
+            position.line += 1;
+            position.character = 0;
+        } else {
+            position.character += c.len_utf16() as u32;
+        }
+    }
+    position
+}
+
+/// Returns the byte offset of the position, or `None` if it is past the end of its line.
+fn position_to_offset(text: &str, position: Position) -> Option<usize> {
+    let mut current = Position::default();
+    for (i, c) in text.char_indices() {
+        if current == position {
+            return Some(i);
+        }
+        if c == '\n' {
+            if current.line == position.line {
+                return None;
+            }
+            current.line += 1;
+            current.character = 0;
+        } else {
+            current.character += c.len_utf16() as u32;
+        }
+    }
+    (current == position).then_some(text.len())
+}
+
+/// Returns the byte range of the name of the first definition mentioned by the message, as
+/// diagnostics of the validators carry no location.
+fn locate_message(protocol: &Protocol, message: &str) -> Option<(usize, usize)> {
+    let words: Vec<&str> = message
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .collect();
+    protocol
+        .definitions
+        .iter()
+        .map(|definition| definition.name())
+        .find(|name| words.contains(&name.name.as_str()))
+        .map(|name| (name.span.start, name.span.end))
+}
+
+/// Returns the diagnostics of the validators, followed by the circular dependency found while
+/// sorting the definitions if the validators found no error, like [`crate::pipeline::compile`].
+fn protocol_diagnostics(protocol: &Protocol) -> Vec<Diagnostic> {
+    let mut diagnostics = crate::validation::collect_diagnostics(protocol);
+    if !diagnostics.iter().any(Diagnostic::is_error)
+        && let Err(error) = crate::ast::sort_protocol_by_dependencies(protocol)
+    {
+        diagnostics.push(Diagnostic::error(error.to_string()));
+    }
+    diagnostics
+}
+
+/// Parses and validates the document, returning the syntax errors if it cannot be parsed,
+/// or the diagnostics of the validators otherwise. Diagnostics of the validators are placed
+/// at the name of the definition they mention, or at the start of the document.
+pub fn document_diagnostics(text: &str) -> Vec<DocumentDiagnostic> {
+    match crate::parse_protocol_detailed(text) {
+        Err(errors) => errors
+            .into_iter()
+            .map(|error| DocumentDiagnostic {
+                start: offset_to_position(text, error.span.start),
+                end: offset_to_position(text, error.span.end),
+                severity: Severity::Error,
+                message: error.message,
+            })
+            .collect(),
+        Ok(protocol) => protocol_diagnostics(&protocol)
+            .into_iter()
+            .map(|diagnostic| {
+                let (start, end) = locate_message(&protocol, &diagnostic.message).unwrap_or((0, 0));
+                DocumentDiagnostic {
+                    start: offset_to_position(text, start),
+                    end: offset_to_position(text, end),
+                    severity: diagnostic.severity,
+                    message: diagnostic.message,
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Returns the size on the wire of the type named at the position, see
+/// [`crate::ast::definition_size_in_bits`]. Nothing is shown for documents with errors,
+/// as e.g. sizes of recursive types cannot be computed.
+pub fn hover(text: &str, position: Position) -> Option<String> {
+    let offset = position_to_offset(text, position)?;
+    let mut protocol = crate::parse_protocol_detailed(text).ok()?;
+    if crate::ast::prepare(&protocol).is_err() {
+        return None;
+    }
+    let name = crate::ast::identifiers_mut(&mut protocol)
+        .into_iter()
+        .find(|identifier| identifier.span.start <= offset && offset <= identifier.span.end)?
+        .name
+        .clone();
+    match crate::ast::definition_size_in_bits(&protocol, &name).ok()? {
+        Some(size) => Some(format!("{name}: {size} bits")),
+        None => Some(format!("{name}: variable size")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    static INPUT: &str =
+        "enum Kind { a = 0; };\nstruct Message {\n    kind: Kind;\n    data: byte[];\n};\n";
+
+    #[test]
+    fn test_document_diagnostics_of_valid_document() {
+        assert!(document_diagnostics(INPUT).is_empty());
+    }
+
+    #[test]
+    fn test_document_diagnostics_with_syntax_error() {
+        let diagnostics = document_diagnostics("struct Message {\n    kind: uint8\n};\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(
+            diagnostics[0].start,
+            Position {
+                line: 2,
+                character: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_document_diagnostics_with_validation_diagnostics() {
+        let diagnostics = document_diagnostics(
+            "struct Message {\n    kind: Missing;\n};\n\nenum Unused { a = 0; };\n",
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                DocumentDiagnostic {
+                    start: Position {
+                        line: 0,
+                        character: 7
+                    },
+                    end: Position {
+                        line: 0,
+                        character: 14
+                    },
+                    severity: Severity::Error,
+                    message: "Type Missing of field kind in Message is not defined".to_string(),
+                },
+                DocumentDiagnostic {
+                    start: Position {
+                        line: 4,
+                        character: 5
+                    },
+                    end: Position {
+                        line: 4,
+                        character: 11
+                    },
+                    severity: Severity::Warning,
+                    message: "Definition Unused is never used".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::type_reference(2, 12, Some("Kind: 8 bits"))]
+    #[case::definition_name(1, 9, Some("Message: variable size"))]
+    #[case::builtin_type(3, 12, None)]
+    #[case::past_end_of_line(0, 40, None)]
+    fn test_hover(#[case] line: u32, #[case] character: u32, #[case] expected: Option<&str>) {
+        assert_eq!(
+            hover(INPUT, Position { line, character }).as_deref(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_hover_on_array_sized_by_constant() {
+        let input = "const MAX_LEN = 4;\nstruct Message { data: byte[MAX_LEN]; };\n";
+        assert_eq!(
+            hover(
+                input,
+                Position {
+                    line: 1,
+                    character: 9
+                }
+            )
+            .as_deref(),
+            Some("Message: 32 bits")
+        );
+        assert!(document_diagnostics(input).is_empty());
+    }
+
+    #[test]
+    fn test_document_diagnostics_with_circular_dependency() {
+        let diagnostics = document_diagnostics("struct A { b: B; };\nstruct B { a: A; };\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(
+            crate::pipeline::compile(
+                "struct A { b: B; };\nstruct B { a: A; };\n",
+                crate::pipeline::Backend::C,
+                &crate::pipeline::CompileOptions::default()
+            )
+            .is_err_and(|error| error.to_string() == diagnostics[0].message)
+        );
+    }
+
+    #[test]
+    fn test_hover_on_invalid_document() {
+        let input = "struct Message { kind: Missing; };";
+        assert_eq!(
+            hover(
+                input,
+                Position {
+                    line: 0,
+                    character: 8
+                }
+            ),
+            None
+        );
+    }
+}
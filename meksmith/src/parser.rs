@@ -1,54 +1,75 @@
 //! Grammar for the meklang is defined as follows:
 //! ```text
-//! <protocol> ::= (<definition> | <comment>)+
-//! <comment> ::= '#' <text> '\n'
+//! <protocol> ::= <comment>* [<module_header>] (<definition> | <comment>)+
+//! <comment> ::= '#' <text> '\n' | '/*' <text> '*/'
+//! <module_header> ::= 'module' <identifier> ['version' <unsigned_integer>] <semicolon>
 //! <definition> ::=
 //!       <enumeration_definition>
 //!     | <structure_definition>
 //!     | <union_definition>
+//!     | <oneof_definition>
 //!     | <type_definition>
+//!     | <flags_definition>
+//!     | <constant_definition>
 //!
-//! <enumeration_definition> ::= 'enum' <identifier> <left_brace> <enumeration_field>+ <right_brace> <semicolon>
-//! <enumeration_field> ::= <identifier> <equal> (<unsigned_integer> | <range>) <semicolon>
+//! <enumeration_definition> ::= [<attributes>] 'enum' <identifier> [<colon> <builtin_type>] <left_brace> <enumeration_field>+ <right_brace> <semicolon>
+//! <enumeration_field> ::= <comment>* <identifier> <equal> (<signed_integer> | <signed_range>) <semicolon>
 //!
-//! <structure_definition> ::= 'struct' <identifier> <left_brace> <structure_field>+ <right_brace> <semicolon>
-//! <structure_field> ::= [<attributes>] <identifier> <colon> <type_identifier> <semicolon>
+//! <structure_definition> ::= [<attributes>] 'struct' <identifier> <left_brace> <structure_field>+ <right_brace> <semicolon>
+//! <structure_field> ::= <comment>* [<attributes>] <identifier> <colon> <type_identifier> <semicolon>
 //!
-//! <union_definition> ::= 'union' <identifier> <left_brace> <union_field>+ <right_brace> <semicolon>
-//! <union_field> ::= (<unsigned_integer> | <range>) <maps_to> <identifier> <colon> <type_identifier> <semicolon>
+//! <union_definition> ::= [<attributes>] 'union' <identifier> <left_brace> <union_field>+ <right_brace> <semicolon>
+//! <union_field> ::= <comment>* (<unsigned_integer> | <range>) <maps_to> <identifier> <colon> <type_identifier> <semicolon>
+//!
+//! <oneof_definition> ::= [<attributes>] 'oneof' <identifier> <left_brace> <oneof_field>+ <right_brace> <semicolon>
+//! <oneof_field> ::= <comment>* <identifier> <colon> <type_identifier> <semicolon>
 //!
 //! <attribute> ::=
 //!       'discriminated_by' <equal> <identifier>
 //!     | 'bits' <equal> <unsigned_integer>
 //!     | 'bytes' <equal> <unsigned_integer>
+//!     | 'id' <equal> <unsigned_integer>
+//!     | 'c_type' <equal> <string_literal>
+//!     | 'repr' <equal> <repr>
+//!     | 'assert' <equal> <string_literal>
+//!     | 'length_of' <equal> <identifier>
+//!     | 'length' <equal> <identifier>
+//! <repr> ::= 'c' | 'c_packed' | 'transparent'
 //! <attribute_tail> ::= <comma> <attribute>
 //! <attributes> ::= <left_bracket> <attribute> <attribute_tail>* <right_bracket>
 //!
-//! <type_definition> ::= 'using' <identifier> <equal> <type_identifier> <semicolon>
+//! <type_definition> ::= [<attributes>] 'using' <identifier> <equal> <type_identifier> <semicolon>
+//!
+//! <flags_definition> ::= [<attributes>] 'flags' <identifier> <colon> <unsigned_integer_type> <left_brace> <flags_field>+ <right_brace> <semicolon>
+//! <flags_field> ::= <comment>* <identifier> <equal> <unsigned_integer> <semicolon>
+//!
+//! <constant_definition> ::= 'const' <identifier> <equal> <unsigned_integer> <semicolon>
 //!
 //! <type_identifier> ::=
-//!       <builtin_type>
+//!       <string_type>
+//!     | <builtin_type>
 //!     | <user_defined_type>
-//!     | <static_array_type>
-//!     | <dynamic_array_type>
+//!     | <array_type>
 //!
 //! <builtin_type> ::=
 //!       'int8' | 'int16' | 'int32' | 'int64'
 //!     | 'uint8' | 'uint16' | 'uint32' | 'uint64'
 //!     | 'float32' | 'float64'
-//!     | 'bit' | 'byte'
+//!     | 'bit' | 'byte' | 'char' | 'void'
+//! <unsigned_integer_type> ::= 'uint8' | 'uint16' | 'uint32' | 'uint64'
+//! <string_type> ::= 'string' [<left_bracket> (<unsigned_integer> | <identifier>) <right_bracket>]
 //! <user_defined_type> ::= <identifier>
-//! <static_array_type> ::=
-//!       <builtin_type> <left_bracket> <unsigned_integer> <right_bracket>
-//!     | <user_defined_type> <left_bracket> <unsigned_integer> <right_bracket>
-//! <dynamic_array_type> ::=
-//!       <builtin_type> <left_bracket> <right_bracket>
-//!     | <user_defined_type> <left_bracket> <right_bracket>
+//! <array_type> ::= (<builtin_type> | <user_defined_type>) <array_dimension>+
+//! <array_dimension> ::= <left_bracket> [<array_size>] <right_bracket>
+//! <array_size> ::= <unsigned_integer> | <identifier> | 'bytes' <colon> <unsigned_integer>
 //!
 //! <range> ::= <unsigned_integer> <double_dot> <unsigned_integer>
+//! <signed_range> ::= <signed_integer> <double_dot> <signed_integer>
 //! <identifier> ::= [a-zA-Z_][a-zA-Z0-9_]*
+//! <string_literal> ::= '"' [^"\n]* '"'
 //!
-//! <unsigned_integer> ::= <hexadecimal> | <binary> | <decimal>
+//! <unsigned_integer> ::= <hexadecimal> | <binary> | <decimal>  (in the range of a 64-bit unsigned integer)
+//! <signed_integer> ::= ['-'] <unsigned_integer>  (negative ones in the range of a 64-bit signed integer)
 //! <hexadecimal> ::= "0x" [0-9a-fA-F]+
 //! <binary> ::= "0b" [01]+
 //! <decimal> ::= [0-9]+
@@ -70,10 +91,32 @@
 //! This grammar defines the structure of a protocol of the meklang, whose
 //! main purpose is to define data structures and types that can be used in code generation.
 //!
-//! Currently `<comment>` is supported only in between definitions, but not inside them.
+//! `<comment>` can appear wherever whitespace can, including inside definitions.
+//! A block of `#` comments directly preceding a definition or a field of a structure or
+//! enumeration (without empty lines in between) is attached to it as its documentation.
+//!
+//! Values of `<flags_field>`s are masks, which can be combined with each other.
+//!
+//! The size of a static array is a number of elements, unless prefixed with `bytes:`, in which
+//! case it is a number of bytes holding the elements, e.g. `uint16[bytes: 10]` is parsed into
+//! an array of 5 elements. The bytes must hold a whole number of elements. A size given by
+//! an `<identifier>` refers to a `<constant_definition>` holding the number of elements.
+//! An `<array_dimension>` without a size is a dynamic array, prefixed with its length on the
+//! wire unless an integer field preceding it holds the length, which links the two fields with
+//! `length` on the array or `length_of` on the integer. The first dimension of an
+//! `<array_type>` is the outermost one, e.g. `byte[][8]` is a dynamic array of arrays of
+//! 8 bytes, and only the innermost one can be sized in bytes of a `<builtin_type>`.
+//!
+//! `<oneof_definition>` is a shorthand for a union whose discriminators are assigned
+//! in the order of fields, starting from 0. It is parsed into a regular [`UnionDefinition`].
+//!
+//! A `<structure_field>` whose name starts with `_` is reserved: it takes its place on the
+//! wire, but is always encoded as zeros and skipped when decoding. Its `bits` or `bytes`
+//! attribute sets the width of the padding, e.g. `[bits=3] _padding: uint8;` is 3 zero bits.
 
 use crate::ast::*;
 
+use chumsky::input::InputRef;
 use chumsky::prelude::*;
 
 pub(crate) type RichError<'src> = chumsky::error::Rich<'src, char>;
@@ -81,81 +124,131 @@ pub(crate) type ErrorType<'src> = extra::Err<RichError<'src>>;
 
 /// Parses a left brace `{` followed by optional whitespace.
 pub(crate) fn left_brace<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just('{').padded().to(()).labelled("left brace ({)")
+    just('{')
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("left brace ({)")
 }
 
 /// Parses a left brace `}` followed by optional whitespace.
 pub(crate) fn right_brace<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just('}').padded().to(()).labelled("right brace (})")
+    just('}')
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("right brace (})")
 }
 
 /// Parses a left bracket `[` followed by optional whitespace.
 pub(crate) fn left_bracket<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just('[').padded().to(()).labelled("left bracket ([)")
+    just('[')
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("left bracket ([)")
 }
 
 /// Parses a right bracket `]` followed by optional whitespace.
 pub(crate) fn right_bracket<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just(']').padded().to(()).labelled("right bracket (])")
+    just(']')
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("right bracket (])")
 }
 
 /// Parses a semicolon `;` followed by optional whitespace.
 pub(crate) fn semicolon<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just(';').padded().to(()).labelled("semicolon (;)")
+    just(';')
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("semicolon (;)")
 }
 
 /// Parses a colon `:` followed by optional whitespace.
 pub(crate) fn colon<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just(':').padded().to(()).labelled("colon (:)")
+    just(':')
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("colon (:)")
 }
 
 /// Parses a maps to operator `=>` followed by optional whitespace.
 pub(crate) fn maps_to<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just("=>").padded().to(()).labelled("maps to (=>)")
+    just("=>")
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("maps to (=>)")
 }
 
 /// Parses an equal sign `=` followed by optional whitespace.
 pub(crate) fn equal<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just('=').padded().to(()).labelled("equal (=)")
+    just('=')
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("equal (=)")
 }
 
 /// Parses a comma `,` followed by optional whitespace.
 pub(crate) fn comma<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just(',').padded().to(()).labelled("comma (,)")
+    just(',')
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("comma (,)")
 }
 
 /// Parses a double dot `..` followed by optional whitespace.
 pub(crate) fn double_dot<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
-    just("..").padded().to(()).labelled("double dot (..)")
+    just("..")
+        .padded_by(whitespace_or_comment())
+        .to(())
+        .labelled("double dot (..)")
+}
+
+/// Parses the digits of an unsigned integer in the given radix, which has to fit in a 64-bit
+/// unsigned integer. An out of range integer is reported without failing the parser, as its
+/// error would otherwise be replaced by the labels of the parsers starting at the integer.
+fn checked_digits<'src>(
+    digits: impl Parser<'src, &'src str, String, ErrorType<'src>>,
+    radix: u32,
+) -> impl Parser<'src, &'src str, u64, ErrorType<'src>> {
+    digits.validate(move |digits, e, emitter| {
+        u64::from_str_radix(&digits, radix).unwrap_or_else(|_| {
+            emitter.emit(RichError::custom(
+                e.span(),
+                format!(
+                    "integer does not fit in a 64-bit unsigned integer (0..{})",
+                    u64::MAX
+                ),
+            ));
+            u64::MAX
+        })
+    })
 }
 
 /// Parses an unsigned integer in hexadecimal format.
 pub(crate) fn hexadecimal<'src>() -> impl Parser<'src, &'src str, u64, ErrorType<'src>> {
-    just("0x")
-        .ignore_then(text::digits(16).at_least(1).collect::<String>())
-        .map(|s: String| u64::from_str_radix(&s, 16).unwrap())
-        .labelled("hexadecimal")
-        .padded()
+    checked_digits(
+        just("0x").ignore_then(text::digits(16).at_least(1).collect::<String>()),
+        16,
+    )
+    .labelled("hexadecimal")
+    .padded_by(whitespace_or_comment())
 }
 
 /// Parses an unsigned integer in binary format. It supports leading zeros and
 /// only allows `0` and `1` digits.
 pub(crate) fn binary<'src>() -> impl Parser<'src, &'src str, u64, ErrorType<'src>> {
-    just("0b")
-        .ignore_then(text::digits(2).at_least(1).collect::<String>())
-        .map(|s: String| u64::from_str_radix(&s, 2).unwrap())
-        .labelled("binary")
-        .padded()
+    checked_digits(
+        just("0b").ignore_then(text::digits(2).at_least(1).collect::<String>()),
+        2,
+    )
+    .labelled("binary")
+    .padded_by(whitespace_or_comment())
 }
 
 /// Parses an unsigned integer in decimal format.
 pub(crate) fn decimal<'src>() -> impl Parser<'src, &'src str, u64, ErrorType<'src>> {
-    text::digits(10)
-        .at_least(1)
-        .collect::<String>()
-        .map(|s: String| s.parse::<u64>().unwrap())
+    checked_digits(text::digits(10).at_least(1).collect::<String>(), 10)
         .labelled("decimal")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses an unsigned integer in decimal, hexadecimal, or binary format.
@@ -163,14 +256,64 @@ pub(crate) fn unsigned_integer<'src>() -> impl Parser<'src, &'src str, u64, Erro
     choice((hexadecimal(), binary(), decimal())).labelled("unsigned_integer")
 }
 
+/// Parses an integer in decimal, hexadecimal, or binary format, optionally preceded by `-`.
+/// Positive integers can take the whole range of a 64-bit unsigned integer, while negative
+/// ones have to fit in a 64-bit signed integer.
+pub(crate) fn signed_integer<'src>() -> impl Parser<'src, &'src str, i128, ErrorType<'src>> {
+    just('-')
+        .padded_by(whitespace_or_comment())
+        .or_not()
+        .then(unsigned_integer())
+        .labelled("signed integer")
+        // Labels replace errors at their start, so the range is checked after labelling
+        .try_map(|(minus, magnitude), span| {
+            if minus.is_none() {
+                return Ok(i128::from(magnitude));
+            }
+            let value = -i128::from(magnitude);
+            if value < i128::from(i64::MIN) {
+                return Err(RichError::custom(
+                    span,
+                    format!(
+                        "negative integer does not fit in a 64-bit signed integer, \
+                         which enumeration values are limited to ({}..{})",
+                        i64::MIN,
+                        u64::MAX
+                    ),
+                ));
+            }
+            Ok(value)
+        })
+}
+
 /// Parses an identifier from the input string. Identifier has to start with
 /// either alphabetic characters or an underscore, followed by alphanumeric
 /// characters or underscores.
 pub(crate) fn identifier<'src>() -> impl Parser<'src, &'src str, Identifier, ErrorType<'src>> {
     text::ident()
-        .map(|s: &str| Identifier::new(s))
+        .map_with(|s: &str, e| {
+            let span: SimpleSpan = e.span();
+            Identifier {
+                name: s.to_string(),
+                span: Span {
+                    start: span.start,
+                    end: span.end,
+                    line: 0,
+                },
+            }
+        })
         .labelled("identifier")
-        .padded()
+        .padded_by(whitespace_or_comment())
+}
+
+/// Parses a string literal enclosed in double quotes, which cannot span multiple lines.
+pub(crate) fn string_literal<'src>() -> impl Parser<'src, &'src str, String, ErrorType<'src>> {
+    none_of("\"\n")
+        .repeated()
+        .collect::<String>()
+        .delimited_by(just('"'), just('"'))
+        .labelled("string literal")
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a built-in type identifier from the input string.
@@ -189,6 +332,8 @@ pub(crate) fn builtin_type<'src>() -> impl Parser<'src, &'src str, TypeIdentifie
         just("float64").to(TypeIdentifier::Float64),
         just("bit").to(TypeIdentifier::Bit),
         just("byte").to(TypeIdentifier::Byte),
+        just("char").to(TypeIdentifier::Char),
+        just("void").to(TypeIdentifier::Void),
     ))
     .labelled("builtin type")
 }
@@ -199,62 +344,151 @@ pub(crate) fn user_defined_type<'src>()
     identifier()
         .map(TypeIdentifier::UserDefined)
         .labelled("user defined type")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
-/// Parses a static array type identifier from the input string.
-pub(crate) fn static_array_type<'src>()
--> impl Parser<'src, &'src str, TypeIdentifier, ErrorType<'src>> {
+/// Size of a static array as written in the input, before converting it to a number of elements.
+#[derive(Debug, Clone)]
+enum ArraySize {
+    Elements(u64),
+    Bytes(u64),
+    Constant(Identifier),
+}
+
+/// Parses an array type identifier from the input string, which is an element type followed by
+/// one or more dimensions, each being `[size]` for a static array or `[]` for a dynamic one.
+/// The first dimension is the outermost one, e.g. `int32[2][3]` is an array of 2 arrays of
+/// 3 elements. Sizes given in bytes are converted to numbers of elements, which requires
+/// the size of the element type to be known.
+pub(crate) fn array_type<'src>() -> impl Parser<'src, &'src str, TypeIdentifier, ErrorType<'src>> {
+    let size = choice((
+        just("bytes")
+            .padded_by(whitespace_or_comment())
+            .ignore_then(colon())
+            .ignore_then(unsigned_integer())
+            .map(ArraySize::Bytes),
+        unsigned_integer().map(ArraySize::Elements),
+        identifier().map(ArraySize::Constant),
+    ));
+    let dimension = left_bracket()
+        .ignore_then(size.or_not())
+        .then_ignore(right_bracket());
+
     choice((builtin_type(), user_defined_type()))
-        .then_ignore(left_bracket())
-        .then(unsigned_integer())
-        .then_ignore(right_bracket())
-        .map(|(r#type, size)| TypeIdentifier::StaticArray {
-            r#type: Box::new(r#type),
-            size,
+        .then(dimension.repeated().at_least(1).collect::<Vec<_>>())
+        .labelled("array type")
+        // Errors are emitted instead of failing the parser, so that they are reported as they
+        // are rather than as the tokens expected after the element type
+        .validate(|(element, dimensions), e, emitter| {
+            // The innermost array is the one of the last dimension
+            dimensions
+                .into_iter()
+                .rev()
+                .fold(element, |r#type, dimension| {
+                    let (size, size_constant) = match dimension {
+                        None => {
+                            return TypeIdentifier::DynamicArray {
+                                r#type: Box::new(r#type),
+                            };
+                        }
+                        Some(ArraySize::Elements(size)) => (size, None),
+                        // The value is filled in once all constants of the protocol are known
+                        Some(ArraySize::Constant(constant)) => (0, Some(constant)),
+                        Some(ArraySize::Bytes(bytes)) => {
+                            let Some(element_size) = r#type.builtin_size_in_bits() else {
+                                emitter.emit(RichError::custom(
+                                    e.span(),
+                                    "array sized in bytes must have a built-in element type",
+                                ));
+                                return TypeIdentifier::StaticArray {
+                                    r#type: Box::new(r#type),
+                                    size: bytes,
+                                    size_constant: None,
+                                };
+                            };
+                            if bytes == 0 {
+                                emitter.emit(RichError::custom(
+                                    e.span(),
+                                    "array sized in bytes must hold at least 1 byte",
+                                ));
+                            } else if u128::from(bytes) * 8 % u128::from(element_size) != 0 {
+                                emitter.emit(RichError::custom(
+                                    e.span(),
+                                    format!(
+                                        "{bytes} bytes do not hold a whole number of {element_size}-bit elements"
+                                    ),
+                                ));
+                            }
+                            (bytes.saturating_mul(8) / element_size, None)
+                        }
+                    };
+                    TypeIdentifier::StaticArray {
+                        r#type: Box::new(r#type),
+                        size,
+                        size_constant,
+                    }
+                })
         })
-        .labelled("static array type")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
-pub(crate) fn dynamic_array_type<'src>()
--> impl Parser<'src, &'src str, TypeIdentifier, ErrorType<'src>> {
-    choice((builtin_type(), user_defined_type()))
-        .then_ignore(left_bracket())
-        .then_ignore(right_bracket())
-        .map(|r#type| TypeIdentifier::DynamicArray {
-            r#type: Box::new(r#type),
+/// Parses text, which is a static array of characters when written as `string[size]`, and a
+/// dynamic array of characters when written as `string` alone.
+pub(crate) fn string_type<'src>() -> impl Parser<'src, &'src str, TypeIdentifier, ErrorType<'src>> {
+    let size = choice((
+        unsigned_integer().map(|size| (size, None)),
+        // The value is filled in once all constants of the protocol are known
+        identifier().map(|constant| (0, Some(constant))),
+    ));
+
+    identifier()
+        .filter(|identifier| identifier.name == "string")
+        .ignore_then(size.delimited_by(left_bracket(), right_bracket()).or_not())
+        .map(|size| match size {
+            Some((size, size_constant)) => TypeIdentifier::StaticArray {
+                r#type: Box::new(TypeIdentifier::Char),
+                size,
+                size_constant,
+            },
+            None => TypeIdentifier::DynamicArray {
+                r#type: Box::new(TypeIdentifier::Char),
+            },
         })
-        .labelled("dynamic array type")
-        .padded()
+        .labelled("string type")
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a type identifier from the input string. It can be a predefined type
 /// like `int8`, `uint16`, `float32`, etc., or a user-defined type.
 /// It can also be a static or dynamic array of a given type.
 /// The static array is defined as `type[size]`, and the dynamic array is defined as `type[]`.
+/// Arrays can have several dimensions, e.g. `int32[4][4]` or `byte[][8]`.
+/// Text is written as `string[size]` or `string`, standing for arrays of `char`.
 pub(crate) fn type_identifier<'src>()
 -> impl Parser<'src, &'src str, TypeIdentifier, ErrorType<'src>> {
     recursive(|_| {
         choice((
-            static_array_type().boxed(),
-            dynamic_array_type().boxed(),
+            string_type().boxed(),
+            array_type().boxed(),
             builtin_type().boxed(),
             user_defined_type().boxed(),
         ))
     })
 }
 
-/// Parses a single value enumeration field in the format `name = value;`
+/// Parses a single value enumeration field in the format `name = value;`, optionally preceded
+/// by a doc comment.
 pub(crate) fn enumeration_field_single_value<'src>()
 -> impl Parser<'src, &'src str, EnumerationField, ErrorType<'src>> {
-    identifier()
+    doc_comment()
+        .or_not()
+        .then(identifier())
         .then_ignore(equal())
-        .then(unsigned_integer())
+        .then(signed_integer())
         .then_ignore(semicolon())
-        .map(|(name, value)| EnumerationField::SingleValue { name, value })
+        .map(|((doc, name), value)| EnumerationField::SingleValue { name, value, doc })
         .labelled("enumeration field single value")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a range of values defined by `start..end`.
@@ -264,19 +498,38 @@ pub(crate) fn range<'src>() -> impl Parser<'src, &'src str, (u64, u64), ErrorTyp
         .then(unsigned_integer())
         .map(|(start, end)| (start, end))
         .labelled("range")
-        .padded()
+        .padded_by(whitespace_or_comment())
+}
+
+/// Parses a range of values defined by `start..end`, whose ends can be negative.
+pub(crate) fn signed_range<'src>() -> impl Parser<'src, &'src str, (i128, i128), ErrorType<'src>> {
+    signed_integer()
+        .then_ignore(double_dot())
+        .then(signed_integer())
+        .labelled("range")
+        .padded_by(whitespace_or_comment())
 }
 
-/// Parses a range of values enumeration field in the format `name = start..end;`
+/// Parses a range of values enumeration field in the format `name = start..end;`, optionally
+/// preceded by a doc comment. Unlike discriminators of unions, the values can be negative.
 pub(crate) fn enumeration_field_range_of_values<'src>()
 -> impl Parser<'src, &'src str, EnumerationField, ErrorType<'src>> {
-    identifier()
+    doc_comment()
+        .or_not()
+        .then(identifier())
         .then_ignore(equal())
-        .then(range())
+        .then(signed_range())
         .then_ignore(semicolon())
-        .map(|(name, (start, end))| EnumerationField::RangeOfValues { name, start, end })
+        .map(
+            |((doc, name), (start, end))| EnumerationField::RangeOfValues {
+                name,
+                start,
+                end,
+                doc,
+            },
+        )
         .labelled("enumeration field range of values")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses an enumeration field from the input string.
@@ -287,15 +540,16 @@ pub(crate) fn enumeration_field<'src>()
         enumeration_field_range_of_values(),
     ))
     .labelled("enumeration field")
-    .padded()
+    .padded_by(whitespace_or_comment())
 }
 
-/// Parses an enumeration with fields.
+/// Parses an enumeration with fields, optionally declaring the type its values are stored in.
 pub(crate) fn enumeration_definition<'src>()
 -> impl Parser<'src, &'src str, EnumerationDefinition, ErrorType<'src>> {
-    just("enum")
-        .padded()
-        .ignore_then(identifier())
+    optional_attributes()
+        .then_ignore(just("enum").padded_by(whitespace_or_comment()))
+        .then(identifier())
+        .then(colon().ignore_then(builtin_type()).or_not())
         .then_ignore(left_brace())
         .then(
             enumeration_field()
@@ -305,45 +559,163 @@ pub(crate) fn enumeration_definition<'src>()
         )
         .then_ignore(right_brace())
         .then_ignore(semicolon())
-        .map(|(name, fields)| EnumerationDefinition { name, fields })
+        .map(
+            |(((attributes, name), base), fields)| EnumerationDefinition {
+                name,
+                base,
+                fields,
+                attributes,
+                doc: None,
+            },
+        )
         .labelled("enumeration")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
-/// Parses a single structure field attribute, which consists of a name and a value.
-pub(crate) fn attribute<'src>() -> impl Parser<'src, &'src str, Attribute, ErrorType<'src>> {
+/// Kind of the value of an attribute, holding the function building the attribute from it.
+#[derive(Clone, Copy)]
+enum AttributeValueKind {
+    Number(fn(u64) -> Attribute),
+    Identifier(fn(Identifier) -> Result<Attribute, String>),
+    String(fn(String) -> Attribute),
+}
+
+impl AttributeValueKind {
+    fn description(&self) -> &'static str {
+        match self {
+            AttributeValueKind::Number(_) => "an unsigned integer",
+            AttributeValueKind::Identifier(_) => "an identifier",
+            AttributeValueKind::String(_) => "a string literal",
+        }
+    }
+}
+
+/// Value of an attribute, parsed before its kind is checked against the attribute name.
+#[derive(Debug, Clone)]
+enum AttributeValue {
+    Number(u64),
+    Identifier(Identifier),
+    String(String),
+}
+
+/// Names of all attributes with the kinds of their values. A new attribute only needs
+/// a new entry here and a variant of [`Attribute`].
+const ATTRIBUTES: &[(&str, AttributeValueKind)] = &[
+    (
+        "discriminated_by",
+        AttributeValueKind::Identifier(|field| Ok(Attribute::DiscriminatedBy { field })),
+    ),
+    (
+        "bits",
+        AttributeValueKind::Number(|size| Attribute::BitsSize { size }),
+    ),
+    (
+        "bytes",
+        AttributeValueKind::Number(|size| Attribute::BytesSize { size }),
+    ),
+    (
+        "id",
+        AttributeValueKind::Number(|value| Attribute::Id { value }),
+    ),
+    (
+        "c_type",
+        AttributeValueKind::String(|name| Attribute::CType { name }),
+    ),
+    (
+        "repr",
+        AttributeValueKind::Identifier(|name| {
+            let repr = match name.name.as_str() {
+                "c" => Repr::C,
+                "c_packed" => Repr::CPacked,
+                "transparent" => Repr::Transparent,
+                other => {
+                    return Err(format!(
+                        "unknown repr {other}, expected one of: c, c_packed, transparent"
+                    ));
+                }
+            };
+            Ok(Attribute::Repr { repr })
+        }),
+    ),
+    (
+        "assert",
+        AttributeValueKind::String(|expression| Attribute::Assert { expression }),
+    ),
+    (
+        "length_of",
+        AttributeValueKind::Identifier(|field| Ok(Attribute::LengthOf { field })),
+    ),
+    (
+        "length",
+        AttributeValueKind::Identifier(|field| Ok(Attribute::Length { field })),
+    ),
+];
+
+/// Parses the value of an attribute, which is an unsigned integer, a string literal
+/// or an identifier.
+fn attribute_value<'src>() -> impl Parser<'src, &'src str, AttributeValue, ErrorType<'src>> {
     choice((
-        just("discriminated_by")
-            .ignore_then(equal())
-            .ignore_then(identifier())
-            .map(|field| Attribute::DiscriminatedBy { field }),
-        just("bits")
-            .ignore_then(equal())
-            .ignore_then(unsigned_integer())
-            .map(|size| Attribute::BitsSize { size }),
-        just("bytes")
-            .ignore_then(equal())
-            .ignore_then(unsigned_integer())
-            .map(|size| Attribute::BytesSize { size }),
+        unsigned_integer().map(AttributeValue::Number),
+        string_literal().map(AttributeValue::String),
+        identifier().map(AttributeValue::Identifier),
     ))
-    .labelled("attribute")
-    .padded()
+    .labelled("attribute value")
+}
+
+/// Parses a single structure field attribute, which consists of a name and a value.
+/// The name must be one of [`ATTRIBUTES`] and the value must be of the kind it expects.
+pub(crate) fn attribute<'src>() -> impl Parser<'src, &'src str, Attribute, ErrorType<'src>> {
+    text::ident()
+        .labelled("attribute")
+        .then_ignore(equal())
+        .then(attribute_value())
+        .try_map(|(name, value): (&str, AttributeValue), span| {
+            let Some((_, kind)) = ATTRIBUTES
+                .iter()
+                .find(|(attribute_name, _)| *attribute_name == name)
+            else {
+                let names: Vec<&str> = ATTRIBUTES.iter().map(|(name, _)| *name).collect();
+                return Err(RichError::custom(
+                    span,
+                    format!(
+                        "unknown attribute {name}, expected one of: {}",
+                        names.join(", ")
+                    ),
+                ));
+            };
+            match (kind, value) {
+                (AttributeValueKind::Number(build), AttributeValue::Number(number)) => {
+                    Ok(build(number))
+                }
+                (AttributeValueKind::Identifier(build), AttributeValue::Identifier(identifier)) => {
+                    build(identifier).map_err(|message| RichError::custom(span, message))
+                }
+                (AttributeValueKind::String(build), AttributeValue::String(string)) => {
+                    Ok(build(string))
+                }
+                (kind, _) => Err(RichError::custom(
+                    span,
+                    format!("attribute {name} expects {}", kind.description()),
+                )),
+            }
+        })
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a structure field attribute tail, which is a comma followed by another attribute.
 pub(crate) fn attribute_tail<'src>() -> impl Parser<'src, &'src str, Attribute, ErrorType<'src>> {
     comma()
-        .padded()
+        .padded_by(whitespace_or_comment())
         .ignore_then(attribute())
         .labelled("attribute tail")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a collection of structure field attributes, which are enclosed in square brackets
 /// and separated by commas.
 pub(crate) fn attributes<'src>() -> impl Parser<'src, &'src str, Vec<Attribute>, ErrorType<'src>> {
     left_bracket()
-        .padded()
+        .padded_by(whitespace_or_comment())
         .ignore_then(
             attribute()
                 .then(attribute_tail().repeated().collect::<Vec<_>>())
@@ -355,34 +727,43 @@ pub(crate) fn attributes<'src>() -> impl Parser<'src, &'src str, Vec<Attribute>,
         )
         .then_ignore(right_bracket())
         .labelled("attributes")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
-/// Parses a structure field, which consists of a name and a type identifier.
+/// Parses an optional collection of attributes, returning an empty collection when there are none.
+pub(crate) fn optional_attributes<'src>()
+-> impl Parser<'src, &'src str, Vec<Attribute>, ErrorType<'src>> {
+    attributes().or_not().map(|attrs| attrs.unwrap_or_default())
+}
+
+/// Parses a structure field, which consists of a name and a type identifier, optionally
+/// preceded by a doc comment.
 pub(crate) fn structure_field<'src>()
 -> impl Parser<'src, &'src str, StructureField, ErrorType<'src>> {
-    attributes()
+    doc_comment()
         .or_not()
-        .map(|attrs| attrs.unwrap_or_default())
+        .then(optional_attributes())
         .then(identifier())
         .then_ignore(colon())
         .then(type_identifier())
         .then_ignore(semicolon())
-        .map(|((attributes, name), r#type)| StructureField {
+        .map(|(((doc, attributes), name), r#type)| StructureField {
+            reserved: StructureField::is_reserved_name(&name.name),
             attributes,
             name,
             r#type,
+            doc,
         })
         .labelled("structure field")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a structure definition, which consists of a name and a collection of fields.
 pub(crate) fn structure_definition<'src>()
 -> impl Parser<'src, &'src str, StructureDefinition, ErrorType<'src>> {
-    just("struct")
-        .padded()
-        .ignore_then(identifier())
+    optional_attributes()
+        .then_ignore(just("struct").padded_by(whitespace_or_comment()))
+        .then(identifier())
         .then_ignore(left_brace())
         .then(
             structure_field()
@@ -392,9 +773,14 @@ pub(crate) fn structure_definition<'src>()
         )
         .then_ignore(right_brace())
         .then_ignore(semicolon())
-        .map(|(name, fields)| StructureDefinition { name, fields })
+        .map(|((attributes, name), fields)| StructureDefinition {
+            name,
+            fields,
+            attributes,
+            doc: None,
+        })
         .labelled("structure definition")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a union field with a single discriminator, which consists of a discriminator, name, and type identifier.
@@ -412,7 +798,7 @@ pub(crate) fn union_field_single_value<'src>()
             discriminator,
         })
         .labelled("union field")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a union field with a range of discriminators, which consists of a start and end discriminator, name, and type identifier.
@@ -435,22 +821,28 @@ pub(crate) fn union_field_range_of_values<'src>()
             },
         )
         .labelled("union field range of values")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a union field, which can either be a single value or a range of values.
 pub(crate) fn union_field<'src>() -> impl Parser<'src, &'src str, UnionField, ErrorType<'src>> {
-    choice((union_field_single_value(), union_field_range_of_values()))
+    // Union fields are not documented, so comments directly preceding them are dropped
+    doc_comment()
+        .or_not()
+        .ignore_then(choice((
+            union_field_single_value(),
+            union_field_range_of_values(),
+        )))
         .labelled("union field")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a union definition, which consists of a name and a collection of union fields.
 pub(crate) fn union_definition<'src>()
 -> impl Parser<'src, &'src str, UnionDefinition, ErrorType<'src>> {
-    just("union")
-        .padded()
-        .ignore_then(identifier())
+    optional_attributes()
+        .then_ignore(just("union").padded_by(whitespace_or_comment()))
+        .then(identifier())
         .then_ignore(left_brace())
         .then(
             union_field()
@@ -460,35 +852,180 @@ pub(crate) fn union_definition<'src>()
         )
         .then_ignore(right_brace())
         .then_ignore(semicolon())
-        .map(|(name, fields)| UnionDefinition { name, fields })
+        .map(|((attributes, name), fields)| UnionDefinition {
+            name,
+            fields,
+            attributes,
+            doc: None,
+        })
         .labelled("union")
-        .padded()
+        .padded_by(whitespace_or_comment())
+}
+
+/// Parses a single field of a `oneof`, which consists of a name and a type identifier.
+pub(crate) fn oneof_field<'src>()
+-> impl Parser<'src, &'src str, (Identifier, TypeIdentifier), ErrorType<'src>> {
+    // Like union fields, oneof fields are not documented
+    doc_comment()
+        .or_not()
+        .ignore_then(identifier())
+        .then_ignore(colon())
+        .then(type_identifier())
+        .then_ignore(semicolon())
+        .labelled("oneof field")
+        .padded_by(whitespace_or_comment())
+}
+
+/// Parses a `oneof`, which is a union with discriminators assigned automatically
+/// in the order of its fields, starting from 0.
+pub(crate) fn oneof_definition<'src>()
+-> impl Parser<'src, &'src str, UnionDefinition, ErrorType<'src>> {
+    optional_attributes()
+        .then_ignore(just("oneof").padded_by(whitespace_or_comment()))
+        .then(identifier())
+        .then_ignore(left_brace())
+        .then(oneof_field().repeated().at_least(1).collect::<Vec<_>>())
+        .then_ignore(right_brace())
+        .then_ignore(semicolon())
+        .map(|((attributes, name), fields)| UnionDefinition {
+            name,
+            fields: fields
+                .into_iter()
+                .zip(0..)
+                .map(|((name, r#type), discriminator)| UnionField::SingleValue {
+                    name,
+                    r#type,
+                    discriminator,
+                })
+                .collect(),
+            attributes,
+            doc: None,
+        })
+        .labelled("oneof")
+        .padded_by(whitespace_or_comment())
 }
 
 /// Parses a type definition, which consists of a new type name and an existing type.
 pub(crate) fn type_definition<'src>()
 -> impl Parser<'src, &'src str, TypeDefinition, ErrorType<'src>> {
-    just("using")
-        .padded()
-        .ignore_then(identifier())
+    optional_attributes()
+        .then_ignore(just("using").padded_by(whitespace_or_comment()))
+        .then(identifier())
         .then_ignore(equal())
         .then(type_identifier())
         .then_ignore(semicolon())
-        .map(|(new_type, r#type)| TypeDefinition { new_type, r#type })
+        .map(|((attributes, new_type), r#type)| TypeDefinition {
+            new_type,
+            r#type,
+            attributes,
+            doc: None,
+        })
         .labelled("type definition")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
-/// Parses a single definition, which can be an enumeration, structure, union, or type definition.
+/// Parses a built-in unsigned integer type, which can be used as the underlying type of flags.
+pub(crate) fn unsigned_integer_type<'src>()
+-> impl Parser<'src, &'src str, TypeIdentifier, ErrorType<'src>> {
+    choice((
+        just("uint8").to(TypeIdentifier::UnsignedInteger8),
+        just("uint16").to(TypeIdentifier::UnsignedInteger16),
+        just("uint32").to(TypeIdentifier::UnsignedInteger32),
+        just("uint64").to(TypeIdentifier::UnsignedInteger64),
+    ))
+    .labelled("unsigned integer type")
+    .padded_by(whitespace_or_comment())
+}
+
+/// Parses a single flag in the format `name = mask;`
+pub(crate) fn flags_field<'src>() -> impl Parser<'src, &'src str, FlagsField, ErrorType<'src>> {
+    // Flags fields are not documented, so comments directly preceding them are dropped
+    doc_comment()
+        .or_not()
+        .ignore_then(identifier())
+        .then_ignore(equal())
+        .then(unsigned_integer())
+        .then_ignore(semicolon())
+        .map(|(name, value)| FlagsField { name, value })
+        .labelled("flags field")
+        .padded_by(whitespace_or_comment())
+}
+
+/// Parses a set of flags, which consists of a name, an underlying unsigned integer type
+/// and the masks of the flags.
+pub(crate) fn flags_definition<'src>()
+-> impl Parser<'src, &'src str, FlagsDefinition, ErrorType<'src>> {
+    optional_attributes()
+        .then_ignore(just("flags").padded_by(whitespace_or_comment()))
+        .then(identifier())
+        .then_ignore(colon())
+        .then(unsigned_integer_type())
+        .then_ignore(left_brace())
+        .then(flags_field().repeated().at_least(1).collect::<Vec<_>>())
+        .then_ignore(right_brace())
+        .then_ignore(semicolon())
+        .map(|(((attributes, name), r#type), fields)| FlagsDefinition {
+            name,
+            r#type,
+            fields,
+            attributes,
+            doc: None,
+        })
+        .labelled("flags")
+        .padded_by(whitespace_or_comment())
+}
+
+/// Parses a constant in the format `const NAME = value;`
+pub(crate) fn constant_definition<'src>()
+-> impl Parser<'src, &'src str, ConstantDefinition, ErrorType<'src>> {
+    just("const")
+        .padded_by(whitespace_or_comment())
+        .ignore_then(identifier())
+        .then_ignore(equal())
+        .then(unsigned_integer())
+        .then_ignore(semicolon())
+        .map(|(name, value)| ConstantDefinition {
+            name,
+            value,
+            doc: None,
+        })
+        .labelled("constant")
+        .padded_by(whitespace_or_comment())
+}
+
+/// Parses the header naming the module of the protocol and optionally giving its version,
+/// in the format `module name version 1;`
+pub(crate) fn module_header<'src>()
+-> impl Parser<'src, &'src str, (String, Option<u64>), ErrorType<'src>> {
+    just("module")
+        .padded_by(whitespace_or_comment())
+        .ignore_then(identifier())
+        .then(
+            just("version")
+                .padded_by(whitespace_or_comment())
+                .ignore_then(unsigned_integer())
+                .or_not(),
+        )
+        .then_ignore(semicolon())
+        .map(|(name, version)| (name.name, version))
+        .labelled("module header")
+        .padded_by(whitespace_or_comment())
+}
+
+/// Parses a single definition, which can be an enumeration, structure, union, type definition,
+/// a set of flags or a constant.
 pub(crate) fn definition<'src>() -> impl Parser<'src, &'src str, Definition, ErrorType<'src>> {
     choice((
         enumeration_definition().map(Definition::Enumeration),
         structure_definition().map(Definition::Structure),
         union_definition().map(Definition::Union),
+        oneof_definition().map(Definition::Union),
         type_definition().map(Definition::Type),
+        flags_definition().map(Definition::Flags),
+        constant_definition().map(Definition::Constant),
     ))
     .labelled("definition")
-    .padded()
+    .padded_by(whitespace_or_comment())
 }
 
 /// Parses a comment which is the whole line starting with `#` and ending with a newline.
@@ -505,24 +1042,183 @@ pub(crate) fn comment<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src
         .padded()
 }
 
+/// Parses optional whitespace, which can contain `/* ... */` block comments and `#` comments
+/// other than doc comments, which are left to be attached to the following definition or field.
+/// Like `.padded()`, it never fails and does not add anything to the expected input of errors.
+pub(crate) fn whitespace_or_comment<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
+    custom(|input| {
+        loop {
+            while input.peek().is_some_and(char::is_whitespace) {
+                input.skip();
+            }
+            match input.peek() {
+                Some('#') => {
+                    let checkpoint = input.save();
+                    let is_doc_comment = starts_doc_comment(input);
+                    input.rewind(checkpoint);
+                    if is_doc_comment {
+                        return Ok(());
+                    }
+                    while input.peek().is_some_and(|c| c != '\n' && c != '\r') {
+                        input.skip();
+                    }
+                }
+                Some('/') => {
+                    let checkpoint = input.save();
+                    input.skip();
+                    if input.peek() != Some('*') {
+                        input.rewind(checkpoint);
+                        return Ok(());
+                    }
+                    input.skip();
+                    let mut previous = None;
+                    loop {
+                        match input.next() {
+                            Some('/') if previous == Some('*') => break,
+                            // An unterminated block comment is reported by the parser after it
+                            None => {
+                                input.rewind(checkpoint);
+                                return Ok(());
+                            }
+                            current => previous = current,
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    })
+}
+
+/// Checks whether the input starts with a block of comment lines, which [`doc_comment`] would
+/// attach to the definition or field following it. Consumes the input it looks at.
+fn starts_doc_comment<'src>(input: &mut InputRef<'src, '_, &'src str, ErrorType<'src>>) -> bool {
+    let is_inline_whitespace = |c: char| c.is_whitespace() && c != '\n' && c != '\r';
+    while input.peek() == Some('#') {
+        while input.peek().is_some_and(|c| c != '\n' && c != '\r') {
+            input.skip();
+        }
+        match input.next() {
+            Some('\r') => {
+                if input.peek() == Some('\n') {
+                    input.skip();
+                }
+            }
+            Some('\n') => {}
+            _ => return false,
+        }
+        while input.peek().is_some_and(is_inline_whitespace) {
+            input.skip();
+        }
+    }
+    !matches!(input.peek(), None | Some('\r' | '\n' | '}'))
+}
+
+/// Parses a single comment line, returning its text without the `#` and the space following it.
+fn doc_comment_line<'src>() -> impl Parser<'src, &'src str, String, ErrorType<'src>> {
+    text::inline_whitespace()
+        .ignore_then(just('#'))
+        .ignore_then(none_of("\r\n").repeated().to_slice())
+        .then_ignore(text::newline())
+        .map(|text: &str| {
+            text.strip_prefix(' ')
+                .unwrap_or(text)
+                .trim_end()
+                .to_string()
+        })
+}
+
+/// Parses a block of comment lines which is directly followed by a non-empty line,
+/// so that it can be attached to the definition starting there. A block followed by the
+/// closing brace of a definition is not attached to anything.
+pub(crate) fn doc_comment<'src>() -> impl Parser<'src, &'src str, String, ErrorType<'src>> {
+    doc_comment_line()
+        .repeated()
+        .at_least(1)
+        .collect::<Vec<String>>()
+        .then_ignore(text::inline_whitespace())
+        .then_ignore(none_of("\r\n}").rewind())
+        .map(|lines| lines.join("\n"))
+        .labelled("doc comment")
+}
+
+fn with_doc(mut definition: Definition, doc: String) -> Definition {
+    let target = match &mut definition {
+        Definition::Enumeration(enumeration_def) => &mut enumeration_def.doc,
+        Definition::Structure(structure_def) => &mut structure_def.doc,
+        Definition::Union(union_def) => &mut union_def.doc,
+        Definition::Type(type_def) => &mut type_def.doc,
+        Definition::Flags(flags_def) => &mut flags_def.doc,
+        Definition::Constant(constant_def) => &mut constant_def.doc,
+    };
+    *target = Some(doc);
+    definition
+}
+
+/// Skips a definition that failed to parse, up to and including the `;` ending it outside of
+/// braces, or up to the end of the input. Used to recover from errors, so that the following
+/// definitions are still parsed and their errors reported as well. Fails when only whitespace
+/// is left.
+fn skip_definition<'src>() -> impl Parser<'src, &'src str, (), ErrorType<'src>> {
+    custom(|input| {
+        let before = input.cursor();
+        let mut depth = 0usize;
+        let mut skipped_definition = false;
+        loop {
+            let next = input.next();
+            skipped_definition |= next.is_some_and(|c: char| !c.is_whitespace());
+            match next {
+                Some('{') => depth += 1,
+                Some('}') => depth = depth.saturating_sub(1),
+                Some(';') if depth == 0 => return Ok(()),
+                Some(_) => {}
+                None if skipped_definition => return Ok(()),
+                None => {
+                    let span = input.span_since(&before);
+                    return Err(RichError::custom(span, "expected a definition"));
+                }
+            }
+        }
+    })
+}
+
 /// Parses the entire protocol, which consists of multiple definitions and comments
 /// that can be mixed (i.e. definition, comment, definition, definition, comment, etc.).
 pub(crate) fn protocol<'src>() -> impl Parser<'src, &'src str, Protocol, ErrorType<'src>> {
+    // The optional header comes first, possibly after comments
+    let header = comment().repeated().ignore_then(module_header()).or_not();
     // Accept either a definition or a comment, and collect only definitions
-    choice((definition().map(Some), comment().to(None)))
-        .repeated()
-        .collect::<Vec<Option<Definition>>>()
-        .map(|items| {
+    // (with the comments directly preceding them attached)
+    let items = choice((
+        doc_comment()
+            .then(definition())
+            .map(|(doc, definition)| Some(with_doc(definition, doc))),
+        definition().map(Some),
+        comment().to(None),
+    ))
+    .recover_with(via_parser(skip_definition().to(None)))
+    .repeated()
+    .collect::<Vec<Option<Definition>>>();
+    header
+        .then(items)
+        .map(|(header, items)| {
+            let (name, version) =
+                header.map_or((None, None), |(name, version)| (Some(name), version));
             let definitions = items.into_iter().flatten().collect();
-            Protocol { definitions }
+            Protocol {
+                name,
+                version,
+                definitions,
+            }
         })
         .labelled("protocol")
-        .padded()
+        .padded_by(whitespace_or_comment())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
 
     #[test]
     fn test_left_brace() {
@@ -690,6 +1386,19 @@ mod tests {
         assert_eq!(result.into_output().unwrap(), 12345);
     }
 
+    #[rstest]
+    #[case::decimal("18446744073709551616")]
+    #[case::hexadecimal("0x10000000000000000")]
+    #[case::binary("0b10000000000000000000000000000000000000000000000000000000000000000")]
+    fn test_unsigned_integer_out_of_range(#[case] input: &str) {
+        let result = unsigned_integer().parse(input);
+        let errors: Vec<String> = result.errors().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec!["integer does not fit in a 64-bit unsigned integer (0..18446744073709551615)"]
+        );
+    }
+
     #[test]
     fn test_unsigned_integer() {
         for value in ["5589", "0x15D5", "0b1010111010101"] {
@@ -699,15 +1408,62 @@ mod tests {
         }
     }
 
+    #[rstest]
+    #[case::positive("42", 42)]
+    #[case::negative("-42", -42)]
+    #[case::negative_hexadecimal("- 0x10", -16)]
+    #[case::smallest("-9223372036854775808", i64::MIN.into())]
+    #[case::largest_signed("9223372036854775807", i64::MAX.into())]
+    #[case::largest("0xFFFFFFFFFFFFFFFF", u64::MAX.into())]
+    fn test_signed_integer(#[case] input: &str, #[case] expected: i128) {
+        let result = signed_integer().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(result.into_output().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::too_large("18446744073709551616")]
+    #[case::too_small("-9223372036854775809")]
+    fn test_signed_integer_out_of_range(#[case] input: &str) {
+        assert!(signed_integer().parse(input).has_errors());
+    }
+
+    #[test]
+    fn test_enumeration_definition_with_value_out_of_range() {
+        let result = enumeration_definition().parse("enum E { a = 0; b = -0x8000000000000001; };");
+        let errors: Vec<String> = result.errors().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "negative integer does not fit in a 64-bit signed integer, which enumeration \
+                 values are limited to (-9223372036854775808..18446744073709551615)"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enumeration_definition_with_value_above_signed_range() {
+        let result =
+            enumeration_definition().parse("enum E: uint64 { a = 0; b = 0xFFFFFFFFFFFFFFFF; };");
+        assert!(!result.has_errors());
+        assert_eq!(
+            result.into_output().unwrap().fields[1],
+            EnumerationField::SingleValue {
+                name: Identifier::new("b"),
+                value: u64::MAX.into(),
+                doc: None,
+            }
+        );
+    }
+
     #[test]
     fn test_identifier() {
         let result = identifier().parse("myIdentifier");
         assert!(!result.has_errors() && result.has_output());
         assert_eq!(
             result.into_output().unwrap(),
-            Identifier {
-                name: "myIdentifier".to_string()
-            }
+            Identifier::new("myIdentifier")
         );
     }
 
@@ -717,9 +1473,7 @@ mod tests {
         assert!(!result.has_errors() && result.has_output());
         assert_eq!(
             result.into_output().unwrap(),
-            Identifier {
-                name: "_myIdentifier".to_string()
-            }
+            Identifier::new("_myIdentifier")
         );
     }
 
@@ -729,9 +1483,7 @@ mod tests {
         assert!(!result.has_errors() && result.has_output());
         assert_eq!(
             result.into_output().unwrap(),
-            Identifier {
-                name: "myIdentifier123".to_string()
-            }
+            Identifier::new("myIdentifier123")
         );
     }
 
@@ -758,23 +1510,10 @@ mod tests {
 
     #[test]
     fn test_builtin_type() {
-        for (type_str, expected_type) in [
-            ("int8", TypeIdentifier::Integer8),
-            ("int16", TypeIdentifier::Integer16),
-            ("int32", TypeIdentifier::Integer32),
-            ("int64", TypeIdentifier::Integer64),
-            ("uint8", TypeIdentifier::UnsignedInteger8),
-            ("uint16", TypeIdentifier::UnsignedInteger16),
-            ("uint32", TypeIdentifier::UnsignedInteger32),
-            ("uint64", TypeIdentifier::UnsignedInteger64),
-            ("float32", TypeIdentifier::Float32),
-            ("float64", TypeIdentifier::Float64),
-            ("bit", TypeIdentifier::Bit),
-            ("byte", TypeIdentifier::Byte),
-        ] {
+        for (type_str, expected_type) in crate::ast::BUILTIN_TYPES {
             let result = builtin_type().parse(type_str);
             assert!(!result.has_errors() && result.has_output());
-            assert_eq!(result.into_output().unwrap(), expected_type);
+            assert_eq!(&result.into_output().unwrap(), expected_type);
         }
     }
 
@@ -790,47 +1529,152 @@ mod tests {
 
     #[test]
     fn test_static_array_type_with_builtin_type() {
-        let result = static_array_type().parse("int32[5]");
+        let result = array_type().parse("int32[5]");
         assert!(!result.has_errors() && result.has_output());
         assert_eq!(
             result.into_output().unwrap(),
             TypeIdentifier::StaticArray {
                 r#type: Box::new(TypeIdentifier::Integer32),
                 size: 5,
+                size_constant: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_static_array_type_with_custom_type() {
+        let result = array_type().parse("MyType[10]");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            TypeIdentifier::StaticArray {
+                r#type: Box::new(TypeIdentifier::UserDefined(Identifier::new("MyType"))),
+                size: 10,
+                size_constant: None,
+            }
+        );
+    }
+
+    #[rstest]
+    #[case("uint16[bytes: 10]", TypeIdentifier::UnsignedInteger16, 5)]
+    #[case("int64[bytes:0x10]", TypeIdentifier::Integer64, 2)]
+    #[case("byte[ bytes : 3 ]", TypeIdentifier::Byte, 3)]
+    #[case("bit[bytes: 2]", TypeIdentifier::Bit, 16)]
+    fn test_static_array_type_sized_in_bytes(
+        #[case] input: &str,
+        #[case] element_type: TypeIdentifier,
+        #[case] size: u64,
+    ) {
+        let result = array_type().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            TypeIdentifier::StaticArray {
+                r#type: Box::new(element_type),
+                size,
+                size_constant: None,
+            }
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "uint32[bytes: 6]",
+        "6 bytes do not hold a whole number of 32-bit elements"
+    )]
+    #[case(
+        "MyType[bytes: 4]",
+        "array sized in bytes must have a built-in element type"
+    )]
+    #[case("uint8[bytes: 0]", "array sized in bytes must hold at least 1 byte")]
+    fn test_static_array_type_sized_in_bytes_invalid(
+        #[case] input: &str,
+        #[case] expected_error: &str,
+    ) {
+        let result = array_type().parse(input);
+        assert!(result.has_errors());
+        assert!(
+            result
+                .errors()
+                .any(|error| error.to_string().contains(expected_error))
+        );
+    }
+
+    #[test]
+    fn test_static_array_type_with_wrong_size() {
+        let result = array_type().parse("int32[1.5]");
+        assert!(result.has_errors());
+        assert!(!result.has_output());
+    }
+
+    #[test]
+    fn test_static_array_type_sized_by_constant() {
+        let result = array_type().parse("byte[MAX_LEN]");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            TypeIdentifier::StaticArray {
+                r#type: Box::new(TypeIdentifier::Byte),
+                size: 0,
+                size_constant: Some(Identifier::new("MAX_LEN")),
+            }
+        );
+    }
+
+    #[test]
+    fn test_static_array_type_with_negative_size() {
+        let result = array_type().parse("int32[-5]");
+        assert!(result.has_errors());
+        assert!(!result.has_output());
+    }
+
+    #[test]
+    fn test_array_type_with_several_dimensions() {
+        let result = array_type().parse("int32[4][ bytes: 8 ]");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            TypeIdentifier::StaticArray {
+                r#type: Box::new(TypeIdentifier::StaticArray {
+                    r#type: Box::new(TypeIdentifier::Integer32),
+                    size: 2,
+                    size_constant: None,
+                }),
+                size: 4,
+                size_constant: None,
             }
         );
     }
 
     #[test]
-    fn test_static_array_type_with_custom_type() {
-        let result = static_array_type().parse("MyType[10]");
+    fn test_array_type_with_dynamic_array_of_static_arrays() {
+        let result = array_type().parse("byte[][8]");
         assert!(!result.has_errors() && result.has_output());
         assert_eq!(
             result.into_output().unwrap(),
-            TypeIdentifier::StaticArray {
-                r#type: Box::new(TypeIdentifier::UserDefined(Identifier::new("MyType"))),
-                size: 10,
+            TypeIdentifier::DynamicArray {
+                r#type: Box::new(TypeIdentifier::StaticArray {
+                    r#type: Box::new(TypeIdentifier::Byte),
+                    size: 8,
+                    size_constant: None,
+                }),
             }
         );
     }
 
     #[test]
-    fn test_static_array_type_with_wrong_size() {
-        let result = static_array_type().parse("int32[invalid]");
-        assert!(result.has_errors());
-        assert!(!result.has_output());
-    }
-
-    #[test]
-    fn test_static_array_type_with_negative_size() {
-        let result = static_array_type().parse("int32[-5]");
-        assert!(result.has_errors());
-        assert!(!result.has_output());
+    fn test_array_type_sized_in_bytes_outside_of_innermost_dimension() {
+        let result = array_type().parse("byte[bytes: 4][2]");
+        assert!(result.errors().any(|error| {
+            error
+                .to_string()
+                .contains("array sized in bytes must have a built-in element type")
+        }));
     }
 
     #[test]
     fn test_dynamic_array_type_with_builtin_type() {
-        let result = dynamic_array_type().parse("uint64[]");
+        let result = array_type().parse("uint64[]");
         assert!(!result.has_errors() && result.has_output());
         assert_eq!(
             result.into_output().unwrap(),
@@ -842,7 +1686,7 @@ mod tests {
 
     #[test]
     fn test_dynamic_array_type_with_user_defined_type() {
-        let result = dynamic_array_type().parse("MyType[]");
+        let result = array_type().parse("MyType[]");
         assert!(!result.has_errors() && result.has_output());
         assert_eq!(
             result.into_output().unwrap(),
@@ -852,6 +1696,30 @@ mod tests {
         );
     }
 
+    #[rstest]
+    #[case("string[16]", TypeIdentifier::StaticArray {
+        r#type: Box::new(TypeIdentifier::Char),
+        size: 16,
+        size_constant: None,
+    })]
+    #[case("string[ NAME_LEN ]", TypeIdentifier::StaticArray {
+        r#type: Box::new(TypeIdentifier::Char),
+        size: 0,
+        size_constant: Some(Identifier::new("NAME_LEN")),
+    })]
+    #[case("string", TypeIdentifier::DynamicArray { r#type: Box::new(TypeIdentifier::Char) })]
+    #[case("char[4]", TypeIdentifier::StaticArray {
+        r#type: Box::new(TypeIdentifier::Char),
+        size: 4,
+        size_constant: None,
+    })]
+    #[case("strings", TypeIdentifier::UserDefined(Identifier::new("strings")))]
+    fn test_type_identifier_with_text(#[case] input: &str, #[case] expected: TypeIdentifier) {
+        let result = type_identifier().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(result.into_output().unwrap(), expected);
+    }
+
     #[test]
     fn test_type_identifier_with_builtin_type() {
         let result = type_identifier().parse("int32");
@@ -878,6 +1746,7 @@ mod tests {
             TypeIdentifier::StaticArray {
                 r#type: Box::new(TypeIdentifier::Integer32),
                 size: 10,
+                size_constant: None,
             }
         );
     }
@@ -902,7 +1771,8 @@ mod tests {
             result.into_output().unwrap(),
             EnumerationField::SingleValue {
                 name: Identifier::new("myField"),
-                value: 42
+                value: 42,
+                doc: None,
             }
         );
     }
@@ -979,7 +1849,34 @@ mod tests {
             EnumerationField::RangeOfValues {
                 name: Identifier::new("myRange"),
                 start: 10,
-                end: 20
+                end: 20,
+                doc: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_enumeration_field_negative_values() {
+        let result = enumeration_field_single_value().parse("error = -1;");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            EnumerationField::SingleValue {
+                name: Identifier::new("error"),
+                value: -1,
+                doc: None,
+            }
+        );
+
+        let result = enumeration_field_range_of_values().parse("reserved = -5..-0x2;");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            EnumerationField::RangeOfValues {
+                name: Identifier::new("reserved"),
+                start: -5,
+                end: -2,
+                doc: None,
             }
         );
     }
@@ -1020,7 +1917,8 @@ mod tests {
             result.into_output().unwrap(),
             EnumerationField::SingleValue {
                 name: Identifier::new("myField"),
-                value: 42
+                value: 42,
+                doc: None,
             }
         );
 
@@ -1031,7 +1929,8 @@ mod tests {
             EnumerationField::RangeOfValues {
                 name: Identifier::new("myRange"),
                 start: 10,
-                end: 20
+                end: 20,
+                doc: None,
             }
         );
     }
@@ -1044,22 +1943,42 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             EnumerationDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyEnum"),
+                base: None,
                 fields: vec![
                     EnumerationField::SingleValue {
                         name: Identifier::new("myField"),
-                        value: 42
+                        value: 42,
+                        doc: None,
                     },
                     EnumerationField::RangeOfValues {
                         name: Identifier::new("myRange"),
                         start: 10,
-                        end: 20
+                        end: 20,
+                        doc: None,
                     }
                 ],
+                doc: None,
             }
         );
     }
 
+    #[rstest]
+    #[case::unsigned("enum Status : uint8 { ok = 0; };", TypeIdentifier::UnsignedInteger8)]
+    #[case::signed("enum Status:int32 { ok = 0; };", TypeIdentifier::Integer32)]
+    fn test_enumeration_with_base_type(#[case] input: &str, #[case] expected: TypeIdentifier) {
+        let result = enumeration_definition().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(result.into_output().unwrap().base, Some(expected));
+    }
+
+    #[test]
+    fn test_enumeration_with_user_defined_base_type() {
+        let result = enumeration_definition().parse("enum Status : Other { ok = 0; };");
+        assert!(result.has_errors());
+    }
+
     #[test]
     fn test_enumeration_with_multiline_input() {
         let input = "enum MyEnum {
@@ -1071,18 +1990,23 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             EnumerationDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyEnum"),
+                base: None,
                 fields: vec![
                     EnumerationField::SingleValue {
                         name: Identifier::new("myField"),
-                        value: 42
+                        value: 42,
+                        doc: None,
                     },
                     EnumerationField::RangeOfValues {
                         name: Identifier::new("myRange"),
                         start: 10,
-                        end: 20
+                        end: 20,
+                        doc: None,
                     }
                 ],
+                doc: None,
             }
         );
     }
@@ -1095,18 +2019,23 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             EnumerationDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyEnum"),
+                base: None,
                 fields: vec![
                     EnumerationField::SingleValue {
                         name: Identifier::new("myField"),
-                        value: 42
+                        value: 42,
+                        doc: None,
                     },
                     EnumerationField::RangeOfValues {
                         name: Identifier::new("myRange"),
                         start: 10,
-                        end: 20
+                        end: 20,
+                        doc: None,
                     }
                 ],
+                doc: None,
             }
         );
     }
@@ -1156,6 +2085,129 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_attribute_id() {
+        let result = attribute().parse("id = 0x10");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(result.into_output().unwrap(), Attribute::Id { value: 16 });
+    }
+
+    #[test]
+    fn test_attribute_c_type() {
+        let result = attribute().parse("c_type = \"size_t\"");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            Attribute::CType {
+                name: "size_t".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_attribute_c_type_with_spaces_in_name() {
+        let result = attribute().parse("c_type=\"unsigned long long\"");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            Attribute::CType {
+                name: "unsigned long long".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_attribute_c_type_without_quotes() {
+        let result = attribute().parse("c_type = size_t");
+        assert!(result.has_errors());
+    }
+
+    #[test]
+    fn test_attribute_assert() {
+        let result = attribute().parse("assert=\"value != 0 && value < 10\"");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            Attribute::Assert {
+                expression: "value != 0 && value < 10".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_attribute_assert_without_quotes() {
+        let result = attribute().parse("assert = value");
+        assert!(result.has_errors());
+    }
+
+    #[rstest]
+    #[case("repr = c", Repr::C)]
+    #[case("repr=c_packed", Repr::CPacked)]
+    #[case("repr = transparent", Repr::Transparent)]
+    fn test_attribute_repr(#[case] input: &str, #[case] repr: Repr) {
+        let result = attribute().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(result.into_output().unwrap(), Attribute::Repr { repr });
+    }
+
+    #[test]
+    fn test_attribute_repr_unknown() {
+        let result = attribute().parse("repr = packed");
+        assert!(result.has_errors());
+        assert!(result.errors().any(|error| {
+            error
+                .to_string()
+                .contains("unknown repr packed, expected one of: c, c_packed, transparent")
+        }));
+    }
+
+    #[rstest]
+    #[case("discriminated_by = kind", Attribute::DiscriminatedBy { field: Identifier::new("kind") })]
+    #[case("bits = 3", Attribute::BitsSize { size: 3 })]
+    #[case("bytes = 0b10", Attribute::BytesSize { size: 2 })]
+    #[case("id = 0x10", Attribute::Id { value: 16 })]
+    #[case("c_type = \"size_t\"", Attribute::CType { name: "size_t".to_string() })]
+    #[case("repr = c", Attribute::Repr { repr: Repr::C })]
+    #[case("assert = \"value <= 100\"", Attribute::Assert { expression: "value <= 100".to_string() })]
+    #[case("length_of = data", Attribute::LengthOf { field: Identifier::new("data") })]
+    #[case("length = count", Attribute::Length { field: Identifier::new("count") })]
+    fn test_attribute_from_table(#[case] input: &str, #[case] expected: Attribute) {
+        let result = attribute().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(result.into_output().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::unknown_name(
+        "size = 4",
+        "unknown attribute size, expected one of: discriminated_by, bits, bytes, id, c_type, repr, assert"
+    )]
+    #[case::identifier_instead_of_number(
+        "bits = eight",
+        "attribute bits expects an unsigned integer"
+    )]
+    #[case::number_instead_of_identifier(
+        "discriminated_by = 1",
+        "attribute discriminated_by expects an identifier"
+    )]
+    fn test_attribute_invalid(#[case] input: &str, #[case] expected_error: &str) {
+        let result = attribute().parse(input);
+        assert!(result.has_errors());
+        assert!(
+            result
+                .errors()
+                .any(|error| error.to_string().contains(expected_error)),
+            "{:?}",
+            result.errors().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_string_literal_unterminated() {
+        let result = string_literal().parse("\"size_t\n\"");
+        assert!(result.has_errors());
+    }
+
     #[test]
     fn test_attribute_tail() {
         let result = attribute_tail().parse(", bits = 10");
@@ -1193,6 +2245,24 @@ mod tests {
                 attributes: vec![],
                 name: Identifier::new("myField"),
                 r#type: TypeIdentifier::Integer32,
+                doc: None,
+                reserved: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_reserved_structure_field() {
+        let result = structure_field().parse("[bits=4] _padding: uint8;");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            StructureField {
+                attributes: vec![Attribute::BitsSize { size: 4 }],
+                name: Identifier::new("_padding"),
+                r#type: TypeIdentifier::UnsignedInteger8,
+                doc: None,
+                reserved: true,
             }
         );
     }
@@ -1207,6 +2277,8 @@ mod tests {
                 attributes: vec![],
                 name: Identifier::new("myField"),
                 r#type: TypeIdentifier::UserDefined(Identifier::new("MyCustomType")),
+                doc: None,
+                reserved: false,
             }
         );
     }
@@ -1223,7 +2295,10 @@ mod tests {
                 r#type: TypeIdentifier::StaticArray {
                     r#type: Box::new(TypeIdentifier::Integer32),
                     size: 10,
+                    size_constant: None,
                 },
+                doc: None,
+                reserved: false,
             }
         );
     }
@@ -1240,6 +2315,8 @@ mod tests {
                 r#type: TypeIdentifier::DynamicArray {
                     r#type: Box::new(TypeIdentifier::UnsignedInteger64),
                 },
+                doc: None,
+                reserved: false,
             }
         );
     }
@@ -1252,12 +2329,15 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             StructureDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyStruct"),
                 fields: vec![
                     StructureField {
                         attributes: vec![],
                         name: Identifier::new("myField"),
                         r#type: TypeIdentifier::Integer32,
+                        doc: None,
+                        reserved: false,
                     },
                     StructureField {
                         attributes: vec![],
@@ -1265,8 +2345,11 @@ mod tests {
                         r#type: TypeIdentifier::DynamicArray {
                             r#type: Box::new(TypeIdentifier::UnsignedInteger64),
                         },
+                        doc: None,
+                        reserved: false,
                     }
                 ],
+                doc: None,
             }
         );
     }
@@ -1279,12 +2362,15 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             StructureDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyStruct"),
                 fields: vec![
                     StructureField {
                         attributes: vec![],
                         name: Identifier::new("myField"),
                         r#type: TypeIdentifier::Integer32,
+                        doc: None,
+                        reserved: false,
                     },
                     StructureField {
                         attributes: vec![],
@@ -1292,8 +2378,11 @@ mod tests {
                         r#type: TypeIdentifier::DynamicArray {
                             r#type: Box::new(TypeIdentifier::UnsignedInteger64),
                         },
+                        doc: None,
+                        reserved: false,
                     }
                 ],
+                doc: None,
             }
         );
     }
@@ -1333,6 +2422,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_union_field_single_value_with_void() {
+        let result = union_field_single_value().parse("0 => none: void;");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            UnionField::SingleValue {
+                name: Identifier::new("none"),
+                r#type: TypeIdentifier::Void,
+                discriminator: 0,
+            }
+        );
+    }
+
     #[test]
     fn test_union_field_single_value_with_user_defined_type() {
         let result = union_field_single_value().parse("2 => myField: MyCustomType;");
@@ -1358,6 +2461,7 @@ mod tests {
                 r#type: TypeIdentifier::StaticArray {
                     r#type: Box::new(TypeIdentifier::Integer32),
                     size: 10,
+                    size_constant: None,
                 },
                 discriminator: 3,
             }
@@ -1431,6 +2535,7 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             UnionDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyUnion"),
                 fields: vec![
                     UnionField::SingleValue {
@@ -1446,6 +2551,7 @@ mod tests {
                         discriminator: 2,
                     }
                 ],
+                doc: None,
             }
         );
     }
@@ -1458,6 +2564,7 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             UnionDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyUnion"),
                 fields: vec![
                     UnionField::SingleValue {
@@ -1473,10 +2580,117 @@ mod tests {
                         discriminator: 2,
                     }
                 ],
+                doc: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_oneof() {
+        let input = "oneof MyOneof { first: int32; second: MyStruct; };";
+        let result = oneof_definition().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            UnionDefinition {
+                attributes: vec![],
+                name: Identifier::new("MyOneof"),
+                fields: vec![
+                    UnionField::SingleValue {
+                        name: Identifier::new("first"),
+                        r#type: TypeIdentifier::Integer32,
+                        discriminator: 0,
+                    },
+                    UnionField::SingleValue {
+                        name: Identifier::new("second"),
+                        r#type: TypeIdentifier::UserDefined(Identifier::new("MyStruct")),
+                        discriminator: 1,
+                    }
+                ],
+                doc: None,
             }
         );
     }
 
+    #[test]
+    fn test_oneof_produces_same_definition_as_union() {
+        let oneof = definition().parse("oneof MyUnion { first: int32; second: uint64[]; };");
+        let union =
+            definition().parse("union MyUnion { 0 => first: int32; 1 => second: uint64[]; };");
+        assert!(!oneof.has_errors() && !union.has_errors());
+        assert_eq!(oneof.into_output().unwrap(), union.into_output().unwrap());
+    }
+
+    #[test]
+    fn test_oneof_with_discriminators() {
+        let result = oneof_definition().parse("oneof MyOneof { 0 => first: int32; };");
+        assert!(result.has_errors());
+    }
+
+    #[test]
+    fn test_flags() {
+        let input = "flags Permissions : uint8 { read = 0x1; write = 0x2; execute = 4; };";
+        let result = definition().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            Definition::Flags(FlagsDefinition {
+                name: Identifier::new("Permissions"),
+                r#type: TypeIdentifier::UnsignedInteger8,
+                fields: vec![
+                    FlagsField {
+                        name: Identifier::new("read"),
+                        value: 1,
+                    },
+                    FlagsField {
+                        name: Identifier::new("write"),
+                        value: 2,
+                    },
+                    FlagsField {
+                        name: Identifier::new("execute"),
+                        value: 4,
+                    },
+                ],
+                attributes: vec![],
+                doc: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_constant() {
+        let result = definition().parse("const MAX_LEN = 0x100;");
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            Definition::Constant(ConstantDefinition {
+                name: Identifier::new("MAX_LEN"),
+                value: 256,
+                doc: None,
+            })
+        );
+    }
+
+    #[rstest]
+    #[case::negative("const MAX_LEN = -1;")]
+    #[case::missing_value("const MAX_LEN;")]
+    #[case::type_value("const MAX_LEN = uint8;")]
+    fn test_constant_invalid(#[case] input: &str) {
+        let result = constant_definition().parse(input);
+        assert!(result.has_errors());
+    }
+
+    #[rstest]
+    #[case::signed_type("flags Permissions : int8 { read = 1; };")]
+    #[case::user_defined_type("flags Permissions : MyType { read = 1; };")]
+    #[case::missing_type("flags Permissions { read = 1; };")]
+    #[case::range("flags Permissions : uint8 { read = 1..2; };")]
+    #[case::no_fields("flags Permissions : uint8 { };")]
+    fn test_flags_invalid(#[case] input: &str) {
+        let result = flags_definition().parse(input);
+        assert!(result.has_errors());
+    }
+
     #[test]
     fn test_union_without_identifier() {
         let result = union_definition().parse("union { 1 => myField: int32; };");
@@ -1491,8 +2705,10 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             TypeDefinition {
+                attributes: vec![],
                 new_type: Identifier::new("MyType"),
                 r#type: TypeIdentifier::Integer32,
+                doc: None,
             }
         );
     }
@@ -1504,8 +2720,10 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             TypeDefinition {
+                attributes: vec![],
                 new_type: Identifier::new("MyType"),
                 r#type: TypeIdentifier::UserDefined(Identifier::new("MyCustomType")),
+                doc: None,
             }
         );
     }
@@ -1517,11 +2735,14 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             TypeDefinition {
+                attributes: vec![],
                 new_type: Identifier::new("MyType"),
                 r#type: TypeIdentifier::StaticArray {
                     r#type: Box::new(TypeIdentifier::Integer32),
                     size: 10,
+                    size_constant: None,
                 },
+                doc: None,
             }
         );
     }
@@ -1533,10 +2754,12 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             TypeDefinition {
+                attributes: vec![],
                 new_type: Identifier::new("MyType"),
                 r#type: TypeIdentifier::DynamicArray {
                     r#type: Box::new(TypeIdentifier::UnsignedInteger64),
                 },
+                doc: None,
             }
         );
     }
@@ -1549,18 +2772,23 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             Definition::Enumeration(EnumerationDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyEnum"),
+                base: None,
                 fields: vec![
                     EnumerationField::SingleValue {
                         name: Identifier::new("myField"),
-                        value: 42
+                        value: 42,
+                        doc: None,
                     },
                     EnumerationField::RangeOfValues {
                         name: Identifier::new("myRange"),
                         start: 10,
-                        end: 20
+                        end: 20,
+                        doc: None,
                     }
                 ],
+                doc: None,
             })
         );
     }
@@ -1573,12 +2801,15 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             Definition::Structure(StructureDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyStruct"),
                 fields: vec![
                     StructureField {
                         attributes: vec![],
                         name: Identifier::new("myField"),
                         r#type: TypeIdentifier::Integer32,
+                        doc: None,
+                        reserved: false,
                     },
                     StructureField {
                         attributes: vec![],
@@ -1586,8 +2817,11 @@ mod tests {
                         r#type: TypeIdentifier::DynamicArray {
                             r#type: Box::new(TypeIdentifier::UnsignedInteger64),
                         },
+                        doc: None,
+                        reserved: false,
                     }
                 ],
+                doc: None,
             })
         );
     }
@@ -1600,6 +2834,7 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             Definition::Union(UnionDefinition {
+                attributes: vec![],
                 name: Identifier::new("MyUnion"),
                 fields: vec![
                     UnionField::SingleValue {
@@ -1615,6 +2850,7 @@ mod tests {
                         discriminator: 2,
                     }
                 ],
+                doc: None,
             })
         );
     }
@@ -1627,12 +2863,49 @@ mod tests {
         assert_eq!(
             result.into_output().unwrap(),
             Definition::Type(TypeDefinition {
+                attributes: vec![],
                 new_type: Identifier::new("MyType"),
                 r#type: TypeIdentifier::Integer32,
+                doc: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_definition_with_attributes() {
+        let input = "[id=0x10] struct Ping { sequence_number: uint32; };";
+        let result = definition().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(
+            result.into_output().unwrap(),
+            Definition::Structure(StructureDefinition {
+                attributes: vec![Attribute::Id { value: 16 }],
+                name: Identifier::new("Ping"),
+                fields: vec![StructureField {
+                    attributes: vec![],
+                    name: Identifier::new("sequence_number"),
+                    r#type: TypeIdentifier::UnsignedInteger32,
+                    doc: None,
+                    reserved: false,
+                }],
+                doc: None,
             })
         );
     }
 
+    #[test]
+    fn test_definition_with_attributes_on_every_kind() {
+        for input in [
+            "[id=1] enum MyEnum { a = 1; };",
+            "[id=1] union MyUnion { 1 => a: int32; };",
+            "[id=1] using MyType = int32;",
+        ] {
+            let result = definition().parse(input);
+            assert!(!result.has_errors() && result.has_output(), "{input}");
+            assert_eq!(result.into_output().unwrap().id(), Some(1));
+        }
+    }
+
     #[test]
     fn test_comment_starting_after_space() {
         let input = "# This is a comment\n";
@@ -1654,6 +2927,200 @@ mod tests {
         assert!(!result.has_errors() && result.has_output());
     }
 
+    #[test]
+    fn test_protocol_attaches_preceding_comments_to_definition() {
+        let input = r#"
+# Header of the file, separated by an empty line.
+
+# Describes the type
+#    of a message.
+enum MessageType {
+    ping = 0;
+};
+using Undocumented = uint8;
+"#;
+        let result = protocol().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        let definitions = result.into_output().unwrap().definitions;
+        assert_eq!(
+            definitions[0].doc(),
+            Some("Describes the type\n   of a message.")
+        );
+        assert_eq!(definitions[1].doc(), None);
+    }
+
+    #[rstest]
+    #[case::array_size("struct A { x: uint8[18446744073709551616]; };", 20..40)]
+    #[case::attribute("struct A { [bits=0x10000000000000000] x: uint8; };", 17..36)]
+    #[case::union_field("union U { 18446744073709551616 => a: uint8; };", 10..30)]
+    #[case::constant("const N = 0b10000000000000000000000000000000000000000000000000000000000000000;", 10..77)]
+    fn test_protocol_with_unsigned_integer_out_of_range(
+        #[case] input: &str,
+        #[case] expected_span: std::ops::Range<usize>,
+    ) {
+        let result = protocol().parse(input);
+        let errors: Vec<(String, std::ops::Range<usize>)> = result
+            .errors()
+            .map(|error| (error.to_string(), error.span().into_range()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![(
+                "integer does not fit in a 64-bit unsigned integer (0..18446744073709551615)"
+                    .to_string(),
+                expected_span
+            )]
+        );
+    }
+
+    #[rstest]
+    #[case::partial_element(
+        "struct A { x: uint32[bytes: 6]; };",
+        "6 bytes do not hold a whole number of 32-bit elements"
+    )]
+    #[case::empty(
+        "struct A { x: uint8[bytes: 0]; };",
+        "array sized in bytes must hold at least 1 byte"
+    )]
+    #[case::user_defined_element(
+        "struct A { x: B[bytes: 4]; };",
+        "array sized in bytes must have a built-in element type"
+    )]
+    fn test_protocol_with_invalid_array_sized_in_bytes(
+        #[case] input: &str,
+        #[case] expected_error: &str,
+    ) {
+        let result = protocol().parse(input);
+        let errors: Vec<String> = result.errors().map(|error| error.to_string()).collect();
+        assert_eq!(errors, vec![expected_error.to_string()]);
+    }
+
+    #[test]
+    fn test_protocol_does_not_attach_comment_separated_by_empty_line() {
+        let input = "# Not a doc comment\n\nstruct MyStruct { myField: int32; };";
+        let result = protocol().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(result.into_output().unwrap().definitions[0].doc(), None);
+    }
+
+    #[test]
+    fn test_protocol_attaches_preceding_comments_to_fields() {
+        let input = r#"
+enum MessageType {
+    # Asks for a pong.
+    ping = 0;
+    # Reserved for
+    # future use.
+    reserved = 1..3;
+};
+struct Message {
+    # Kind of the message.
+    [bits=2] kind: MessageType;
+    payload: byte[];
+};
+"#;
+        let result = protocol().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        let definitions = result.into_output().unwrap().definitions;
+        let (Definition::Enumeration(enumeration), Definition::Structure(structure)) =
+            (&definitions[0], &definitions[1])
+        else {
+            panic!("unexpected definitions {definitions:?}");
+        };
+        assert_eq!(enumeration.fields[0].doc(), Some("Asks for a pong."));
+        assert_eq!(
+            enumeration.fields[1].doc(),
+            Some("Reserved for\nfuture use.")
+        );
+        assert_eq!(
+            structure.fields[0].doc.as_deref(),
+            Some("Kind of the message.")
+        );
+        assert_eq!(structure.fields[1].doc, None);
+    }
+
+    #[rstest]
+    #[case("module ping_pong;", ("ping_pong".to_string(), None))]
+    #[case("module ping_pong version 2;", ("ping_pong".to_string(), Some(2)))]
+    #[case("module ping_pong /* v */ version 0x10 ;", ("ping_pong".to_string(), Some(16)))]
+    fn test_module_header(#[case] input: &str, #[case] expected: (String, Option<u64>)) {
+        let result = module_header().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        assert_eq!(result.into_output().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_protocol_with_module_header() {
+        let input = "# Ping pong protocol\n\nmodule ping_pong version 2;\n\nconst N = 1;\n";
+        let parsed = protocol().parse(input).into_output().unwrap();
+        assert_eq!(parsed.name.as_deref(), Some("ping_pong"));
+        assert_eq!(parsed.version, Some(2));
+        assert_eq!(parsed.definitions.len(), 1);
+
+        let parsed = protocol().parse("const N = 1;").into_output().unwrap();
+        assert_eq!((parsed.name, parsed.version), (None, None));
+        assert!(protocol().parse("const N = 1; module late;").has_errors());
+    }
+
+    #[test]
+    fn test_protocol_with_comments_inside_definitions() {
+        let input = r#"
+enum MessageType /* on the wire */ : uint8 {
+    ping = 0; /* pings */ pong = 1;
+    # Not attached to anything.
+
+    # Asks for data.
+    request = 2;
+    # Last of the values.
+};
+struct Message {
+    /* Kind of the message. */
+    kind: MessageType;
+
+    # Separated from the field by an empty line.
+
+    [bits=4] /* padded */ flags: uint8;
+    # Trailing comment.
+};
+union Payload {
+    # Not a doc comment, as unions do not keep them.
+    0 => first: uint8;
+    1 => /* second */ second: int16;
+};
+flags Status : uint8 {
+    # Set once the device is ready.
+    ready = 0x01;
+};
+"#;
+        let result = protocol().parse(input);
+        assert!(!result.has_errors() && result.has_output());
+        let definitions = result.into_output().unwrap().definitions;
+        let (
+            Definition::Enumeration(enumeration),
+            Definition::Structure(structure),
+            Definition::Union(union),
+        ) = (&definitions[0], &definitions[1], &definitions[2])
+        else {
+            panic!("unexpected definitions {definitions:?}");
+        };
+        assert_eq!(enumeration.base, Some(TypeIdentifier::UnsignedInteger8));
+        assert_eq!(enumeration.fields.len(), 3);
+        assert_eq!(enumeration.fields[1].doc(), None);
+        assert_eq!(enumeration.fields[2].doc(), Some("Asks for data."));
+        assert_eq!(structure.fields.len(), 2);
+        assert_eq!(structure.fields[0].doc, None);
+        assert_eq!(structure.fields[1].doc, None);
+        assert_eq!(structure.fields[1].name, Identifier::new("flags"));
+        assert_eq!(union.fields.len(), 2);
+        assert!(matches!(&definitions[3], Definition::Flags(flags) if flags.fields.len() == 1));
+    }
+
+    #[test]
+    fn test_protocol_with_unterminated_block_comment() {
+        let result = protocol().parse("struct Message { /* kind: uint8; };");
+        assert!(result.has_errors());
+    }
+
     #[test]
     fn test_protocol() {
         let input = r#"
@@ -1682,35 +3149,48 @@ union MyUnion {
         assert_eq!(
             result.into_output().unwrap(),
             Protocol {
+                name: None,
+                version: None,
                 definitions: vec![
                     Definition::Type(TypeDefinition {
+                        attributes: vec![],
                         new_type: Identifier::new("MyType"),
                         r#type: TypeIdentifier::StaticArray {
                             r#type: Box::new(TypeIdentifier::Integer32),
                             size: 10,
+                            size_constant: None,
                         },
+                        doc: None,
                     }),
                     Definition::Enumeration(EnumerationDefinition {
+                        attributes: vec![],
                         name: Identifier::new("MyEnum"),
+                        base: None,
                         fields: vec![
                             EnumerationField::SingleValue {
                                 name: Identifier::new("myField"),
-                                value: 42
+                                value: 42,
+                                doc: None,
                             },
                             EnumerationField::RangeOfValues {
                                 name: Identifier::new("myRange"),
                                 start: 10,
-                                end: 20
+                                end: 20,
+                                doc: None,
                             }
                         ],
+                        doc: Some("full line comment does not break things".to_string()),
                     }),
                     Definition::Structure(StructureDefinition {
+                        attributes: vec![],
                         name: Identifier::new("MyStruct"),
                         fields: vec![
                             StructureField {
                                 attributes: vec![],
                                 name: Identifier::new("myField"),
                                 r#type: TypeIdentifier::Integer32,
+                                doc: None,
+                                reserved: false,
                             },
                             StructureField {
                                 attributes: vec![],
@@ -1718,6 +3198,8 @@ union MyUnion {
                                 r#type: TypeIdentifier::DynamicArray {
                                     r#type: Box::new(TypeIdentifier::UnsignedInteger64),
                                 },
+                                doc: None,
+                                reserved: false,
                             },
                             StructureField {
                                 attributes: vec![
@@ -1729,10 +3211,14 @@ union MyUnion {
                                 ],
                                 name: Identifier::new("myType"),
                                 r#type: TypeIdentifier::UserDefined(Identifier::new("MyType")),
+                                doc: None,
+                                reserved: false,
                             }
                         ],
+                        doc: Some("some strange formatted comment also works".to_string()),
                     }),
                     Definition::Union(UnionDefinition {
+                        attributes: vec![],
                         name: Identifier::new("MyUnion"),
                         fields: vec![
                             UnionField::SingleValue {
@@ -1748,6 +3234,7 @@ union MyUnion {
                                 discriminator: 2,
                             }
                         ],
+                        doc: Some("and without space it also does work".to_string()),
                     }),
                 ],
             }
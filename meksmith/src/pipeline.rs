@@ -0,0 +1,299 @@
+use crate::MeksmithError;
+use crate::ast::{DefinitionKind, Protocol};
+use crate::smith::Smith;
+use crate::smith_c::CCodegenOptions;
+use crate::smith_kaitai::KaitaiCodegenOptions;
+use crate::smith_kotlin::KotlinCodegenOptions;
+use crate::smith_rust::RustCodegenOptions;
+use crate::smith_wireshark::WiresharkCodegenOptions;
+use crate::validation::{Diagnostic, ValidationOptions};
+
+/// Selects the smith generating the output of [`compile`], one for each of the
+/// [`crate::smith::LANGUAGES`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Backend {
+    #[default]
+    C,
+    Rust,
+    Kotlin,
+    Swift,
+    Python,
+    Protobuf,
+    Kaitai,
+    Wireshark,
+    AsciiDoc,
+}
+
+impl Backend {
+    /// All backends, in the order of [`crate::smith::LANGUAGES`].
+    pub const ALL: [Backend; 9] = [
+        Backend::C,
+        Backend::Rust,
+        Backend::Kotlin,
+        Backend::Swift,
+        Backend::Python,
+        Backend::Protobuf,
+        Backend::Kaitai,
+        Backend::Wireshark,
+        Backend::AsciiDoc,
+    ];
+
+    /// Returns the language of the smith, see [`crate::smith::smith_for`].
+    pub fn language(self) -> &'static str {
+        match self {
+            Backend::C => "c",
+            Backend::Rust => "rust",
+            Backend::Kotlin => "kotlin",
+            Backend::Swift => "swift",
+            Backend::Python => "python",
+            Backend::Protobuf => "protobuf",
+            Backend::Kaitai => "kaitai",
+            Backend::Wireshark => "wireshark",
+            Backend::AsciiDoc => "asciidoc",
+        }
+    }
+
+    /// Returns the smith of the language, configured with the options of the language.
+    pub fn smith(self, options: &CompileOptions) -> Box<dyn Smith> {
+        crate::smith::smith_with_options(self.language(), options)
+            .expect("Every backend has a smith")
+    }
+}
+
+/// Options of all smiths, only the ones of the selected [`Backend`] are used.
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    pub c: CCodegenOptions,
+    pub rust: RustCodegenOptions,
+    pub kotlin: KotlinCodegenOptions,
+    /// Package the generated Kotlin code is placed in.
+    pub kotlin_package: String,
+    pub kaitai: KaitaiCodegenOptions,
+    pub wireshark: WiresharkCodegenOptions,
+    pub validation: ValidationOptions,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            c: CCodegenOptions::default(),
+            rust: RustCodegenOptions::default(),
+            kotlin: KotlinCodegenOptions::default(),
+            kotlin_package: "meksmith.generated".to_string(),
+            kaitai: KaitaiCodegenOptions::default(),
+            wireshark: WiresharkCodegenOptions::default(),
+            validation: ValidationOptions::default(),
+        }
+    }
+}
+
+/// Overview of the definitions of a protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolSummary {
+    pub enumerations: usize,
+    pub structures: usize,
+    pub unions: usize,
+    pub type_definitions: usize,
+    pub flags: usize,
+    pub constants: usize,
+    /// Content hash of the protocol, see [`crate::ast::protocol_hash`].
+    pub hash: u64,
+}
+
+impl ProtocolSummary {
+    pub fn new(protocol: &Protocol) -> Self {
+        let count = |kind: DefinitionKind| {
+            protocol
+                .definitions
+                .iter()
+                .filter(|definition| definition.kind() == kind)
+                .count()
+        };
+        ProtocolSummary {
+            enumerations: count(DefinitionKind::Enumeration),
+            structures: count(DefinitionKind::Structure),
+            unions: count(DefinitionKind::Union),
+            type_definitions: count(DefinitionKind::Type),
+            flags: count(DefinitionKind::Flags),
+            constants: count(DefinitionKind::Constant),
+            hash: crate::ast::protocol_hash(protocol),
+        }
+    }
+}
+
+/// Result of a successful [`compile`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileOutput {
+    pub code: String,
+    /// Warnings found in the protocol, errors make [`compile`] fail instead.
+    pub diagnostics: Vec<Diagnostic>,
+    pub summary: ProtocolSummary,
+    /// Name given by the `module` header of the protocol, if it has one.
+    pub module_name: Option<String>,
+}
+
+/// Parses the input, validates and sorts the protocol, and generates code with the smith
+/// selected by `backend`.
+pub fn compile(
+    input: &str,
+    backend: Backend,
+    options: &CompileOptions,
+) -> Result<CompileOutput, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    compile_protocol(&protocol, backend, options)
+}
+
+/// Same as [`compile`], but takes a protocol which is already parsed.
+pub fn compile_protocol(
+    protocol: &Protocol,
+    backend: Backend,
+    options: &CompileOptions,
+) -> Result<CompileOutput, MeksmithError> {
+    let (sorted, diagnostics) =
+        crate::ast::prepare_with_warnings_and_options(protocol, &options.validation)?;
+    let code = backend.smith(options).generate_prepared(&sorted)?;
+    Ok(CompileOutput {
+        code,
+        diagnostics,
+        summary: ProtocolSummary::new(&sorted),
+        module_name: sorted.name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    #[test]
+    fn test_compile_c_backend() {
+        let output = compile(PING_PONG, Backend::C, &CompileOptions::default()).unwrap();
+        assert_eq!(
+            output.code,
+            crate::smith_c::generate_c_code_from_string_with_options(
+                PING_PONG,
+                &crate::smith_c::CCodegenOptions::default()
+            )
+            .unwrap()
+        );
+        assert!(output.diagnostics.is_empty());
+        assert_eq!(output.module_name, None);
+        assert_eq!(
+            output.summary,
+            ProtocolSummary {
+                enumerations: 2,
+                structures: 3,
+                unions: 1,
+                type_definitions: 2,
+                flags: 0,
+                constants: 0,
+                hash: crate::ast::protocol_hash(&crate::parse_protocol_to_ast(PING_PONG).unwrap()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_compile_returns_module_name() {
+        let output = compile(
+            "module Telemetry version 1;\nstruct Message { field: uint8; };",
+            Backend::Rust,
+            &CompileOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(output.module_name.as_deref(), Some("Telemetry"));
+    }
+
+    #[rstest]
+    #[case(Backend::Rust, "pub struct Message {")]
+    #[case(Backend::Kotlin, "package meksmith.generated\n")]
+    #[case(Backend::Swift, "struct Message: Codable {")]
+    #[case(Backend::Python, "class Message:")]
+    #[case(Backend::Protobuf, "message Ping {")]
+    #[case(Backend::Kaitai, "id: message")]
+    #[case(Backend::Wireshark, "Proto(\"message\", \"Message\")")]
+    #[case(Backend::AsciiDoc, "== Message")]
+    fn test_compile_other_backends(#[case] backend: Backend, #[case] expected: &str) {
+        let output = compile(PING_PONG, backend, &CompileOptions::default()).unwrap();
+        assert!(output.code.contains(expected));
+    }
+
+    #[rstest]
+    #[case::c(
+        Backend::C,
+        "        length = (uint64_t)out->count;\n",
+        "meksmith_write_length(writer, in->payload_length, 32)"
+    )]
+    #[case::rust(
+        Backend::Rust,
+        "read_vec_of_length(input, field_count as u64, |input| Ok(read_unsigned(input, 16)? as u16))?",
+        "read_vec(input, |input|"
+    )]
+    #[case::kotlin(
+        Backend::Kotlin,
+        "List(count.toLong().toInt()) { readUnsigned(reader, 16).toUShort() }",
+        "writeUnsigned(writer, payload.size.toLong(), 32)"
+    )]
+    fn test_compile_linked_dynamic_array_without_length_prefix(
+        #[case] backend: Backend,
+        #[case] reads_linked_length: &str,
+        #[case] length_prefix: &str,
+    ) {
+        let mut options = CompileOptions::default();
+        options.c.emit_encoders = true;
+        options.c.emit_decoders = true;
+        options.rust.emit_parsers = true;
+        for input in [
+            "struct Data { count: uint8; [length=count] payload: uint16[]; };",
+            "struct Data { [length_of=payload] count: uint8; payload: uint16[]; };",
+        ] {
+            let code = compile(input, backend, &options).unwrap().code;
+            assert!(code.contains(reads_linked_length), "{code}");
+            assert!(!code.contains(length_prefix), "{code}");
+        }
+        let code = compile("struct Data { payload: uint16[]; };", backend, &options)
+            .unwrap()
+            .code;
+        assert!(code.contains(length_prefix), "{code}");
+    }
+
+    #[test]
+    fn test_backends_cover_all_smiths() {
+        let languages = Backend::ALL.map(|backend| {
+            let smith = backend.smith(&CompileOptions::default());
+            assert_eq!(smith.language(), backend.language());
+            backend.language()
+        });
+        assert_eq!(languages, crate::smith::LANGUAGES);
+    }
+
+    #[test]
+    fn test_compile_returns_warnings_and_errors() {
+        let output = compile(
+            "enum Unused { a = 0; }; struct Message { field: uint8; };",
+            Backend::C,
+            &CompileOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            output.diagnostics,
+            vec![Diagnostic::warning(
+                "Definition Unused is never used".to_string()
+            )]
+        );
+
+        assert!(matches!(
+            compile(
+                "struct Message { field: Missing; };",
+                Backend::C,
+                &CompileOptions::default()
+            ),
+            Err(MeksmithError::Validation(_))
+        ));
+        assert!(matches!(
+            compile("struct Message {", Backend::C, &CompileOptions::default()),
+            Err(MeksmithError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::MeksmithError;
+use crate::ast::Protocol;
+use crate::pipeline::CompileOptions;
+use crate::smith_c::CCodegenOptions;
+use crate::smith_kaitai::KaitaiCodegenOptions;
+use crate::smith_kotlin::KotlinCodegenOptions;
+use crate::smith_rust::RustCodegenOptions;
+use crate::smith_wireshark::WiresharkCodegenOptions;
+
+/// Code generator producing code of a single language out of a protocol.
+pub trait Smith {
+    /// Validates the protocol, sorts it by dependencies and generates the code.
+    fn generate(&self, protocol: &Protocol) -> Result<String, MeksmithError> {
+        let (sorted, _) = crate::ast::prepare_with_warnings(protocol)?;
+        self.generate_prepared(&sorted)
+    }
+
+    /// Generates the code of a protocol which is already validated and sorted by
+    /// dependencies, checking only the requirements of the language itself.
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError>;
+
+    /// Returns the name the smith is looked up by with [`smith_for`].
+    fn language(&self) -> &str;
+
+    /// Returns the extension of the files holding the generated code, without the dot.
+    fn file_extension(&self) -> &str;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CSmith {
+    pub options: CCodegenOptions,
+}
+
+impl Smith for CSmith {
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError> {
+        self.options.validate()?;
+        self.options.validate_protocol(sorted)?;
+        Ok(crate::smith_c::generate_c_code_with_options(
+            sorted,
+            &self.options,
+        ))
+    }
+
+    fn language(&self) -> &str {
+        "c"
+    }
+
+    fn file_extension(&self) -> &str {
+        "h"
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RustSmith {
+    pub options: RustCodegenOptions,
+}
+
+impl Smith for RustSmith {
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError> {
+        Ok(crate::smith_rust::generate_rust_code_with_options(
+            sorted,
+            &self.options,
+        ))
+    }
+
+    fn language(&self) -> &str {
+        "rust"
+    }
+
+    fn file_extension(&self) -> &str {
+        "rs"
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KotlinSmith {
+    /// Package the generated code is placed in.
+    pub package: String,
+    pub options: KotlinCodegenOptions,
+}
+
+impl Default for KotlinSmith {
+    fn default() -> Self {
+        KotlinSmith {
+            package: "meksmith.generated".to_string(),
+            options: KotlinCodegenOptions::default(),
+        }
+    }
+}
+
+impl Smith for KotlinSmith {
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError> {
+        Ok(crate::smith_kotlin::generate_kotlin_with_options(
+            sorted,
+            &self.package,
+            &self.options,
+        ))
+    }
+
+    fn language(&self) -> &str {
+        "kotlin"
+    }
+
+    fn file_extension(&self) -> &str {
+        "kt"
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SwiftSmith;
+
+impl Smith for SwiftSmith {
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError> {
+        Ok(crate::smith_swift::generate_swift(sorted))
+    }
+
+    fn language(&self) -> &str {
+        "swift"
+    }
+
+    fn file_extension(&self) -> &str {
+        "swift"
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PythonSmith;
+
+impl Smith for PythonSmith {
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError> {
+        Ok(crate::smith_python::generate_python(sorted))
+    }
+
+    fn language(&self) -> &str {
+        "python"
+    }
+
+    fn file_extension(&self) -> &str {
+        "py"
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtobufSmith;
+
+impl Smith for ProtobufSmith {
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError> {
+        crate::smith_protobuf::validate(sorted).map_err(MeksmithError::Validation)?;
+        Ok(crate::smith_protobuf::generate_proto(sorted))
+    }
+
+    fn language(&self) -> &str {
+        "protobuf"
+    }
+
+    fn file_extension(&self) -> &str {
+        "proto"
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct KaitaiSmith {
+    pub options: KaitaiCodegenOptions,
+}
+
+impl Smith for KaitaiSmith {
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError> {
+        Ok(crate::smith_kaitai::generate_kaitai_with_options(
+            sorted,
+            &self.options,
+        ))
+    }
+
+    fn language(&self) -> &str {
+        "kaitai"
+    }
+
+    fn file_extension(&self) -> &str {
+        "ksy"
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WiresharkSmith {
+    pub options: WiresharkCodegenOptions,
+}
+
+impl Smith for WiresharkSmith {
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError> {
+        Ok(crate::smith_wireshark::generate_wireshark_with_options(
+            sorted,
+            &self.options,
+        ))
+    }
+
+    fn language(&self) -> &str {
+        "wireshark"
+    }
+
+    fn file_extension(&self) -> &str {
+        "lua"
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsciiDocSmith;
+
+impl Smith for AsciiDocSmith {
+    fn generate_prepared(&self, sorted: &Protocol) -> Result<String, MeksmithError> {
+        Ok(crate::smith_asciidoc::generate_adoc(sorted))
+    }
+
+    fn language(&self) -> &str {
+        "asciidoc"
+    }
+
+    fn file_extension(&self) -> &str {
+        "adoc"
+    }
+}
+
+/// Names of the languages of all smiths, which [`smith_for`] accepts.
+pub const LANGUAGES: [&str; 9] = [
+    "c",
+    "rust",
+    "kotlin",
+    "swift",
+    "python",
+    "protobuf",
+    "kaitai",
+    "wireshark",
+    "asciidoc",
+];
+
+/// Returns the smith generating the given language with default options, or `None` if there
+/// is no smith for it.
+pub fn smith_for(language: &str) -> Option<Box<dyn Smith>> {
+    smith_with_options(language, &CompileOptions::default())
+}
+
+/// Same as [`smith_for`], but configures the smith with the options of its language.
+pub fn smith_with_options(language: &str, options: &CompileOptions) -> Option<Box<dyn Smith>> {
+    match language {
+        "c" => Some(Box::new(CSmith {
+            options: options.c.clone(),
+        })),
+        "rust" => Some(Box::new(RustSmith {
+            options: options.rust.clone(),
+        })),
+        "kotlin" => Some(Box::new(KotlinSmith {
+            package: options.kotlin_package.clone(),
+            options: options.kotlin.clone(),
+        })),
+        "swift" => Some(Box::new(SwiftSmith)),
+        "python" => Some(Box::new(PythonSmith)),
+        "protobuf" => Some(Box::new(ProtobufSmith)),
+        "kaitai" => Some(Box::new(KaitaiSmith {
+            options: options.kaitai.clone(),
+        })),
+        "wireshark" => Some(Box::new(WiresharkSmith {
+            options: options.wireshark.clone(),
+        })),
+        "asciidoc" => Some(Box::new(AsciiDocSmith)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    #[test]
+    fn test_smith_for_c() {
+        let protocol = crate::parse_protocol_to_ast(PING_PONG).unwrap();
+        let smith = smith_for("c").unwrap();
+        assert_eq!(smith.language(), "c");
+        assert_eq!(
+            smith.generate(&protocol).unwrap(),
+            crate::smith_c::generate_c_code_from_string_with_options(
+                PING_PONG,
+                &crate::smith_c::CCodegenOptions::default()
+            )
+            .unwrap()
+        );
+    }
+
+    #[rstest]
+    #[case("rust", "Message")]
+    #[case("kotlin", "Message")]
+    #[case("swift", "Message")]
+    #[case("python", "Message")]
+    #[case("protobuf", "Message")]
+    #[case("kaitai", "message")]
+    #[case("wireshark", "Message")]
+    #[case("asciidoc", "Message")]
+    fn test_smith_for_other_languages(#[case] language: &str, #[case] expected: &str) {
+        let protocol = crate::parse_protocol_to_ast(PING_PONG).unwrap();
+        let smith = smith_for(language).unwrap();
+        assert_eq!(smith.language(), language);
+        assert!(smith.generate(&protocol).unwrap().contains(expected));
+    }
+
+    #[test]
+    fn test_smith_for_unknown_language() {
+        assert!(smith_for("nope").is_none());
+    }
+
+    #[test]
+    fn test_smith_generate_reports_validation_errors() {
+        let protocol = crate::parse_protocol_to_ast("struct Message { field: Missing; };").unwrap();
+        assert!(matches!(
+            CSmith::default().generate(&protocol),
+            Err(MeksmithError::Validation(errors)) if errors.len() == 1
+        ));
+    }
+}
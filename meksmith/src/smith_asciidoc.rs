@@ -0,0 +1,292 @@
+use crate::MeksmithError;
+use crate::ast::{
+    Definition, EnumerationDefinition, EnumerationField, FlagsDefinition, LayoutOptions, Protocol,
+    StructureDefinition, TypeDefinition, TypeIdentifier, UnionDefinition, UnionField,
+    structure_layout,
+};
+
+/// Returns the meklang name of a type, with user-defined types being cross-references to
+/// the sections describing them.
+fn generate_type_identifier_code(type_identifier: &TypeIdentifier) -> String {
+    match type_identifier {
+        TypeIdentifier::Integer8 => "int8".to_string(),
+        TypeIdentifier::Integer16 => "int16".to_string(),
+        TypeIdentifier::Integer32 => "int32".to_string(),
+        TypeIdentifier::Integer64 => "int64".to_string(),
+        TypeIdentifier::UnsignedInteger8 => "uint8".to_string(),
+        TypeIdentifier::UnsignedInteger16 => "uint16".to_string(),
+        TypeIdentifier::UnsignedInteger32 => "uint32".to_string(),
+        TypeIdentifier::UnsignedInteger64 => "uint64".to_string(),
+        TypeIdentifier::Float32 => "float32".to_string(),
+        TypeIdentifier::Float64 => "float64".to_string(),
+        TypeIdentifier::Bit => "bit".to_string(),
+        TypeIdentifier::Byte => "byte".to_string(),
+        TypeIdentifier::Char => "char".to_string(),
+        TypeIdentifier::Void => "void".to_string(),
+        TypeIdentifier::UserDefined(identifier) => format!("<<{}>>", identifier.name),
+        TypeIdentifier::StaticArray { .. } | TypeIdentifier::DynamicArray { .. } => {
+            // The outermost array is written first, e.g. `int32[2][3]` holds 2 arrays of 3
+            let mut dimensions = String::new();
+            let mut element_type = type_identifier;
+            loop {
+                match element_type {
+                    TypeIdentifier::StaticArray {
+                        r#type,
+                        size_constant: Some(constant),
+                        ..
+                    } => {
+                        dimensions.push_str(&format!("[<<{}>>]", constant.name));
+                        element_type = r#type;
+                    }
+                    TypeIdentifier::StaticArray { r#type, size, .. } => {
+                        dimensions.push_str(&format!("[{size}]"));
+                        element_type = r#type;
+                    }
+                    TypeIdentifier::DynamicArray { r#type } => {
+                        dimensions.push_str("[]");
+                        element_type = r#type;
+                    }
+                    _ => break,
+                }
+            }
+            format!(
+                "{}{dimensions}",
+                generate_type_identifier_code(element_type)
+            )
+        }
+    }
+}
+
+/// Returns the anchor and title of the section of a definition, followed by its doc comment.
+fn generate_section_header_code(definition: &Definition) -> String {
+    let name = &definition.name().name;
+    let mut code = format!("[[{name}]]\n== {name}\n\n");
+    if let Some(doc) = definition.doc() {
+        code.push_str(doc);
+        code.push_str("\n\n");
+    }
+    code
+}
+
+/// Returns a table with a header row, where every row is a list of cells.
+fn generate_table_code(columns: &str, header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut code = format!("[cols=\"{columns}\",options=\"header\"]\n|===\n");
+    code.push_str(
+        &header
+            .iter()
+            .map(|cell| format!("|{cell}"))
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    code.push('\n');
+    for row in rows {
+        code.push('\n');
+        for cell in row {
+            code.push_str(&format!("|{cell}\n"));
+        }
+    }
+    code.push_str("|===\n\n");
+    code
+}
+
+fn generate_structure_code(protocol: &Protocol, structure: &StructureDefinition) -> String {
+    let layout = structure_layout(protocol, &structure.name.name, &LayoutOptions::default())
+        .unwrap_or_default();
+    let rows: Vec<Vec<String>> = structure
+        .fields
+        .iter()
+        .zip(layout)
+        .map(|(field, field_layout)| {
+            vec![
+                field.name.name.clone(),
+                generate_type_identifier_code(&field.r#type),
+                field_layout
+                    .size_in_bits
+                    .map_or_else(|| "variable".to_string(), |size| size.to_string()),
+            ]
+        })
+        .collect();
+    format!(
+        "Structure with fields in the following order.\n\n{}",
+        generate_table_code("2,2,1", &["Field", "Type", "Size in bits"], &rows)
+    )
+}
+
+fn generate_enumeration_code(enumeration: &EnumerationDefinition) -> String {
+    let rows: Vec<Vec<String>> = enumeration
+        .fields
+        .iter()
+        .map(|field| match field {
+            EnumerationField::SingleValue { name, value, .. } => {
+                vec![name.name.clone(), value.to_string()]
+            }
+            EnumerationField::RangeOfValues {
+                name, start, end, ..
+            } => {
+                vec![name.name.clone(), format!("{start}..{end}")]
+            }
+        })
+        .collect();
+    format!(
+        "Enumeration encoded in {} bits{}.\n\n{}",
+        enumeration.size_in_bits(),
+        if enumeration.is_signed() {
+            " as a signed integer"
+        } else {
+            ""
+        },
+        generate_table_code("3,1", &["Name", "Value"], &rows)
+    )
+}
+
+fn generate_union_code(union: &UnionDefinition) -> String {
+    let rows: Vec<Vec<String>> = union
+        .fields
+        .iter()
+        .map(|field| match field {
+            UnionField::SingleValue {
+                name,
+                r#type,
+                discriminator,
+            } => vec![
+                discriminator.to_string(),
+                name.name.clone(),
+                generate_type_identifier_code(r#type),
+            ],
+            UnionField::RangeOfValues {
+                name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => vec![
+                format!("{start_discriminator}..{end_discriminator}"),
+                name.name.clone(),
+                generate_type_identifier_code(r#type),
+            ],
+        })
+        .collect();
+    format!(
+        "Union holding one of the following fields, selected by the discriminator.\n\n{}",
+        generate_table_code("1,2,2", &["Discriminator", "Field", "Type"], &rows)
+    )
+}
+
+fn generate_flags_code(flags: &FlagsDefinition) -> String {
+    let rows: Vec<Vec<String>> = flags
+        .fields
+        .iter()
+        .map(|field| vec![field.name.name.clone(), format!("0x{:X}", field.value)])
+        .collect();
+    format!(
+        "Flags stored in a {}, which can be combined with each other.\n\n{}",
+        generate_type_identifier_code(&flags.r#type),
+        generate_table_code("3,1", &["Flag", "Mask"], &rows)
+    )
+}
+
+fn generate_type_definition_code(type_definition: &TypeDefinition) -> String {
+    format!(
+        "Type definition.\n\n{}",
+        generate_table_code(
+            "1",
+            &["Aliased type"],
+            &[vec![generate_type_identifier_code(&type_definition.r#type)]]
+        )
+    )
+}
+
+/// Generates an AsciiDoc document describing the protocol, with a section per definition
+/// holding a table of its fields or values. Types used by a definition link to their sections.
+pub fn generate_adoc(protocol: &Protocol) -> String {
+    let mut code = String::from("= Protocol\n\n");
+    for definition in &protocol.definitions {
+        code.push_str(&generate_section_header_code(definition));
+        match definition {
+            Definition::Enumeration(enumeration) => {
+                code.push_str(&generate_enumeration_code(enumeration));
+            }
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_code(protocol, structure));
+            }
+            Definition::Type(type_definition) => {
+                code.push_str(&generate_type_definition_code(type_definition));
+            }
+            Definition::Flags(flags) => {
+                code.push_str(&generate_flags_code(flags));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_code(union));
+            }
+            Definition::Constant(constant) => {
+                code.push_str(&format!("Constant equal to {}.\n\n", constant.value));
+            }
+        }
+    }
+    code
+}
+
+pub fn generate_adoc_from_string(input: &str) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok(generate_adoc(&sorted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    #[test]
+    fn test_generate_adoc_has_section_and_table_per_definition() {
+        let output = generate_adoc_from_string(PING_PONG).unwrap();
+        let definitions = crate::parse_protocol_to_ast(PING_PONG)
+            .unwrap()
+            .definitions
+            .len();
+        assert_eq!(output.matches("\n== ").count(), definitions);
+        assert_eq!(output.matches("|===\n").count(), 2 * definitions);
+        assert!(output.contains(
+            r#"[[Ping]]
+== Ping
+
+Structure with fields in the following order.
+
+[cols="2,2,1",options="header"]
+|===
+|Field |Type |Size in bits
+
+|device_ip
+|<<IpAddress>>
+|32
+
+|device_port
+|uint16
+|16
+
+|sequence_number
+|uint32
+|32
+|===
+"#
+        ));
+    }
+
+    #[test]
+    fn test_generate_adoc_enumerations_and_unions() {
+        let output = generate_adoc_from_string(PING_PONG).unwrap();
+        assert!(output.contains("|reserved\n|5..15\n|===\n"));
+        assert!(output.contains("|0\n|ping\n|<<Ping>>\n\n|1\n|pong\n|<<Pong>>\n|===\n"));
+        assert!(output.contains("|Aliased type\n\n|byte[4]\n|===\n"));
+    }
+
+    #[test]
+    fn test_generate_adoc_writes_outer_array_size_first() {
+        let output = generate_adoc_from_string(
+            "const ROWS = 2;\nstruct Grid { cells: int32[ROWS][3]; rows: byte[][8]; };",
+        )
+        .unwrap();
+        assert!(output.contains("|cells\n|int32[<<ROWS>>][3]\n"));
+        assert!(output.contains("|rows\n|byte[][8]\n"));
+    }
+}
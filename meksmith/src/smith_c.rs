@@ -1,337 +1,6363 @@
 use crate::ast::{
-    Definition, EnumerationDefinition, EnumerationField, Protocol, StructureDefinition,
-    TypeDefinition, TypeIdentifier, UnionDefinition, UnionField,
+    Attribute, ConstantDefinition, Definition, DefinitionLookup, EnumerationDefinition,
+    EnumerationField, FlagsDefinition, Identifier, LayoutOptions, Protocol, StructureDefinition,
+    StructureField, TypeDefinition, TypeIdentifier, TypeMapper, UnionDefinition, UnionField,
+    c_type_override, discriminated_by, field_size_in_bits_from_attributes, length_field,
+    range_member_name,
 };
 
-fn generate_enumeration_code(enumeration: &EnumerationDefinition) -> String {
+use crate::validation::Diagnostic;
+use crate::{Endianness, MeksmithError};
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Options controlling the output of the C smith.
+#[derive(Debug, Clone, Default)]
+pub struct CCodegenOptions {
+    /// Emits `<Type>_encode` functions writing every structure in its bit-packed wire format.
+    /// Dynamic array fields get an additional `<field>_length` member holding their length,
+    /// unless [`CCodegenOptions::fat_pointers`] is set.
+    pub emit_encoders: bool,
+    /// Emits `<Type>_decode` functions reading every structure back from the wire format
+    /// written by the encoders, with the same `<field>_length` members. Elements of dynamic
+    /// arrays are allocated with `malloc` and stay owned by the caller, also when decoding
+    /// fails halfway.
+    pub emit_decoders: bool,
+    /// Emits `<Struct>_get_<field>` functions decoding a single field straight from an encoded
+    /// buffer, for every field whose offset does not depend on preceding fields of variable
+    /// size. Dynamic arrays and unions are skipped. Has no effect unless
+    /// [`CCodegenOptions::emit_decoders`] is set.
+    pub emit_field_getters: bool,
+    /// Emits `<Struct>_stream` functions parsing a structure without storing it, which pass
+    /// every value to the callbacks of a `meksmith_callbacks` instead, and call `on_begin` and
+    /// `on_end` around nested structures, unions and arrays. Has no effect unless
+    /// [`CCodegenOptions::emit_decoders`] is set.
+    pub emit_streaming_decoders: bool,
+    /// Generates a `<Struct>_<field>_array` type holding both the length and the pointer to the
+    /// elements of every dynamic array field of a structure, and uses it as the field type.
+    pub fat_pointers: bool,
+    /// Emits a C11 `encode(x, buf, len)` macro dispatching to the right encoder with `_Generic`.
+    /// Has no effect unless [`CCodegenOptions::emit_encoders`] is set.
+    pub emit_generic_macros: bool,
+    /// Integer written before the elements of every dynamic array to hold their count.
+    pub length_prefix: LengthPrefix,
+    /// Order in which the encoders fill the bits of every byte.
+    pub bit_order: BitOrder,
+    /// Byte order of the values of the encoders and decoders. The bytes of every value whose
+    /// size is a multiple of 8 bits are swapped whenever [`CCodegenOptions::bit_order`] alone
+    /// would put them in another order, e.g. for [`Endianness::Little`] with the default
+    /// [`BitOrder::MsbFirst`], as for the Rust parsers.
+    pub endianness: Endianness,
+    /// Emits `<Enum>_values` and `<Enum>_names` arrays after every enumeration, holding all
+    /// its values (including the ones of ranges) and their names in the same order.
+    pub emit_enum_tables: bool,
+    /// Emits `<Struct>_set_<field>_<member>` functions for every discriminated union field,
+    /// which set both the union member and its discriminator.
+    pub emit_union_setters: bool,
+    /// Precedes every generated definition with a comment naming the meklang definition
+    /// and the input line it comes from.
+    pub emit_source_refs: bool,
+    /// Emits the `#` comment block preceding a meklang definition, or a field of a structure or
+    /// an enumeration, as a `/** ... */` doc comment above the generated C definition or member.
+    pub emit_doc_comments: bool,
+    /// Makes the encoders insert zero padding bits before every field with the `bytes`
+    /// attribute, so that it starts on a byte boundary (see [`crate::ast::LayoutOptions`]).
+    pub align_bytes_fields_to_byte: bool,
+    /// Emits a `<Type>_zero` function clearing every structure and union, and a
+    /// `<Enum>_default` function returning the first declared value of every enumeration.
+    pub emit_init: bool,
+    /// Renders the built-in types, [`DefaultCMapper`] is used when not set.
+    pub type_mapper: Option<Arc<dyn TypeMapper>>,
+    /// Skips `#include <stdint.h>` and `<stdbool.h>` for projects providing their own integer
+    /// types, which requires [`CCodegenOptions::type_mapper`] to map every built-in type to
+    /// them (see [`CCodegenOptions::validate`]). The helpers of the encoders and decoders still
+    /// use `uint8_t`, `uint64_t` and `bool`, which the including code must then provide.
+    pub omit_stdint_include: bool,
+    /// Qualifies the structure, buffer, writer and reader pointers taken by the encoders,
+    /// decoders, field getters and streaming decoders with `restrict`, as they never alias.
+    /// Off by default, as the keyword requires C99.
+    pub use_restrict: bool,
+    /// Emits `<Struct>_from_json` functions filling every structure of scalar fields from a
+    /// flat JSON object, with a minimal embedded JSON scanner. Structures holding arrays,
+    /// structures or unions are skipped.
+    pub emit_json_io: bool,
+    /// Emits `<Struct>_wire_size` functions returning the number of bytes written by the
+    /// encoder of every structure, including the lengths of its dynamic arrays and the members
+    /// selected by the discriminators of its unions.
+    pub emit_wire_size: bool,
+    /// Emits `<Struct>_free` functions releasing the dynamic arrays of every structure holding
+    /// any, including those of nested structures and of the union members selected by their
+    /// discriminators. Freed pointers are reset to `NULL`, so freeing twice is harmless, but
+    /// a structure must be zeroed before decoding into it, as failing decoders leave the
+    /// remaining fields untouched.
+    pub emit_destructors: bool,
+    /// Emits `<Struct>_equals` functions telling whether two structures hold the same values,
+    /// e.g. to test round trips through the encoders and decoders. Arrays are compared element
+    /// by element, dynamic arrays by their lengths first, and unions by their discriminators
+    /// and the selected members only. Floating-point fields are compared with `==`.
+    pub emit_equals: bool,
+    /// Lays structures out in memory the way they are laid out on the wire, for protocols of
+    /// byte-aligned fields: structures and unions are packed with `__attribute__((packed))`, or
+    /// with `#pragma pack` for MSVC, every enumeration is backed by a fixed-width type and every
+    /// structure is followed by a `_Static_assert` on its size, failing the build on compilers
+    /// honouring neither. Structures holding dynamic arrays, C bitfields or fields with a
+    /// `c_type` are rejected (see [`CCodegenOptions::validate_protocol`]). The sizes assume that
+    /// a type mapper keeps the widths of the built-in types. Requires C11 for `_Static_assert`.
+    pub packed: bool,
+    /// Prepended to every emitted type, enumeration constant, function and macro, e.g.
+    /// `acme_Message` and `acme_Message_encode` for the prefix `acme_`, so that the code of
+    /// several protocols can be linked together. The prefix is used as it is, without an
+    /// added separator.
+    pub prefix: Option<String>,
+}
+
+impl CCodegenOptions {
+    fn type_mapper(&self) -> &dyn TypeMapper {
+        self.type_mapper.as_deref().unwrap_or(&DefaultCMapper)
+    }
+
+    /// Returns the qualifier following the `*` of pointer parameters, see
+    /// [`CCodegenOptions::use_restrict`].
+    fn restrict(&self) -> &'static str {
+        if self.use_restrict { " restrict" } else { "" }
+    }
+
+    /// Returns the namespace of the helpers used by the generated code, i.e. `meksmith_`
+    /// preceded by [`CCodegenOptions::prefix`], so that the helpers of differently prefixed
+    /// protocols do not clash either.
+    fn helpers(&self) -> String {
+        format!("{}meksmith_", self.prefix.as_deref().unwrap_or_default())
+    }
+
+    /// Returns the attribute following the closing brace of structures and unions, see
+    /// [`CCodegenOptions::packed`].
+    fn packed_attribute(&self) -> &'static str {
+        if self.packed { " MEKSMITH_PACKED" } else { "" }
+    }
+
+    /// Checks that the options can be used together, i.e. that the prefix can start a C
+    /// identifier and that a type mapper replacing all types of `<stdint.h>` and `<stdbool.h>`
+    /// is given when they are not included.
+    pub fn validate(&self) -> Result<(), MeksmithError> {
+        self.validate_options()
+            .map_err(|error| MeksmithError::Validation(vec![error]))
+    }
+
+    fn validate_options(&self) -> Result<(), String> {
+        if let Some(prefix) = &self.prefix {
+            let is_identifier = prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && prefix.starts_with(|c: char| !c.is_ascii_digit());
+            if !is_identifier {
+                return Err(format!("Prefix {prefix} is not a valid C identifier"));
+            }
+        }
+        if !self.omit_stdint_include {
+            return Ok(());
+        }
+        let Some(mapper) = self.type_mapper.as_deref() else {
+            return Err("A type mapper is required when <stdint.h> is omitted".to_string());
+        };
+        for (name, type_identifier) in crate::ast::BUILTIN_TYPES {
+            let default = DefaultCMapper.map_builtin(type_identifier);
+            // Floating-point types, characters and void are part of the language
+            if !["float", "double", "char", "void"].contains(&default.as_str())
+                && mapper.map_builtin(type_identifier) == default
+            {
+                return Err(format!(
+                    "Type mapper maps {name} to {default}, which is not declared when \
+                     <stdint.h> is omitted"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the protocol can be generated with the options, i.e. that the encoders and
+    /// decoders can handle every field (see [`crate::validation::validate_protocol_codecs`])
+    /// and that the size in memory of every structure is known with [`CCodegenOptions::packed`].
+    pub fn validate_protocol(&self, protocol: &Protocol) -> Result<(), MeksmithError> {
+        if self.emit_encoders || self.emit_decoders || self.emit_wire_size {
+            crate::validation::validate_protocol_codecs(protocol)
+                .map_err(MeksmithError::Validation)?;
+        }
+        if !self.packed {
+            return Ok(());
+        }
+        let lookup = DefinitionLookup::new(protocol);
+        for definition in &protocol.definitions {
+            if let Definition::Structure(structure) = definition {
+                packed_structure_size_in_bytes(&lookup, structure, self)
+                    .map_err(|error| MeksmithError::Validation(vec![error]))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Maps built-in types to the fixed-width types of `<stdint.h>`, `float`, `double` and `bool`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultCMapper;
+
+impl TypeMapper for DefaultCMapper {
+    fn map_builtin(&self, type_identifier: &TypeIdentifier) -> String {
+        match type_identifier {
+            TypeIdentifier::Integer8 => "int8_t".to_string(),
+            TypeIdentifier::Integer16 => "int16_t".to_string(),
+            TypeIdentifier::Integer32 => "int32_t".to_string(),
+            TypeIdentifier::Integer64 => "int64_t".to_string(),
+            TypeIdentifier::UnsignedInteger8 => "uint8_t".to_string(),
+            TypeIdentifier::UnsignedInteger16 => "uint16_t".to_string(),
+            TypeIdentifier::UnsignedInteger32 => "uint32_t".to_string(),
+            TypeIdentifier::UnsignedInteger64 => "uint64_t".to_string(),
+            TypeIdentifier::Float32 => "float".to_string(),
+            TypeIdentifier::Float64 => "double".to_string(),
+            TypeIdentifier::Bit => "bool".to_string(),
+            TypeIdentifier::Byte => "uint8_t".to_string(),
+            TypeIdentifier::Char => "char".to_string(),
+            TypeIdentifier::Void => "void".to_string(),
+            _ => generate_type_identifier_code(type_identifier, self),
+        }
+    }
+}
+
+/// Encoding of the element count written before the elements of a dynamic array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthPrefix {
+    U8,
+    U16,
+    #[default]
+    U32,
+    /// Unsigned LEB128, using the lower 7 bits of each byte and the highest bit as continuation.
+    Varint,
+}
+
+impl LengthPrefix {
+    /// Returns the expression writing `length` to the `writer` of an encoder, calling the
+    /// helpers of the given namespace.
+    fn generate_write_expression(&self, helpers: &str, length: &str) -> String {
+        match self {
+            LengthPrefix::U8 => format!("{helpers}write_length(writer, {length}, 8)"),
+            LengthPrefix::U16 => format!("{helpers}write_length(writer, {length}, 16)"),
+            LengthPrefix::U32 => format!("{helpers}write_length(writer, {length}, 32)"),
+            LengthPrefix::Varint => format!("{helpers}write_varint(writer, {length})"),
+        }
+    }
+
+    /// Returns the expression reading the length from the `reader` of a decoder to the
+    /// `uint64_t` pointed to by `length`.
+    fn generate_read_expression(&self, helpers: &str, length: &str) -> String {
+        match self {
+            LengthPrefix::U8 => format!("{helpers}read_bits(reader, {length}, 8)"),
+            LengthPrefix::U16 => format!("{helpers}read_bits(reader, {length}, 16)"),
+            LengthPrefix::U32 => format!("{helpers}read_bits(reader, {length}, 32)"),
+            LengthPrefix::Varint => format!("{helpers}read_varint(reader, {length})"),
+        }
+    }
+
+    /// Returns the expression of the size in bits of the prefix holding `length`.
+    fn generate_size_expression(&self, helpers: &str, length: &str) -> String {
+        match self {
+            LengthPrefix::U8 => "8".to_string(),
+            LengthPrefix::U16 => "16".to_string(),
+            LengthPrefix::U32 => "32".to_string(),
+            LengthPrefix::Varint => format!("{helpers}varint_size_in_bits({length})"),
+        }
+    }
+}
+
+/// Order in which bit-packed fields fill the bits of a byte. Independent of the byte order
+/// of multi-byte values, which is set by [`CCodegenOptions::endianness`] for either order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    /// The first field occupies the most significant bits of a byte, its most significant
+    /// bit first.
+    #[default]
+    MsbFirst,
+    /// The first field occupies the least significant bits of a byte, its least significant
+    /// bit first.
+    LsbFirst,
+}
+
+impl BitOrder {
+    /// Returns the statement of `meksmith_write_bits` setting the `i`-th of the remaining bits
+    /// of `value` in the current `byte`.
+    fn generate_write_bit_statement(&self) -> &'static str {
+        match self {
+            BitOrder::MsbFirst => {
+                "*byte |= (uint8_t)(((value >> (i - 1)) & 1u) << (7 - writer->bit_offset % 8));"
+            }
+            BitOrder::LsbFirst => {
+                "*byte |= (uint8_t)(((value >> (bits - i)) & 1u) << (writer->bit_offset % 8));"
+            }
+        }
+    }
+
+    /// Returns the statement of `meksmith_read_bits` setting the `i`-th of the remaining bits
+    /// of `value` from the current `byte`, the inverse of
+    /// [`BitOrder::generate_write_bit_statement`].
+    fn generate_read_bit_statement(&self) -> &'static str {
+        match self {
+            BitOrder::MsbFirst => {
+                "*value |= (uint64_t)((byte >> (7 - reader->bit_offset % 8)) & 1u) << (i - 1);"
+            }
+            BitOrder::LsbFirst => {
+                "*value |= (uint64_t)((byte >> (reader->bit_offset % 8)) & 1u) << (bits - i);"
+            }
+        }
+    }
+}
+
+/// Returns the field of the structure the given field depends on, which holds the
+/// discriminator of a union or the length of a dynamic array.
+fn linked_field<'a>(
+    structure: &'a StructureDefinition,
+    field: &'a StructureField,
+) -> Option<&'a str> {
+    discriminated_by(&field.attributes).or_else(|| length_field(structure, field))
+}
+
+/// Name of the structure member holding the number of elements of a dynamic array field.
+fn dynamic_array_length_member(field_name: &str) -> String {
+    format!("{field_name}_length")
+}
+
+/// Returns the name of the length and pointer pair generated for a dynamic array field
+/// with [`CCodegenOptions::fat_pointers`].
+fn fat_pointer_type_name(structure_name: &str, field_name: &str) -> String {
+    format!("{structure_name}_{field_name}_array")
+}
+
+/// Returns the names of the C enumerators of an enumeration field without the enumeration
+/// name prefix, together with their values. Ranges get one enumerator per value.
+fn field_enumerators(field: &EnumerationField) -> Vec<(String, i128)> {
+    match field {
+        EnumerationField::SingleValue { name, value, .. } => vec![(name.name.clone(), *value)],
+        EnumerationField::RangeOfValues {
+            name, start, end, ..
+        } => {
+            if start == end {
+                vec![(name.name.clone(), *start)]
+            } else {
+                (*start..=*end)
+                    .map(|i| (range_member_name(&name.name, i), i))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Returns the names of all C enumerators of an enumeration without the enumeration name
+/// prefix, together with their values, see [`field_enumerators`].
+fn enumerators(enumeration: &EnumerationDefinition) -> Vec<(String, i128)> {
+    enumeration
+        .fields
+        .iter()
+        .flat_map(field_enumerators)
+        .collect()
+}
+
+/// Returns the fixed-width type backing an enumeration, if it is backed by one. That is
+/// the declared base type, or the smallest type holding all values if they do not fit in a byte
+/// or if [`CCodegenOptions::packed`] is set. The type is signed for signed enumerations.
+fn enumeration_backing_type(
+    enumeration: &EnumerationDefinition,
+    options: &CCodegenOptions,
+) -> Option<TypeIdentifier> {
+    if let Some(base) = &enumeration.base {
+        return Some(base.clone());
+    }
+    match (enumeration.is_signed(), enumeration.size_in_bits()) {
+        (true, 8) if options.packed => Some(TypeIdentifier::Integer8),
+        (false, 8) if options.packed => Some(TypeIdentifier::UnsignedInteger8),
+        (_, 8) => None,
+        (true, 16) => Some(TypeIdentifier::Integer16),
+        (true, 32) => Some(TypeIdentifier::Integer32),
+        (true, _) => Some(TypeIdentifier::Integer64),
+        (false, 16) => Some(TypeIdentifier::UnsignedInteger16),
+        (false, 32) => Some(TypeIdentifier::UnsignedInteger32),
+        (false, _) => Some(TypeIdentifier::UnsignedInteger64),
+    }
+}
+
+/// Returns the C literal of a value of a signed enumeration. The smallest value is written
+/// as an expression, as its magnitude does not fit in `long long`.
+fn signed_enumerator_literal(value: i128) -> String {
+    if value == i128::from(i64::MIN) {
+        "(-9223372036854775807 - 1)".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Generates a C `enum` for enumerations whose values all fit in a byte. Enumerations with
+/// a base type or larger values get a typedef of the backing type (see
+/// [`enumeration_backing_type`]) and a `#define` per value instead, as the size of an `enum`
+/// is chosen by the compiler.
+fn generate_enumeration_code(
+    enumeration: &EnumerationDefinition,
+    options: &CCodegenOptions,
+) -> String {
+    let name = &enumeration.name.name;
+    let mapper = options.type_mapper();
+    if let Some(backing_type) = enumeration_backing_type(enumeration, options) {
+        let mut code = format!(
+            "typedef {} {name};\n",
+            generate_type_identifier_code(&backing_type, mapper)
+        );
+        for field in &enumeration.fields {
+            if let Some(doc) = field.doc().filter(|_| options.emit_doc_comments) {
+                code.push_str(&generate_doc_comment(doc, ""));
+            }
+            for (enumerator, value) in field_enumerators(field) {
+                let literal = if enumeration.is_signed() {
+                    signed_enumerator_literal(value)
+                } else {
+                    format!("{value}u")
+                };
+                code.push_str(&format!(
+                    "#define {name}_{enumerator} (({name}){literal})\n"
+                ));
+            }
+        }
+        code.push('\n');
+        return code;
+    }
+
     let mut code = String::new();
     code.push_str("typedef enum {\n");
     for field in &enumeration.fields {
-        match field {
-            EnumerationField::SingleValue { name, value } => {
+        if let Some(doc) = field.doc().filter(|_| options.emit_doc_comments) {
+            code.push_str(&generate_doc_comment(doc, "    "));
+        }
+        for (name, value) in field_enumerators(field) {
+            code.push_str(&format!(
+                "    {}_{} = {},\n",
+                enumeration.name.name, name, value
+            ));
+        }
+    }
+    code.push_str(&format!("}} {};\n\n", enumeration.name.name));
+    code
+}
+
+/// Generates the `<Enum>_values` and `<Enum>_names` arrays, which let callers iterate over
+/// all values of an enumeration, e.g. to validate or print them.
+fn generate_enumeration_tables_code(enumeration: &EnumerationDefinition) -> String {
+    let name = &enumeration.name.name;
+    let enumerators = enumerators(enumeration);
+    let mut code = format!("static const {name} {name}_values[] = {{\n");
+    for (enumerator, _) in &enumerators {
+        code.push_str(&format!("    {name}_{enumerator},\n"));
+    }
+    code.push_str(&format!(
+        "}};\nstatic const char* const {name}_names[] = {{\n"
+    ));
+    for (enumerator, _) in &enumerators {
+        code.push_str(&format!("    \"{enumerator}\",\n"));
+    }
+    code.push_str("};\n\n");
+    code
+}
+
+/// Generates a typedef of the underlying integer type of the flags, a `#define` with the mask
+/// of every flag, and `<Flags>_has`/`<Flags>_set` macros testing and setting flags.
+fn generate_flags_code(flags: &FlagsDefinition, mapper: &dyn TypeMapper) -> String {
+    let name = &flags.name.name;
+    let mut code = format!(
+        "typedef {} {name};\n",
+        generate_type_identifier_code(&flags.r#type, mapper)
+    );
+    for field in &flags.fields {
+        code.push_str(&format!(
+            "#define {name}_{} (({name})0x{:X}u)\n",
+            field.name.name, field.value
+        ));
+    }
+    code.push_str(&format!(
+        "#define {name}_has(flags, flag) (((flags) & (flag)) == (flag))\n\
+         #define {name}_set(flags, flag) ((flags) |= (flag))\n\n"
+    ));
+    code
+}
+
+/// Returns the size of a static array as written in declarations, which is the name of
+/// the constant giving it (if any) or the number of elements.
+fn array_size_code(size: u64, size_constant: Option<&Identifier>) -> String {
+    size_constant.map_or_else(|| size.to_string(), |constant| constant.name.clone())
+}
+
+fn generate_constant_code(constant: &ConstantDefinition) -> String {
+    format!("#define {} {}\n\n", constant.name.name, constant.value)
+}
+
+fn generate_type_definition_code(
+    type_definition: &TypeDefinition,
+    mapper: &dyn TypeMapper,
+) -> String {
+    format!(
+        "typedef {};\n\n",
+        generate_declaration_code(
+            &type_definition.r#type,
+            &type_definition.new_type.name,
+            None,
+            mapper
+        )
+    )
+}
+
+/// Returns the size of commonly used C types on a typical 64-bit platform, or `None` for
+/// types whose size is not known to the C smith.
+pub(crate) fn likely_c_type_size_in_bits(c_type: &str) -> Option<u64> {
+    match c_type {
+        "bool" | "char" | "signed char" | "unsigned char" | "int8_t" | "uint8_t" => Some(8),
+        "short" | "unsigned short" | "int16_t" | "uint16_t" => Some(16),
+        "int" | "unsigned" | "unsigned int" | "int32_t" | "uint32_t" | "float" => Some(32),
+        "long long" | "unsigned long long" | "int64_t" | "uint64_t" | "double" | "size_t"
+        | "ssize_t" | "ptrdiff_t" | "intptr_t" | "uintptr_t" => Some(64),
+        _ => None,
+    }
+}
+
+pub(crate) fn generate_type_identifier_code(
+    type_identifier: &TypeIdentifier,
+    mapper: &dyn TypeMapper,
+) -> String {
+    match type_identifier {
+        TypeIdentifier::UserDefined(identifier) => identifier.name.clone(),
+        TypeIdentifier::StaticArray { r#type, .. } => {
+            // Only return the type, not the array part
+            generate_type_identifier_code(r#type, mapper)
+        }
+        TypeIdentifier::DynamicArray { r#type } => {
+            format!("{}*", generate_type_identifier_code(r#type, mapper))
+        }
+        builtin => mapper.map_builtin(builtin),
+    }
+}
+
+/// Returns the declaration of `declarator` holding a value of the given type, which puts the
+/// sizes of nested arrays after it, e.g. `int32_t grid[4][8]` or `uint8_t (*rows)[8]` for
+/// a dynamic array of static arrays. The `c_type`, if given, replaces the type of the elements.
+fn generate_declaration_code(
+    type_identifier: &TypeIdentifier,
+    declarator: &str,
+    c_type: Option<&str>,
+    mapper: &dyn TypeMapper,
+) -> String {
+    let type_code = |type_identifier| {
+        c_type.map_or_else(
+            || generate_type_identifier_code(type_identifier, mapper),
+            str::to_string,
+        )
+    };
+    match type_identifier {
+        TypeIdentifier::StaticArray {
+            r#type,
+            size,
+            size_constant,
+        } => generate_declaration_code(
+            r#type,
+            &format!(
+                "{declarator}[{}]",
+                array_size_code(*size, size_constant.as_ref())
+            ),
+            c_type,
+            mapper,
+        ),
+        TypeIdentifier::DynamicArray { r#type } => match r#type.as_ref() {
+            TypeIdentifier::StaticArray { .. } => {
+                generate_declaration_code(r#type, &format!("(*{declarator})"), c_type, mapper)
+            }
+            _ => format!("{}* {declarator}", type_code(r#type)),
+        },
+        _ => format!("{} {declarator}", type_code(type_identifier)),
+    }
+}
+
+/// Returns the base type and width of the C bitfield of an integer field narrowed by the `bits`
+/// attribute, the base type being the smallest integer type of the same signedness holding it.
+/// Fields of other types, without the attribute or with a `c_type` are not bitfields.
+fn structure_field_bitfield(
+    lookup: &DefinitionLookup,
+    field: &StructureField,
+) -> Option<(TypeIdentifier, u64)> {
+    if c_type_override(&field.attributes).is_some() {
+        return None;
+    }
+    let bits = field
+        .attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            Attribute::BitsSize { size } => Some(*size),
+            _ => None,
+        })?;
+    let is_signed = match lookup.resolve(&field.r#type) {
+        TypeIdentifier::Integer8
+        | TypeIdentifier::Integer16
+        | TypeIdentifier::Integer32
+        | TypeIdentifier::Integer64 => true,
+        TypeIdentifier::UnsignedInteger8
+        | TypeIdentifier::UnsignedInteger16
+        | TypeIdentifier::UnsignedInteger32
+        | TypeIdentifier::UnsignedInteger64
+        | TypeIdentifier::Byte => false,
+        _ => return None,
+    };
+    let base = match (is_signed, bits) {
+        (true, ..=8) => TypeIdentifier::Integer8,
+        (true, ..=16) => TypeIdentifier::Integer16,
+        (true, ..=32) => TypeIdentifier::Integer32,
+        (true, _) => TypeIdentifier::Integer64,
+        (false, ..=8) => TypeIdentifier::UnsignedInteger8,
+        (false, ..=16) => TypeIdentifier::UnsignedInteger16,
+        (false, ..=32) => TypeIdentifier::UnsignedInteger32,
+        (false, _) => TypeIdentifier::UnsignedInteger64,
+    };
+    Some((base, bits))
+}
+
+/// Returns the size in memory of a value of the type with [`CCodegenOptions::packed`], or
+/// `None` if it holds a pointer or a field of unknown size, or if the size overflows.
+fn packed_size_in_bytes(
+    lookup: &DefinitionLookup,
+    type_identifier: &TypeIdentifier,
+    options: &CCodegenOptions,
+) -> Option<u64> {
+    match lookup.resolve(type_identifier) {
+        TypeIdentifier::Bit => Some(1),
+        TypeIdentifier::Void => Some(0),
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            size.checked_mul(packed_size_in_bytes(lookup, r#type, options)?)
+        }
+        TypeIdentifier::DynamicArray { .. } => None,
+        TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name)? {
+            Definition::Enumeration(enumeration) => enumeration_backing_type(enumeration, options)
+                .and_then(|backing_type| backing_type.builtin_size_in_bits())
+                .map(|bits| bits / 8),
+            Definition::Structure(structure) => {
+                packed_structure_size_in_bytes(lookup, structure, options).ok()
+            }
+            Definition::Union(union) => {
+                let mut size = 0;
+                for field in &union.fields {
+                    let (UnionField::SingleValue { r#type, .. }
+                    | UnionField::RangeOfValues { r#type, .. }) = field;
+                    size = size.max(packed_size_in_bytes(lookup, r#type, options)?);
+                }
+                // Unions of void members only hold a marker byte
+                Some(size.max(1))
+            }
+            Definition::Type(_) | Definition::Flags(_) | Definition::Constant(_) => None,
+        },
+        builtin => builtin.builtin_size_in_bits().map(|bits| bits / 8),
+    }
+}
+
+/// Returns the size in memory of the structure with [`CCodegenOptions::packed`], which is the
+/// sum of the sizes of its fields, or an error naming the first field whose size is unknown.
+fn packed_structure_size_in_bytes(
+    lookup: &DefinitionLookup,
+    structure: &StructureDefinition,
+    options: &CCodegenOptions,
+) -> Result<u64, String> {
+    let mut size = 0;
+    for field in &structure.fields {
+        let field_name = &field.name.name;
+        let structure_name = &structure.name.name;
+        if structure_field_bitfield(lookup, field).is_some() {
+            return Err(format!(
+                "Field {field_name} in {structure_name} is a C bitfield, whose layout C does not \
+                 guarantee, so it cannot be packed"
+            ));
+        }
+        if c_type_override(&field.attributes).is_some() {
+            return Err(format!(
+                "Field {field_name} in {structure_name} has a C type of unknown size, so it \
+                 cannot be packed"
+            ));
+        }
+        let field_size = packed_size_in_bytes(lookup, &field.r#type, options).ok_or_else(|| {
+            if lookup.is_memcpy_safe(&field.r#type) {
+                format!(
+                    "Size of field {field_name} in {structure_name} does not fit in 64 bits, so \
+                     it cannot be packed"
+                )
+            } else {
+                format!(
+                    "Field {field_name} in {structure_name} holds a dynamic array, so it cannot \
+                     be packed"
+                )
+            }
+        })?;
+        size = u64::checked_add(size, field_size).ok_or_else(|| {
+            format!("Size of {structure_name} does not fit in 64 bits, so it cannot be packed")
+        })?;
+    }
+    Ok(size)
+}
+
+fn generate_structure_code(
+    lookup: &DefinitionLookup,
+    structure: &StructureDefinition,
+    options: &CCodegenOptions,
+) -> String {
+    let mapper = options.type_mapper();
+    let mut code = String::new();
+    if options.emit_encoders
+        && !lookup.is_memcpy_safe(&TypeIdentifier::UserDefined(structure.name.clone()))
+    {
+        code.push_str(&format!(
+            "/* {0} contains pointers, so its memory is not wire-identical: \
+             serialize it with {0}_encode instead of memcpy. */\n",
+            structure.name.name
+        ));
+    }
+    if options.fat_pointers {
+        for field in &structure.fields {
+            if let TypeIdentifier::DynamicArray { r#type } = lookup.resolve(&field.r#type) {
                 code.push_str(&format!(
-                    "    {}_{} = {},\n",
-                    enumeration.name.name, name.name, value
+                    "typedef struct {{\n    uint32_t len;\n    {};\n}}{} {};\n\n",
+                    generate_declaration_code(
+                        &TypeIdentifier::DynamicArray {
+                            r#type: r#type.clone()
+                        },
+                        "data",
+                        c_type_override(&field.attributes),
+                        mapper
+                    ),
+                    options.packed_attribute(),
+                    fat_pointer_type_name(&structure.name.name, &field.name.name)
                 ));
             }
-            EnumerationField::RangeOfValues { name, start, end } => {
-                if start == end {
-                    code.push_str(&format!(
-                        "    {}_{} = {},\n",
-                        enumeration.name.name, name.name, start
-                    ));
-                } else {
-                    for i in *start..=*end {
-                        code.push_str(&format!(
-                            "    {}_{}_{} = {},\n",
-                            enumeration.name.name, name.name, i, i
-                        ));
+        }
+    }
+    code.push_str("typedef struct {\n");
+    for field in &structure.fields {
+        if let Some(doc) = field.doc.as_deref().filter(|_| options.emit_doc_comments) {
+            code.push_str(&generate_doc_comment(doc, "    "));
+        }
+        let is_dynamic_array = matches!(
+            lookup.resolve(&field.r#type),
+            TypeIdentifier::DynamicArray { .. }
+        );
+        if options.fat_pointers && is_dynamic_array {
+            code.push_str(&format!(
+                "    {} {};\n",
+                fat_pointer_type_name(&structure.name.name, &field.name.name),
+                field.name.name
+            ));
+            continue;
+        }
+        if (options.emit_encoders || options.emit_decoders || options.emit_wire_size)
+            && is_dynamic_array
+        {
+            code.push_str(&format!(
+                "    uint32_t {};\n",
+                dynamic_array_length_member(&field.name.name)
+            ));
+        }
+        let name = &field.name.name;
+        if let Some((base, bits)) = structure_field_bitfield(lookup, field) {
+            code.push_str(&format!(
+                "    {} {name} : {bits};\n",
+                generate_type_identifier_code(&base, mapper)
+            ));
+            continue;
+        }
+        let c_type = c_type_override(&field.attributes);
+        let declaration = generate_declaration_code(&field.r#type, name, c_type, mapper);
+        match discriminated_by(&field.attributes).filter(|_| c_type.is_none()) {
+            // The structure is the tagged union, holding the discriminator next to it
+            Some(discriminator) => code.push_str(&format!(
+                "    {declaration}; /* selected by {discriminator} */\n"
+            )),
+            None => code.push_str(&format!("    {declaration};\n")),
+        }
+    }
+    code.push_str(&format!(
+        "}}{} {};\n",
+        options.packed_attribute(),
+        structure.name.name
+    ));
+    match packed_structure_size_in_bytes(lookup, structure, options) {
+        Ok(size) if options.packed => code.push_str(&format!(
+            "_Static_assert(sizeof({}) == {size}, \"unexpected padding\");\n\n",
+            structure.name.name
+        )),
+        _ => code.push('\n'),
+    }
+    code
+}
+
+/// Defines `MEKSMITH_PACKED` and starts packing the types following it with MSVC, which has no
+/// attribute for it, see [`CCodegenOptions::packed`].
+const PACKED_BEGIN_CODE: &str = r#"#ifndef MEKSMITH_PACKED
+#ifdef _MSC_VER
+#define MEKSMITH_PACKED
+#else
+#define MEKSMITH_PACKED __attribute__((packed))
+#endif
+#endif
+
+#ifdef _MSC_VER
+#pragma pack(push, 1)
+#endif
+
+"#;
+
+/// Ends packing started by [`PACKED_BEGIN_CODE`].
+const PACKED_END_CODE: &str = r#"#ifdef _MSC_VER
+#pragma pack(pop)
+#endif
+
+"#;
+
+fn generate_union_code(union: &UnionDefinition, options: &CCodegenOptions) -> String {
+    let mapper = options.type_mapper();
+    let mut code = String::new();
+    code.push_str("typedef union {\n");
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue { name, r#type, .. } => match r#type {
+                TypeIdentifier::Void => {}
+                _ => code.push_str(&format!(
+                    "    {};\n",
+                    generate_declaration_code(r#type, &name.name, None, mapper)
+                )),
+            },
+            UnionField::RangeOfValues {
+                name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => {
+                for i in *start_discriminator..=*end_discriminator {
+                    match r#type {
+                        TypeIdentifier::Void => {}
+                        _ => code.push_str(&format!(
+                            "    {};\n",
+                            generate_declaration_code(
+                                r#type,
+                                &format!("{}_{i}", name.name),
+                                None,
+                                mapper
+                            )
+                        )),
                     }
                 }
             }
         }
     }
-    code.push_str(&format!("}} {};\n\n", enumeration.name.name));
-    code
+    // Empty unions are not standard C, so unions of void members only hold a marker byte
+    if code == "typedef union {\n" {
+        code.push_str("    uint8_t _void;\n");
+    }
+    code.push_str(&format!(
+        "}}{} {};\n\n",
+        options.packed_attribute(),
+        union.name.name
+    ));
+    code
+}
+
+const ENCODER_HELPERS_CODE: &str = r#"typedef struct {
+    uint8_t* buf;
+    size_t buf_len;
+    size_t bit_offset;
+} {helpers}writer;
+
+static inline bool {helpers}write_bits({helpers}writer* writer, uint64_t value, unsigned bits) {
+    if (writer->bit_offset > writer->buf_len * 8 || bits > writer->buf_len * 8 - writer->bit_offset) {
+        return false;
+    }
+{swap_bytes}    for (unsigned i = bits; i > 0; i--) {
+        uint8_t* byte = &writer->buf[writer->bit_offset / 8];
+        if (writer->bit_offset % 8 == 0) {
+            *byte = 0;
+        }
+        {write_bit}
+        writer->bit_offset++;
+    }
+    return true;
+}
+
+static inline bool {helpers}write_length({helpers}writer* writer, uint64_t length, unsigned bits) {
+    if (bits < 64 && (length >> bits) != 0) {
+        return false;
+    }
+    return {helpers}write_bits(writer, length, bits);
+}
+
+static inline bool {helpers}write_varint({helpers}writer* writer, uint64_t value) {
+    do {
+        uint8_t byte = value & 0x7f;
+        value >>= 7;
+        if (value != 0) {
+            byte |= 0x80;
+        }
+        if (!{helpers}write_bits(writer, byte, 8)) {
+            return false;
+        }
+    } while (value != 0);
+    return true;
+}
+
+static inline bool {helpers}write_float32({helpers}writer* writer, float value) {
+    uint32_t bits;
+    memcpy(&bits, &value, sizeof(bits));
+    return {helpers}write_bits(writer, bits, 32);
+}
+
+static inline bool {helpers}write_float64({helpers}writer* writer, double value) {
+    uint64_t bits;
+    memcpy(&bits, &value, sizeof(bits));
+    return {helpers}write_bits(writer, bits, 64);
+}
+
+"#;
+
+/// Returns the statement of `meksmith_write_bits` and `meksmith_read_bits` reversing the bytes
+/// of `value` whenever the bit order would otherwise put them in the wrong byte order. Values
+/// written most significant bit first come out big-endian, so little-endian ones are swapped,
+/// and values written least significant bit first come out little-endian, so big-endian ones
+/// are swapped. Native values are swapped only when the compiler targets a host of the other
+/// byte order; all targets of MSVC are little-endian.
+fn generate_swap_bytes_statement(
+    endianness: Endianness,
+    bit_order: BitOrder,
+    value: &str,
+) -> String {
+    let swap = format!(
+        "    if (bits % 8 == 0) {{\n        \
+         uint64_t swapped = 0;\n        \
+         for (unsigned i = 0; i < bits / 8; i++) {{\n            \
+         swapped = (swapped << 8) | (({value} >> (8 * i)) & 0xff);\n        \
+         }}\n        \
+         {value} = swapped;\n    \
+         }}\n"
+    );
+    match (endianness, bit_order) {
+        (Endianness::Big, BitOrder::MsbFirst) | (Endianness::Little, BitOrder::LsbFirst) => {
+            String::new()
+        }
+        (Endianness::Little, BitOrder::MsbFirst) | (Endianness::Big, BitOrder::LsbFirst) => swap,
+        (Endianness::Native, BitOrder::MsbFirst) => format!(
+            "#if defined(_MSC_VER) || \
+             (defined(__BYTE_ORDER__) && __BYTE_ORDER__ == __ORDER_LITTLE_ENDIAN__)\n\
+             {swap}#endif\n"
+        ),
+        (Endianness::Native, BitOrder::LsbFirst) => format!(
+            "#if !defined(_MSC_VER) && \
+             (defined(__BYTE_ORDER__) && __BYTE_ORDER__ == __ORDER_BIG_ENDIAN__)\n\
+             {swap}#endif\n"
+        ),
+    }
+}
+
+/// Definitions and options shared by the generators of all functions of the protocol.
+struct CodegenContext<'a> {
+    lookup: DefinitionLookup<'a>,
+    options: &'a CCodegenOptions,
+    /// Namespace of the helpers, see [`CCodegenOptions::helpers`].
+    helpers: String,
+    /// Specifiers preceding the functions visible outside of the generated source, which are
+    /// `static inline` in the single file C code.
+    linkage: &'static str,
+    /// Signatures of the functions visible outside of the generated source, in the order they
+    /// were generated, which [`generate_c_header`] declares.
+    signatures: RefCell<Vec<String>>,
+}
+
+impl<'a> CodegenContext<'a> {
+    fn new(protocol: &'a Protocol, options: &'a CCodegenOptions) -> Self {
+        CodegenContext {
+            lookup: DefinitionLookup::new(protocol),
+            options,
+            helpers: options.helpers(),
+            linkage: "",
+            signatures: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the line beginning the definition of a function visible outside of the
+    /// generated source, and records its signature.
+    fn begin_function(&self, signature: String) -> String {
+        let line = format!("{}{signature} {{\n", self.linkage);
+        self.signatures.borrow_mut().push(signature);
+        line
+    }
+}
+
+/// Returns the statement making a codec fail on a value it cannot handle, which
+/// [`CCodegenOptions::validate_protocol`] rejects when the codecs are generated from a string.
+fn generate_unsupported_statement(reason: &str, indent: &str) -> String {
+    format!("{indent}return false; /* {reason} */\n")
+}
+
+/// Returns whether the statements generated for a value are the ones of
+/// [`generate_unsupported_statement`], after which no statement would be reached.
+fn is_unsupported_statement(statements: &str) -> bool {
+    statements.trim_start().starts_with("return false; /*")
+}
+
+/// Wraps a boolean expression of an encoder, so that the encoding stops on the first failure.
+fn generate_checked_statement(expression: &str, indent: &str) -> String {
+    format!("{indent}if (!{expression}) {{\n{indent}    return false;\n{indent}}}\n")
+}
+
+/// Returns a statement failing the encoding when `value` does not fit in `size_in_bits`, which is
+/// needed when the `bits`/`bytes` attributes make a field narrower than its natural size, as
+/// otherwise the excess bits would be silently dropped.
+fn generate_range_check(
+    value: &str,
+    is_signed: bool,
+    size_in_bits: Option<u64>,
+    natural_size_in_bits: u64,
+    indent: &str,
+) -> String {
+    let Some(size_in_bits) = size_in_bits.filter(|size| *size < natural_size_in_bits) else {
+        return String::new();
+    };
+    let condition = if is_signed {
+        let max = (1u64 << (size_in_bits - 1)) - 1;
+        format!("(int64_t){value} < -{max} - 1 || (int64_t){value} > {max}")
+    } else {
+        format!("(uint64_t){value} > {}u", (1u64 << size_in_bits) - 1)
+    };
+    format!("{indent}if ({condition}) {{\n{indent}    return false;\n{indent}}}\n")
+}
+
+/// Returns statements writing `value` of the given type to `writer`. The `size_in_bits`
+/// overrides the natural size of scalar types, as requested by the `bits`/`bytes` attributes,
+/// while `length` is the expression holding the number of elements of a dynamic array.
+/// The `linked_field` is the field of the structure holding the discriminator of a union or
+/// the length of a dynamic array, which then has no length prefix.
+fn generate_encode_statements(
+    context: &CodegenContext,
+    type_identifier: &TypeIdentifier,
+    value: &str,
+    size_in_bits: Option<u64>,
+    length: Option<&str>,
+    linked_field: Option<&str>,
+    depth: usize,
+) -> String {
+    let helpers = &context.helpers;
+    let indent = "    ".repeat(depth + 1);
+    let type_identifier = context.lookup.resolve(type_identifier);
+    let bits = size_in_bits.or(type_identifier.builtin_size_in_bits());
+
+    match type_identifier {
+        TypeIdentifier::Void => String::new(),
+        TypeIdentifier::Float32 => {
+            generate_checked_statement(&format!("{helpers}write_float32(writer, {value})"), &indent)
+        }
+        TypeIdentifier::Float64 => {
+            generate_checked_statement(&format!("{helpers}write_float64(writer, {value})"), &indent)
+        }
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            let index = format!("i{depth}");
+            format!(
+                "{indent}for (size_t {index} = 0; {index} < {size}; {index}++) {{\n{}{indent}}}\n",
+                generate_encode_statements(
+                    context,
+                    r#type,
+                    &format!("{value}[{index}]"),
+                    None,
+                    None,
+                    None,
+                    depth + 1
+                )
+            )
+        }
+        TypeIdentifier::DynamicArray { r#type } => match length {
+            Some(length) => {
+                let index = format!("i{depth}");
+                let mut code = match linked_field {
+                    // The field is encoded before the array, so it must already hold its length
+                    Some(field) => format!(
+                        "{indent}if ((uint64_t)in->{field} != {length}) {{\n{indent}    return false;\n{indent}}}\n"
+                    ),
+                    None => generate_checked_statement(
+                        &context
+                            .options
+                            .length_prefix
+                            .generate_write_expression(&context.helpers, length),
+                        &indent,
+                    ),
+                };
+                code.push_str(&format!(
+                    "{indent}for (size_t {index} = 0; {index} < {length}; {index}++) {{\n{}{indent}}}\n",
+                    generate_encode_statements(
+                        context,
+                        r#type,
+                        &format!("{value}[{index}]"),
+                        None,
+                        None,
+                        None,
+                        depth + 1
+                    )
+                ));
+                code
+            }
+            None => {
+                generate_unsupported_statement("length of the dynamic array is unknown", &indent)
+            }
+        },
+        TypeIdentifier::UserDefined(identifier) => match context.lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => {
+                let mut code = generate_range_check(
+                    value,
+                    enumeration.is_signed(),
+                    size_in_bits,
+                    enumeration.size_in_bits(),
+                    &indent,
+                );
+                code.push_str(&generate_checked_statement(
+                    &format!(
+                        "{helpers}write_bits(writer, (uint64_t){value}, {})",
+                        size_in_bits.unwrap_or(enumeration.size_in_bits())
+                    ),
+                    &indent,
+                ));
+                code
+            }
+            Some(Definition::Union(union)) => match linked_field {
+                Some(discriminator) => generate_checked_statement(
+                    &format!(
+                        "{}_encode_bits(&{value}, (uint64_t)in->{discriminator}, writer)",
+                        union.name.name
+                    ),
+                    &indent,
+                ),
+                None => generate_unsupported_statement("union has no discriminator", &indent),
+            },
+            _ => generate_checked_statement(
+                &format!("{}_encode_bits(&{value}, writer)", identifier.name),
+                &indent,
+            ),
+        },
+        _ => {
+            let is_signed = matches!(
+                type_identifier,
+                TypeIdentifier::Integer8
+                    | TypeIdentifier::Integer16
+                    | TypeIdentifier::Integer32
+                    | TypeIdentifier::Integer64
+            );
+            let mut code = generate_range_check(
+                value,
+                is_signed,
+                size_in_bits,
+                type_identifier.builtin_size_in_bits().unwrap_or(64),
+                &indent,
+            );
+            code.push_str(&generate_checked_statement(
+                &format!(
+                    "{helpers}write_bits(writer, (uint64_t){value}, {})",
+                    bits.unwrap_or(64)
+                ),
+                &indent,
+            ));
+            code
+        }
+    }
+}
+
+/// Returns the number of integers a reserved field consists of, flattening its static arrays,
+/// and the size in bits of each of them on the wire.
+fn reserved_field_layout(context: &CodegenContext, field: &StructureField) -> (u64, u64) {
+    let mut type_identifier = context.lookup.resolve(&field.r#type);
+    let mut size_in_bits = field_size_in_bits_from_attributes(&field.attributes);
+    let mut count = 1;
+    while let TypeIdentifier::StaticArray { r#type, size, .. } = type_identifier {
+        count *= size;
+        type_identifier = context.lookup.resolve(r#type);
+        size_in_bits = None;
+    }
+    (
+        count,
+        size_in_bits
+            .or(type_identifier.builtin_size_in_bits())
+            .unwrap_or(64),
+    )
+}
+
+/// Returns the statements generated for every integer of a reserved field with the given
+/// indentation, see [`reserved_field_layout`].
+fn generate_reserved_statements(count: u64, statements: impl Fn(&str) -> String) -> String {
+    if count == 1 {
+        return statements("    ");
+    }
+    format!(
+        "    for (size_t i0 = 0; i0 < {count}; i0++) {{\n{}    }}\n",
+        statements("        ")
+    )
+}
+
+fn generate_structure_encoder_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+) -> String {
+    let helpers = &context.helpers;
+    let name = &structure.name.name;
+    let restrict = context.options.restrict();
+    let mut code = String::new();
+    code.push_str(&format!(
+        "static bool {name}_encode_bits(const {name}*{restrict} in, {helpers}writer*{restrict} writer) {{\n"
+    ));
+    let mut is_supported = true;
+    for field in &structure.fields {
+        if context.options.align_bytes_fields_to_byte
+            && field
+                .attributes
+                .iter()
+                .any(|attribute| matches!(attribute, Attribute::BytesSize { .. }))
+        {
+            code.push_str(&format!(
+                "    if (!{helpers}write_bits(writer, 0, (8 - writer->bit_offset % 8) % 8)) {{\n        \
+                 return false;\n    }}\n",
+            ));
+        }
+        if field.reserved {
+            let (count, bits) = reserved_field_layout(context, field);
+            code.push_str(&generate_reserved_statements(count, |indent| {
+                generate_checked_statement(
+                    &format!("{helpers}write_bits(writer, 0, {bits})"),
+                    indent,
+                )
+            }));
+            continue;
+        }
+        let field_name = &field.name.name;
+        let is_fat_pointer = context.options.fat_pointers
+            && matches!(
+                context.lookup.resolve(&field.r#type),
+                TypeIdentifier::DynamicArray { .. }
+            );
+        let (value, length) = if is_fat_pointer {
+            (
+                format!("in->{field_name}.data"),
+                format!("in->{field_name}.len"),
+            )
+        } else {
+            (
+                format!("in->{field_name}"),
+                format!("in->{}", dynamic_array_length_member(field_name)),
+            )
+        };
+        // Bitfields cannot hold values out of their range, so only the write is needed
+        if let Some((_, bits)) = structure_field_bitfield(&context.lookup, field) {
+            code.push_str(&generate_checked_statement(
+                &format!("{helpers}write_bits(writer, (uint64_t){value}, {bits})"),
+                "    ",
+            ));
+            continue;
+        }
+        let statements = generate_encode_statements(
+            context,
+            &field.r#type,
+            &value,
+            field_size_in_bits_from_attributes(&field.attributes),
+            Some(&length),
+            linked_field(structure, field),
+            0,
+        );
+        code.push_str(&statements);
+        if is_unsupported_statement(&statements) {
+            is_supported = false;
+            break;
+        }
+    }
+    if is_supported {
+        code.push_str("    return true;\n");
+    }
+    code.push_str("}\n\n");
+
+    code.push_str(&context.begin_function(format!(
+        "size_t {name}_encode(const {name}*{restrict} in, uint8_t*{restrict} buf, size_t buf_len)"
+    )));
+    code.push_str(&format!(
+        "    {helpers}writer writer = {{buf, buf_len, 0}};\n    \
+         if (!{name}_encode_bits(in, &writer)) {{\n        \
+         return 0;\n    \
+         }}\n    \
+         return (writer.bit_offset + 7) / 8;\n}}\n\n"
+    ));
+    code
+}
+
+/// Returns whether no field of the union carries a value, so that its encoder and decoder
+/// do not touch the union nor the wire.
+fn has_only_void_fields(union: &UnionDefinition) -> bool {
+    union.fields.iter().all(|field| match field {
+        UnionField::SingleValue { r#type, .. } | UnionField::RangeOfValues { r#type, .. } => {
+            matches!(r#type, TypeIdentifier::Void)
+        }
+    })
+}
+
+fn generate_union_encoder_code(context: &CodegenContext, union: &UnionDefinition) -> String {
+    let helpers = &context.helpers;
+    let name = &union.name.name;
+    let restrict = context.options.restrict();
+    let mut code = String::new();
+    code.push_str(&format!(
+        "static bool {name}_encode_bits(const {name}*{restrict} in, uint64_t discriminator, {helpers}writer*{restrict} writer) {{\n"
+    ));
+    if has_only_void_fields(union) {
+        code.push_str("    (void)in;\n    (void)writer;\n");
+    }
+    code.push_str("    switch (discriminator) {\n");
+    let mut push_case = |discriminator: u64, member: String, r#type: &TypeIdentifier| {
+        let statements = generate_encode_statements(
+            context,
+            r#type,
+            &format!("in->{member}"),
+            None,
+            None,
+            None,
+            1,
+        );
+        code.push_str(&format!("    case {discriminator}:\n"));
+        code.push_str(&statements);
+        if !is_unsupported_statement(&statements) {
+            code.push_str("        return true;\n");
+        }
+    };
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue {
+                name: field_name,
+                r#type,
+                discriminator,
+            } => push_case(*discriminator, field_name.name.clone(), r#type),
+            UnionField::RangeOfValues {
+                name: field_name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => {
+                for i in *start_discriminator..=*end_discriminator {
+                    push_case(i, format!("{}_{}", field_name.name, i), r#type);
+                }
+            }
+        }
+    }
+    code.push_str("    default:\n        return false;\n    }\n}\n\n");
+    code
+}
+
+/// Generates the `_encode` functions of all structures and unions of the protocol,
+/// preceded by the bit-packing helpers they use. Definitions must be sorted by dependencies.
+pub fn generate_encoders_code(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    generate_encoders_code_in(&CodegenContext::new(protocol, options), protocol)
+}
+
+/// Same as [`generate_encoders_code`], generating the functions within the given context.
+fn generate_encoders_code_in(context: &CodegenContext, protocol: &Protocol) -> String {
+    let mut code = ENCODER_HELPERS_CODE
+        .replace("{helpers}", &context.helpers)
+        .replace(
+            "{write_bit}",
+            context.options.bit_order.generate_write_bit_statement(),
+        )
+        .replace(
+            "{swap_bytes}",
+            &generate_swap_bytes_statement(
+                context.options.endianness,
+                context.options.bit_order,
+                "value",
+            ),
+        );
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_encoder_code(context, structure));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_encoder_code(context, union));
+            }
+            Definition::Enumeration(_)
+            | Definition::Type(_)
+            | Definition::Flags(_)
+            | Definition::Constant(_) => {}
+        }
+    }
+    code
+}
+
+const WIRE_SIZE_HELPERS_CODE: &str = r#"static inline size_t {helpers}varint_size_in_bits(uint64_t value) {
+    size_t bits = 8;
+    while (value >= 0x80) {
+        value >>= 7;
+        bits += 8;
+    }
+    return bits;
+}
+
+"#;
+
+/// Returns the size in bits of every value of the type, if it does not depend on the value.
+fn constant_size_in_bits(
+    context: &CodegenContext,
+    type_identifier: &TypeIdentifier,
+) -> Option<u64> {
+    match context.lookup.resolve(type_identifier) {
+        TypeIdentifier::UserDefined(identifier) => match context.lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => Some(enumeration.size_in_bits()),
+            _ => None,
+        },
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            constant_size_in_bits(context, r#type)?.checked_mul(*size)
+        }
+        type_identifier => type_identifier.builtin_size_in_bits(),
+    }
+}
+
+/// Returns statements adding the size in bits of `value` of the given type, as written by
+/// [`generate_encode_statements`] with the same arguments, to the `size_t` pointed to by `bits`.
+fn generate_wire_size_statements(
+    context: &CodegenContext,
+    type_identifier: &TypeIdentifier,
+    value: &str,
+    size_in_bits: Option<u64>,
+    length: Option<&str>,
+    linked_field: Option<&str>,
+    depth: usize,
+) -> String {
+    let indent = "    ".repeat(depth + 1);
+    let type_identifier = context.lookup.resolve(type_identifier);
+
+    match type_identifier {
+        TypeIdentifier::Void => String::new(),
+        TypeIdentifier::Float32 | TypeIdentifier::Float64 => format!(
+            "{indent}*bits += {};\n",
+            type_identifier.builtin_size_in_bits().unwrap_or_default()
+        ),
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            if let Some(array_size) = constant_size_in_bits(context, type_identifier) {
+                return format!("{indent}*bits += {array_size};\n");
+            }
+            let index = format!("i{depth}");
+            format!(
+                "{indent}for (size_t {index} = 0; {index} < {size}; {index}++) {{\n{}{indent}}}\n",
+                generate_wire_size_statements(
+                    context,
+                    r#type,
+                    &format!("{value}[{index}]"),
+                    None,
+                    None,
+                    None,
+                    depth + 1
+                )
+            )
+        }
+        TypeIdentifier::DynamicArray { r#type } => match length {
+            Some(length) => {
+                let mut code = match linked_field {
+                    Some(_) => String::new(),
+                    None => format!(
+                        "{indent}*bits += {};\n",
+                        context
+                            .options
+                            .length_prefix
+                            .generate_size_expression(&context.helpers, length)
+                    ),
+                };
+                if let Some(element_size) = constant_size_in_bits(context, r#type) {
+                    code.push_str(&format!(
+                        "{indent}*bits += (size_t){length} * {element_size};\n"
+                    ));
+                    return code;
+                }
+                let index = format!("i{depth}");
+                code.push_str(&format!(
+                    "{indent}for (size_t {index} = 0; {index} < {length}; {index}++) {{\n{}{indent}}}\n",
+                    generate_wire_size_statements(
+                        context,
+                        r#type,
+                        &format!("{value}[{index}]"),
+                        None,
+                        None,
+                        None,
+                        depth + 1
+                    )
+                ));
+                code
+            }
+            None => {
+                generate_unsupported_statement("length of the dynamic array is unknown", &indent)
+            }
+        },
+        TypeIdentifier::UserDefined(identifier) => match context.lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => format!(
+                "{indent}*bits += {};\n",
+                size_in_bits.unwrap_or(enumeration.size_in_bits())
+            ),
+            Some(Definition::Union(union)) => match linked_field {
+                Some(discriminator) => generate_checked_statement(
+                    &format!(
+                        "{}_wire_size_bits(&{value}, (uint64_t)in->{discriminator}, bits)",
+                        union.name.name
+                    ),
+                    &indent,
+                ),
+                None => generate_unsupported_statement("union has no discriminator", &indent),
+            },
+            _ => generate_checked_statement(
+                &format!("{}_wire_size_bits(&{value}, bits)", identifier.name),
+                &indent,
+            ),
+        },
+        _ => format!(
+            "{indent}*bits += {};\n",
+            size_in_bits
+                .or(type_identifier.builtin_size_in_bits())
+                .unwrap_or(64)
+        ),
+    }
+}
+
+/// Generates the `_wire_size` function of a structure, returning the number of bytes written
+/// by its encoder, or 0 if the encoder would fail, e.g. for unknown discriminators.
+fn generate_structure_wire_size_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+) -> String {
+    let name = &structure.name.name;
+    let restrict = context.options.restrict();
+    let mut body = String::new();
+    let mut is_supported = true;
+    for field in &structure.fields {
+        if context.options.align_bytes_fields_to_byte
+            && field
+                .attributes
+                .iter()
+                .any(|attribute| matches!(attribute, Attribute::BytesSize { .. }))
+        {
+            body.push_str("    *bits += (8 - *bits % 8) % 8;\n");
+        }
+        let field_name = &field.name.name;
+        let is_fat_pointer = context.options.fat_pointers
+            && matches!(
+                context.lookup.resolve(&field.r#type),
+                TypeIdentifier::DynamicArray { .. }
+            );
+        let (value, length) = if is_fat_pointer {
+            (
+                format!("in->{field_name}.data"),
+                format!("in->{field_name}.len"),
+            )
+        } else {
+            (
+                format!("in->{field_name}"),
+                format!("in->{}", dynamic_array_length_member(field_name)),
+            )
+        };
+        let statements = generate_wire_size_statements(
+            context,
+            &field.r#type,
+            &value,
+            field_size_in_bits_from_attributes(&field.attributes),
+            Some(&length),
+            linked_field(structure, field),
+            0,
+        );
+        body.push_str(&statements);
+        if is_unsupported_statement(&statements) {
+            is_supported = false;
+            break;
+        }
+    }
+
+    let mut code = format!(
+        "static bool {name}_wire_size_bits(const {name}*{restrict} in, size_t*{restrict} bits) {{\n"
+    );
+    if !body.contains("in->") {
+        code.push_str("    (void)in;\n");
+    }
+    code.push_str(&body);
+    if is_supported {
+        code.push_str("    return true;\n");
+    }
+    code.push_str("}\n\n");
+    code.push_str(&context.begin_function(format!("size_t {name}_wire_size(const {name}* in)")));
+    code.push_str(&format!(
+        "    size_t bits = 0;\n    \
+         if (!{name}_wire_size_bits(in, &bits)) {{\n        \
+         return 0;\n    \
+         }}\n    \
+         return (bits + 7) / 8;\n}}\n\n"
+    ));
+    code
+}
+
+fn generate_union_wire_size_code(context: &CodegenContext, union: &UnionDefinition) -> String {
+    let name = &union.name.name;
+    let restrict = context.options.restrict();
+    let mut body = String::from("    switch (discriminator) {\n");
+    let mut push_case = |discriminator: u64, member: String, r#type: &TypeIdentifier| {
+        let statements = generate_wire_size_statements(
+            context,
+            r#type,
+            &format!("in->{member}"),
+            None,
+            None,
+            None,
+            1,
+        );
+        body.push_str(&format!("    case {discriminator}:\n"));
+        body.push_str(&statements);
+        if !is_unsupported_statement(&statements) {
+            body.push_str("        return true;\n");
+        }
+    };
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue {
+                name: field_name,
+                r#type,
+                discriminator,
+            } => push_case(*discriminator, field_name.name.clone(), r#type),
+            UnionField::RangeOfValues {
+                name: field_name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => {
+                for i in *start_discriminator..=*end_discriminator {
+                    push_case(i, format!("{}_{}", field_name.name, i), r#type);
+                }
+            }
+        }
+    }
+    body.push_str("    default:\n        return false;\n    }\n}\n\n");
+
+    let mut code = format!(
+        "static bool {name}_wire_size_bits(const {name}*{restrict} in, uint64_t discriminator, size_t*{restrict} bits) {{\n"
+    );
+    if !body.contains("in->") {
+        code.push_str("    (void)in;\n");
+    }
+    if !body.contains("*bits") && !body.contains(", bits)") {
+        code.push_str("    (void)bits;\n");
+    }
+    code.push_str(&body);
+    code
+}
+
+/// Generates the `_wire_size` functions of all structures of the protocol, which return the
+/// number of bytes their encoders write, e.g. to allocate a buffer before encoding. Unions
+/// are measured through the structures holding them. Definitions must be sorted by
+/// dependencies.
+pub fn generate_wire_size_code(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    generate_wire_size_code_in(&CodegenContext::new(protocol, options), protocol)
+}
+
+/// Same as [`generate_wire_size_code`], generating the functions within the given context.
+fn generate_wire_size_code_in(context: &CodegenContext, protocol: &Protocol) -> String {
+    let mut code = WIRE_SIZE_HELPERS_CODE.replace("{helpers}", &context.helpers);
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_wire_size_code(context, structure));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_wire_size_code(context, union));
+            }
+            Definition::Enumeration(_)
+            | Definition::Type(_)
+            | Definition::Flags(_)
+            | Definition::Constant(_) => {}
+        }
+    }
+    code
+}
+
+/// Generates the C11 `encode` macro, which picks the encoder matching the type of its argument.
+pub fn generate_generic_macros_code(protocol: &Protocol) -> String {
+    generate_prefixed_generic_macros_code(protocol, "")
+}
+
+/// Same as [`generate_generic_macros_code`], with `prefix` prepended to the macro.
+fn generate_prefixed_generic_macros_code(protocol: &Protocol, prefix: &str) -> String {
+    let associations: Vec<String> = protocol
+        .definitions
+        .iter()
+        .filter_map(|definition| match definition {
+            Definition::Structure(structure) => Some(&structure.name.name),
+            _ => None,
+        })
+        .flat_map(|name| {
+            [
+                format!("    {name}*: {name}_encode"),
+                format!("    const {name}*: {name}_encode"),
+            ]
+        })
+        .collect();
+    if associations.is_empty() {
+        return String::new();
+    }
+
+    format!(
+        "#define {prefix}encode(x, buf, len) _Generic((x), \\\n{})(x, buf, len)\n\n",
+        associations.join(", \\\n")
+    )
+}
+
+const DECODER_HELPERS_CODE: &str = r#"typedef struct {
+    const uint8_t* buf;
+    size_t buf_len;
+    size_t bit_offset;
+} {helpers}reader;
+
+static inline bool {helpers}read_bits({helpers}reader* reader, uint64_t* value, unsigned bits) {
+    if (reader->bit_offset > reader->buf_len * 8 || bits > reader->buf_len * 8 - reader->bit_offset) {
+        return false;
+    }
+    *value = 0;
+    for (unsigned i = bits; i > 0; i--) {
+        uint8_t byte = reader->buf[reader->bit_offset / 8];
+        {read_bit}
+        reader->bit_offset++;
+    }
+{swap_bytes}    return true;
+}
+
+static inline bool {helpers}read_varint({helpers}reader* reader, uint64_t* value) {
+    *value = 0;
+    for (unsigned shift = 0; shift < 64; shift += 7) {
+        uint64_t byte;
+        if (!{helpers}read_bits(reader, &byte, 8)) {
+            return false;
+        }
+        *value |= (byte & 0x7f) << shift;
+        if ((byte & 0x80) == 0) {
+            return true;
+        }
+    }
+    return false;
+}
+
+static inline int64_t {helpers}sign_extend(uint64_t value, unsigned bits) {
+    uint64_t sign = (uint64_t)1 << (bits - 1);
+    return (int64_t)((value ^ sign) - sign);
+}
+
+static inline bool {helpers}read_float32({helpers}reader* reader, float* value) {
+    uint64_t bits;
+    if (!{helpers}read_bits(reader, &bits, 32)) {
+        return false;
+    }
+    uint32_t narrowed = (uint32_t)bits;
+    memcpy(value, &narrowed, sizeof(*value));
+    return true;
+}
+
+static inline bool {helpers}read_float64({helpers}reader* reader, double* value) {
+    uint64_t bits;
+    if (!{helpers}read_bits(reader, &bits, 64)) {
+        return false;
+    }
+    memcpy(value, &bits, sizeof(*value));
+    return true;
+}
+
+"#;
+
+/// Returns a block reading `bits` bits to a `uint64_t value`, which is then stored by the
+/// `assignment` statement.
+fn generate_read_block(
+    context: &CodegenContext,
+    bits: u64,
+    assignment: &str,
+    indent: &str,
+) -> String {
+    let helpers = &context.helpers;
+    format!(
+        "{indent}{{\n{indent}    uint64_t value;\n{}{indent}    {assignment}\n{indent}}}\n",
+        generate_checked_statement(
+            &format!("{helpers}read_bits(reader, &value, {bits})"),
+            &format!("{indent}    ")
+        )
+    )
+}
+
+/// Returns statements reading a value of the given type from `reader` to `target`. The
+/// arguments match the ones of [`generate_encode_statements`], with `length` being the
+/// expression the number of elements of a dynamic array is stored to.
+fn generate_decode_statements(
+    context: &CodegenContext,
+    type_identifier: &TypeIdentifier,
+    target: &str,
+    size_in_bits: Option<u64>,
+    length: Option<&str>,
+    linked_field: Option<&str>,
+    depth: usize,
+) -> String {
+    let helpers = &context.helpers;
+    let indent = "    ".repeat(depth + 1);
+    let type_identifier = context.lookup.resolve(type_identifier);
+    let bits = size_in_bits
+        .or(type_identifier.builtin_size_in_bits())
+        .unwrap_or(64);
+
+    match type_identifier {
+        TypeIdentifier::Void => String::new(),
+        TypeIdentifier::Float32 | TypeIdentifier::Float64 => {
+            let (c_type, function) = match type_identifier {
+                TypeIdentifier::Float32 => ("float", "read_float32"),
+                _ => ("double", "read_float64"),
+            };
+            format!(
+                "{indent}{{\n{indent}    {c_type} value;\n{}{indent}    {target} = value;\n{indent}}}\n",
+                generate_checked_statement(
+                    &format!("{}{function}(reader, &value)", context.helpers),
+                    &format!("{indent}    ")
+                )
+            )
+        }
+        TypeIdentifier::Bit => {
+            generate_read_block(context, bits, &format!("{target} = value != 0;"), &indent)
+        }
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            let index = format!("i{depth}");
+            format!(
+                "{indent}for (size_t {index} = 0; {index} < {size}; {index}++) {{\n{}{indent}}}\n",
+                generate_decode_statements(
+                    context,
+                    r#type,
+                    &format!("{target}[{index}]"),
+                    None,
+                    None,
+                    None,
+                    depth + 1
+                )
+            )
+        }
+        TypeIdentifier::DynamicArray { r#type } => match length {
+            Some(length) => {
+                let index = format!("i{depth}");
+                let inner_indent = format!("{indent}    ");
+                let mut code = format!("{indent}{{\n{inner_indent}uint64_t length;\n");
+                code.push_str(&match linked_field {
+                    Some(field) => format!("{inner_indent}length = (uint64_t)out->{field};\n"),
+                    None => generate_checked_statement(
+                        &context
+                            .options
+                            .length_prefix
+                            .generate_read_expression(&context.helpers, "&length"),
+                        &inner_indent,
+                    ),
+                });
+                // Text gets a terminating null character, so that it can be used as a C string
+                let is_text = matches!(context.lookup.resolve(r#type), TypeIdentifier::Char);
+                let (allocation, allocation_failed) = if is_text {
+                    ("(length + 1)", format!("{target} == NULL"))
+                } else {
+                    ("length", format!("length != 0 && {target} == NULL"))
+                };
+                // Every element takes at least a bit, which bounds the allocation by the input
+                code.push_str(&format!(
+                    "{inner_indent}if (length > UINT32_MAX || length > reader->buf_len * 8 - reader->bit_offset) {{\n\
+                     {inner_indent}    return false;\n\
+                     {inner_indent}}}\n\
+                     {inner_indent}{length} = (uint32_t)length;\n\
+                     {inner_indent}{target} = malloc({allocation} * sizeof(*{target}));\n\
+                     {inner_indent}if ({allocation_failed}) {{\n\
+                     {inner_indent}    return false;\n\
+                     {inner_indent}}}\n\
+                     {inner_indent}for (size_t {index} = 0; {index} < length; {index}++) {{\n{}{inner_indent}}}\n",
+                    generate_decode_statements(
+                        context,
+                        r#type,
+                        &format!("{target}[{index}]"),
+                        None,
+                        None,
+                        None,
+                        depth + 2
+                    )
+                ));
+                if is_text {
+                    code.push_str(&format!("{inner_indent}{target}[length] = '\\0';\n"));
+                }
+                code.push_str(&format!("{indent}}}\n"));
+                code
+            }
+            None => {
+                generate_unsupported_statement("length of the dynamic array is unknown", &indent)
+            }
+        },
+        TypeIdentifier::UserDefined(identifier) => match context.lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => {
+                let bits = size_in_bits.unwrap_or(enumeration.size_in_bits());
+                let value = if enumeration.is_signed() && bits < 64 {
+                    format!("{helpers}sign_extend(value, {bits})")
+                } else {
+                    "value".to_string()
+                };
+                generate_read_block(
+                    context,
+                    bits,
+                    &format!("{target} = ({}){value};", enumeration.name.name),
+                    &indent,
+                )
+            }
+            Some(Definition::Union(union)) => match linked_field {
+                Some(discriminator) => generate_checked_statement(
+                    &format!(
+                        "{}_decode_bits(&{target}, (uint64_t)out->{discriminator}, reader)",
+                        union.name.name
+                    ),
+                    &indent,
+                ),
+                None => generate_unsupported_statement("union has no discriminator", &indent),
+            },
+            _ => generate_checked_statement(
+                &format!("{}_decode_bits(&{target}, reader)", identifier.name),
+                &indent,
+            ),
+        },
+        _ => {
+            let c_type =
+                generate_type_identifier_code(type_identifier, context.options.type_mapper());
+            let is_signed = matches!(
+                type_identifier,
+                TypeIdentifier::Integer8
+                    | TypeIdentifier::Integer16
+                    | TypeIdentifier::Integer32
+                    | TypeIdentifier::Integer64
+            );
+            let value = if is_signed && bits < 64 {
+                format!("{helpers}sign_extend(value, {bits})")
+            } else {
+                "value".to_string()
+            };
+            generate_read_block(
+                context,
+                bits,
+                &format!("{target} = ({c_type}){value};"),
+                &indent,
+            )
+        }
+    }
+}
+
+fn generate_structure_decoder_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+) -> String {
+    let helpers = &context.helpers;
+    let name = &structure.name.name;
+    let restrict = context.options.restrict();
+    let mut code = String::new();
+    code.push_str(&format!(
+        "static bool {name}_decode_bits({name}*{restrict} out, {helpers}reader*{restrict} reader) {{\n"
+    ));
+    let mut is_supported = true;
+    for field in &structure.fields {
+        if context.options.align_bytes_fields_to_byte
+            && field
+                .attributes
+                .iter()
+                .any(|attribute| matches!(attribute, Attribute::BytesSize { .. }))
+        {
+            code.push_str(&format!(
+                "    {{\n        uint64_t padding;\n        \
+                 if (!{helpers}read_bits(reader, &padding, (8 - reader->bit_offset % 8) % 8)) {{\n            \
+                 return false;\n        }}\n    }}\n",
+            ));
+        }
+        let field_name = &field.name.name;
+        if field.reserved {
+            let (count, bits) = reserved_field_layout(context, field);
+            code.push_str(&generate_reserved_statements(count, |indent| {
+                format!(
+                    "{indent}{{\n{indent}    uint64_t padding;\n{}{indent}}}\n",
+                    generate_checked_statement(
+                        &format!("{helpers}read_bits(reader, &padding, {bits})"),
+                        &format!("{indent}    ")
+                    )
+                )
+            }));
+            // Reserved integers may be bitfields, whose address cannot be taken
+            code.push_str(&match context.lookup.resolve(&field.r#type) {
+                TypeIdentifier::StaticArray { .. } => {
+                    format!("    memset(out->{field_name}, 0, sizeof(out->{field_name}));\n")
+                }
+                _ => format!("    out->{field_name} = 0;\n"),
+            });
+            continue;
+        }
+        let is_fat_pointer = context.options.fat_pointers
+            && matches!(
+                context.lookup.resolve(&field.r#type),
+                TypeIdentifier::DynamicArray { .. }
+            );
+        let (target, length) = if is_fat_pointer {
+            (
+                format!("out->{field_name}.data"),
+                format!("out->{field_name}.len"),
+            )
+        } else {
+            (
+                format!("out->{field_name}"),
+                format!("out->{}", dynamic_array_length_member(field_name)),
+            )
+        };
+        let statements = generate_decode_statements(
+            context,
+            &field.r#type,
+            &target,
+            field_size_in_bits_from_attributes(&field.attributes),
+            Some(&length),
+            linked_field(structure, field),
+            0,
+        );
+        code.push_str(&statements);
+        if is_unsupported_statement(&statements) {
+            is_supported = false;
+            break;
+        }
+    }
+    if is_supported {
+        code.push_str("    return true;\n");
+    }
+    code.push_str("}\n\n");
+
+    code.push_str(&context.begin_function(format!(
+        "size_t {name}_decode({name}*{restrict} out, const uint8_t*{restrict} buf, size_t buf_len)"
+    )));
+    code.push_str(&format!(
+        "    {helpers}reader reader = {{buf, buf_len, 0}};\n    \
+         if (!{name}_decode_bits(out, &reader)) {{\n        \
+         return 0;\n    \
+         }}\n    \
+         return (reader.bit_offset + 7) / 8;\n}}\n\n"
+    ));
+    code
+}
+
+fn generate_union_decoder_code(context: &CodegenContext, union: &UnionDefinition) -> String {
+    let helpers = &context.helpers;
+    let name = &union.name.name;
+    let restrict = context.options.restrict();
+    let mut code = String::new();
+    code.push_str(&format!(
+        "static bool {name}_decode_bits({name}*{restrict} out, uint64_t discriminator, {helpers}reader*{restrict} reader) {{\n"
+    ));
+    if has_only_void_fields(union) {
+        code.push_str("    (void)out;\n    (void)reader;\n");
+    }
+    code.push_str("    switch (discriminator) {\n");
+    let mut push_case = |discriminator: u64, member: String, r#type: &TypeIdentifier| {
+        let statements = generate_decode_statements(
+            context,
+            r#type,
+            &format!("out->{member}"),
+            None,
+            None,
+            None,
+            1,
+        );
+        code.push_str(&format!("    case {discriminator}:\n"));
+        code.push_str(&statements);
+        if !is_unsupported_statement(&statements) {
+            code.push_str("        return true;\n");
+        }
+    };
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue {
+                name: field_name,
+                r#type,
+                discriminator,
+            } => push_case(*discriminator, field_name.name.clone(), r#type),
+            UnionField::RangeOfValues {
+                name: field_name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => {
+                for i in *start_discriminator..=*end_discriminator {
+                    push_case(i, format!("{}_{}", field_name.name, i), r#type);
+                }
+            }
+        }
+    }
+    code.push_str("    default:\n        return false;\n    }\n}\n\n");
+    code
+}
+
+/// Callbacks of the streaming decoders receiving values of built-in types, with the name of
+/// the callback and the C type of its value.
+const STREAM_CALLBACKS: &[(&str, &str)] = &[
+    ("int8", "int8_t"),
+    ("int16", "int16_t"),
+    ("int32", "int32_t"),
+    ("int64", "int64_t"),
+    ("uint8", "uint8_t"),
+    ("uint16", "uint16_t"),
+    ("uint32", "uint32_t"),
+    ("uint64", "uint64_t"),
+    ("float32", "float"),
+    ("float64", "double"),
+    ("bit", "bool"),
+    ("enum", "uint64_t"),
+];
+
+/// Generates the `meksmith_callbacks` type passed to the streaming decoders. Callbacks left
+/// `NULL` are not invoked.
+pub fn generate_stream_callbacks_code() -> String {
+    generate_prefixed_stream_callbacks_code(&CCodegenOptions::default().helpers())
+}
+
+/// Same as [`generate_stream_callbacks_code`], with the type in the given namespace of helpers.
+fn generate_prefixed_stream_callbacks_code(helpers: &str) -> String {
+    let mut code = "typedef struct {\n".to_string();
+    for (name, c_type) in STREAM_CALLBACKS {
+        code.push_str(&format!(
+            "    void (*on_{name})(const char* name, {c_type} value, void* ctx);\n"
+        ));
+    }
+    code.push_str(&format!(
+        "    void (*on_begin)(const char* name, void* ctx);\n    \
+         void (*on_end)(const char* name, void* ctx);\n}} {helpers}callbacks;\n\n",
+    ));
+    code
+}
+
+/// Returns the statement invoking the given callback with the arguments following the name.
+fn generate_callback_statement(
+    callback: &str,
+    name: &str,
+    arguments: &str,
+    indent: &str,
+) -> String {
+    format!(
+        "{indent}if (callbacks->on_{callback} != NULL) {{\n\
+         {indent}    callbacks->on_{callback}(\"{name}\", {arguments}ctx);\n\
+         {indent}}}\n"
+    )
+}
+
+/// Name of the local variable of a streaming decoder holding the value of a discriminator.
+fn stream_discriminator_variable(field_name: &str) -> String {
+    format!("discriminator_{field_name}")
+}
+
+/// Name of the local variable of a streaming decoder holding the length of a dynamic array.
+fn stream_length_variable(field_name: &str) -> String {
+    format!("length_{field_name}")
+}
+
+/// Returns statements reading a value of the given type from `reader` and passing it to the
+/// callbacks under `name`, like [`generate_decode_statements`]. The value of scalars is also
+/// assigned to `store` when given, which keeps discriminators of later union fields and
+/// lengths of later dynamic array fields.
+fn generate_stream_statements(
+    context: &CodegenContext,
+    type_identifier: &TypeIdentifier,
+    name: &str,
+    size_in_bits: Option<u64>,
+    linked_field: Option<&str>,
+    store: Option<&str>,
+    depth: usize,
+) -> String {
+    let helpers = &context.helpers;
+    let indent = "    ".repeat(depth + 1);
+    let inner_indent = format!("{indent}    ");
+    let type_identifier = context.lookup.resolve(type_identifier);
+    let bits = size_in_bits
+        .or(type_identifier.builtin_size_in_bits())
+        .unwrap_or(64);
+    let stored = store.map_or_else(String::new, |store| {
+        format!("{store} = value;\n{inner_indent}")
+    });
+    let nested = |statements: String| {
+        format!(
+            "{}{statements}{}",
+            generate_callback_statement("begin", name, "", &indent),
+            generate_callback_statement("end", name, "", &indent)
+        )
+    };
+
+    match type_identifier {
+        TypeIdentifier::Void => String::new(),
+        TypeIdentifier::Float32 | TypeIdentifier::Float64 => {
+            let (callback, c_type, function) = match type_identifier {
+                TypeIdentifier::Float32 => ("float32", "float", "read_float32"),
+                _ => ("float64", "double", "read_float64"),
+            };
+            format!(
+                "{indent}{{\n{inner_indent}{c_type} value;\n{}{}{indent}}}\n",
+                generate_checked_statement(
+                    &format!("{}{function}(reader, &value)", context.helpers),
+                    &inner_indent
+                ),
+                generate_callback_statement(callback, name, "value, ", &inner_indent)
+            )
+        }
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            let index = format!("i{depth}");
+            nested(format!(
+                "{indent}for (size_t {index} = 0; {index} < {size}; {index}++) {{\n{}{indent}}}\n",
+                generate_stream_statements(context, r#type, name, None, None, None, depth + 1)
+            ))
+        }
+        TypeIdentifier::DynamicArray { r#type } => {
+            let index = format!("i{depth}");
+            nested(format!(
+                "{indent}{{\n{inner_indent}uint64_t length;\n{}\
+                 {inner_indent}for (uint64_t {index} = 0; {index} < length; {index}++) {{\n{}{inner_indent}}}\n\
+                 {indent}}}\n",
+                match linked_field {
+                    Some(field) => format!(
+                        "{inner_indent}length = {};\n",
+                        stream_length_variable(field)
+                    ),
+                    None => generate_checked_statement(
+                        &context
+                            .options
+                            .length_prefix
+                            .generate_read_expression(&context.helpers, "&length"),
+                        &inner_indent,
+                    ),
+                },
+                generate_stream_statements(context, r#type, name, None, None, None, depth + 2)
+            ))
+        }
+        TypeIdentifier::UserDefined(identifier) => match context.lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => generate_read_block(
+                context,
+                size_in_bits.unwrap_or(enumeration.size_in_bits()),
+                &format!(
+                    "{stored}{}",
+                    generate_callback_statement("enum", name, "value, ", &inner_indent)
+                        .trim_start()
+                        .trim_end()
+                ),
+                &indent,
+            ),
+            Some(Definition::Union(union)) => match linked_field {
+                Some(discriminator) => nested(generate_checked_statement(
+                    &format!(
+                        "{}_stream_bits({}, callbacks, ctx, reader)",
+                        union.name.name,
+                        stream_discriminator_variable(discriminator)
+                    ),
+                    &indent,
+                )),
+                None => generate_unsupported_statement("union has no discriminator", &indent),
+            },
+            _ => nested(generate_checked_statement(
+                &format!("{}_stream_bits(callbacks, ctx, reader)", identifier.name),
+                &indent,
+            )),
+        },
+        _ => {
+            let (callback, c_type) = match type_identifier {
+                TypeIdentifier::Integer8 => ("int8", "int8_t"),
+                TypeIdentifier::Integer16 => ("int16", "int16_t"),
+                TypeIdentifier::Integer32 => ("int32", "int32_t"),
+                TypeIdentifier::Integer64 => ("int64", "int64_t"),
+                TypeIdentifier::UnsignedInteger8 | TypeIdentifier::Byte | TypeIdentifier::Char => {
+                    ("uint8", "uint8_t")
+                }
+                TypeIdentifier::UnsignedInteger16 => ("uint16", "uint16_t"),
+                TypeIdentifier::UnsignedInteger32 => ("uint32", "uint32_t"),
+                TypeIdentifier::Bit => ("bit", "bool"),
+                _ => ("uint64", "uint64_t"),
+            };
+            let value = match type_identifier {
+                TypeIdentifier::Bit => "value != 0".to_string(),
+                TypeIdentifier::Integer8
+                | TypeIdentifier::Integer16
+                | TypeIdentifier::Integer32
+                | TypeIdentifier::Integer64
+                    if bits < 64 =>
+                {
+                    format!("({c_type}){helpers}sign_extend(value, {bits})")
+                }
+                _ => format!("({c_type})value"),
+            };
+            generate_read_block(
+                context,
+                bits,
+                &format!(
+                    "{stored}{}",
+                    generate_callback_statement(
+                        callback,
+                        name,
+                        &format!("{value}, "),
+                        &inner_indent
+                    )
+                    .trim_start()
+                    .trim_end()
+                ),
+                &indent,
+            )
+        }
+    }
+}
+
+fn generate_structure_streaming_decoder_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+) -> String {
+    let helpers = &context.helpers;
+    let name = &structure.name.name;
+    let discriminators: HashSet<&str> = structure
+        .fields
+        .iter()
+        .filter_map(|field| discriminated_by(&field.attributes))
+        .collect();
+    let lengths: HashSet<&str> = structure
+        .fields
+        .iter()
+        .filter_map(|field| length_field(structure, field))
+        .collect();
+    let restrict = context.options.restrict();
+    let mut code = format!(
+        "static bool {name}_stream_bits(const {helpers}callbacks* callbacks, void* ctx, {helpers}reader*{restrict} reader) {{\n"
+    );
+    for field in &structure.fields {
+        if discriminators.contains(field.name.name.as_str()) {
+            code.push_str(&format!(
+                "    uint64_t {} = 0;\n",
+                stream_discriminator_variable(&field.name.name)
+            ));
+        }
+        if lengths.contains(field.name.name.as_str()) {
+            code.push_str(&format!(
+                "    uint64_t {} = 0;\n",
+                stream_length_variable(&field.name.name)
+            ));
+        }
+    }
+    let mut is_supported = true;
+    for field in &structure.fields {
+        if context.options.align_bytes_fields_to_byte
+            && field
+                .attributes
+                .iter()
+                .any(|attribute| matches!(attribute, Attribute::BytesSize { .. }))
+        {
+            code.push_str(&format!(
+                "    {{\n        uint64_t padding;\n        \
+                 if (!{helpers}read_bits(reader, &padding, (8 - reader->bit_offset % 8) % 8)) {{\n            \
+                 return false;\n        }}\n    }}\n",
+            ));
+        }
+        if field.reserved {
+            let (count, bits) = reserved_field_layout(context, field);
+            code.push_str(&generate_reserved_statements(count, |indent| {
+                format!(
+                    "{indent}{{\n{indent}    uint64_t padding;\n{}{indent}}}\n",
+                    generate_checked_statement(
+                        &format!("{helpers}read_bits(reader, &padding, {bits})"),
+                        &format!("{indent}    ")
+                    )
+                )
+            }));
+            continue;
+        }
+        let field_name = &field.name.name;
+        let (is_discriminator, is_length) = (
+            discriminators.contains(field_name.as_str()),
+            lengths.contains(field_name.as_str()),
+        );
+        let store = if is_discriminator {
+            Some(stream_discriminator_variable(field_name))
+        } else {
+            is_length.then(|| stream_length_variable(field_name))
+        };
+        let statements = generate_stream_statements(
+            context,
+            &field.r#type,
+            field_name,
+            field_size_in_bits_from_attributes(&field.attributes),
+            linked_field(structure, field),
+            store.as_deref(),
+            0,
+        );
+        code.push_str(&statements);
+        if is_unsupported_statement(&statements) {
+            is_supported = false;
+            break;
+        }
+        if is_discriminator && is_length {
+            code.push_str(&format!(
+                "    {} = {};\n",
+                stream_length_variable(field_name),
+                stream_discriminator_variable(field_name)
+            ));
+        }
+    }
+    if is_supported {
+        code.push_str("    return true;\n");
+    }
+    code.push_str("}\n\n");
+
+    code.push_str(&context.begin_function(format!(
+        "size_t {name}_stream(const uint8_t*{restrict} buf, size_t buf_len, const {helpers}callbacks* callbacks, void* ctx)"
+    )));
+    code.push_str(&format!(
+        "    {helpers}reader reader = {{buf, buf_len, 0}};\n    \
+         if (!{name}_stream_bits(callbacks, ctx, &reader)) {{\n        \
+         return 0;\n    \
+         }}\n    \
+         return (reader.bit_offset + 7) / 8;\n}}\n\n"
+    ));
+    code
+}
+
+fn generate_union_streaming_decoder_code(
+    context: &CodegenContext,
+    union: &UnionDefinition,
+) -> String {
+    let helpers = &context.helpers;
+    let name = &union.name.name;
+    let restrict = context.options.restrict();
+    let mut code = format!(
+        "static bool {name}_stream_bits(uint64_t discriminator, const {helpers}callbacks* callbacks, void* ctx, {helpers}reader*{restrict} reader) {{\n"
+    );
+    if has_only_void_fields(union) {
+        code.push_str("    (void)callbacks;\n    (void)ctx;\n    (void)reader;\n");
+    }
+    code.push_str("    switch (discriminator) {\n");
+    let mut push_case = |discriminator: u64, member: String, r#type: &TypeIdentifier| {
+        let statements = generate_stream_statements(context, r#type, &member, None, None, None, 1);
+        code.push_str(&format!("    case {discriminator}:\n"));
+        code.push_str(&statements);
+        if !is_unsupported_statement(&statements) {
+            code.push_str("        return true;\n");
+        }
+    };
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue {
+                name: field_name,
+                r#type,
+                discriminator,
+            } => push_case(*discriminator, field_name.name.clone(), r#type),
+            UnionField::RangeOfValues {
+                name: field_name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => {
+                for i in *start_discriminator..=*end_discriminator {
+                    push_case(i, format!("{}_{}", field_name.name, i), r#type);
+                }
+            }
+        }
+    }
+    code.push_str("    default:\n        return false;\n    }\n}\n\n");
+    code
+}
+
+/// Generates the `_stream` functions of all structures and unions of the protocol, which parse
+/// a message field by field, passing every value to the [`generate_stream_callbacks_code`]
+/// callbacks instead of storing it. They use the helpers of [`generate_decoders_code`].
+/// Definitions must be sorted by dependencies.
+pub fn generate_streaming_decoders_code(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    generate_streaming_decoders_code_in(&CodegenContext::new(protocol, options), protocol)
+}
+
+/// Same as [`generate_streaming_decoders_code`], generating the functions within the given context.
+fn generate_streaming_decoders_code_in(context: &CodegenContext, protocol: &Protocol) -> String {
+    let mut code = String::new();
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_streaming_decoder_code(
+                    context, structure,
+                ));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_streaming_decoder_code(context, union));
+            }
+            Definition::Enumeration(_)
+            | Definition::Type(_)
+            | Definition::Flags(_)
+            | Definition::Constant(_) => {}
+        }
+    }
+    code
+}
+
+const JSON_HELPERS_CODE: &str = r#"static inline const char* {helpers}json_skip_whitespace(const char* p) {
+    while (*p == ' ' || *p == '\t' || *p == '\n' || *p == '\r') {
+        p++;
+    }
+    return p;
+}
+
+/* Returns the value of the member named key of a flat JSON object, or NULL if there is no
+   such member or the object holds nested objects or arrays. */
+static inline const char* {helpers}json_find(const char* json, const char* key) {
+    size_t key_len = strlen(key);
+    const char* p = {helpers}json_skip_whitespace(json);
+    if (*p != '{') {
+        return NULL;
+    }
+    p = {helpers}json_skip_whitespace(p + 1);
+    while (*p == '"') {
+        const char* name = ++p;
+        while (*p != '"') {
+            if (*p == '\0') {
+                return NULL;
+            }
+            p++;
+        }
+        size_t name_len = (size_t)(p - name);
+        p = {helpers}json_skip_whitespace(p + 1);
+        if (*p != ':') {
+            return NULL;
+        }
+        p = {helpers}json_skip_whitespace(p + 1);
+        if (name_len == key_len && strncmp(name, key, key_len) == 0) {
+            return p;
+        }
+        if (*p == '"') {
+            for (p++; *p != '"'; p++) {
+                if (*p == '\0') {
+                    return NULL;
+                }
+                if (*p == '\\' && p[1] != '\0') {
+                    p++;
+                }
+            }
+            p++;
+        }
+        while (*p != ',' && *p != '}') {
+            if (*p == '\0' || *p == '{' || *p == '[' || *p == '"') {
+                return NULL;
+            }
+            p++;
+        }
+        if (*p == '}') {
+            return NULL;
+        }
+        p = {helpers}json_skip_whitespace(p + 1);
+    }
+    return NULL;
+}
+
+static inline bool {helpers}json_value_ends(const char* start, const char* end) {
+    end = {helpers}json_skip_whitespace(end);
+    return end != start && (*end == ',' || *end == '}');
+}
+
+static inline bool {helpers}json_get_uint(const char* json, const char* key, uint64_t max, uint64_t* value) {
+    const char* p = {helpers}json_find(json, key);
+    char* end;
+    if (p == NULL || *p == '-') {
+        return false;
+    }
+    errno = 0;
+    *value = strtoull(p, &end, 10);
+    return errno == 0 && {helpers}json_value_ends(p, end) && *value <= max;
+}
+
+static inline bool {helpers}json_get_int(const char* json, const char* key, int64_t min, int64_t max, int64_t* value) {
+    const char* p = {helpers}json_find(json, key);
+    char* end;
+    if (p == NULL) {
+        return false;
+    }
+    errno = 0;
+    *value = strtoll(p, &end, 10);
+    return errno == 0 && {helpers}json_value_ends(p, end) && *value >= min && *value <= max;
+}
+
+static inline bool {helpers}json_get_double(const char* json, const char* key, double* value) {
+    const char* p = {helpers}json_find(json, key);
+    char* end;
+    if (p == NULL) {
+        return false;
+    }
+    *value = strtod(p, &end);
+    return {helpers}json_value_ends(p, end);
+}
+
+static inline bool {helpers}json_get_bool(const char* json, const char* key, bool* value) {
+    const char* p = {helpers}json_find(json, key);
+    if (p != NULL && strncmp(p, "true", 4) == 0 && {helpers}json_value_ends(p, p + 4)) {
+        *value = true;
+        return true;
+    }
+    if (p != NULL && strncmp(p, "false", 5) == 0 && {helpers}json_value_ends(p, p + 5)) {
+        *value = false;
+        return true;
+    }
+    return false;
+}
+
+"#;
+
+/// Returns the statements reading the scalar member `name` of the JSON object to `target`,
+/// or `None` if the type is not a scalar, which is not supported by the JSON converters.
+fn generate_json_read_statements(
+    context: &CodegenContext,
+    type_identifier: &TypeIdentifier,
+    size_in_bits: Option<u64>,
+    name: &str,
+    target: &str,
+    c_type: &str,
+) -> Option<String> {
+    let helpers = &context.helpers;
+    let type_identifier = context.lookup.resolve(type_identifier);
+    let (natural_size_in_bits, is_signed_enumeration) = match type_identifier {
+        TypeIdentifier::UserDefined(identifier) => match context.lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => {
+                (enumeration.size_in_bits(), enumeration.is_signed())
+            }
+            _ => return None,
+        },
+        _ => (type_identifier.builtin_size_in_bits()?, false),
+    };
+    let bits = size_in_bits.map_or(natural_size_in_bits, |size| size.min(natural_size_in_bits));
+    let is_signed = is_signed_enumeration
+        || matches!(
+            type_identifier,
+            TypeIdentifier::Integer8
+                | TypeIdentifier::Integer16
+                | TypeIdentifier::Integer32
+                | TypeIdentifier::Integer64
+        );
+    let (local, read) = match type_identifier {
+        TypeIdentifier::Bit => (
+            "bool",
+            format!("{helpers}json_get_bool(json, \"{name}\", &value)"),
+        ),
+        TypeIdentifier::Float32 | TypeIdentifier::Float64 => (
+            "double",
+            format!("{helpers}json_get_double(json, \"{name}\", &value)"),
+        ),
+        _ if is_signed => {
+            let max = (1u64 << (bits - 1)) - 1;
+            (
+                "int64_t",
+                format!("{helpers}json_get_int(json, \"{name}\", -{max} - 1, {max}, &value)"),
+            )
+        }
+        TypeIdentifier::Void
+        | TypeIdentifier::StaticArray { .. }
+        | TypeIdentifier::DynamicArray { .. } => return None,
+        _ => {
+            let max = if bits >= 64 {
+                u64::MAX
+            } else {
+                (1u64 << bits) - 1
+            };
+            (
+                "uint64_t",
+                format!("{helpers}json_get_uint(json, \"{name}\", {max}u, &value)"),
+            )
+        }
+    };
+    Some(format!(
+        "    {{\n        {local} value;\n{}        {target} = ({c_type})value;\n    }}\n",
+        generate_checked_statement(&read, "        ")
+    ))
+}
+
+/// Generates the `_from_json` function of a structure, see [`CCodegenOptions::emit_json_io`].
+/// Only structures of scalar fields are supported, so nothing is generated for the others.
+fn generate_structure_json_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+) -> String {
+    let name = &structure.name.name;
+    let restrict = context.options.restrict();
+    let mapper = context.options.type_mapper();
+    let mut body = String::new();
+    for field in structure.fields.iter().filter(|field| !field.reserved) {
+        let c_type = c_type_override(&field.attributes).map_or_else(
+            || generate_type_identifier_code(&field.r#type, mapper),
+            str::to_string,
+        );
+        let Some(statements) = generate_json_read_statements(
+            context,
+            &field.r#type,
+            field_size_in_bits_from_attributes(&field.attributes),
+            &field.name.name,
+            &format!("out->{}", field.name.name),
+            &c_type,
+        ) else {
+            return String::new();
+        };
+        body.push_str(&statements);
+    }
+    format!(
+        "{}{body}    return true;\n}}\n\n",
+        context.begin_function(format!(
+            "bool {name}_from_json(const char*{restrict} json, {name}*{restrict} out)"
+        ))
+    )
+}
+
+/// Generates the `_from_json` functions of all structures of scalar fields, which fill the
+/// structure from the members of a flat JSON object named after its fields, so that it can
+/// then be passed to the encoder. Other members of the object are ignored.
+pub fn generate_json_io_code(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    generate_json_io_code_in(&CodegenContext::new(protocol, options), protocol)
+}
+
+/// Same as [`generate_json_io_code`], generating the functions within the given context.
+fn generate_json_io_code_in(context: &CodegenContext, protocol: &Protocol) -> String {
+    let mut code = JSON_HELPERS_CODE.replace("{helpers}", &context.helpers);
+    for definition in &protocol.definitions {
+        if let Definition::Structure(structure) = definition {
+            code.push_str(&generate_structure_json_code(context, structure));
+        }
+    }
+    code
+}
+
+/// Generates the getters of the fields of a structure which start at a fixed offset, see
+/// [`CCodegenOptions::emit_field_getters`].
+fn generate_structure_field_getters_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+) -> String {
+    let helpers = &context.helpers;
+    let mapper = context.options.type_mapper();
+    let restrict = context.options.restrict();
+    let layout_options = LayoutOptions {
+        align_bytes_fields_to_byte: context.options.align_bytes_fields_to_byte,
+    };
+    // Structures too large for their offsets to fit in 64 bits have no getters
+    let layout = context
+        .lookup
+        .structure_layout(structure, Some(0), &layout_options)
+        .unwrap_or_default();
+    let name = &structure.name.name;
+    let mut code = String::new();
+    for (field, field_layout) in structure.fields.iter().zip(layout) {
+        let Some(offset) = field_layout.offset_in_bits else {
+            break;
+        };
+        let resolved = context.lookup.resolve(&field.r#type);
+        let is_union = matches!(resolved, TypeIdentifier::UserDefined(identifier)
+            if matches!(context.lookup.get(&identifier.name), Some(Definition::Union(_))));
+        if field.reserved || is_union || matches!(resolved, TypeIdentifier::DynamicArray { .. }) {
+            continue;
+        }
+
+        let field_name = &field.name.name;
+        let (parameter, target) = match (&field.r#type, c_type_override(&field.attributes)) {
+            (
+                TypeIdentifier::StaticArray {
+                    r#type,
+                    size,
+                    size_constant,
+                },
+                c_type,
+            ) => (
+                // Nested arrays add their sizes after the first one, e.g. `int32_t out[2][3]`
+                generate_declaration_code(
+                    r#type,
+                    &format!(
+                        "out[{}{}]",
+                        if context.options.use_restrict {
+                            "restrict "
+                        } else {
+                            ""
+                        },
+                        array_size_code(*size, size_constant.as_ref())
+                    ),
+                    c_type,
+                    mapper,
+                ),
+                "out",
+            ),
+            (_, Some(c_type)) => (format!("{c_type}*{restrict} out"), "(*out)"),
+            (r#type, None) => (
+                format!(
+                    "{}*{restrict} out",
+                    generate_type_identifier_code(r#type, mapper)
+                ),
+                "(*out)",
+            ),
+        };
+        code.push_str(&context.begin_function(format!(
+            "bool {name}_get_{field_name}(const uint8_t*{restrict} buf, size_t buf_len, {parameter})"
+        )));
+        code.push_str(&format!(
+            "    {helpers}reader state = {{buf, buf_len, {offset}}};\n    \
+             {helpers}reader* reader = &state;\n"
+        ));
+        code.push_str(&generate_decode_statements(
+            context,
+            &field.r#type,
+            target,
+            field_size_in_bits_from_attributes(&field.attributes),
+            None,
+            None,
+            0,
+        ));
+        code.push_str("    return true;\n}\n\n");
+    }
+    code
+}
+
+/// Generates the `_decode` functions of all structures and unions of the protocol, preceded
+/// by the bit-unpacking helpers they use. Definitions must be sorted by dependencies.
+pub fn generate_decoders_code(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    generate_decoders_code_in(&CodegenContext::new(protocol, options), protocol)
+}
+
+/// Same as [`generate_decoders_code`], generating the functions within the given context.
+fn generate_decoders_code_in(context: &CodegenContext, protocol: &Protocol) -> String {
+    let mut code = DECODER_HELPERS_CODE
+        .replace("{helpers}", &context.helpers)
+        .replace(
+            "{read_bit}",
+            context.options.bit_order.generate_read_bit_statement(),
+        )
+        .replace(
+            "{swap_bytes}",
+            &generate_swap_bytes_statement(
+                context.options.endianness,
+                context.options.bit_order,
+                "*value",
+            ),
+        );
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_decoder_code(context, structure));
+                if context.options.emit_field_getters {
+                    code.push_str(&generate_structure_field_getters_code(context, structure));
+                }
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_decoder_code(context, union));
+            }
+            Definition::Enumeration(_)
+            | Definition::Type(_)
+            | Definition::Flags(_)
+            | Definition::Constant(_) => {}
+        }
+    }
+    code
+}
+
+/// Generates the statements releasing the dynamic arrays held by a value of the given type,
+/// which reset freed pointers to `NULL` and the lengths of their arrays to 0.
+fn generate_free_statements(
+    context: &CodegenContext,
+    type_identifier: &TypeIdentifier,
+    target: &str,
+    length: Option<&str>,
+    discriminator: Option<&str>,
+    depth: usize,
+) -> String {
+    let indent = "    ".repeat(depth + 1);
+    let type_identifier = context.lookup.resolve(type_identifier);
+    if context.lookup.is_memcpy_safe(type_identifier) {
+        return String::new();
+    }
+
+    match type_identifier {
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            let index = format!("i{depth}");
+            format!(
+                "{indent}for (size_t {index} = 0; {index} < {size}; {index}++) {{\n{}{indent}}}\n",
+                generate_free_statements(
+                    context,
+                    r#type,
+                    &format!("{target}[{index}]"),
+                    None,
+                    None,
+                    depth + 1
+                )
+            )
+        }
+        TypeIdentifier::DynamicArray { r#type } => {
+            let mut code = String::new();
+            if let Some(length) = length
+                && !context.lookup.is_memcpy_safe(r#type)
+            {
+                let index = format!("i{depth}");
+                code.push_str(&format!(
+                    "{indent}for (size_t {index} = 0; {index} < {length}; {index}++) {{\n{}{indent}}}\n",
+                    generate_free_statements(
+                        context,
+                        r#type,
+                        &format!("{target}[{index}]"),
+                        None,
+                        None,
+                        depth + 1
+                    )
+                ));
+            }
+            code.push_str(&format!(
+                "{indent}free({target});\n{indent}{target} = NULL;\n"
+            ));
+            if let Some(length) = length {
+                code.push_str(&format!("{indent}{length} = 0;\n"));
+            }
+            code
+        }
+        TypeIdentifier::UserDefined(identifier) => match context.lookup.get(&identifier.name) {
+            Some(Definition::Union(union)) => match discriminator {
+                Some(discriminator) => format!(
+                    "{indent}{}_free(&{target}, (uint64_t)in->{discriminator});\n",
+                    union.name.name
+                ),
+                // The active member of a union without discriminator is unknown
+                None => String::new(),
+            },
+            _ => format!("{indent}{}_free(&{target});\n", identifier.name),
+        },
+        _ => String::new(),
+    }
+}
+
+/// Generates the `<Struct>_free` function enabled by [`CCodegenOptions::emit_destructors`],
+/// if the structure holds any dynamic arrays.
+fn generate_structure_destructor_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+) -> String {
+    let name = &structure.name.name;
+    if context
+        .lookup
+        .is_memcpy_safe(&TypeIdentifier::UserDefined(structure.name.clone()))
+    {
+        return String::new();
+    }
+
+    let mut code = context.begin_function(format!("void {name}_free({name}* in)"));
+    code.push_str("    if (in == NULL) {\n        return;\n    }\n");
+    for field in &structure.fields {
+        let field_name = &field.name.name;
+        let is_fat_pointer = context.options.fat_pointers
+            && matches!(
+                context.lookup.resolve(&field.r#type),
+                TypeIdentifier::DynamicArray { .. }
+            );
+        let (target, length) = if is_fat_pointer {
+            (
+                format!("in->{field_name}.data"),
+                format!("in->{field_name}.len"),
+            )
+        } else {
+            (
+                format!("in->{field_name}"),
+                format!("in->{}", dynamic_array_length_member(field_name)),
+            )
+        };
+        code.push_str(&generate_free_statements(
+            context,
+            &field.r#type,
+            &target,
+            Some(&length),
+            discriminated_by(&field.attributes),
+            0,
+        ));
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+/// Generates the function releasing the dynamic arrays held by the member of a union selected
+/// by the discriminator, if any of its members holds dynamic arrays.
+fn generate_union_destructor_code(context: &CodegenContext, union: &UnionDefinition) -> String {
+    let name = &union.name.name;
+    if context
+        .lookup
+        .is_memcpy_safe(&TypeIdentifier::UserDefined(union.name.clone()))
+    {
+        return String::new();
+    }
+
+    let mut code = format!(
+        "static void {name}_free({name}* in, uint64_t discriminator) {{\n    switch (discriminator) {{\n"
+    );
+    let mut push_case = |discriminator: u64, member: String, r#type: &TypeIdentifier| {
+        let statements =
+            generate_free_statements(context, r#type, &format!("in->{member}"), None, None, 1);
+        if !statements.is_empty() {
+            code.push_str(&format!(
+                "    case {discriminator}:\n{statements}        break;\n"
+            ));
+        }
+    };
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue {
+                name: field_name,
+                r#type,
+                discriminator,
+            } => push_case(*discriminator, field_name.name.clone(), r#type),
+            UnionField::RangeOfValues {
+                name: field_name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => {
+                for i in *start_discriminator..=*end_discriminator {
+                    push_case(i, format!("{}_{}", field_name.name, i), r#type);
+                }
+            }
+        }
+    }
+    code.push_str("    default:\n        break;\n    }\n}\n\n");
+    code
+}
+
+/// Generates the destructors of all structures and unions holding dynamic arrays, see
+/// [`CCodegenOptions::emit_destructors`].
+pub fn generate_destructors_code(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    generate_destructors_code_in(&CodegenContext::new(protocol, options), protocol)
+}
+
+/// Same as [`generate_destructors_code`], generating the functions within the given context.
+fn generate_destructors_code_in(context: &CodegenContext, protocol: &Protocol) -> String {
+    let mut code = String::new();
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_destructor_code(context, structure));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_destructor_code(context, union));
+            }
+            Definition::Enumeration(_)
+            | Definition::Type(_)
+            | Definition::Flags(_)
+            | Definition::Constant(_) => {}
+        }
+    }
+    code
+}
+
+/// Returns whether values of the type can be compared with `memcmp`, i.e. whether it is an
+/// integer type, which has neither padding nor several representations of the same value.
+fn is_memcmp_comparable(lookup: &DefinitionLookup, type_identifier: &TypeIdentifier) -> bool {
+    match lookup.resolve(type_identifier) {
+        TypeIdentifier::StaticArray { r#type, .. } => is_memcmp_comparable(lookup, r#type),
+        TypeIdentifier::Integer8
+        | TypeIdentifier::Integer16
+        | TypeIdentifier::Integer32
+        | TypeIdentifier::Integer64
+        | TypeIdentifier::UnsignedInteger8
+        | TypeIdentifier::UnsignedInteger16
+        | TypeIdentifier::UnsignedInteger32
+        | TypeIdentifier::UnsignedInteger64
+        | TypeIdentifier::Byte
+        | TypeIdentifier::Char => true,
+        _ => false,
+    }
+}
+
+/// Generates the statements returning `false` when the values `a` and `b` of the given type
+/// differ. Dynamic arrays are compared by their lengths first, given as `(a, b)`, and unions
+/// by the members selected by the discriminator of `a`, which must equal the one of `b`.
+fn generate_equals_statements(
+    context: &CodegenContext,
+    type_identifier: &TypeIdentifier,
+    (a, b): (&str, &str),
+    lengths: Option<(&str, &str)>,
+    discriminator: Option<&str>,
+    depth: usize,
+) -> String {
+    let indent = "    ".repeat(depth + 1);
+    let type_identifier = context.lookup.resolve(type_identifier);
+    let return_false_if = |condition: String| {
+        format!("{indent}if ({condition}) {{\n{indent}    return false;\n{indent}}}\n")
+    };
+
+    match type_identifier {
+        TypeIdentifier::Void => String::new(),
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            if is_memcmp_comparable(&context.lookup, r#type) {
+                return return_false_if(format!("memcmp({a}, {b}, sizeof({a})) != 0"));
+            }
+            let index = format!("i{depth}");
+            format!(
+                "{indent}for (size_t {index} = 0; {index} < {size}; {index}++) {{\n{}{indent}}}\n",
+                generate_equals_statements(
+                    context,
+                    r#type,
+                    (&format!("{a}[{index}]"), &format!("{b}[{index}]")),
+                    None,
+                    None,
+                    depth + 1
+                )
+            )
+        }
+        TypeIdentifier::DynamicArray { r#type } => {
+            // Elements cannot be compared without knowing their number
+            let Some((a_length, b_length)) = lengths else {
+                return String::new();
+            };
+            let mut code = return_false_if(format!("{a_length} != {b_length}"));
+            if is_memcmp_comparable(&context.lookup, r#type) {
+                code.push_str(&return_false_if(format!(
+                    "{a_length} != 0 && memcmp({a}, {b}, {a_length} * sizeof(*{a})) != 0"
+                )));
+            } else {
+                let index = format!("i{depth}");
+                code.push_str(&format!(
+                    "{indent}for (size_t {index} = 0; {index} < {a_length}; {index}++) {{\n{}{indent}}}\n",
+                    generate_equals_statements(
+                        context,
+                        r#type,
+                        (&format!("{a}[{index}]"), &format!("{b}[{index}]")),
+                        None,
+                        None,
+                        depth + 1
+                    )
+                ));
+            }
+            code
+        }
+        TypeIdentifier::UserDefined(identifier) => match context.lookup.get(&identifier.name) {
+            Some(Definition::Structure(structure)) => {
+                return_false_if(format!("!{}_equals(&{a}, &{b})", structure.name.name))
+            }
+            Some(Definition::Union(union)) => match discriminator {
+                Some(discriminator) => return_false_if(format!(
+                    "!{}_equals(&{a}, &{b}, (uint64_t)a->{discriminator})",
+                    union.name.name
+                )),
+                None => format!(
+                    "{indent}/* the member of a union without discriminator is unknown */\n"
+                ),
+            },
+            _ => return_false_if(format!("{a} != {b}")),
+        },
+        _ => return_false_if(format!("{a} != {b}")),
+    }
+}
+
+/// Generates the `<Struct>_equals` function enabled by [`CCodegenOptions::emit_equals`].
+fn generate_structure_equals_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+) -> String {
+    let name = &structure.name.name;
+    let mut statements = String::new();
+    for (i, field) in structure.fields.iter().enumerate() {
+        if field.reserved {
+            continue;
+        }
+        let field_name = &field.name.name;
+        // The discriminators select the members of unions to compare, so they are compared first
+        if let Some(discriminator) = discriminated_by(&field.attributes)
+            && !structure.fields[..i]
+                .iter()
+                .any(|preceding| preceding.name.name == discriminator)
+        {
+            statements.push_str(&format!(
+                "    if (a->{discriminator} != b->{discriminator}) {{\n        return false;\n    }}\n"
+            ));
+        }
+        let is_fat_pointer = context.options.fat_pointers
+            && matches!(
+                context.lookup.resolve(&field.r#type),
+                TypeIdentifier::DynamicArray { .. }
+            );
+        let (member, length) = if is_fat_pointer {
+            (format!("{field_name}.data"), format!("{field_name}.len"))
+        } else {
+            (field_name.clone(), dynamic_array_length_member(field_name))
+        };
+        statements.push_str(&generate_equals_statements(
+            context,
+            &field.r#type,
+            (&format!("a->{member}"), &format!("b->{member}")),
+            Some((&format!("a->{length}"), &format!("b->{length}"))),
+            discriminated_by(&field.attributes),
+            0,
+        ));
+    }
+    if statements.is_empty() {
+        statements.push_str("    (void)a;\n    (void)b;\n");
+    }
+    format!(
+        "{}{statements}    return true;\n}}\n\n",
+        context.begin_function(format!(
+            "bool {name}_equals(const {name}* a, const {name}* b)"
+        ))
+    )
+}
+
+/// Generates the function comparing the members of two unions selected by the discriminator,
+/// which is known to be the same for both.
+fn generate_union_equals_code(context: &CodegenContext, union: &UnionDefinition) -> String {
+    let name = &union.name.name;
+    let mut cases = String::new();
+    let mut push_case = |discriminator: u64, member: String, r#type: &TypeIdentifier| {
+        let statements = generate_equals_statements(
+            context,
+            r#type,
+            (&format!("a->{member}"), &format!("b->{member}")),
+            None,
+            None,
+            1,
+        );
+        if !statements.is_empty() {
+            cases.push_str(&format!(
+                "    case {discriminator}:\n{statements}        return true;\n"
+            ));
+        }
+    };
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue {
+                name: field_name,
+                r#type,
+                discriminator,
+            } => push_case(*discriminator, field_name.name.clone(), r#type),
+            UnionField::RangeOfValues {
+                name: field_name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => {
+                for i in *start_discriminator..=*end_discriminator {
+                    push_case(i, format!("{}_{}", field_name.name, i), r#type);
+                }
+            }
+        }
+    }
+
+    let mut code = format!(
+        "static bool {name}_equals(const {name}* a, const {name}* b, uint64_t discriminator) {{\n"
+    );
+    if cases.is_empty() {
+        code.push_str(
+            "    (void)a;\n    (void)b;\n    (void)discriminator;\n    return true;\n}\n\n",
+        );
+        return code;
+    }
+    code.push_str("    switch (discriminator) {\n");
+    code.push_str(&cases);
+    code.push_str("    default:\n        return true;\n    }\n}\n\n");
+    code
+}
+
+/// Generates the equality functions of all structures and unions, see
+/// [`CCodegenOptions::emit_equals`].
+pub fn generate_equals_code(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    generate_equals_code_in(&CodegenContext::new(protocol, options), protocol)
+}
+
+/// Same as [`generate_equals_code`], generating the functions within the given context.
+fn generate_equals_code_in(context: &CodegenContext, protocol: &Protocol) -> String {
+    let mut code = String::new();
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_equals_code(context, structure));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_equals_code(context, union));
+            }
+            Definition::Enumeration(_)
+            | Definition::Type(_)
+            | Definition::Flags(_)
+            | Definition::Constant(_) => {}
+        }
+    }
+    code
+}
+
+/// Returns whether a discriminator field can hold the given discriminator value, taking
+/// its `bits`/`bytes` attributes into account.
+fn discriminator_can_represent(
+    lookup: &DefinitionLookup,
+    discriminator: &StructureField,
+    value: u64,
+) -> bool {
+    let fits = |bits: u64| bits >= 64 || value < (1 << bits);
+    let size_in_bits = field_size_in_bits_from_attributes(&discriminator.attributes);
+
+    match lookup.resolve(&discriminator.r#type) {
+        TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => {
+                enumeration.contains_value(i128::from(value)) && size_in_bits.is_none_or(fits)
+            }
+            _ => false,
+        },
+        TypeIdentifier::Integer8
+        | TypeIdentifier::Integer16
+        | TypeIdentifier::Integer32
+        | TypeIdentifier::Integer64 => size_in_bits
+            .or(discriminator.r#type.builtin_size_in_bits())
+            .is_some_and(|bits| fits(bits - 1)),
+        TypeIdentifier::Float32
+        | TypeIdentifier::Float64
+        | TypeIdentifier::StaticArray { .. }
+        | TypeIdentifier::DynamicArray { .. } => false,
+        type_identifier => size_in_bits
+            .or(type_identifier.builtin_size_in_bits())
+            .is_some_and(fits),
+    }
+}
+
+/// Generates a setter of a single member of a discriminated union field, which keeps
+/// the discriminator consistent with the active member.
+fn generate_union_setter_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+    union_field: &StructureField,
+    discriminator: &StructureField,
+    member: &str,
+    member_type: &TypeIdentifier,
+    value: u64,
+) -> String {
+    let mapper = context.options.type_mapper();
+    let structure_name = &structure.name.name;
+    let union_field_name = &union_field.name.name;
+    let parameter = match member_type {
+        TypeIdentifier::Void => String::new(),
+        TypeIdentifier::StaticArray { .. } => format!(
+            ", const {}",
+            generate_declaration_code(member_type, "value", None, mapper)
+        ),
+        _ => format!(
+            ", {} value",
+            generate_type_identifier_code(member_type, mapper)
+        ),
+    };
+
+    let mut code = context.begin_function(format!(
+        "bool {structure_name}_set_{union_field_name}_{member}({structure_name}* w{parameter})"
+    ));
+    if discriminator_can_represent(&context.lookup, discriminator, value) {
+        let discriminator_type = c_type_override(&discriminator.attributes).map_or_else(
+            || generate_type_identifier_code(&discriminator.r#type, mapper),
+            str::to_string,
+        );
+        code.push_str(&format!(
+            "    w->{} = ({discriminator_type}){value};\n",
+            discriminator.name.name
+        ));
+        match member_type {
+            TypeIdentifier::Void => {}
+            TypeIdentifier::StaticArray { .. } => code.push_str(&format!(
+                "    memcpy(w->{union_field_name}.{member}, value, sizeof(w->{union_field_name}.{member}));\n"
+            )),
+            _ => code.push_str(&format!("    w->{union_field_name}.{member} = value;\n")),
+        }
+        code.push_str("    return true;\n}\n\n");
+    } else {
+        code.push_str("    (void)w;\n");
+        if !matches!(member_type, TypeIdentifier::Void) {
+            code.push_str("    (void)value;\n");
+        }
+        code.push_str("    return false;\n}\n\n");
+    }
+    code
+}
+
+/// Generates setters for all members of the discriminated union fields of a structure.
+fn generate_structure_union_setters_code(
+    context: &CodegenContext,
+    structure: &StructureDefinition,
+) -> String {
+    let mut code = String::new();
+    for field in &structure.fields {
+        let Some(discriminator) = discriminated_by(&field.attributes).and_then(|name| {
+            structure
+                .fields
+                .iter()
+                .find(|field| field.name.name == name)
+        }) else {
+            continue;
+        };
+        let TypeIdentifier::UserDefined(identifier) = context.lookup.resolve(&field.r#type) else {
+            continue;
+        };
+        let Some(Definition::Union(union)) = context.lookup.get(&identifier.name) else {
+            continue;
+        };
+
+        for union_field in &union.fields {
+            match union_field {
+                UnionField::SingleValue {
+                    name,
+                    r#type,
+                    discriminator: value,
+                } => code.push_str(&generate_union_setter_code(
+                    context,
+                    structure,
+                    field,
+                    discriminator,
+                    &name.name,
+                    r#type,
+                    *value,
+                )),
+                UnionField::RangeOfValues {
+                    name,
+                    r#type,
+                    start_discriminator,
+                    end_discriminator,
+                } => {
+                    for value in *start_discriminator..=*end_discriminator {
+                        code.push_str(&generate_union_setter_code(
+                            context,
+                            structure,
+                            field,
+                            discriminator,
+                            &format!("{}_{value}", name.name),
+                            r#type,
+                            value,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    code
+}
+
+/// Generates setters for all members of the discriminated union fields of the protocol.
+pub fn generate_union_setters_code(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    generate_union_setters_code_in(&CodegenContext::new(protocol, options), protocol)
+}
+
+/// Same as [`generate_union_setters_code`], generating the functions within the given context.
+fn generate_union_setters_code_in(context: &CodegenContext, protocol: &Protocol) -> String {
+    let mut code = String::new();
+    for definition in &protocol.definitions {
+        if let Definition::Structure(structure) = definition {
+            code.push_str(&generate_structure_union_setters_code(context, structure));
+        }
+    }
+    code
+}
+
+/// Generates a comment pointing at the meklang definition the following C code comes from.
+fn generate_source_ref_comment(definition: &Definition) -> String {
+    let name = definition.name();
+    match name.span.line {
+        0 => format!("/* meklang: {} */\n", name.name),
+        line => format!("/* meklang: {} (input line {line}) */\n", name.name),
+    }
+}
+
+/// Generates a `/** ... */` doc comment with the given text, one line of text per line
+/// of the comment, each line starting with the given indentation.
+fn generate_doc_comment(doc: &str, indent: &str) -> String {
+    let mut code = format!("{indent}/**\n");
+    for line in doc.lines() {
+        let line = line.replace("*/", "* /");
+        if line.is_empty() {
+            code.push_str(&format!("{indent} *\n"));
+        } else {
+            code.push_str(&format!("{indent} * {line}\n"));
+        }
+    }
+    code.push_str(&format!("{indent} */\n"));
+    code
+}
+
+/// Returns the top-level messages of the protocol with their wire identifiers. Messages are
+/// the structures not used by any other definition, as well as all definitions with an `id`
+/// attribute. Declared identifiers are used as they are, the remaining messages get sequential
+/// indices (in the order of declaration) which skip the declared ones.
+fn collect_message_ids(protocol: &Protocol) -> Vec<(String, u64)> {
+    let used_types: HashSet<String> = protocol
+        .definitions
+        .iter()
+        .flat_map(crate::ast::extract_definition_subtypes)
+        .collect();
+    let declared_ids: HashSet<u64> = protocol
+        .definitions
+        .iter()
+        .filter_map(Definition::id)
+        .collect();
+
+    let mut next_index = 0;
+    let mut message_ids = Vec::new();
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        let is_top_level_structure =
+            matches!(definition, Definition::Structure(_)) && !used_types.contains(name);
+        if !is_top_level_structure && definition.id().is_none() {
+            continue;
+        }
+
+        let id = definition.id().unwrap_or_else(|| {
+            while declared_ids.contains(&next_index) {
+                next_index += 1;
+            }
+            next_index += 1;
+            next_index - 1
+        });
+        message_ids.push((name.clone(), id));
+    }
+    message_ids
+}
+
+/// Generates the `MessageId` enumeration listing identifiers of all top-level messages,
+/// which can be used to dispatch messages received from the wire.
+pub fn generate_message_id_enumeration_code(protocol: &Protocol) -> String {
+    generate_prefixed_message_id_enumeration_code(protocol, "")
+}
+
+/// Same as [`generate_message_id_enumeration_code`] for a protocol whose names start with
+/// `prefix`, which is moved to the front of the enumeration and its constants.
+fn generate_prefixed_message_id_enumeration_code(protocol: &Protocol, prefix: &str) -> String {
+    let mut code = String::new();
+    code.push_str("typedef enum {\n");
+    for (name, id) in collect_message_ids(protocol) {
+        let name = name.strip_prefix(prefix).unwrap_or(&name);
+        code.push_str(&format!("    {prefix}MessageId_{name} = {id},\n"));
+    }
+    code.push_str(&format!("}} {prefix}MessageId;\n\n"));
+    code
+}
+
+/// Generates the initializer of a definition enabled by [`CCodegenOptions::emit_init`], if it
+/// has one. Type definitions and flags are initialized through the types they alias.
+fn generate_init_code(context: &CodegenContext, definition: &Definition) -> String {
+    match definition {
+        Definition::Structure(StructureDefinition { name, .. })
+        | Definition::Union(UnionDefinition { name, .. }) => format!(
+            "{}    memset(out, 0, sizeof(*out));\n}}\n\n",
+            context.begin_function(format!("void {0}_zero({0}* out)", name.name))
+        ),
+        Definition::Enumeration(enumeration) => {
+            let name = &enumeration.name.name;
+            let (first, _) = enumerators(enumeration).swap_remove(0);
+            format!(
+                "{}    return {name}_{first};\n}}\n\n",
+                context.begin_function(format!("{name} {name}_default(void)"))
+            )
+        }
+        Definition::Type(_) | Definition::Flags(_) | Definition::Constant(_) => String::new(),
+    }
+}
+
+/// Generates the type of a single definition, preceded by its source reference and doc comment
+/// when enabled in the options.
+fn generate_definition_code(
+    lookup: &DefinitionLookup,
+    definition: &Definition,
+    options: &CCodegenOptions,
+) -> String {
+    let mapper = options.type_mapper();
+    let mut code = String::new();
+    if options.emit_source_refs {
+        code.push_str(&generate_source_ref_comment(definition));
+    }
+    if let Some(doc) = definition.doc().filter(|_| options.emit_doc_comments) {
+        code.push_str(&generate_doc_comment(doc, ""));
+    }
+    match definition {
+        Definition::Enumeration(enumeration) => {
+            code.push_str(&generate_enumeration_code(enumeration, options));
+            if options.emit_enum_tables {
+                code.push_str(&generate_enumeration_tables_code(enumeration));
+            }
+        }
+        Definition::Structure(structure) => {
+            code.push_str(&generate_structure_code(lookup, structure, options));
+        }
+        Definition::Type(type_definition) => {
+            code.push_str(&generate_type_definition_code(type_definition, mapper));
+        }
+        Definition::Flags(flags) => {
+            code.push_str(&generate_flags_code(flags, mapper));
+        }
+        Definition::Union(union) => {
+            code.push_str(&generate_union_code(union, options));
+        }
+        Definition::Constant(constant) => {
+            code.push_str(&generate_constant_code(constant));
+        }
+    }
+    code
+}
+
+/// Returns the protocol with [`CCodegenOptions::prefix`] prepended to the names of all
+/// definitions and to all references to them, or the protocol itself when there is no prefix.
+fn prefixed_protocol<'a>(protocol: &'a Protocol, options: &CCodegenOptions) -> Cow<'a, Protocol> {
+    fn prefix_type_identifier(type_identifier: &mut TypeIdentifier, prefix: &str) {
+        match type_identifier {
+            TypeIdentifier::UserDefined(identifier) => {
+                identifier.name.insert_str(0, prefix);
+            }
+            TypeIdentifier::StaticArray {
+                r#type,
+                size_constant,
+                ..
+            } => {
+                if let Some(constant) = size_constant {
+                    constant.name.insert_str(0, prefix);
+                }
+                prefix_type_identifier(r#type, prefix);
+            }
+            TypeIdentifier::DynamicArray { r#type } => prefix_type_identifier(r#type, prefix),
+            _ => {}
+        }
+    }
+
+    let Some(prefix) = options.prefix.as_deref() else {
+        return Cow::Borrowed(protocol);
+    };
+    let mut protocol = protocol.clone();
+    for definition in &mut protocol.definitions {
+        match definition {
+            Definition::Enumeration(enumeration) => enumeration.name.name.insert_str(0, prefix),
+            Definition::Flags(flags) => flags.name.name.insert_str(0, prefix),
+            Definition::Constant(constant) => constant.name.name.insert_str(0, prefix),
+            Definition::Structure(structure) => {
+                structure.name.name.insert_str(0, prefix);
+                for field in &mut structure.fields {
+                    prefix_type_identifier(&mut field.r#type, prefix);
+                }
+            }
+            Definition::Union(union) => {
+                union.name.name.insert_str(0, prefix);
+                for field in &mut union.fields {
+                    match field {
+                        UnionField::SingleValue { r#type, .. }
+                        | UnionField::RangeOfValues { r#type, .. } => {
+                            prefix_type_identifier(r#type, prefix)
+                        }
+                    }
+                }
+            }
+            Definition::Type(type_definition) => {
+                type_definition.new_type.name.insert_str(0, prefix);
+                prefix_type_identifier(&mut type_definition.r#type, prefix);
+            }
+        }
+    }
+    Cow::Owned(protocol)
+}
+
+/// Generates the macro holding the version given by the `module` header of the protocol,
+/// if it has one.
+fn generate_version_code(protocol: &Protocol, prefix: &str) -> String {
+    protocol.version.map_or_else(String::new, |version| {
+        format!("#define {prefix}PROTOCOL_VERSION {version}\n\n")
+    })
+}
+
+/// Generates the C code of the protocol as a single file meant to be included as a header.
+/// Its functions are `static inline`, so that several sources of a program can include it;
+/// use [`generate_c_header`] and [`generate_c_source`] to define them only once instead.
+/// The code is wrapped in an include guard derived from the name of the module and the
+/// prefix, or preceded by `#pragma once` when the protocol has no name, so that it can be
+/// included twice.
+pub fn generate_c_code_with_options(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    generate_guarded_c_code(protocol, options, protocol.name.as_deref())
+}
+
+/// Wraps the code generated by [`generate_c_code_body`] in an include guard derived from the
+/// name, or precedes it by `#pragma once` without one.
+fn generate_guarded_c_code(
+    protocol: &Protocol,
+    options: &CCodegenOptions,
+    name: Option<&str>,
+) -> String {
+    let body = generate_c_code_body(protocol, options);
+    match name {
+        Some(name) => generate_header_code(
+            &format!("{}{name}", options.prefix.as_deref().unwrap_or_default()),
+            &[],
+            &body,
+        ),
+        None => format!("#pragma once\n\n{body}"),
+    }
+}
+
+/// Generates the types and functions of the single file C code, without an include guard.
+fn generate_c_code_body(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    let protocol = &*prefixed_protocol(protocol, options);
+    let prefix = options.prefix.as_deref().unwrap_or_default();
+    let context = CodegenContext {
+        linkage: "static inline ",
+        ..CodegenContext::new(protocol, options)
+    };
+    let mut code = String::new();
+    if !options.omit_stdint_include {
+        code.push_str("#include <stdint.h>\n#include <stdbool.h>\n");
+    }
+    if options.emit_encoders || options.emit_decoders || options.emit_wire_size {
+        code.push_str("#include <stddef.h>\n");
+    }
+    if options.emit_json_io {
+        code.push_str("#include <errno.h>\n");
+    }
+    if options.emit_decoders || options.emit_destructors || options.emit_json_io {
+        code.push_str("#include <stdlib.h>\n");
+    }
+    if options.emit_encoders
+        || options.emit_decoders
+        || options.emit_union_setters
+        || options.emit_init
+        || options.emit_equals
+        || options.emit_json_io
+    {
+        code.push_str("#include <string.h>\n");
+    }
+    if !code.is_empty() {
+        code.push('\n');
+    }
+    code.push_str(&generate_version_code(protocol, prefix));
+
+    if options.packed {
+        code.push_str(PACKED_BEGIN_CODE);
+    }
+    for definition in &protocol.definitions {
+        code.push_str(&generate_definition_code(
+            &context.lookup,
+            definition,
+            options,
+        ));
+    }
+    if options.packed {
+        code.push_str(PACKED_END_CODE);
+    }
+
+    if protocol
+        .definitions
+        .iter()
+        .any(|definition| definition.id().is_some())
+    {
+        code.push_str(&generate_prefixed_message_id_enumeration_code(
+            protocol, prefix,
+        ));
+    }
+
+    if options.emit_init {
+        for definition in &protocol.definitions {
+            code.push_str(&generate_init_code(&context, definition));
+        }
+    }
+
+    if options.emit_union_setters {
+        code.push_str(&generate_union_setters_code_in(&context, protocol));
+    }
+
+    if options.emit_encoders {
+        code.push_str(&generate_encoders_code_in(&context, protocol));
+        if options.emit_generic_macros {
+            code.push_str(&generate_prefixed_generic_macros_code(protocol, prefix));
+        }
+    }
+
+    if options.emit_wire_size {
+        code.push_str(&generate_wire_size_code_in(&context, protocol));
+    }
+
+    if options.emit_decoders {
+        code.push_str(&generate_decoders_code_in(&context, protocol));
+        if options.emit_streaming_decoders {
+            code.push_str(&generate_prefixed_stream_callbacks_code(&context.helpers));
+            code.push_str(&generate_streaming_decoders_code_in(&context, protocol));
+        }
+    }
+
+    if options.emit_destructors {
+        code.push_str(&generate_destructors_code_in(&context, protocol));
+    }
+
+    if options.emit_equals {
+        code.push_str(&generate_equals_code_in(&context, protocol));
+    }
+
+    if options.emit_json_io {
+        code.push_str(&generate_json_io_code_in(&context, protocol));
+    }
+    code
+}
+
+/// Generates a header declaring the types of the protocol and the functions defined by
+/// [`generate_c_source`]. Every definition gets a `/* --- <Type> --- */` section holding its
+/// type followed by the prototypes of its functions, in the order of the definitions.
+pub fn generate_c_header(protocol: &Protocol, options: &CCodegenOptions) -> String {
+    let protocol = &*prefixed_protocol(protocol, options);
+    let prefix = options.prefix.as_deref().unwrap_or_default();
+    let context = CodegenContext::new(protocol, options);
+    let mut includes = Vec::new();
+    if !options.omit_stdint_include {
+        includes.extend(["<stdint.h>".to_string(), "<stdbool.h>".to_string()]);
+    }
+    if options.emit_encoders || options.emit_decoders || options.emit_wire_size {
+        includes.push("<stddef.h>".to_string());
+    }
+
+    let mut body = generate_version_code(protocol, prefix);
+    if options.emit_decoders && options.emit_streaming_decoders {
+        body.push_str(&generate_prefixed_stream_callbacks_code(&options.helpers()));
+    }
+    if options.packed {
+        body.push_str(PACKED_BEGIN_CODE);
+    }
+    for definition in &protocol.definitions {
+        body.push_str(&format!("/* --- {} --- */\n", definition.name().name));
+        body.push_str(&generate_definition_code(
+            &context.lookup,
+            definition,
+            options,
+        ));
+        // The functions are generated only to record their signatures, as the source
+        // defines them
+        let mut functions = String::new();
+        if options.emit_init {
+            functions.push_str(&generate_init_code(&context, definition));
+        }
+        if let Definition::Structure(structure) = definition {
+            if options.emit_union_setters {
+                functions.push_str(&generate_structure_union_setters_code(&context, structure));
+            }
+            if options.emit_encoders {
+                functions.push_str(&generate_structure_encoder_code(&context, structure));
+            }
+            if options.emit_wire_size {
+                functions.push_str(&generate_structure_wire_size_code(&context, structure));
+            }
+            if options.emit_decoders {
+                functions.push_str(&generate_structure_decoder_code(&context, structure));
+                if options.emit_field_getters {
+                    functions.push_str(&generate_structure_field_getters_code(&context, structure));
+                }
+                if options.emit_streaming_decoders {
+                    functions.push_str(&generate_structure_streaming_decoder_code(
+                        &context, structure,
+                    ));
+                }
+            }
+            if options.emit_destructors {
+                functions.push_str(&generate_structure_destructor_code(&context, structure));
+            }
+            if options.emit_equals {
+                functions.push_str(&generate_structure_equals_code(&context, structure));
+            }
+            if options.emit_json_io {
+                functions.push_str(&generate_structure_json_code(&context, structure));
+            }
+        }
+        let signatures = context.signatures.take();
+        if !signatures.is_empty() {
+            for signature in signatures {
+                body.push_str(&format!("{signature};\n"));
+            }
+            body.push('\n');
+        }
+    }
+    if options.packed {
+        body.push_str(PACKED_END_CODE);
+    }
+
+    if protocol
+        .definitions
+        .iter()
+        .any(|definition| definition.id().is_some())
+    {
+        body.push_str("/* --- MessageId --- */\n");
+        body.push_str(&generate_prefixed_message_id_enumeration_code(
+            protocol, prefix,
+        ));
+    }
+    if options.emit_encoders && options.emit_generic_macros {
+        body.push_str(&generate_prefixed_generic_macros_code(protocol, prefix));
+    }
+    generate_header_code(
+        &format!("{prefix}{}", protocol.name.as_deref().unwrap_or("protocol")),
+        &includes,
+        &body,
+    )
+}
+
+/// Generates the source defining the functions declared by [`generate_c_header`], which is
+/// included as `header_name`.
+pub fn generate_c_source(
+    protocol: &Protocol,
+    options: &CCodegenOptions,
+    header_name: &str,
+) -> String {
+    let protocol = &*prefixed_protocol(protocol, options);
+    let context = CodegenContext::new(protocol, options);
+    let mut code = format!("#include \"{header_name}\"\n");
+    if options.emit_json_io {
+        code.push_str("#include <errno.h>\n");
+    }
+    if options.emit_decoders || options.emit_destructors || options.emit_json_io {
+        code.push_str("#include <stdlib.h>\n");
+    }
+    if options.emit_encoders
+        || options.emit_decoders
+        || options.emit_union_setters
+        || options.emit_init
+        || options.emit_equals
+        || options.emit_json_io
+    {
+        code.push_str("#include <string.h>\n");
+    }
+    code.push('\n');
+    if options.emit_init {
+        for definition in &protocol.definitions {
+            code.push_str(&generate_init_code(&context, definition));
+        }
+    }
+    if options.emit_union_setters {
+        code.push_str(&generate_union_setters_code_in(&context, protocol));
+    }
+    if options.emit_encoders {
+        code.push_str(&generate_encoders_code_in(&context, protocol));
+    }
+    if options.emit_wire_size {
+        code.push_str(&generate_wire_size_code_in(&context, protocol));
+    }
+    if options.emit_decoders {
+        code.push_str(&generate_decoders_code_in(&context, protocol));
+        if options.emit_streaming_decoders {
+            code.push_str(&generate_streaming_decoders_code_in(&context, protocol));
+        }
+    }
+    if options.emit_destructors {
+        code.push_str(&generate_destructors_code_in(&context, protocol));
+    }
+    if options.emit_equals {
+        code.push_str(&generate_equals_code_in(&context, protocol));
+    }
+    if options.emit_json_io {
+        code.push_str(&generate_json_io_code_in(&context, protocol));
+    }
+    code
+}
+
+/// Generates the C code of the protocol with default options, wrapped in an include guard
+/// derived from the name of its module, or preceded by `#pragma once` when it has none, so
+/// that it can be included twice. Use [`generate_c_code_named`] for a portable include guard
+/// of another name.
+pub fn generate_c_code(protocol: &Protocol) -> String {
+    generate_c_code_with_options(protocol, &CCodegenOptions::default())
+}
+
+/// Same as [`generate_c_code`], but wraps the code in a `MEKSMITH_<NAME>_H` include guard
+/// derived from the given name, e.g. the name of the generated file.
+pub fn generate_c_code_named(protocol: &Protocol, name: &str) -> String {
+    generate_guarded_c_code(protocol, &CCodegenOptions::default(), Some(name))
+}
+
+pub fn generate_c_code_from_string_with_options(
+    input: &str,
+    options: &CCodegenOptions,
+) -> Result<String, MeksmithError> {
+    options.validate()?;
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    options.validate_protocol(&sorted)?;
+    Ok(generate_c_code_with_options(&sorted, options))
+}
+
+pub fn generate_c_code_from_string(input: &str) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok(generate_c_code(&sorted))
+}
+
+/// Same as [`generate_c_code_from_string`], but also returns warnings found in the protocol.
+/// Errors still prevent the code from being generated.
+pub fn generate_c_code_from_string_with_diagnostics(
+    input: &str,
+) -> Result<(String, Vec<Diagnostic>), MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, warnings) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok((generate_c_code(&sorted), warnings))
+}
+
+/// Name of the header written by [`generate_c_files`] which includes the headers of all
+/// definitions.
+pub const UMBRELLA_HEADER_NAME: &str = "meksmith.h";
+
+/// Returns the include guard of the header of the given name. Characters of the name which
+/// cannot appear in a macro name are replaced with underscores.
+fn header_guard(name: &str) -> String {
+    format!(
+        "MEKSMITH_{}_H",
+        name.to_uppercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    )
+}
+
+/// Wraps the code of a header in an include guard derived from the name, see
+/// [`header_guard`], preceded by the given includes.
+fn generate_header_code(name: &str, includes: &[String], body: &str) -> String {
+    let guard = header_guard(name);
+    let mut code = format!("#ifndef {guard}\n#define {guard}\n\n");
+    for include in includes {
+        code.push_str(&format!("#include {include}\n"));
+    }
+    if !includes.is_empty() {
+        code.push('\n');
+    }
+    code.push_str(body);
+    code.push_str(&format!("#endif /* {guard} */\n"));
+    code
+}
+
+/// Checks that the headers written by [`generate_c_files`] neither overwrite each other, even
+/// on file systems ignoring the case of file names, nor share an include guard, which would
+/// make including one of them skip the other.
+fn validate_c_files(protocol: &Protocol) -> Result<(), MeksmithError> {
+    let umbrella_name = protocol.name.as_deref().unwrap_or("protocol");
+    let mut headers = vec![(
+        "the umbrella header".to_string(),
+        UMBRELLA_HEADER_NAME.to_string(),
+        header_guard(umbrella_name),
+    )];
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        headers.push((
+            format!("definition {name}"),
+            format!("{name}.h"),
+            header_guard(name),
+        ));
+    }
+
+    let mut errors = Vec::new();
+    for (index, (description, file_name, guard)) in headers.iter().enumerate() {
+        for (other_description, other_file_name, other_guard) in &headers[..index] {
+            if file_name.eq_ignore_ascii_case(other_file_name) {
+                errors.push(format!(
+                    "Header {file_name} of {description} clashes with header \
+                     {other_file_name} of {other_description}"
+                ));
+            } else if guard == other_guard {
+                errors.push(format!(
+                    "Include guard {guard} of {description} clashes with the one of \
+                     {other_description}"
+                ));
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(MeksmithError::Validation(errors))
+    }
+}
+
+/// Writes a `<Definition>.h` header for every definition to `out_dir`, including only the
+/// headers of the definitions it refers to, so that a change of a definition recompiles only
+/// the code depending on it. The [`UMBRELLA_HEADER_NAME`] header includes all of them.
+/// Definitions must be sorted by dependencies. Returns the paths of all written files, or an
+/// error without writing any file when two headers would clash, see [`validate_c_files`].
+pub fn generate_c_files(
+    protocol: &Protocol,
+    out_dir: &Path,
+) -> Result<Vec<PathBuf>, MeksmithError> {
+    validate_c_files(protocol)?;
+    std::fs::create_dir_all(out_dir)
+        .map_err(|e| MeksmithError::io("Failed to create directory", e))?;
+    let lookup = DefinitionLookup::new(protocol);
+    let options = CCodegenOptions::default();
+    let write = |file_name: String, code: String| {
+        let path = out_dir.join(file_name);
+        std::fs::write(&path, code).map_err(|e| MeksmithError::io("Failed to write to file", e))?;
+        Ok::<_, MeksmithError>(path)
+    };
+
+    let mut paths = Vec::new();
+    let mut umbrella_includes = vec![];
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        let mut includes = vec!["<stdint.h>".to_string(), "<stdbool.h>".to_string()];
+        let dependencies: BTreeSet<String> = crate::ast::extract_definition_subtypes(definition)
+            .into_iter()
+            .collect();
+        includes.extend(
+            dependencies
+                .iter()
+                .map(|dependency| format!("\"{dependency}.h\"")),
+        );
+        let body = generate_definition_code(&lookup, definition, &options);
+        paths.push(write(
+            format!("{name}.h"),
+            generate_header_code(name, &includes, &body),
+        )?);
+        umbrella_includes.push(format!("\"{name}.h\""));
+    }
+
+    let mut body = generate_version_code(protocol, "");
+    if protocol
+        .definitions
+        .iter()
+        .any(|definition| definition.id().is_some())
+    {
+        body.push_str(&generate_message_id_enumeration_code(protocol));
+    }
+    paths.push(write(
+        UMBRELLA_HEADER_NAME.to_string(),
+        generate_header_code(
+            protocol.name.as_deref().unwrap_or("protocol"),
+            &umbrella_includes,
+            &body,
+        ),
+    )?);
+    Ok(paths)
+}
+
+pub fn generate_from_file(file_path: &str) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_from_file_to_ast(file_path)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok(generate_c_code(&sorted))
+}
+
+pub fn generate_from_file_to_file(
+    input_file_path: &str,
+    output_file_path: &str,
+) -> Result<(), MeksmithError> {
+    let protocol = crate::parse_protocol_from_file_to_ast(input_file_path)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    // The name of the module takes precedence over the name of the file
+    let name = sorted.name.as_deref().unwrap_or_else(|| {
+        Path::new(output_file_path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("protocol")
+    });
+    let c_code = generate_c_code_named(&sorted, name);
+    std::fs::write(output_file_path, c_code)
+        .map_err(|e| MeksmithError::io("Failed to write to file", e))?;
+    Ok(())
+}
+
+/// Writes the protocol defined in the input file as a header declaring its types and functions
+/// (see [`generate_c_header`]) and a source defining the encoders and decoders (see
+/// [`generate_c_source`]), which includes the header by its file name.
+pub fn generate_from_file_to_files(
+    input_file_path: &str,
+    header_path: &str,
+    source_path: &str,
+) -> Result<(), MeksmithError> {
+    let protocol = crate::parse_protocol_from_file_to_ast(input_file_path)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    let options = CCodegenOptions {
+        emit_encoders: true,
+        emit_decoders: true,
+        ..Default::default()
+    };
+    let header_name = Path::new(header_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| {
+            MeksmithError::io(
+                format!("Invalid header path {header_path}"),
+                std::io::ErrorKind::InvalidInput.into(),
+            )
+        })?;
+    std::fs::write(header_path, generate_c_header(&sorted, &options))
+        .map_err(|e| MeksmithError::io("Failed to write to file", e))?;
+    std::fs::write(
+        source_path,
+        generate_c_source(&sorted, &options, header_name),
+    )
+    .map_err(|e| MeksmithError::io("Failed to write to file", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempfile::NamedTempFile;
+
+    static INPUT_FILE_CONTENT: &str = r#"
+using BuiltInType = int32;
+using UserDefinedType = MyEnum;
+using StaticArrayType = uint32[10];
+using DynamicArrayType = byte[];
+
+enum MyEnum {
+    Value = 1;
+    Range = 2..5;
+    RangeOneValue = 6..6;
+};
+
+using my_enum_alias_t = MyEnum;
+
+struct MyStruct {
+    field1: int32;
+    field2: MyEnum;
+    field3: uint32[10];
+    field4: byte[];
+    field5: my_enum_alias_t;
+    field6: MyEnum[2];
+    builtin1: int8;
+    builtin2: int16;
+    builtin3: int32;
+    builtin4: int64;
+    builtin5: uint8;
+    builtin6: uint16;
+    builtin7: uint32;
+    builtin8: uint64;
+    builtin9: float32;
+    builtin10: float64;
+    builtin11: bit;
+    builtin12: byte;
+};
+
+union MyUnion {
+    0 => field1: bit;
+    1 => field2: MyEnum;
+    2 => field3: uint64[10];
+    3 => field4: MyStruct;
+    4..6 => reserved: uint16;
+    7..8 => static_array: uint16[10];
+};
+"#;
+
+    static EXPECTED_C_OUTPUT: &str = r#"#pragma once
+
+#include <stdint.h>
+#include <stdbool.h>
+
+typedef int32_t BuiltInType;
+
+typedef enum {
+    MyEnum_Value = 1,
+    MyEnum_Range_2 = 2,
+    MyEnum_Range_3 = 3,
+    MyEnum_Range_4 = 4,
+    MyEnum_Range_5 = 5,
+    MyEnum_RangeOneValue = 6,
+} MyEnum;
+
+typedef MyEnum UserDefinedType;
+
+typedef uint32_t StaticArrayType[10];
+
+typedef uint8_t* DynamicArrayType;
+
+typedef MyEnum my_enum_alias_t;
+
+typedef struct {
+    int32_t field1;
+    MyEnum field2;
+    uint32_t field3[10];
+    uint8_t* field4;
+    my_enum_alias_t field5;
+    MyEnum field6[2];
+    int8_t builtin1;
+    int16_t builtin2;
+    int32_t builtin3;
+    int64_t builtin4;
+    uint8_t builtin5;
+    uint16_t builtin6;
+    uint32_t builtin7;
+    uint64_t builtin8;
+    float builtin9;
+    double builtin10;
+    bool builtin11;
+    uint8_t builtin12;
+} MyStruct;
+
+typedef union {
+    bool field1;
+    MyEnum field2;
+    uint64_t field3[10];
+    MyStruct field4;
+    uint16_t reserved_4;
+    uint16_t reserved_5;
+    uint16_t reserved_6;
+    uint16_t static_array_7[10];
+    uint16_t static_array_8[10];
+} MyUnion;
+
+"#;
+
+    #[test]
+    fn test_generate_c_code_from_string() {
+        let input = INPUT_FILE_CONTENT;
+        let output = generate_c_code_from_string(input).unwrap();
+        assert_eq!(output, EXPECTED_C_OUTPUT);
+    }
+
+    #[rstest]
+    #[case::name("ping_pong", "MEKSMITH_PING_PONG_H")]
+    #[case::name_with_dashes("can-bus.v2", "MEKSMITH_CAN_BUS_V2_H")]
+    fn test_generate_c_code_named(#[case] name: &str, #[case] guard: &str) {
+        let protocol = crate::parse_protocol_to_ast("struct Data { length: uint16; };").unwrap();
+        let output = generate_c_code_named(&protocol, name);
+        assert!(output.starts_with(&format!(
+            "#ifndef {guard}\n#define {guard}\n\n#include <stdint.h>\n"
+        )));
+        assert!(output.ends_with(&format!("}} Data;\n\n#endif /* {guard} */\n")));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_module_header() {
+        let output = generate_c_code_from_string(
+            "module ping_pong version 3; struct Data { length: uint16; };",
+        )
+        .unwrap();
+        assert!(output.starts_with(
+            "#ifndef MEKSMITH_PING_PONG_H\n#define MEKSMITH_PING_PONG_H\n\n\
+             #include <stdint.h>\n#include <stdbool.h>\n\n#define PROTOCOL_VERSION 3\n\n"
+        ));
+        assert!(output.ends_with("#endif /* MEKSMITH_PING_PONG_H */\n"));
+
+        let options = CCodegenOptions {
+            prefix: Some("acme_".to_string()),
+            ..Default::default()
+        };
+        let protocol = crate::parse_protocol_to_ast(
+            "module ping_pong version 3; struct Data { length: uint16; };",
+        )
+        .unwrap();
+        let header = generate_c_header(&protocol, &options);
+        assert!(header.starts_with("#ifndef MEKSMITH_ACME_PING_PONG_H\n"));
+        assert!(header.contains("#define acme_PROTOCOL_VERSION 3\n"));
+    }
+
+    #[test]
+    fn test_generate_c_code_without_ids_has_no_message_id_enumeration() {
+        let output = generate_c_code_from_string(INPUT_FILE_CONTENT).unwrap();
+        assert!(!output.contains("MessageId"));
+    }
+
+    #[test]
+    fn test_generate_message_id_enumeration_code_with_sequential_indices() {
+        let ping_pong = include_str!("../examples/data/ping-pong.mek");
+        let protocol = crate::parse_protocol_to_ast(ping_pong).unwrap();
+        assert_eq!(
+            generate_message_id_enumeration_code(&protocol),
+            "typedef enum {\n    MessageId_Message = 0,\n} MessageId;\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_with_declared_ids() {
+        let input = r#"
+[id=0x10] struct Ping { sequence_number: uint32; };
+[id=0x20] struct Pong { sequence_number: uint32; };
+struct Unnumbered { field: uint8; };
+struct Nested { ping: Ping; };
+"#;
+        let output = generate_c_code_from_string(input).unwrap();
+        assert!(output.ends_with(
+            r#"typedef enum {
+    MessageId_Ping = 16,
+    MessageId_Pong = 32,
+    MessageId_Unnumbered = 0,
+    MessageId_Nested = 1,
+} MessageId;
+
+"#
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_circular_dependency() {
+        let result = generate_c_code_from_string("struct A { b: B; }; struct B { a: A; };");
+        assert!(matches!(
+            result,
+            Err(MeksmithError::CircularDependency(name)) if name == "A"
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_duplicated_ids() {
+        let input = r#"
+[id=1] struct Ping { sequence_number: uint32; };
+[id=1] struct Pong { sequence_number: uint32; };
+"#;
+        assert_eq!(
+            generate_c_code_from_string(input).map_err(|error| error.to_string()),
+            Err("Validation failed. Errors: Id 1 is used by both Ping and Pong".to_string())
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_from_string_with_diagnostics() {
+        let input = r#"
+enum Unused { a = 1; };
+struct Message { field: uint8; };
+"#;
+        let (output, diagnostics) = generate_c_code_from_string_with_diagnostics(input).unwrap();
+        assert_eq!(output, generate_c_code_from_string(input).unwrap());
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::warning(
+                "Definition Unused is never used".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_from_string_with_diagnostics_fails_on_errors() {
+        let input = r#"
+[id=1] struct Ping { sequence_number: uint32; };
+[id=1] struct Pong { sequence_number: uint32; };
+"#;
+        assert_eq!(
+            generate_c_code_from_string_with_diagnostics(input).map_err(|error| error.to_string()),
+            Err("Validation failed. Errors: Id 1 is used by both Ping and Pong".to_string())
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_with_c_type_override() {
+        let input = r#"
+struct Buffer {
+    [c_type="size_t"] capacity: uint32;
+    [c_type="char"] name: byte[16];
+    [c_type="char"] data: byte[];
+    flags: uint8;
+};
+"#;
+        let output = generate_c_code_from_string(input).unwrap();
+        assert!(output.contains(
+            "typedef struct {\n    size_t capacity;\n    char name[16];\n    char* data;\n    uint8_t flags;\n} Buffer;\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_c_type_override_keeps_wire_size() {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(
+            "struct Buffer { [c_type=\"size_t\"] capacity: uint32; };",
+            &options,
+        )
+        .unwrap();
+        assert!(output.contains("meksmith_write_bits(writer, (uint64_t)in->capacity, 32)"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_multi_dimensional_arrays() {
+        let input = r#"
+const N = 3;
+using Grid = int32[2][N];
+struct Message {
+    matrix: int32[4][4];
+    rows: byte[][8];
+    grid: Grid;
+};
+union Payload { 0 => cells: int16[2][2]; 1..2 => rows: byte[][3]; };
+"#;
+        let output = generate_c_code_from_string(input).unwrap();
+        assert!(output.contains("typedef int32_t Grid[2][N];\n"));
+        assert!(output.contains(
+            "typedef struct {\n    int32_t matrix[4][4];\n    uint8_t (*rows)[8];\n    Grid grid;\n} Message;\n"
+        ));
+        assert!(output.contains(
+            "typedef union {\n    int16_t cells[2][2];\n    uint8_t (*rows_1)[3];\n    uint8_t (*rows_2)[3];\n} Payload;\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_text() {
+        let input = "const NOTE_LEN = 8; struct Message { name: string[16]; note: string[NOTE_LEN]; text: string; initial: char; };";
+        let output = generate_c_code_from_string(input).unwrap();
+        assert!(output.contains(
+            "typedef struct {\n    char name[16];\n    char note[NOTE_LEN];\n    char* text;\n    char initial;\n} Message;\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_decodes_null_terminated_text() {
+        let options = CCodegenOptions {
+            emit_decoders: true,
+            ..Default::default()
+        };
+        let output =
+            generate_c_code_from_string_with_options("struct Message { text: string; };", &options)
+                .unwrap();
+        assert!(output.contains(
+            "        out->text = malloc((length + 1) * sizeof(*out->text));\n        if (out->text == NULL) {\n"
+        ));
+        assert!(output.contains("                out->text[i0] = (char)value;\n"));
+        assert!(output.contains("        out->text[length] = '\\0';\n    }\n"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_reserved_fields() {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            emit_equals: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(
+            "struct Header { version: uint8; _reserved: uint8[3]; [bits=4] _padding: uint8; [bits=4] flags: uint8; };",
+            &options,
+        )
+        .unwrap();
+        assert!(output.contains(
+            "    for (size_t i0 = 0; i0 < 3; i0++) {\n        if (!meksmith_write_bits(writer, 0, 8)) {\n"
+        ));
+        assert!(output.contains(
+            "    if (!meksmith_write_bits(writer, 0, 4)) {\n        return false;\n    }\n"
+        ));
+        assert!(output.contains(
+            "            if (!meksmith_read_bits(reader, &padding, 8)) {\n                return false;\n            }\n        }\n    }\n    \
+             memset(out->_reserved, 0, sizeof(out->_reserved));\n"
+        ));
+        assert!(output.contains("    out->_padding = 0;\n"));
+        assert!(output.contains(
+            "bool Header_equals(const Header* a, const Header* b) {\n    \
+             if (a->version != b->version) {\n        return false;\n    }\n    \
+             if (a->flags != b->flags) {\n        return false;\n    }\n    return true;\n}\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_union_setters_code() {
+        let ping_pong = include_str!("../examples/data/ping-pong.mek");
+        let options = CCodegenOptions {
+            emit_union_setters: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(ping_pong, &options).unwrap();
+        assert!(output.contains(
+            r#"bool Message_set_message_pong(Message* w, Pong value) {
+    w->message_type = (MessageType)1;
+    w->message.pong = value;
+    return true;
+}
+"#
+        ));
+        assert!(output.contains("bool Message_set_message_ping(Message* w, Ping value) {\n"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_singleton_range_union_field() {
+        let output =
+            generate_c_code_from_string("union Body { 0..1 => a: uint8; 6..6 => x: uint16; };")
+                .unwrap();
+        assert!(output.contains(
+            "typedef union {\n    uint8_t a_0;\n    uint8_t a_1;\n    uint16_t x;\n} Body;\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_void_union_fields() {
+        let input = r#"
+union Reply { 0 => ack: void; 1 => error: uint16; 2..3 => reserved: void; };
+union Signal { 0 => none: void; };
+struct Message { kind: uint8; [discriminated_by=kind] reply: Reply; };
+"#;
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_union_setters: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains("typedef union {\n    uint16_t error;\n} Reply;\n"));
+        assert!(output.contains("typedef union {\n    uint8_t _void;\n} Signal;\n"));
+        assert!(output.contains(
+            "bool Message_set_reply_ack(Message* w) {\n    w->kind = (uint8_t)0;\n    return true;\n}\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_union_setters_code_with_unrepresentable_discriminator() {
+        let input = r#"
+enum Kind { a = 0; b = 1; };
+union Payload { 0 => a: uint8; 1 => b: uint16[2]; 2 => c: uint8; 3..4 => d: bit; };
+struct Message { [bits=2] kind: uint8; [discriminated_by=kind] payload: Payload; };
+struct Typed { kind: Kind; [discriminated_by=kind] payload: Payload; };
+"#;
+        let protocol = crate::parse_protocol_to_ast(input).unwrap();
+        let code = generate_union_setters_code(&protocol, &CCodegenOptions::default());
+        assert!(code.contains(
+            r#"bool Message_set_payload_b(Message* w, const uint16_t value[2]) {
+    w->kind = (uint8_t)1;
+    memcpy(w->payload.b, value, sizeof(w->payload.b));
+    return true;
+}
+"#
+        ));
+        assert!(code.contains(
+            "bool Message_set_payload_d_3(Message* w, bool value) {\n    w->kind = (uint8_t)3;\n"
+        ));
+        assert!(code.contains(
+            r#"bool Typed_set_payload_c(Typed* w, uint8_t value) {
+    (void)w;
+    (void)value;
+    return false;
+}
+"#
+        ));
+        assert!(code.contains(
+            "bool Typed_set_payload_a(Typed* w, uint8_t value) {\n    w->kind = (Kind)0;\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_source_refs() {
+        let ping_pong = include_str!("../examples/data/ping-pong.mek");
+        let options = CCodegenOptions {
+            emit_source_refs: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(ping_pong, &options).unwrap();
+        assert!(output.contains("/* meklang: MessageType (input line 12) */\ntypedef enum {\n"));
+        assert!(output.contains("/* meklang: Message (input line 7) */\ntypedef struct {\n"));
+        assert!(
+            output.contains(
+                "/* meklang: IpAddress (input line 28) */\ntypedef uint8_t IpAddress[4];\n"
+            )
+        );
+        assert!(
+            !generate_c_code_from_string(ping_pong)
+                .unwrap()
+                .contains("meklang:")
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_with_flags() {
+        let input = r#"
+flags Permissions : uint16 { read = 0x1; write = 0x2; admin = 0x100; };
+struct File { permissions: Permissions; };
+"#;
+        let output = generate_c_code_from_string(input).unwrap();
+        assert!(output.contains(
+            r#"typedef uint16_t Permissions;
+#define Permissions_read ((Permissions)0x1u)
+#define Permissions_write ((Permissions)0x2u)
+#define Permissions_admin ((Permissions)0x100u)
+#define Permissions_has(flags, flag) (((flags) & (flag)) == (flag))
+#define Permissions_set(flags, flag) ((flags) |= (flag))
+"#
+        ));
+        assert!(output.contains("    Permissions permissions;\n"));
+    }
+
+    #[test]
+    fn test_generate_c_code_encodes_flags_as_underlying_type() {
+        let input =
+            "flags Permissions : uint8 { read = 0x1; }; struct File { permissions: Permissions; };";
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains("meksmith_write_bits(writer, (uint64_t)in->permissions, 8)"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_bitfields() {
+        let input = r#"
+using Counter = uint32;
+enum Kind { a = 0; };
+struct Header {
+    [bits=5] myField: uint8;
+    [bits=12] offset: int32;
+    [bits=20] count: Counter;
+    [bits=2] kind: Kind;
+    [bits=3, c_type="unsigned int"] raw: uint8;
+    plain: uint16;
+};
+"#;
+        let output = generate_c_code_from_string(input).unwrap();
+        assert!(output.contains(
+            "typedef struct {\n    uint8_t myField : 5;\n    int16_t offset : 12;\n    uint32_t count : 20;\n    Kind kind;\n    unsigned int raw;\n    uint16_t plain;\n} Header;\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_bits_exceeding_field_type() {
+        let result = generate_c_code_from_string("struct Header { [bits=9] myField: uint8; };");
+        assert_eq!(
+            result.map_err(|error| error.to_string()),
+            Err(
+                "Validation failed. Errors: Field myField in Header has 9 bits, which exceeds \
+                 the 8 bits of its type"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_aligns_bytes_fields_to_byte() {
+        let input = "struct Header { [bits=3] version: uint8; [bytes=1] flags: uint8; };";
+        let alignment = "    if (!meksmith_write_bits(writer, 0, (8 - writer->bit_offset % 8) % 8)) {\n        return false;\n    }\n";
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            align_bytes_fields_to_byte: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains(&format!(
+            "{alignment}    if (!meksmith_write_bits(writer, (uint64_t)in->flags, 8)) {{\n"
+        )));
+        assert_eq!(output.matches(alignment).count(), 1);
+
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(!output.contains(alignment));
+    }
+
+    #[rstest]
+    #[case::static_array("using Frames = Frame[8];", "typedef Frame Frames[8];\n")]
+    #[case::dynamic_array("using List = Frame[];", "typedef Frame* List;\n")]
+    fn test_generate_type_definition_of_array_of_user_defined_type(
+        #[case] type_definition: &str,
+        #[case] expected: &str,
+    ) {
+        let input = format!("struct Frame {{ data: uint8; }};\n{type_definition}");
+        let output = generate_c_code_from_string(&input).unwrap();
+        assert!(output.contains(&format!(
+            "typedef struct {{\n    uint8_t data;\n}} Frame;\n\n{expected}"
+        )));
+    }
+
+    #[derive(Debug)]
+    struct FixedPointMapper;
+
+    impl TypeMapper for FixedPointMapper {
+        fn map_builtin(&self, type_identifier: &TypeIdentifier) -> String {
+            match type_identifier {
+                TypeIdentifier::Float32 => "fixed16_16_t".to_string(),
+                _ => DefaultCMapper.map_builtin(type_identifier),
+            }
+        }
+    }
+
+    #[rstest]
+    #[case::fits_in_byte(
+        "enum Kind { a = 0; b = 255; };",
+        "typedef enum {\n    Kind_a = 0,\n    Kind_b = 255,\n} Kind;\n"
+    )]
+    #[case::range_end(
+        "enum Kind { a = 0; b = 250..256; };",
+        "typedef uint16_t Kind;\n#define Kind_a ((Kind)0u)\n#define Kind_b_250 ((Kind)250u)\n"
+    )]
+    #[case::large_value(
+        "enum Kind { a = 1; b = 0x1FFFF; };",
+        "typedef uint32_t Kind;\n#define Kind_a ((Kind)1u)\n#define Kind_b ((Kind)131071u)\n\n"
+    )]
+    #[case::wide_value(
+        "enum Kind { a = 0x100000000; };",
+        "typedef uint64_t Kind;\n#define Kind_a ((Kind)4294967296u)\n\n"
+    )]
+    fn test_generate_enumeration_code_with_backing_type(
+        #[case] input: &str,
+        #[case] expected: &str,
+    ) {
+        let output = generate_c_code_from_string(input).unwrap();
+        assert!(output.contains(expected), "{output}");
+    }
+
+    #[test]
+    fn test_generate_c_code_with_discriminated_union() {
+        let input = r#"
+union Payload { 0 => ping: uint8; 1 => pong: uint16; };
+struct Message { kind: uint8; [discriminated_by=kind] payload: Payload; };
+"#;
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains(
+            "typedef struct {\n    uint8_t kind;\n    Payload payload; /* selected by kind */\n} Message;\n"
+        ));
+        assert!(output.contains("Payload_encode_bits(&in->payload, (uint64_t)in->kind, writer)"));
+        assert!(output.contains("Payload_decode_bits(&out->payload, (uint64_t)out->kind, reader)"));
+        assert!(output.contains("    switch (discriminator) {\n    case 0:\n"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_custom_type_mapper() {
+        let input = r#"
+using Samples = float32[4];
+union Reading { 0 => value: float32; 1 => raw: uint16; };
+struct Sensor {
+    gain: float32;
+    history: float32[];
+    kind: uint8;
+    [discriminated_by=kind] reading: Reading;
+};
+"#;
+        let options = CCodegenOptions {
+            emit_union_setters: true,
+            type_mapper: Some(Arc::new(FixedPointMapper)),
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains("typedef fixed16_16_t Samples[4];\n"));
+        assert!(output.contains("    fixed16_16_t value;\n    uint16_t raw;\n"));
+        assert!(output.contains("    fixed16_16_t gain;\n    fixed16_16_t* history;\n"));
+        assert!(
+            output.contains("bool Sensor_set_reading_value(Sensor* w, fixed16_16_t value) {\n")
+        );
+        assert!(!output.contains("float"));
+    }
+
+    #[derive(Debug)]
+    struct ProjectTypesMapper;
+
+    impl TypeMapper for ProjectTypesMapper {
+        fn map_builtin(&self, type_identifier: &TypeIdentifier) -> String {
+            match type_identifier {
+                TypeIdentifier::Integer8 => "s8_t".to_string(),
+                TypeIdentifier::Integer16 => "s16_t".to_string(),
+                TypeIdentifier::Integer32 => "s32_t".to_string(),
+                TypeIdentifier::Integer64 => "s64_t".to_string(),
+                TypeIdentifier::UnsignedInteger8 | TypeIdentifier::Byte => "u8_t".to_string(),
+                TypeIdentifier::UnsignedInteger16 => "u16_t".to_string(),
+                TypeIdentifier::UnsignedInteger32 => "u32_t".to_string(),
+                TypeIdentifier::UnsignedInteger64 => "u64_t".to_string(),
+                TypeIdentifier::Bit => "bool_t".to_string(),
+                _ => DefaultCMapper.map_builtin(type_identifier),
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_c_code_without_stdint_include() {
+        let options = CCodegenOptions {
+            omit_stdint_include: true,
+            type_mapper: Some(Arc::new(ProjectTypesMapper)),
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(
+            "struct Data { length: uint16; flag: bit; gain: float32; };",
+            &options,
+        )
+        .unwrap();
+        assert!(!output.contains("#include"));
+        assert!(output.starts_with(
+            "#pragma once\n\ntypedef struct {\n    u16_t length;\n    bool_t flag;\n    float gain;\n"
+        ));
+    }
+
+    #[rstest]
+    #[case::without_type_mapper(None, "A type mapper is required when <stdint.h> is omitted")]
+    #[case::with_partial_type_mapper(
+        Some(Arc::new(FixedPointMapper) as Arc<dyn TypeMapper>),
+        "Type mapper maps int8 to int8_t, which is not declared when <stdint.h> is omitted"
+    )]
+    fn test_generate_c_code_without_stdint_include_requires_full_type_mapper(
+        #[case] type_mapper: Option<Arc<dyn TypeMapper>>,
+        #[case] expected: &str,
+    ) {
+        let options = CCodegenOptions {
+            omit_stdint_include: true,
+            type_mapper,
+            ..Default::default()
+        };
+        assert!(matches!(
+            generate_c_code_from_string_with_options("struct Data { length: uint16; };", &options),
+            Err(MeksmithError::Validation(errors)) if errors == [expected]
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_rejects_fields_without_codecs() {
+        let input = "union Payload { 0 => raw: byte[]; 1 => small: uint8; };\n\
+                     struct Message { kind: uint8; [discriminated_by=kind] payload: Payload; \
+                     loose: Payload; after: uint8; };";
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            emit_wire_size: true,
+            emit_streaming_decoders: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            generate_c_code_from_string_with_options(input, &options),
+            Err(MeksmithError::Validation(errors)) if errors.len() == 2
+        ));
+        assert!(generate_c_code_from_string(input).is_ok());
+
+        // Without validation, the functions fail right away instead of holding dead code
+        let protocol = crate::parse_protocol_to_ast(input).unwrap();
+        let (sorted, _) = crate::ast::prepare_with_warnings(&protocol).unwrap();
+        let output = generate_c_code_with_options(&sorted, &options);
+        assert!(output.contains(
+            "    case 0:\n        \
+             return false; /* length of the dynamic array is unknown */\n    \
+             case 1:\n"
+        ));
+        assert!(output.contains("    return false; /* union has no discriminator */\n}\n\n"));
+        assert!(!output.contains("in->after"));
+        assert!(!output.contains("out->after"));
+    }
+
+    #[rstest]
+    #[case::msb_first(
+        BitOrder::MsbFirst,
+        "*byte |= (uint8_t)(((value >> (i - 1)) & 1u) << (7 - writer->bit_offset % 8));"
+    )]
+    #[case::lsb_first(
+        BitOrder::LsbFirst,
+        "*byte |= (uint8_t)(((value >> (bits - i)) & 1u) << (writer->bit_offset % 8));"
+    )]
+    fn test_generate_c_code_with_bit_order(#[case] bit_order: BitOrder, #[case] expected: &str) {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            bit_order,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(
+            "struct Header { [bits=3] version: uint8; [bits=5] length: uint8; };",
+            &options,
+        )
+        .unwrap();
+        assert!(output.contains(&format!(
+            "        uint8_t* byte = &writer->buf[writer->bit_offset / 8];\n        \
+             if (writer->bit_offset % 8 == 0) {{\n            *byte = 0;\n        }}\n        \
+             {expected}\n"
+        )));
+        assert!(!output.contains("{write_bit}"));
+        assert!(!output.contains("{swap_bytes}"));
+        assert!(output.contains("meksmith_write_bits(writer, (uint64_t)in->version, 3)"));
+    }
+
+    #[rstest]
+    #[case::big(Endianness::Big, BitOrder::MsbFirst, 0)]
+    #[case::little(Endianness::Little, BitOrder::MsbFirst, 2)]
+    #[case::native(Endianness::Native, BitOrder::MsbFirst, 2)]
+    #[case::big_lsb_first(Endianness::Big, BitOrder::LsbFirst, 2)]
+    #[case::little_lsb_first(Endianness::Little, BitOrder::LsbFirst, 0)]
+    #[case::native_lsb_first(Endianness::Native, BitOrder::LsbFirst, 2)]
+    fn test_generate_c_code_with_endianness(
+        #[case] endianness: Endianness,
+        #[case] bit_order: BitOrder,
+        #[case] swaps: usize,
+    ) {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            endianness,
+            bit_order,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(
+            "struct Header { length: uint16; [bits=3] version: uint8; };",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(output.matches("    if (bits % 8 == 0) {\n").count(), swaps);
+        assert!(!output.contains("{swap_bytes}"));
+        assert!(output.contains("meksmith_write_bits(writer, (uint64_t)in->length, 16)"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_enum_tables() {
+        let options = CCodegenOptions {
+            emit_enum_tables: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(
+            "enum Status { up = 1; reserved = 2..4; down = 0x10; }; struct Device { status: Status; };",
+            &options,
+        )
+        .unwrap();
+        assert!(output.contains(
+            r#"} Status;
+
+static const Status Status_values[] = {
+    Status_up,
+    Status_reserved_2,
+    Status_reserved_3,
+    Status_reserved_4,
+    Status_down,
+};
+static const char* const Status_names[] = {
+    "up",
+    "reserved_2",
+    "reserved_3",
+    "reserved_4",
+    "down",
+};
+"#
+        ));
+
+        let without_tables = generate_c_code_from_string("enum Status { up = 1; };").unwrap();
+        assert!(!without_tables.contains("_values[]"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_range_checks_of_narrowed_fields() {
+        let input = r#"
+enum Status { up = 1; down = 2; };
+struct Header {
+    [bytes=1] version: uint16;
+    [bytes=1] offset: int16;
+    [bits=4] status: Status;
+    [bits=3] packed: uint8;
+    [bytes=6] seconds: uint64;
+};
+"#;
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains(
+            "    if ((uint64_t)in->version > 255u) {\n        return false;\n    }\n    if (!meksmith_write_bits(writer, (uint64_t)in->version, 8)) {\n"
+        ));
+        assert!(
+            output.contains(
+                "    if ((int64_t)in->offset < -127 - 1 || (int64_t)in->offset > 127) {\n"
+            )
+        );
+        assert!(output.contains("    if ((uint64_t)in->status > 15u) {\n"));
+        assert!(!output.contains("in->packed >"));
+        assert!(output.contains("    if ((uint64_t)in->seconds > 281474976710655u) {\n"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_doc_comments() {
+        let input = "# Kind of a message.\n# Sent as the first byte.\nenum Kind { a = 0; };";
+        let options = CCodegenOptions {
+            emit_doc_comments: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains(
+            "/**\n * Kind of a message.\n * Sent as the first byte.\n */\ntypedef enum {\n"
+        ));
+        assert!(!generate_c_code_from_string(input).unwrap().contains("/**"));
+    }
+
+    #[rstest]
+    #[case::enumeration(
+        false,
+        "typedef enum {\n    /**\n     * Asks for a pong.\n     */\n    Kind_ping = 0,\n"
+    )]
+    #[case::packed_enumeration(
+        true,
+        "typedef uint8_t Kind;\n/**\n * Asks for a pong.\n */\n#define Kind_ping ((Kind)0u)\n"
+    )]
+    #[case::structure(
+        false,
+        "typedef struct {\n    /**\n     * Kind of the message.\n     */\n    Kind kind;\n"
+    )]
+    fn test_generate_c_code_with_field_doc_comments(#[case] packed: bool, #[case] expected: &str) {
+        let input = "enum Kind {\n# Asks for a pong.\nping = 0; };\n\
+                     struct Message {\n# Kind of the message.\nkind: Kind; };";
+        let options = CCodegenOptions {
+            emit_doc_comments: true,
+            packed,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains(expected), "missing {expected} in {output}");
+    }
+
+    #[rstest]
+    #[case::enumeration(false, "typedef enum {\n    Status_error = -1,\n    Status_ok = 0,\n")]
+    #[case::packed_enumeration(true, "typedef int8_t Status;\n#define Status_error ((Status)-1)\n")]
+    fn test_generate_c_code_with_negative_enumerators(
+        #[case] packed: bool,
+        #[case] expected: &str,
+    ) {
+        let input = "enum Status { error = -1; ok = 0; };";
+        let options = CCodegenOptions {
+            packed,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains(expected), "missing {expected} in {output}");
+    }
+
+    #[test]
+    fn test_generate_c_code_with_constants() {
+        let input = "struct Message { data: byte[MAX_LEN]; };\n\
+                     const MAX_LEN = 256;\n\
+                     using Buffer = uint8[MAX_LEN];";
+        let output = generate_c_code_from_string(input).unwrap();
+        let define = output.find("#define MAX_LEN 256\n").unwrap();
+        assert!(define < output.find("    uint8_t data[MAX_LEN];\n").unwrap());
+        assert!(define < output.find("typedef uint8_t Buffer[MAX_LEN];\n").unwrap());
+    }
+
+    #[test]
+    fn test_generate_c_code_with_prefixed_constants() {
+        let options = CCodegenOptions {
+            prefix: Some("mek_".to_string()),
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(
+            "const MAX_LEN = 4; struct Message { data: byte[MAX_LEN]; };",
+            &options,
+        )
+        .unwrap();
+        assert!(output.contains("#define mek_MAX_LEN 4\n"), "{output}");
+        assert!(
+            output.contains("    uint8_t data[mek_MAX_LEN];\n"),
+            "{output}"
+        );
+    }
+
+    #[rstest]
+    #[case::unsigned(
+        "enum Status : uint16 { ok = 0; };",
+        "typedef uint16_t Status;\n#define Status_ok ((Status)0u)\n"
+    )]
+    #[case::signed(
+        "enum Status : int8 { ok = 0; };",
+        "typedef int8_t Status;\n#define Status_ok ((Status)0)\n"
+    )]
+    fn test_generate_c_code_with_enumeration_base_type(
+        #[case] input: &str,
+        #[case] expected: &str,
+    ) {
+        let output = generate_c_code_from_string(input).unwrap();
+        assert!(output.contains(expected), "missing {expected} in {output}");
+    }
+
+    #[test]
+    fn test_generate_c_code_with_wide_negative_enumerators() {
+        let output = generate_c_code_from_string("enum Big { neg = -200; pos = 1; };").unwrap();
+        assert!(output.contains("typedef int16_t Big;\n"), "{output}");
+        assert!(output.contains("#define Big_neg ((Big)-200)\n"), "{output}");
+    }
+
+    #[test]
+    fn test_generate_doc_comment_escapes_comment_end() {
+        assert_eq!(
+            generate_doc_comment("Ends early */ here\n\nand continues", ""),
+            "/**\n * Ends early * / here\n *\n * and continues\n */\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_source_ref_comment_without_span() {
+        let definition = Definition::Structure(StructureDefinition {
+            name: crate::ast::Identifier::new("Built"),
+            fields: vec![],
+            attributes: vec![],
+            doc: None,
+        });
+        assert_eq!(
+            generate_source_ref_comment(&definition),
+            "/* meklang: Built */\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_notes_structures_which_are_not_memcpy_safe() {
+        let input = "struct Fixed { a: uint8; }; struct Message { fixed: Fixed; data: byte[]; };";
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains(
+            "/* Message contains pointers, so its memory is not wire-identical: \
+             serialize it with Message_encode instead of memcpy. */\ntypedef struct {\n"
+        ));
+        assert!(!output.contains("/* Fixed contains pointers"));
+        assert!(
+            !generate_c_code_from_string(input)
+                .unwrap()
+                .contains("contains pointers")
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_without_encoders() {
+        let output = generate_c_code_from_string(INPUT_FILE_CONTENT).unwrap();
+        assert!(!output.contains("_encode"));
+        assert!(!output.contains("_decode"));
+        assert!(!output.contains("_Generic"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_encoders() {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            ..Default::default()
+        };
+        let output =
+            generate_c_code_from_string_with_options(INPUT_FILE_CONTENT, &options).unwrap();
+        assert!(output.contains("    uint32_t field4_length;\n    uint8_t* field4;\n"));
+        assert!(output.contains(
+            "size_t MyStruct_encode(const MyStruct* in, uint8_t* buf, size_t buf_len) {\n"
+        ));
+        assert!(output.contains(
+            "static bool MyUnion_encode_bits(const MyUnion* in, uint64_t discriminator, meksmith_writer* writer) {\n"
+        ));
+        assert!(!output.contains("_Generic"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_restrict() {
+        let input = "struct Message { values: uint8[4]; count: uint16; };";
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            emit_field_getters: true,
+            emit_streaming_decoders: true,
+            use_restrict: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        for signature in [
+            "static bool Message_encode_bits(const Message* restrict in, meksmith_writer* restrict writer) {\n",
+            "size_t Message_encode(const Message* restrict in, uint8_t* restrict buf, size_t buf_len) {\n",
+            "static bool Message_decode_bits(Message* restrict out, meksmith_reader* restrict reader) {\n",
+            "size_t Message_decode(Message* restrict out, const uint8_t* restrict buf, size_t buf_len) {\n",
+            "bool Message_get_values(const uint8_t* restrict buf, size_t buf_len, uint8_t out[restrict 4]) {\n",
+            "bool Message_get_count(const uint8_t* restrict buf, size_t buf_len, uint16_t* restrict out) {\n",
+            "size_t Message_stream(const uint8_t* restrict buf, size_t buf_len, const meksmith_callbacks* callbacks, void* ctx) {\n",
+        ] {
+            assert!(output.contains(signature), "missing {signature}");
+        }
+
+        let options = CCodegenOptions {
+            use_restrict: false,
+            ..options
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(!output.contains("restrict"));
+    }
+
+    #[test]
+    fn test_generate_c_code_packed() {
+        let input = r#"
+            enum Kind { a = 0; b = 1; };
+            union Body { 0 => x: uint32; 1 => y: uint8[3]; };
+            struct Inner { a: uint8; b: uint32; };
+            struct Message { inner: Inner; [bits=1] kind: Kind; ok: bit; [discriminated_by=kind] body: Body; };
+        "#;
+        let options = CCodegenOptions {
+            packed: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains(PACKED_BEGIN_CODE));
+        assert!(output.contains("} MEKSMITH_PACKED Message;\n_Static_assert(sizeof(Message) == 11, \"unexpected padding\");\n\n#ifdef _MSC_VER\n#pragma pack(pop)\n#endif\n\n"));
+        assert!(output.contains("typedef uint8_t Kind;\n#define Kind_a ((Kind)0u)\n"));
+        assert!(output.contains(
+            "} MEKSMITH_PACKED Inner;\n_Static_assert(sizeof(Inner) == 5, \"unexpected padding\");\n\n"
+        ));
+        assert!(output.contains("} MEKSMITH_PACKED Body;\n\n"));
+        assert!(!output.contains("sizeof(Body)"));
+    }
+
+    #[rstest]
+    #[case::bitfield(
+        "struct Message { [bits=3] small: uint8; };",
+        "Field small in Message is a C bitfield, whose layout C does not guarantee, so it cannot be packed"
+    )]
+    #[case::c_type(
+        r#"struct Message { [c_type="size_t"] size: uint32; };"#,
+        "Field size in Message has a C type of unknown size, so it cannot be packed"
+    )]
+    #[case::dynamic_array(
+        "struct Inner { data: byte[]; }; struct Message { inner: Inner[2]; };",
+        "Field data in Inner holds a dynamic array, so it cannot be packed"
+    )]
+    #[case::overflowing_array(
+        "struct Message { data: uint64[18446744073709551615]; };",
+        "Size of field data in Message does not fit in 64 bits, so it cannot be packed"
+    )]
+    #[case::overflowing_structure(
+        "struct Message { first: byte[18446744073709551615]; second: uint8; };",
+        "Size of Message does not fit in 64 bits, so it cannot be packed"
+    )]
+    fn test_generate_c_code_packed_rejects_unknown_sizes(
+        #[case] input: &str,
+        #[case] expected: &str,
+    ) {
+        let options = CCodegenOptions {
+            packed: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            generate_c_code_from_string_with_options(input, &options),
+            Err(MeksmithError::Validation(errors)) if errors == [expected]
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_destructors() {
+        let input = r#"
+            struct Inner { data: byte[]; };
+            struct Plain { value: uint8; };
+            union Body { 0 => inner: Inner; 1 => plain: Plain; };
+            struct Message {
+                kind: uint8;
+                [discriminated_by=kind] body: Body;
+                items: Inner[];
+            };
+        "#;
+        let options = CCodegenOptions {
+            emit_decoders: true,
+            emit_destructors: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        for expected in [
+            "void Inner_free(Inner* in) {\n    \
+             if (in == NULL) {\n        return;\n    }\n    \
+             free(in->data);\n    in->data = NULL;\n    in->data_length = 0;\n}\n\n",
+            "static void Body_free(Body* in, uint64_t discriminator) {\n    \
+             switch (discriminator) {\n    \
+             case 0:\n        Inner_free(&in->inner);\n        break;\n    \
+             default:\n        break;\n    }\n}\n\n",
+            "    Body_free(&in->body, (uint64_t)in->kind);\n    \
+             for (size_t i0 = 0; i0 < in->items_length; i0++) {\n        \
+             Inner_free(&in->items[i0]);\n    }\n    \
+             free(in->items);\n",
+        ] {
+            assert!(output.contains(expected), "missing {expected}");
+        }
+        assert!(!output.contains("Plain_free"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_equals() {
+        let input = r#"
+            struct Inner { data: byte[]; };
+            union Body { 0 => inner: Inner; 1 => value: uint16; 2 => empty: void; };
+            struct Message {
+                [discriminated_by=kind] body: Body;
+                kind: uint8;
+                values: uint16[2];
+            };
+        "#;
+        let options = CCodegenOptions {
+            emit_equals: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        for expected in [
+            "#include <string.h>\n",
+            "bool Inner_equals(const Inner* a, const Inner* b) {\n    \
+             if (a->data_length != b->data_length) {\n        return false;\n    }\n    \
+             if (a->data_length != 0 && memcmp(a->data, b->data, a->data_length * sizeof(*a->data)) != 0) {\n        \
+             return false;\n    }\n    return true;\n}\n\n",
+            "static bool Body_equals(const Body* a, const Body* b, uint64_t discriminator) {\n    \
+             switch (discriminator) {\n    \
+             case 0:\n        if (!Inner_equals(&a->inner, &b->inner)) {\n            return false;\n        }\n        \
+             return true;\n    \
+             case 1:\n        if (a->value != b->value) {\n            return false;\n        }\n        \
+             return true;\n    \
+             default:\n        return true;\n    }\n}\n\n",
+            // The discriminator following the union is compared before it as well
+            "bool Message_equals(const Message* a, const Message* b) {\n    \
+             if (a->kind != b->kind) {\n        return false;\n    }\n    \
+             if (!Body_equals(&a->body, &b->body, (uint64_t)a->kind)) {\n        return false;\n    }\n    \
+             if (a->kind != b->kind) {\n        return false;\n    }\n    \
+             if (memcmp(a->values, b->values, sizeof(a->values)) != 0) {\n        return false;\n    }\n    \
+             return true;\n}\n\n",
+        ] {
+            assert!(output.contains(expected), "missing {expected}");
+        }
+    }
+
+    #[test]
+    fn test_generate_c_code_with_prefix() {
+        let input = r#"
+            enum Kind { a = 0; };
+            using Values = uint8[2];
+            [id=1]
+            struct Message { kind: Kind; values: Values; data: byte[]; };
+        "#;
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            emit_streaming_decoders: true,
+            emit_generic_macros: true,
+            prefix: Some("acme_".to_string()),
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        for expected in [
+            "    acme_Kind_a = 0,\n",
+            "} acme_Kind;\n",
+            "typedef uint8_t acme_Values[2];\n",
+            "    acme_Kind kind;\n    acme_Values values;\n",
+            "} acme_Message;\n",
+            "    acme_MessageId_Message = 1,\n} acme_MessageId;\n",
+            "size_t acme_Message_encode(const acme_Message* in, uint8_t* buf, size_t buf_len) {\n",
+            "#define acme_encode(x, buf, len) _Generic((x), \\\n",
+            "static bool acme_Message_decode_bits(acme_Message* out, acme_meksmith_reader* reader) {\n",
+            "} acme_meksmith_callbacks;\n",
+        ] {
+            assert!(output.contains(expected), "missing {expected}");
+        }
+        assert!(!output.contains(" Message_"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_prefix_keeps_user_names() {
+        let input = "# Counts meksmith_ frames.\nstruct Message { meksmith_count: uint8; };";
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_doc_comments: true,
+            prefix: Some("acme_".to_string()),
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains("/**\n * Counts meksmith_ frames.\n */\n"));
+        assert!(output.contains("    uint8_t meksmith_count;\n"));
+        assert!(output.contains(
+            "    if (!acme_meksmith_write_bits(writer, (uint64_t)in->meksmith_count, 8)) {\n"
+        ));
+        assert!(!output.contains("acme_meksmith_count"));
+        assert!(!output.contains(" meksmith_write_bits("));
+    }
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::leading_digit("1acme_")]
+    #[case::separator("acme-")]
+    fn test_generate_c_code_with_invalid_prefix(#[case] prefix: &str) {
+        let options = CCodegenOptions {
+            prefix: Some(prefix.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_c_code_from_string_with_options("struct Message { a: uint8; };", &options)
+                .map_err(|error| error.to_string()),
+            Err(format!(
+                "Validation failed. Errors: Prefix {prefix} is not a valid C identifier"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_with_json_io() {
+        let input = r#"
+union Payload { 0 => ping: uint8; };
+struct Flat { [bits=3] version: uint8; offset: int16; on: bit; };
+struct Nested { kind: uint8; [discriminated_by=kind] payload: Payload; };
+"#;
+        let options = CCodegenOptions {
+            emit_json_io: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains("#include <errno.h>\n"));
+        assert!(output.contains(
+            r#"bool Flat_from_json(const char* json, Flat* out) {
+    {
+        uint64_t value;
+        if (!meksmith_json_get_uint(json, "version", 7u, &value)) {
+            return false;
+        }
+        out->version = (uint8_t)value;
+    }
+    {
+        int64_t value;
+        if (!meksmith_json_get_int(json, "offset", -32767 - 1, 32767, &value)) {
+            return false;
+        }
+        out->offset = (int16_t)value;
+    }
+    {
+        bool value;
+        if (!meksmith_json_get_bool(json, "on", &value)) {
+            return false;
+        }
+        out->on = (bool)value;
+    }
+    return true;
+}
+"#
+        ));
+        assert!(!output.contains("Nested_from_json"));
+        assert!(
+            !generate_c_code_from_string(input)
+                .unwrap()
+                .contains("_from_json")
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_with_wire_size() {
+        let input = r#"
+struct Point { x: int16; y: int16; };
+union Body { 0 => none: void; 1 => point: Point; 2..3 => raw: uint8[4]; };
+struct Message {
+    [bits=3] version: uint8;
+    kind: uint8;
+    [discriminated_by=kind] body: Body;
+    data: uint16[];
+    points: Point[];
+};
+"#;
+        let options = CCodegenOptions {
+            emit_wire_size: true,
+            length_prefix: LengthPrefix::Varint,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains("    uint32_t data_length;\n    uint16_t* data;\n"));
+        assert!(output.contains(
+            "static bool Point_wire_size_bits(const Point* in, size_t* bits) {\n    (void)in;\n    *bits += 16;\n    *bits += 16;\n    return true;\n}\n"
+        ));
+        assert!(output.contains(
+            r#"static bool Body_wire_size_bits(const Body* in, uint64_t discriminator, size_t* bits) {
+    switch (discriminator) {
+    case 0:
+        return true;
+    case 1:
+        if (!Point_wire_size_bits(&in->point, bits)) {
+            return false;
+        }
+        return true;
+    case 2:
+        *bits += 32;
+        return true;
+"#
+        ));
+        assert!(output.contains(
+            r#"static bool Message_wire_size_bits(const Message* in, size_t* bits) {
+    *bits += 3;
+    *bits += 8;
+    if (!Body_wire_size_bits(&in->body, (uint64_t)in->kind, bits)) {
+        return false;
+    }
+    *bits += meksmith_varint_size_in_bits(in->data_length);
+    *bits += (size_t)in->data_length * 16;
+    *bits += meksmith_varint_size_in_bits(in->points_length);
+    for (size_t i0 = 0; i0 < in->points_length; i0++) {
+        if (!Point_wire_size_bits(&in->points[i0], bits)) {
+            return false;
+        }
+    }
+    return true;
+}
+
+static inline size_t Message_wire_size(const Message* in) {
+    size_t bits = 0;
+    if (!Message_wire_size_bits(in, &bits)) {
+        return 0;
+    }
+    return (bits + 7) / 8;
+}
+"#
+        ));
+        assert!(
+            !generate_c_code_from_string(input)
+                .unwrap()
+                .contains("_wire_size")
+        );
+    }
+
+    #[test]
+    fn test_generate_structure_encoder_code() {
+        let input = r#"
+enum Kind { a = 1; b = 300; };
+struct Inner { value: int16; };
+struct Outer {
+    [bits=3] small: uint8;
+    kind: Kind;
+    inner: Inner[2];
+    payload: byte[];
+};
+"#;
+        let protocol = crate::parse_protocol_to_ast(input).unwrap();
+        let options = CCodegenOptions::default();
+        let context = CodegenContext::new(&protocol, &options);
+        let Definition::Structure(outer) = &protocol.definitions[2] else {
+            panic!("expected a structure");
+        };
+        assert_eq!(
+            generate_structure_encoder_code(&context, outer),
+            r#"static bool Outer_encode_bits(const Outer* in, meksmith_writer* writer) {
+    if (!meksmith_write_bits(writer, (uint64_t)in->small, 3)) {
+        return false;
+    }
+    if (!meksmith_write_bits(writer, (uint64_t)in->kind, 16)) {
+        return false;
+    }
+    for (size_t i0 = 0; i0 < 2; i0++) {
+        if (!Inner_encode_bits(&in->inner[i0], writer)) {
+            return false;
+        }
+    }
+    if (!meksmith_write_length(writer, in->payload_length, 32)) {
+        return false;
+    }
+    for (size_t i0 = 0; i0 < in->payload_length; i0++) {
+        if (!meksmith_write_bits(writer, (uint64_t)in->payload[i0], 8)) {
+            return false;
+        }
+    }
+    return true;
+}
+
+size_t Outer_encode(const Outer* in, uint8_t* buf, size_t buf_len) {
+    meksmith_writer writer = {buf, buf_len, 0};
+    if (!Outer_encode_bits(in, &writer)) {
+        return 0;
+    }
+    return (writer.bit_offset + 7) / 8;
+}
+
+"#
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_with_decoders() {
+        let options = CCodegenOptions {
+            emit_decoders: true,
+            ..Default::default()
+        };
+        let output =
+            generate_c_code_from_string_with_options(INPUT_FILE_CONTENT, &options).unwrap();
+        assert!(output.contains("#include <stdlib.h>\n"));
+        assert!(output.contains("    uint32_t field4_length;\n    uint8_t* field4;\n"));
+        assert!(output.contains(
+            "size_t MyStruct_decode(MyStruct* out, const uint8_t* buf, size_t buf_len) {\n"
+        ));
+        assert!(output.contains(
+            "static bool MyUnion_decode_bits(MyUnion* out, uint64_t discriminator, meksmith_reader* reader) {\n"
+        ));
+        assert!(!output.contains("_encode"));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_decoders_in_dependency_order() {
+        let ping_pong = include_str!("../examples/data/ping-pong.mek");
+        let options = CCodegenOptions {
+            emit_decoders: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(ping_pong, &options).unwrap();
+        let position = |name: &str| {
+            output
+                .find(&format!("static bool {name}_decode_bits("))
+                .unwrap()
+        };
+        assert!(position("Ping") < position("PingPong"));
+        assert!(position("Pong") < position("PingPong"));
+        assert!(position("PingPong") < position("Message"));
+    }
+
+    #[test]
+    fn test_generate_structure_decoder_code() {
+        let input = r#"
+enum Kind { a = 1; b = 300; };
+struct Inner { value: int16; };
+struct Outer {
+    [bits=3] small: int8;
+    kind: Kind;
+    inner: Inner[2];
+    payload: byte[];
+};
+"#;
+        let protocol = crate::parse_protocol_to_ast(input).unwrap();
+        let options = CCodegenOptions::default();
+        let context = CodegenContext::new(&protocol, &options);
+        let Definition::Structure(outer) = &protocol.definitions[2] else {
+            panic!("expected a structure");
+        };
+        assert_eq!(
+            generate_structure_decoder_code(&context, outer),
+            r#"static bool Outer_decode_bits(Outer* out, meksmith_reader* reader) {
+    {
+        uint64_t value;
+        if (!meksmith_read_bits(reader, &value, 3)) {
+            return false;
+        }
+        out->small = (int8_t)meksmith_sign_extend(value, 3);
+    }
+    {
+        uint64_t value;
+        if (!meksmith_read_bits(reader, &value, 16)) {
+            return false;
+        }
+        out->kind = (Kind)value;
+    }
+    for (size_t i0 = 0; i0 < 2; i0++) {
+        if (!Inner_decode_bits(&out->inner[i0], reader)) {
+            return false;
+        }
+    }
+    {
+        uint64_t length;
+        if (!meksmith_read_bits(reader, &length, 32)) {
+            return false;
+        }
+        if (length > UINT32_MAX || length > reader->buf_len * 8 - reader->bit_offset) {
+            return false;
+        }
+        out->payload_length = (uint32_t)length;
+        out->payload = malloc(length * sizeof(*out->payload));
+        if (length != 0 && out->payload == NULL) {
+            return false;
+        }
+        for (size_t i0 = 0; i0 < length; i0++) {
+            {
+                uint64_t value;
+                if (!meksmith_read_bits(reader, &value, 8)) {
+                    return false;
+                }
+                out->payload[i0] = (uint8_t)value;
+            }
+        }
+    }
+    return true;
 }
 
-fn generate_type_definition_code(type_definition: &TypeDefinition) -> String {
-    match &type_definition.r#type {
-        TypeIdentifier::StaticArray { r#type, size } => {
-            format!(
-                "typedef {} {}[{}];\n\n",
-                generate_type_identifier_code(r#type),
-                type_definition.new_type.name,
-                size
-            )
-        }
-        TypeIdentifier::DynamicArray { r#type } => {
-            format!(
-                "typedef {}* {};\n\n",
-                generate_type_identifier_code(r#type),
-                type_definition.new_type.name
-            )
-        }
-        _ => {
-            let type_code = generate_type_identifier_code(&type_definition.r#type);
-            format!(
-                "typedef {} {};\n\n",
-                type_code, type_definition.new_type.name
-            )
-        }
+size_t Outer_decode(Outer* out, const uint8_t* buf, size_t buf_len) {
+    meksmith_reader reader = {buf, buf_len, 0};
+    if (!Outer_decode_bits(out, &reader)) {
+        return 0;
     }
+    return (reader.bit_offset + 7) / 8;
 }
 
-fn generate_type_identifier_code(type_identifier: &TypeIdentifier) -> String {
-    match type_identifier {
-        TypeIdentifier::Integer8 => "int8_t".to_string(),
-        TypeIdentifier::Integer16 => "int16_t".to_string(),
-        TypeIdentifier::Integer32 => "int32_t".to_string(),
-        TypeIdentifier::Integer64 => "int64_t".to_string(),
-        TypeIdentifier::UnsignedInteger8 => "uint8_t".to_string(),
-        TypeIdentifier::UnsignedInteger16 => "uint16_t".to_string(),
-        TypeIdentifier::UnsignedInteger32 => "uint32_t".to_string(),
-        TypeIdentifier::UnsignedInteger64 => "uint64_t".to_string(),
-        TypeIdentifier::Float32 => "float".to_string(),
-        TypeIdentifier::Float64 => "double".to_string(),
-        TypeIdentifier::Bit => "bool".to_string(),
-        TypeIdentifier::Byte => "uint8_t".to_string(),
-        TypeIdentifier::UserDefined(identifier) => identifier.name.clone(),
-        TypeIdentifier::StaticArray { r#type, .. } => {
-            // Only return the type, not the array part
-            generate_type_identifier_code(r#type)
+"#
+        );
+    }
+
+    #[test]
+    fn test_generate_c_code_with_streaming_decoders() {
+        let input = r#"
+enum Kind { a = 0; b = 1; };
+union Body { 0 => none: void; 1 => value: float32; };
+struct Message {
+    kind: Kind;
+    [bits=4] version: uint8;
+    offset: int16;
+    valid: bit;
+    [discriminated_by=kind] body: Body;
+};
+"#;
+        let options = CCodegenOptions {
+            emit_decoders: true,
+            emit_streaming_decoders: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(
+            output.contains("    void (*on_uint8)(const char* name, uint8_t value, void* ctx);\n")
+        );
+        assert!(output.contains(
+            "size_t Message_stream(const uint8_t* buf, size_t buf_len, const meksmith_callbacks* callbacks, void* ctx) {\n"
+        ));
+        assert!(output.contains(
+            "        discriminator_kind = value;\n        \
+             if (callbacks->on_enum != NULL) {\n            \
+             callbacks->on_enum(\"kind\", value, ctx);\n        }\n"
+        ));
+        for invocation in [
+            "callbacks->on_uint8(\"version\", (uint8_t)value, ctx);",
+            "callbacks->on_int16(\"offset\", (int16_t)meksmith_sign_extend(value, 16), ctx);",
+            "callbacks->on_bit(\"valid\", value != 0, ctx);",
+            "callbacks->on_float32(\"value\", value, ctx);",
+        ] {
+            assert_eq!(output.matches(invocation).count(), 1, "{invocation}");
         }
-        TypeIdentifier::DynamicArray { r#type } => {
-            format!("{}*", generate_type_identifier_code(r#type))
+        assert!(output.contains(
+            "    if (!Body_stream_bits(discriminator_kind, callbacks, ctx, reader)) {\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_c_code_with_field_getters() {
+        let input = r#"
+struct Fixed {
+    [bits=3] first: uint8;
+    second: uint16;
+    bytes: byte[2];
+    data: byte[];
+    after: uint8;
+};
+"#;
+        let options = CCodegenOptions {
+            emit_decoders: true,
+            emit_field_getters: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(output.contains(
+            r#"bool Fixed_get_second(const uint8_t* buf, size_t buf_len, uint16_t* out) {
+    meksmith_reader state = {buf, buf_len, 3};
+    meksmith_reader* reader = &state;
+    {
+        uint64_t value;
+        if (!meksmith_read_bits(reader, &value, 16)) {
+            return false;
         }
+        (*out) = (uint16_t)value;
     }
+    return true;
 }
+"#
+        ));
+        assert!(output.contains(
+            "bool Fixed_get_bytes(const uint8_t* buf, size_t buf_len, uint8_t out[2]) {\n    \
+             meksmith_reader state = {buf, buf_len, 19};\n"
+        ));
+        assert!(!output.contains("Fixed_get_data"));
+        assert!(!output.contains("Fixed_get_after"));
+    }
 
-fn generate_structure_code(structure: &StructureDefinition) -> String {
-    let mut code = String::new();
-    code.push_str("typedef struct {\n");
-    for field in &structure.fields {
-        match &field.r#type {
-            TypeIdentifier::StaticArray { r#type, size } => {
-                code.push_str(&format!(
-                    "    {} {}[{}];\n",
-                    generate_type_identifier_code(r#type),
-                    field.name.name,
-                    size
-                ));
-            }
-            _ => {
-                code.push_str(&format!(
-                    "    {} {};\n",
-                    generate_type_identifier_code(&field.r#type),
-                    field.name.name
-                ));
+    /// Compiles the C program, whose `main` source includes the generated code as a header,
+    /// with the C compiler of the system and returns the exit code of running it, or `None` if
+    /// there is no C compiler, so that the test can be skipped.
+    fn compile_and_run_c_program(code: &str, main: &str) -> Option<i32> {
+        let main = format!("#include \"protocol.h\"\n{main}");
+        compile_and_run_c_files(&[("protocol.h", code), ("main.c", &main)])
+    }
+
+    /// Same as [`compile_and_run_c_program`], but for a program made of the given files, all
+    /// of which are written next to each other, while only the `.c` ones are compiled.
+    fn compile_and_run_c_files(files: &[(&str, &str)]) -> Option<i32> {
+        let dir = tempfile::tempdir().expect("Failed to create directory");
+        let binary = dir.path().join("main");
+        let mut sources = Vec::new();
+        for (name, code) in files {
+            let path = dir.path().join(name);
+            std::fs::write(&path, code).expect("Failed to write source");
+            if name.ends_with(".c") {
+                sources.push(path);
             }
         }
+        let Ok(compiled) = std::process::Command::new("cc")
+            .args(["-std=c11", "-Wall", "-Werror", "-o"])
+            .arg(&binary)
+            .args(&sources)
+            .output()
+        else {
+            eprintln!("No C compiler found, skipping the test");
+            return None;
+        };
+        assert!(
+            compiled.status.success(),
+            "{}",
+            String::from_utf8_lossy(&compiled.stderr)
+        );
+        std::process::Command::new(&binary)
+            .status()
+            .expect("Failed to run program")
+            .code()
     }
-    code.push_str(&format!("}} {};\n\n", structure.name.name));
-    code
-}
 
-fn generate_union_code(union: &UnionDefinition) -> String {
-    let mut code = String::new();
-    code.push_str("typedef union {\n");
-    for field in &union.fields {
-        match field {
-            UnionField::SingleValue { name, r#type, .. } => match r#type {
-                TypeIdentifier::StaticArray {
-                    r#type: inner_type,
-                    size,
-                } => {
-                    code.push_str(&format!(
-                        "    {} {}[{}];\n",
-                        generate_type_identifier_code(inner_type),
-                        name.name,
-                        size
-                    ));
-                }
-                _ => {
-                    code.push_str(&format!(
-                        "    {} {};\n",
-                        generate_type_identifier_code(r#type),
-                        name.name
-                    ));
-                }
-            },
-            UnionField::RangeOfValues {
-                name,
-                r#type,
-                start_discriminator,
-                end_discriminator,
-            } => {
-                for i in *start_discriminator..=*end_discriminator {
-                    match r#type {
-                        TypeIdentifier::StaticArray {
-                            r#type: inner_type,
-                            size,
-                        } => {
-                            code.push_str(&format!(
-                                "    {} {}_{}[{}];\n",
-                                generate_type_identifier_code(inner_type),
-                                name.name,
-                                i,
-                                size
-                            ));
-                        }
-                        _ => {
-                            code.push_str(&format!(
-                                "    {} {}_{};\n",
-                                generate_type_identifier_code(r#type),
-                                name.name,
-                                i
-                            ));
-                        }
-                    }
-                }
-            }
+    #[rstest]
+    // The status field starts at bit 320, past the end of the buffer
+    #[case::short_buffer(2, 0)]
+    #[case::buffer_ending_before_field(40, 0)]
+    #[case::buffer_ending_with_field(41, 1)]
+    fn test_generate_c_code_field_getter_checks_buffer_length(
+        #[case] buf_len: usize,
+        #[case] expected: i32,
+    ) {
+        let options = CCodegenOptions {
+            emit_decoders: true,
+            emit_field_getters: true,
+            ..Default::default()
+        };
+        let code = generate_c_code_from_string_with_options(
+            include_str!("../examples/data/ping-pong.mek"),
+            &options,
+        )
+        .unwrap();
+        let main = format!(
+            "#include <stdlib.h>\n\nint main(void) {{\n    \
+             uint8_t* buf = calloc({buf_len}, 1);\n    \
+             if ({buf_len} > 40) {{\n        buf[40] = 0x20;\n    }}\n    \
+             DeviceStatus status = DeviceStatus_reserved;\n    \
+             bool found = Pong_get_status(buf, {buf_len}, &status);\n    \
+             free(buf);\n    \
+             if (!found) {{\n        return 0;\n    }}\n    \
+             return status == DeviceStatus_up_but_overloaded ? 1 : 2;\n}}\n"
+        );
+        if let Some(exit_code) = compile_and_run_c_program(&code, &main) {
+            assert_eq!(exit_code, expected);
         }
     }
-    code.push_str(&format!("}} {};\n\n", union.name.name));
-    code
-}
 
-pub fn generate_c_code(protocol: &Protocol) -> String {
-    let mut code = String::new();
-    code.push_str("#include <stdint.h>\n#include <stdbool.h>\n\n");
+    #[test]
+    fn test_generate_c_code_with_native_endianness_keeps_memory_layout() {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            ..Default::default()
+        };
+        let code = generate_c_code_from_string_with_options(
+            "struct Header { length: uint16; checksum: uint32; };",
+            &options,
+        )
+        .unwrap();
+        let main = "#include <string.h>\n\nint main(void) {\n    \
+         Header header = {0x1234, 0x89abcdef};\n    \
+         uint8_t buf[6];\n    \
+         if (Header_encode(&header, buf, sizeof(buf)) != sizeof(buf)) {\n        return 1;\n    }\n    \
+         if (memcmp(buf, &header.length, 2) != 0) {\n        return 2;\n    }\n    \
+         return memcmp(buf + 2, &header.checksum, 4) == 0 ? 0 : 3;\n}\n";
+        if let Some(exit_code) = compile_and_run_c_program(&code, main) {
+            assert_eq!(exit_code, 0);
+        }
+    }
 
-    for definition in &protocol.definitions {
-        match definition {
-            Definition::Enumeration(enumeration) => {
-                code.push_str(&generate_enumeration_code(enumeration));
-            }
-            Definition::Structure(structure) => {
-                code.push_str(&generate_structure_code(structure));
-            }
-            Definition::Type(type_definition) => {
-                code.push_str(&generate_type_definition_code(type_definition));
-            }
-            Definition::Union(union) => {
-                code.push_str(&generate_union_code(union));
-            }
+    #[rstest]
+    #[case::big_msb_first(Endianness::Big, BitOrder::MsbFirst, false, "0xb1")]
+    #[case::big_lsb_first(Endianness::Big, BitOrder::LsbFirst, false, "0x8d")]
+    #[case::little_msb_first(Endianness::Little, BitOrder::MsbFirst, true, "0xb1")]
+    #[case::little_lsb_first(Endianness::Little, BitOrder::LsbFirst, true, "0x8d")]
+    #[case::native_msb_first(
+        Endianness::Native,
+        BitOrder::MsbFirst,
+        cfg!(target_endian = "little"),
+        "0xb1"
+    )]
+    #[case::native_lsb_first(
+        Endianness::Native,
+        BitOrder::LsbFirst,
+        cfg!(target_endian = "little"),
+        "0x8d"
+    )]
+    fn test_generate_c_code_round_trip_with_endianness_and_bit_order(
+        #[case] endianness: Endianness,
+        #[case] bit_order: BitOrder,
+        #[case] is_little_endian: bool,
+        #[case] packed_byte: &str,
+    ) {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            endianness,
+            bit_order,
+            ..Default::default()
+        };
+        let code = generate_c_code_from_string_with_options(
+            "struct Header { length: uint16; checksum: uint32; \
+             [bits=3] version: uint8; [bits=5] flags: uint8; };",
+            &options,
+        )
+        .unwrap();
+        // The bit order only changes how version and flags share their byte
+        let bytes = if is_little_endian {
+            "0x34, 0x12, 0xef, 0xcd, 0xab, 0x89"
+        } else {
+            "0x12, 0x34, 0x89, 0xab, 0xcd, 0xef"
+        };
+        let main = format!(
+            "#include <string.h>\n\nint main(void) {{\n    \
+             Header header = {{0x1234, 0x89abcdef, 5, 0x11}};\n    \
+             const uint8_t expected[7] = {{{bytes}, {packed_byte}}};\n    \
+             uint8_t buf[7];\n    \
+             if (Header_encode(&header, buf, sizeof(buf)) != sizeof(buf)) {{\n        return 1;\n    }}\n    \
+             if (memcmp(buf, expected, sizeof(buf)) != 0) {{\n        return 2;\n    }}\n    \
+             Header decoded = {{0}};\n    \
+             if (Header_decode(&decoded, buf, sizeof(buf)) != sizeof(buf)) {{\n        return 3;\n    }}\n    \
+             return decoded.length == 0x1234 && decoded.checksum == 0x89abcdef \
+             && decoded.version == 5 && decoded.flags == 0x11 ? 0 : 4;\n}}\n"
+        );
+        if let Some(exit_code) = compile_and_run_c_program(&code, &main) {
+            assert_eq!(exit_code, 0);
         }
     }
-    code
-}
 
-pub fn generate_c_code_from_string(input: &str) -> Result<String, String> {
-    let protocol = crate::parse_protocol_to_ast(input)?;
-    let sorted = crate::ast::sort_protocol_by_dependencies(&protocol)?;
-    Ok(generate_c_code(&sorted))
-}
+    #[test]
+    fn test_generate_c_code_multi_dimensional_array_accessors_compile() {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            emit_field_getters: true,
+            emit_union_setters: true,
+            ..Default::default()
+        };
+        let code = generate_c_code_from_string_with_options(
+            "struct Grid { id: uint8; cells: int32[2][3]; };\n\
+             union Shape { 0 => grid: int32[2][3]; 1 => none: void; };\n\
+             struct Holder { kind: uint8; [discriminated_by=kind] shape: Shape; };",
+            &options,
+        )
+        .unwrap();
+        assert!(code.contains(
+            "bool Grid_get_cells(const uint8_t* buf, size_t buf_len, int32_t out[2][3]) {"
+        ));
+        assert!(
+            code.contains("bool Holder_set_shape_grid(Holder* w, const int32_t value[2][3]) {")
+        );
 
-pub fn generate_from_file(file_path: &str) -> Result<String, String> {
-    let protocol = crate::parse_protocol_from_file_to_ast(file_path)?;
-    let sorted = crate::ast::sort_protocol_by_dependencies(&protocol)?;
-    Ok(generate_c_code(&sorted))
-}
+        let main = "#include <string.h>\n\nint main(void) {\n    \
+         Grid grid = {7, {{1, 2, 3}, {4, 5, 6}}};\n    \
+         uint8_t buf[64];\n    \
+         if (Grid_encode(&grid, buf, sizeof(buf)) == 0) {\n        return 1;\n    }\n    \
+         int32_t cells[2][3] = {{0}};\n    \
+         if (!Grid_get_cells(buf, sizeof(buf), cells)) {\n        return 2;\n    }\n    \
+         if (memcmp(cells, grid.cells, sizeof(cells)) != 0) {\n        return 3;\n    }\n    \
+         Holder holder = {0};\n    \
+         Holder_set_shape_grid(&holder, cells);\n    \
+         return memcmp(holder.shape.grid, grid.cells, sizeof(cells)) == 0 ? 0 : 4;\n}\n";
+        if let Some(exit_code) = compile_and_run_c_program(&code, main) {
+            assert_eq!(exit_code, 0);
+        }
+    }
 
-pub fn generate_from_file_to_file(
-    input_file_path: &str,
-    output_file_path: &str,
-) -> Result<(), String> {
-    let c_code = generate_from_file(input_file_path)?;
-    std::fs::write(output_file_path, c_code)
-        .map_err(|e| format!("Failed to write to file: {e}"))?;
-    Ok(())
-}
+    #[rstest]
+    #[case::unnamed("", "#pragma once\n")]
+    #[case::named("module Geometry;\n", "#ifndef MEKSMITH_GEOMETRY_H\n")]
+    fn test_generate_c_code_links_into_several_sources(
+        #[case] module_header: &str,
+        #[case] expected_guard: &str,
+    ) {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            ..Default::default()
+        };
+        let protocol = crate::parse_protocol_to_ast(&format!(
+            "{module_header}struct Point {{ x: uint8; y: uint8; }};"
+        ))
+        .unwrap();
+        let header = generate_c_code_with_options(&protocol, &options);
+        assert!(header.starts_with(expected_guard));
+        assert!(header.contains(
+            "static inline size_t Point_encode(const Point* in, uint8_t* buf, size_t buf_len) {"
+        ));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::NamedTempFile;
+        let encode = "#include \"point.h\"\n\n\
+                      size_t encode_point(uint8_t* buf, size_t buf_len) {\n    \
+                      Point point = {3, 4};\n    \
+                      return Point_encode(&point, buf, buf_len);\n}\n";
+        // Including the header twice must not define its types and helpers twice
+        let main = "#include \"point.h\"\n#include \"point.h\"\n\n\
+                    size_t encode_point(uint8_t* buf, size_t buf_len);\n\n\
+                    int main(void) {\n    \
+                    uint8_t buf[8];\n    \
+                    size_t written = encode_point(buf, sizeof(buf));\n    \
+                    Point point = {0};\n    \
+                    if (Point_decode(&point, buf, written) == 0) {\n        return 1;\n    }\n    \
+                    return point.x == 3 && point.y == 4 ? 0 : 2;\n}\n";
+        let files = [
+            ("point.h", header.as_str()),
+            ("encode.c", encode),
+            ("main.c", main),
+        ];
+        if let Some(exit_code) = compile_and_run_c_files(&files) {
+            assert_eq!(exit_code, 0);
+        }
+    }
 
-    static INPUT_FILE_CONTENT: &str = r#"
-using BuiltInType = int32;
-using UserDefinedType = MyEnum;
-using StaticArrayType = uint32[10];
-using DynamicArrayType = byte[];
+    #[rstest]
+    #[case::u8(
+        LengthPrefix::U8,
+        "meksmith_write_length(writer, in->payload_length, 8)"
+    )]
+    #[case::u16(
+        LengthPrefix::U16,
+        "meksmith_write_length(writer, in->payload_length, 16)"
+    )]
+    #[case::u32(
+        LengthPrefix::U32,
+        "meksmith_write_length(writer, in->payload_length, 32)"
+    )]
+    #[case::varint(
+        LengthPrefix::Varint,
+        "meksmith_write_varint(writer, in->payload_length)"
+    )]
+    fn test_generate_c_code_with_length_prefix(
+        #[case] length_prefix: LengthPrefix,
+        #[case] expected: &str,
+    ) {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            length_prefix,
+            ..Default::default()
+        };
+        let output =
+            generate_c_code_from_string_with_options("struct Data { payload: byte[]; };", &options)
+                .unwrap();
+        assert!(output.contains(&format!("    if (!{expected}) {{\n")));
+    }
 
-enum MyEnum {
-    Value = 1;
-    Range = 2..5;
-    RangeOneValue = 6..6;
-};
+    #[rstest]
+    #[case::length("struct Data { count: uint8; [length=count] payload: byte[]; };")]
+    #[case::length_of("struct Data { [length_of=payload] count: uint8; payload: byte[]; };")]
+    fn test_generate_c_code_with_length_field(#[case] input: &str) {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            emit_streaming_decoders: true,
+            emit_wire_size: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(input, &options).unwrap();
+        assert!(!output.contains("meksmith_write_length(writer, in->payload_length"));
+        assert!(output.contains(
+            "    if ((uint64_t)in->count != in->payload_length) {\n        return false;\n    }\n"
+        ));
+        assert!(
+            output.contains("        uint64_t length;\n        length = (uint64_t)out->count;\n")
+        );
+        assert!(output.contains("    uint64_t length_count = 0;\n"));
+        assert!(output.contains("        length_count = value;\n"));
+        assert!(output.contains("        uint64_t length;\n        length = length_count;\n"));
+        assert!(output.contains("    *bits += 8;\n    *bits += (size_t)in->payload_length * 8;\n"));
+    }
 
-using my_enum_alias_t = MyEnum;
+    #[test]
+    fn test_generate_c_code_with_array_sized_in_bytes() {
+        let output =
+            generate_c_code_from_string("struct Data { samples: uint16[bytes: 10]; };").unwrap();
+        assert!(output.contains("    uint16_t samples[5];\n"));
+    }
 
-struct MyStruct {
-    field1: int32;
-    field2: MyEnum;
-    field3: uint32[10];
-    field4: byte[];
-    field5: my_enum_alias_t;
-    field6: MyEnum[2];
-    builtin1: int8;
-    builtin2: int16;
-    builtin3: int32;
-    builtin4: int64;
-    builtin5: uint8;
-    builtin6: uint16;
-    builtin7: uint32;
-    builtin8: uint64;
-    builtin9: float32;
-    builtin10: float64;
-    builtin11: bit;
-    builtin12: byte;
-};
+    #[test]
+    fn test_generate_c_code_with_init() {
+        let options = CCodegenOptions {
+            emit_init: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(
+            "enum Kind { b = 3; a = 1..2; }; union Body { 0 => a: uint8; }; \
+             struct Data { [discriminated_by=kind] body: Body; kind: Kind; };",
+            &options,
+        )
+        .unwrap();
+        assert!(output.contains("#include <string.h>\n"));
+        assert!(output.contains("Kind Kind_default(void) {\n    return Kind_b;\n}\n"));
+        assert!(
+            output.contains("void Body_zero(Body* out) {\n    memset(out, 0, sizeof(*out));\n}\n")
+        );
+        assert!(
+            output.contains("void Data_zero(Data* out) {\n    memset(out, 0, sizeof(*out));\n}\n")
+        );
 
-union MyUnion {
-    0 => field1: bit;
-    1 => field2: MyEnum;
-    2 => field3: uint64[10];
-    3 => field4: MyStruct;
-    4..6 => reserved: uint16;
-    7..8 => static_array: uint16[10];
-};
-"#;
+        let header = generate_c_header(
+            &crate::parse_protocol_to_ast("struct Data { value: uint8; };").unwrap(),
+            &options,
+        );
+        assert!(header.contains("} Data;\n\nvoid Data_zero(Data* out);\n"));
+    }
 
-    static EXPECTED_C_OUTPUT: &str = r#"#include <stdint.h>
-#include <stdbool.h>
+    #[test]
+    fn test_generate_c_code_with_fat_pointers() {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            fat_pointers: true,
+            ..Default::default()
+        };
+        let output = generate_c_code_from_string_with_options(
+            "struct Data { kind: uint8; payload: byte[]; };",
+            &options,
+        )
+        .unwrap();
+        assert!(output.contains(
+            r#"typedef struct {
+    uint32_t len;
+    uint8_t* data;
+} Data_payload_array;
 
-typedef int32_t BuiltInType;
+typedef struct {
+    uint8_t kind;
+    Data_payload_array payload;
+} Data;
+"#
+        ));
+        assert!(!output.contains("payload_length"));
+        assert!(output.contains("meksmith_write_length(writer, in->payload.len, 32)"));
+        assert!(output.contains("for (size_t i0 = 0; i0 < in->payload.len; i0++) {"));
+        assert!(output.contains("(uint64_t)in->payload.data[i0]"));
+    }
 
-typedef enum {
-    MyEnum_Value = 1,
-    MyEnum_Range_2 = 2,
-    MyEnum_Range_3 = 3,
-    MyEnum_Range_4 = 4,
-    MyEnum_Range_5 = 5,
-    MyEnum_RangeOneValue = 6,
-} MyEnum;
+    #[test]
+    fn test_generate_c_code_with_generic_macros() {
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_generic_macros: true,
+            ..Default::default()
+        };
+        let output =
+            generate_c_code_from_string_with_options(INPUT_FILE_CONTENT, &options).unwrap();
+        assert!(output.ends_with(
+            r#"#define encode(x, buf, len) _Generic((x), \
+    MyStruct*: MyStruct_encode, \
+    const MyStruct*: MyStruct_encode)(x, buf, len)
 
-typedef MyEnum UserDefinedType;
+"#
+        ));
 
-typedef uint32_t StaticArrayType[10];
+        let ping_pong = include_str!("../examples/data/ping-pong.mek");
+        let protocol = crate::parse_protocol_to_ast(ping_pong).unwrap();
+        let macros = generate_generic_macros_code(&protocol);
+        for definition in &protocol.definitions {
+            if let Definition::Structure(structure) = definition {
+                let name = &structure.name.name;
+                assert!(macros.contains(&format!("    {name}*: {name}_encode")));
+            }
+        }
+    }
 
-typedef uint8_t* DynamicArrayType;
+    #[test]
+    fn test_generate_c_files() {
+        let protocol =
+            crate::parse_protocol_to_ast(include_str!("../examples/data/ping-pong.mek")).unwrap();
+        let (sorted, _) = crate::ast::prepare_with_warnings(&protocol).unwrap();
+        let out_dir = tempfile::tempdir().expect("Failed to create temporary directory");
 
-typedef MyEnum my_enum_alias_t;
+        let paths = generate_c_files(&sorted, out_dir.path()).unwrap();
+        let mut file_names: Vec<_> = paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        file_names.sort();
+        assert_eq!(
+            file_names,
+            vec![
+                "DeviceName.h",
+                "DeviceStatus.h",
+                "IpAddress.h",
+                "Message.h",
+                "MessageType.h",
+                "Ping.h",
+                "PingPong.h",
+                "Pong.h",
+                "meksmith.h",
+            ]
+        );
 
-typedef struct {
-    int32_t field1;
-    MyEnum field2;
-    uint32_t field3[10];
-    uint8_t* field4;
-    my_enum_alias_t field5;
-    MyEnum field6[2];
-    int8_t builtin1;
-    int16_t builtin2;
-    int32_t builtin3;
-    int64_t builtin4;
-    uint8_t builtin5;
-    uint16_t builtin6;
-    uint32_t builtin7;
-    uint64_t builtin8;
-    float builtin9;
-    double builtin10;
-    bool builtin11;
-    uint8_t builtin12;
-} MyStruct;
+        let message = std::fs::read_to_string(out_dir.path().join("Message.h")).unwrap();
+        assert!(message.starts_with(
+            "#ifndef MEKSMITH_MESSAGE_H\n#define MEKSMITH_MESSAGE_H\n\n\
+             #include <stdint.h>\n#include <stdbool.h>\n\
+             #include \"MessageType.h\"\n#include \"PingPong.h\"\n\ntypedef struct {\n"
+        ));
+        assert!(message.ends_with("} Message;\n\n#endif /* MEKSMITH_MESSAGE_H */\n"));
 
-typedef union {
-    bool field1;
-    MyEnum field2;
-    uint64_t field3[10];
-    MyStruct field4;
-    uint16_t reserved_4;
-    uint16_t reserved_5;
-    uint16_t reserved_6;
-    uint16_t static_array_7[10];
-    uint16_t static_array_8[10];
-} MyUnion;
+        let umbrella = std::fs::read_to_string(out_dir.path().join(UMBRELLA_HEADER_NAME)).unwrap();
+        assert!(umbrella.contains("#include \"Message.h\"\n"));
+        assert!(umbrella.contains("#include \"DeviceStatus.h\"\n"));
+    }
 
-"#;
+    #[rstest]
+    #[case::umbrella_file(
+        "struct meksmith { a: uint8; };",
+        "Header meksmith.h of definition meksmith clashes with header meksmith.h of the \
+         umbrella header"
+    )]
+    #[case::umbrella_file_of_other_case(
+        "struct Meksmith { a: uint8; };",
+        "Header Meksmith.h of definition Meksmith clashes with header meksmith.h of the \
+         umbrella header"
+    )]
+    #[case::umbrella_guard_of_unnamed_module(
+        "struct protocol { a: uint8; };",
+        "Include guard MEKSMITH_PROTOCOL_H of definition protocol clashes with the one of the \
+         umbrella header"
+    )]
+    #[case::umbrella_guard_of_named_module(
+        "module telemetry; struct Telemetry { a: uint8; };",
+        "Include guard MEKSMITH_TELEMETRY_H of definition Telemetry clashes with the one of the \
+         umbrella header"
+    )]
+    #[case::definitions_of_other_case(
+        "struct Frame { a: uint8; }; struct FRAME { a: uint8; };",
+        "Header FRAME.h of definition FRAME clashes with header Frame.h of definition Frame"
+    )]
+    fn test_generate_c_files_with_clashing_headers(#[case] input: &str, #[case] expected: &str) {
+        let protocol = crate::parse_protocol_to_ast(input).unwrap();
+        let out_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+
+        assert_eq!(
+            generate_c_files(&protocol, out_dir.path()).map_err(|error| error.to_string()),
+            Err(format!("Validation failed. Errors: {expected}"))
+        );
+        assert_eq!(std::fs::read_dir(out_dir.path()).unwrap().count(), 0);
+    }
 
     #[test]
-    fn test_generate_c_code_from_string() {
-        let input = INPUT_FILE_CONTENT;
-        let output = generate_c_code_from_string(input).unwrap();
-        assert_eq!(output, EXPECTED_C_OUTPUT);
+    fn test_generate_c_header_groups_prototypes_by_type() {
+        let protocol =
+            crate::parse_protocol_to_ast(include_str!("../examples/data/ping-pong.mek")).unwrap();
+        let (sorted, _) = crate::ast::prepare_with_warnings(&protocol).unwrap();
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_union_setters: true,
+            ..Default::default()
+        };
+        let header = generate_c_header(&sorted, &options);
+
+        assert!(header.starts_with(
+            "#ifndef MEKSMITH_PROTOCOL_H\n#define MEKSMITH_PROTOCOL_H\n\n\
+             #include <stdint.h>\n#include <stdbool.h>\n#include <stddef.h>\n\n"
+        ));
+        assert!(header.contains(
+            "/* --- Ping --- */\ntypedef struct {\n    IpAddress device_ip;\n    \
+             uint16_t device_port;\n    uint32_t sequence_number;\n} Ping;\n\n\
+             size_t Ping_encode(const Ping* in, uint8_t* buf, size_t buf_len);\n\n"
+        ));
+        assert!(header.contains(
+            "} Message;\n\n\
+             bool Message_set_message_ping(Message* w, Ping value);\n\
+             bool Message_set_message_pong(Message* w, Pong value);\n\
+             size_t Message_encode(const Message* in, uint8_t* buf, size_t buf_len);\n\n"
+        ));
+        assert!(header.contains("/* --- PingPong --- */\ntypedef union {\n"));
+        assert!(!header.contains("_encode_bits"));
+        assert!(!header.contains("return"));
+
+        let source = generate_c_source(&sorted, &options, "ping_pong.h");
+        assert!(source.starts_with("#include \"ping_pong.h\"\n#include <string.h>\n\n"));
+        assert!(source.contains(
+            "size_t Message_encode(const Message* in, uint8_t* buf, size_t buf_len) {\n"
+        ));
+        assert!(!source.contains("typedef struct {\n    IpAddress"));
+    }
+
+    #[test]
+    fn test_generate_c_header_declares_every_visible_function() {
+        let protocol =
+            crate::parse_protocol_to_ast(include_str!("../examples/data/ping-pong.mek")).unwrap();
+        let (sorted, _) = crate::ast::prepare_with_warnings(&protocol).unwrap();
+        let options = CCodegenOptions {
+            emit_encoders: true,
+            emit_decoders: true,
+            emit_field_getters: true,
+            emit_streaming_decoders: true,
+            emit_union_setters: true,
+            emit_init: true,
+            emit_json_io: true,
+            emit_wire_size: true,
+            emit_destructors: true,
+            emit_equals: true,
+            ..Default::default()
+        };
+        let header = generate_c_header(&sorted, &options);
+        let source = generate_c_source(&sorted, &options, "ping_pong.h");
+        let code = generate_c_code_with_options(&sorted, &options);
+
+        let prototypes: Vec<&str> = header
+            .lines()
+            .filter_map(|line| line.strip_suffix(");"))
+            .filter(|line| !line.starts_with(' '))
+            .collect();
+        let definitions = source
+            .lines()
+            .filter(|line| line.ends_with(") {") && !line.starts_with([' ', '}']))
+            .filter(|line| !line.starts_with("static "))
+            .count();
+        assert_eq!(prototypes.len(), definitions);
+        for prototype in prototypes {
+            assert!(
+                source.contains(&format!("\n{prototype}) {{\n")),
+                "{prototype}"
+            );
+            assert!(
+                code.contains(&format!("\nstatic inline {prototype}) {{\n")),
+                "{prototype}"
+            );
+        }
     }
 
     #[test]
@@ -361,8 +6387,58 @@ typedef union {
         );
 
         let output = std::fs::read_to_string(output_file.path().to_str().unwrap()).unwrap();
-        assert_eq!(output, EXPECTED_C_OUTPUT);
+        let guard = format!(
+            "MEKSMITH_{}_H",
+            output_file
+                .path()
+                .file_stem()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_uppercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        );
+        assert_eq!(
+            output,
+            format!(
+                "#ifndef {guard}\n#define {guard}\n\n{}#endif /* {guard} */\n",
+                EXPECTED_C_OUTPUT.trim_start_matches("#pragma once\n\n")
+            )
+        );
         std::fs::remove_file(input_file.path().to_str().unwrap()).unwrap();
         std::fs::remove_file(output_file.path().to_str().unwrap()).unwrap();
     }
+
+    #[test]
+    fn test_generate_from_file_to_files() {
+        let input_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let out_dir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let header_path = out_dir.path().join("ping_pong.h");
+        let source_path = out_dir.path().join("ping_pong.c");
+        std::fs::write(
+            input_file.path(),
+            include_str!("../examples/data/ping-pong.mek"),
+        )
+        .unwrap();
+
+        generate_from_file_to_files(
+            input_file.path().to_str().unwrap(),
+            header_path.to_str().unwrap(),
+            source_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let header = std::fs::read_to_string(&header_path).unwrap();
+        assert!(header.contains("} Message;\n"));
+        assert!(header.contains(
+            "size_t Message_decode(Message* out, const uint8_t* buf, size_t buf_len);\n"
+        ));
+        assert!(!header.contains("return"));
+        let source = std::fs::read_to_string(&source_path).unwrap();
+        assert!(source.starts_with("#include \"ping_pong.h\"\n"));
+        assert!(source.contains(
+            "size_t Message_encode(const Message* in, uint8_t* buf, size_t buf_len) {\n"
+        ));
+        assert!(!source.contains("typedef struct {\n    IpAddress"));
+    }
 }
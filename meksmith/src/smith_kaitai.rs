@@ -0,0 +1,570 @@
+use crate::Endianness;
+use crate::MeksmithError;
+use crate::ast::{
+    Definition, DefinitionLookup, EnumerationDefinition, EnumerationField, Protocol,
+    StructureDefinition, TypeIdentifier, UnionDefinition, UnionField, discriminated_by,
+    field_size_in_bits_from_attributes, length_field, range_member_name, root_structure,
+};
+use crate::smith_rust::to_snake_case;
+
+use std::collections::HashSet;
+
+/// Options controlling the output of the Kaitai Struct smith.
+#[derive(Debug, Clone, Default)]
+pub struct KaitaiCodegenOptions {
+    /// Byte order of the multi-byte values written by the encoders the specification reads.
+    /// A specification has a fixed byte order, so [`Endianness::Native`] is read as
+    /// little-endian, which is the byte order of nearly all hosts.
+    pub endianness: Endianness,
+}
+
+impl KaitaiCodegenOptions {
+    fn is_little_endian(&self) -> bool {
+        self.endianness != Endianness::Big
+    }
+}
+
+/// Returns the id of an attribute generated for a field, as Kaitai Struct identifiers are in
+/// snake case and start with a letter. Reserved fields are named after the space they reserve,
+/// e.g. `_padding` becomes `reserved_padding`.
+fn field_id(field_name: &str) -> String {
+    match field_name.strip_prefix('_') {
+        Some(name) => format!("reserved_{}", to_snake_case(name)),
+        None => to_snake_case(field_name),
+    }
+}
+
+/// Returns the Kaitai Struct type of an integer of the given size in bits. Integers of 1, 2, 4
+/// or 8 bytes are read with the endianness of the specification, all others bit by bit, which
+/// Kaitai Struct only supports for unsigned integers. The encoders swap the bytes of every
+/// little-endian integer of whole bytes, so the bits of those are read in little-endian order.
+fn integer_type(size_in_bits: u64, is_signed: bool, options: &KaitaiCodegenOptions) -> String {
+    match (size_in_bits, is_signed) {
+        (8 | 16 | 32 | 64, true) => format!("s{}", size_in_bits / 8),
+        (8 | 16 | 32 | 64, false) => format!("u{}", size_in_bits / 8),
+        _ if size_in_bits.is_multiple_of(8) && options.is_little_endian() => {
+            format!("b{size_in_bits}le")
+        }
+        _ => format!("b{size_in_bits}"),
+    }
+}
+
+/// Returns the Kaitai Struct type of a built-in scalar type, or `None` for user-defined types,
+/// arrays and `void`. The `size_in_bits` overrides the natural size of integers, as requested
+/// by the `bits`/`bytes` attributes.
+fn builtin_type(
+    type_identifier: &TypeIdentifier,
+    size_in_bits: Option<u64>,
+    options: &KaitaiCodegenOptions,
+) -> Option<String> {
+    let is_signed = matches!(
+        type_identifier,
+        TypeIdentifier::Integer8
+            | TypeIdentifier::Integer16
+            | TypeIdentifier::Integer32
+            | TypeIdentifier::Integer64
+    );
+    match type_identifier {
+        TypeIdentifier::Float32 => Some("f4".to_string()),
+        TypeIdentifier::Float64 => Some("f8".to_string()),
+        TypeIdentifier::Bit => Some(format!("b{}", size_in_bits.unwrap_or(1))),
+        TypeIdentifier::Void
+        | TypeIdentifier::UserDefined(_)
+        | TypeIdentifier::StaticArray { .. }
+        | TypeIdentifier::DynamicArray { .. } => None,
+        _ => Some(integer_type(
+            size_in_bits.or(type_identifier.builtin_size_in_bits())?,
+            is_signed,
+            options,
+        )),
+    }
+}
+
+/// Returns the innermost element type of an array and the factors of its number of elements,
+/// as Kaitai Struct cannot nest repetitions, so nested arrays are flattened. The `length` is
+/// the expression holding the number of elements of an outermost dynamic array. The factors
+/// are `None` when the number of elements is unknown, so the elements are read until the end
+/// of the stream. Types other than arrays have no factors.
+fn flatten_array<'a>(
+    lookup: &DefinitionLookup<'a>,
+    type_identifier: &'a TypeIdentifier,
+    mut length: Option<String>,
+) -> (&'a TypeIdentifier, Option<Vec<String>>) {
+    let mut factors = Some(Vec::new());
+    let mut current = lookup.resolve(type_identifier);
+    loop {
+        match current {
+            TypeIdentifier::StaticArray { r#type, size, .. } => {
+                if let Some(factors) = &mut factors {
+                    factors.push(size.to_string());
+                }
+                current = lookup.resolve(r#type);
+            }
+            TypeIdentifier::DynamicArray { r#type } => {
+                match (length.take(), &mut factors) {
+                    (Some(length), Some(factors)) => factors.push(length),
+                    _ => factors = None,
+                }
+                current = lookup.resolve(r#type);
+            }
+            _ => return (current, factors),
+        }
+    }
+}
+
+/// Returns the name of the type wrapping the value of a union field, which is needed for
+/// fields that cannot be read directly by a case of a `switch-on`, see [`union_case_type`].
+fn union_wrapper_type_name(union_name: &str, field_name: &str) -> String {
+    format!(
+        "{}_{}",
+        to_snake_case(union_name),
+        to_snake_case(field_name)
+    )
+}
+
+/// Returns the type read by the case of a union field, which is the type itself for
+/// structures and scalars, or `None` when the field has to be wrapped in a type holding it in
+/// a `value` attribute, as Kaitai Struct cases cannot repeat or map values to enumerations.
+fn union_case_type(
+    lookup: &DefinitionLookup,
+    type_identifier: &TypeIdentifier,
+    options: &KaitaiCodegenOptions,
+) -> Option<String> {
+    match lookup.resolve(type_identifier) {
+        TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name) {
+            Some(Definition::Structure(_)) => Some(to_snake_case(&identifier.name)),
+            _ => None,
+        },
+        type_identifier => builtin_type(type_identifier, None, options),
+    }
+}
+
+/// Returns the keys of a `seq` attribute reading a value of the given type, one line each, which
+/// are indented relatively to the attribute. The `discriminator` is the expression selecting
+/// the field of a union, and `length` the expression holding the number of elements of a
+/// dynamic array, see [`flatten_array`].
+fn generate_type_keys(
+    lookup: &DefinitionLookup,
+    options: &KaitaiCodegenOptions,
+    type_identifier: &TypeIdentifier,
+    size_in_bits: Option<u64>,
+    discriminator: Option<&str>,
+    length: Option<String>,
+) -> Vec<String> {
+    let (element, factors) = flatten_array(lookup, type_identifier, length);
+    let mut keys = match element {
+        TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => vec![
+                format!(
+                    "type: {}",
+                    integer_type(
+                        size_in_bits.unwrap_or(enumeration.size_in_bits()),
+                        enumeration.is_signed(),
+                        options
+                    )
+                ),
+                format!("enum: {}", to_snake_case(&enumeration.name.name)),
+            ],
+            Some(Definition::Union(union)) => match discriminator {
+                Some(discriminator) => generate_switch_keys(lookup, options, union, discriminator),
+                // Without a discriminator the field cannot be told apart, so it is kept raw
+                None => vec!["size-eos: true".to_string()],
+            },
+            _ => vec![format!("type: {}", to_snake_case(&identifier.name))],
+        },
+        _ => builtin_type(element, size_in_bits, options)
+            .map(|r#type| vec![format!("type: {type}")])
+            .unwrap_or_default(),
+    };
+    match factors {
+        Some(factors) if factors.is_empty() => {}
+        Some(factors) => {
+            keys.push("repeat: expr".to_string());
+            keys.push(format!("repeat-expr: {}", factors.join(" * ")));
+        }
+        None => keys.push("repeat: eos".to_string()),
+    }
+    keys
+}
+
+/// Returns the keys of a `switch-on` type reading the field of a union selected by the
+/// discriminator, with one case per value of a range. Fields of type `void` have no case.
+fn generate_switch_keys(
+    lookup: &DefinitionLookup,
+    options: &KaitaiCodegenOptions,
+    union: &UnionDefinition,
+    discriminator: &str,
+) -> Vec<String> {
+    let mut keys = vec![
+        "type:".to_string(),
+        format!("  switch-on: {discriminator}"),
+        "  cases:".to_string(),
+    ];
+    for field in &union.fields {
+        let (name, r#type, discriminators) = match field {
+            UnionField::SingleValue {
+                name,
+                r#type,
+                discriminator,
+            } => (name, r#type, *discriminator..=*discriminator),
+            UnionField::RangeOfValues {
+                name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => (name, r#type, *start_discriminator..=*end_discriminator),
+        };
+        if *r#type == TypeIdentifier::Void {
+            continue;
+        }
+        let case_type = union_case_type(lookup, r#type, options)
+            .unwrap_or_else(|| union_wrapper_type_name(&union.name.name, &name.name));
+        for value in discriminators {
+            keys.push(format!("    {value}: {case_type}"));
+        }
+    }
+    keys
+}
+
+/// Returns a `seq` attribute with the given id and keys.
+fn generate_attribute_code(id: &str, keys: &[String], indent: &str) -> String {
+    let mut code = format!("{indent}- id: {id}\n");
+    for key in keys {
+        code.push_str(&format!("{indent}  {key}\n"));
+    }
+    code
+}
+
+/// Returns the expression of the discriminator of a union field of a structure, which is
+/// converted to an integer when the discriminating field holds an enumeration.
+fn discriminator_expression(
+    lookup: &DefinitionLookup,
+    structure: &StructureDefinition,
+    discriminator: &str,
+) -> String {
+    let is_enumeration = structure
+        .fields
+        .iter()
+        .find(|field| field.name.name == discriminator)
+        .is_some_and(|field| match lookup.resolve(&field.r#type) {
+            TypeIdentifier::UserDefined(identifier) => matches!(
+                lookup.get(&identifier.name),
+                Some(Definition::Enumeration(_))
+            ),
+            _ => false,
+        });
+    if is_enumeration {
+        format!("{}.to_i", field_id(discriminator))
+    } else {
+        field_id(discriminator)
+    }
+}
+
+/// Returns the `seq` of a structure. Dynamic arrays not linked to a field holding their
+/// length are preceded by a `<field>_length` attribute, which is the 32-bit length prefix
+/// written by the encoders by default.
+fn generate_structure_seq_code(
+    lookup: &DefinitionLookup,
+    options: &KaitaiCodegenOptions,
+    structure: &StructureDefinition,
+    indent: &str,
+) -> String {
+    let mut code = format!("{indent}seq:\n");
+    let indent = format!("{indent}  ");
+    for field in &structure.fields {
+        let id = field_id(&field.name.name);
+        let length = match lookup.resolve(&field.r#type) {
+            TypeIdentifier::DynamicArray { .. } => match length_field(structure, field) {
+                Some(length_field) => Some(field_id(length_field)),
+                None => {
+                    let length_id = format!("{id}_length");
+                    code.push_str(&generate_attribute_code(
+                        &length_id,
+                        &["type: u4".to_string()],
+                        &indent,
+                    ));
+                    Some(length_id)
+                }
+            },
+            _ => None,
+        };
+        let discriminator = discriminated_by(&field.attributes)
+            .map(|discriminator| discriminator_expression(lookup, structure, discriminator));
+        let keys = generate_type_keys(
+            lookup,
+            options,
+            &field.r#type,
+            field_size_in_bits_from_attributes(&field.attributes),
+            discriminator.as_deref(),
+            length,
+        );
+        code.push_str(&generate_attribute_code(&id, &keys, &indent));
+    }
+    code
+}
+
+/// Returns the types wrapping the fields of a union which cannot be read directly by a case,
+/// see [`union_case_type`]. Dynamic arrays are read until the end of the stream, as unions
+/// do not hold their length.
+fn generate_union_wrapper_types_code(
+    lookup: &DefinitionLookup,
+    options: &KaitaiCodegenOptions,
+    union: &UnionDefinition,
+) -> String {
+    let mut code = String::new();
+    for field in &union.fields {
+        let (UnionField::SingleValue { name, r#type, .. }
+        | UnionField::RangeOfValues { name, r#type, .. }) = field;
+        if *r#type == TypeIdentifier::Void || union_case_type(lookup, r#type, options).is_some() {
+            continue;
+        }
+        code.push_str(&format!(
+            "  {}:\n    seq:\n",
+            union_wrapper_type_name(&union.name.name, &name.name)
+        ));
+        code.push_str(&generate_attribute_code(
+            "value",
+            &generate_type_keys(lookup, options, r#type, None, None, None),
+            "      ",
+        ));
+    }
+    code
+}
+
+/// Returns the entries of an enumeration, with one entry per value of a range. Only the first
+/// name of a value is kept, as the values are the keys of the mapping.
+fn generate_enumeration_code(enumeration: &EnumerationDefinition) -> String {
+    let mut code = format!("  {}:\n", to_snake_case(&enumeration.name.name));
+    let mut seen = HashSet::new();
+    for field in &enumeration.fields {
+        let (name, values) = match field {
+            EnumerationField::SingleValue { name, value, .. } => (name, *value..=*value),
+            EnumerationField::RangeOfValues {
+                name, start, end, ..
+            } => (name, *start..=*end),
+        };
+        let is_range = values.start() != values.end();
+        for value in values {
+            if !seen.insert(value) {
+                continue;
+            }
+            let member = if is_range {
+                range_member_name(&name.name, value)
+            } else {
+                name.name.clone()
+            };
+            code.push_str(&format!("    {value}: {}\n", to_snake_case(&member)));
+        }
+    }
+    code
+}
+
+/// Generates a Kaitai Struct specification (`.ksy`) reading the protocol as written by the
+/// encoders with the byte order of [`KaitaiCodegenOptions::endianness`] and their other options
+/// left to default: bit-packed fields filling bytes from their most significant bit and dynamic
+/// arrays prefixed with a 32-bit length. Structures
+/// become types with a `seq`, the first one not used by any other definition being the root,
+/// enumerations become `enums`, and unions become `switch-on` types over their discriminators.
+/// Type aliases and flags are replaced by the types they stand for, and constants are left out.
+///
+/// Unlike the encoders, Kaitai Struct aligns every value of whole bytes to a byte boundary, so
+/// fields following bit-sized ones must end on a byte boundary to be read at the same position.
+pub fn generate_kaitai_with_options(protocol: &Protocol, options: &KaitaiCodegenOptions) -> String {
+    let lookup = DefinitionLookup::new(protocol);
+    let root = root_structure(protocol);
+    let id = match (&protocol.name, root) {
+        (Some(name), _) => to_snake_case(name),
+        (None, Some(root)) => to_snake_case(&root.name.name),
+        (None, None) => "protocol".to_string(),
+    };
+
+    let endian = if options.is_little_endian() {
+        "le"
+    } else {
+        "be"
+    };
+    let mut code = format!("meta:\n  id: {id}\n  endian: {endian}\n  bit-endian: be\n");
+    if let Some(root) = root {
+        code.push_str(&generate_structure_seq_code(&lookup, options, root, ""));
+    }
+
+    let mut types = String::new();
+    let mut enums = String::new();
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Structure(structure) => {
+                if root.is_some_and(|root| root.name == structure.name) {
+                    continue;
+                }
+                types.push_str(&format!("  {}:\n", to_snake_case(&structure.name.name)));
+                types.push_str(&generate_structure_seq_code(
+                    &lookup, options, structure, "    ",
+                ));
+            }
+            Definition::Union(union) => {
+                types.push_str(&generate_union_wrapper_types_code(&lookup, options, union));
+            }
+            Definition::Enumeration(enumeration) => {
+                enums.push_str(&generate_enumeration_code(enumeration));
+            }
+            Definition::Type(_) | Definition::Flags(_) | Definition::Constant(_) => {}
+        }
+    }
+    if !types.is_empty() {
+        code.push_str("types:\n");
+        code.push_str(&types);
+    }
+    if !enums.is_empty() {
+        code.push_str("enums:\n");
+        code.push_str(&enums);
+    }
+    code
+}
+
+pub fn generate_kaitai(protocol: &Protocol) -> String {
+    generate_kaitai_with_options(protocol, &KaitaiCodegenOptions::default())
+}
+
+pub fn generate_kaitai_from_string_with_options(
+    input: &str,
+    options: &KaitaiCodegenOptions,
+) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok(generate_kaitai_with_options(&sorted, options))
+}
+
+pub fn generate_kaitai_from_string(input: &str) -> Result<String, MeksmithError> {
+    generate_kaitai_from_string_with_options(input, &KaitaiCodegenOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+    static CAN_BUS: &str = include_str!("../examples/data/can-bus.mek");
+    static ECPRI: &str = include_str!("../examples/data/ecpri.mek");
+
+    #[rstest]
+    #[case::native(Endianness::Native, "le", "b24le")]
+    #[case::little(Endianness::Little, "le", "b24le")]
+    #[case::big(Endianness::Big, "be", "b24")]
+    fn test_generate_kaitai_endianness(
+        #[case] endianness: Endianness,
+        #[case] endian: &str,
+        #[case] wide_type: &str,
+    ) {
+        let output = generate_kaitai_from_string_with_options(
+            "struct Sample { [bits=24] wide: uint32; [bits=12] narrow: uint16; [bits=4] tail: uint8; };",
+            &KaitaiCodegenOptions { endianness },
+        )
+        .unwrap();
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["meta"]["endian"], endian);
+        assert_eq!(yaml["meta"]["bit-endian"], "be");
+        assert_eq!(yaml["seq"][0]["type"], wide_type);
+        assert_eq!(yaml["seq"][1]["type"], "b12");
+        assert_eq!(yaml["seq"][2]["type"], "b4");
+    }
+
+    #[test]
+    fn test_generate_kaitai_ping_pong() {
+        let output = generate_kaitai_from_string(PING_PONG).unwrap();
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["meta"]["id"], "message");
+        assert!(output.starts_with(
+            r#"meta:
+  id: message
+  endian: le
+  bit-endian: be
+seq:
+  - id: message_type
+    type: b1
+    enum: message_type
+  - id: message
+    type:
+      switch-on: message_type.to_i
+      cases:
+        0: ping
+        1: pong
+types:
+"#
+        ));
+        assert!(output.contains(
+            r#"  ping:
+    seq:
+      - id: device_ip
+        type: u1
+        repeat: expr
+        repeat-expr: 4
+      - id: device_port
+        type: u2
+"#
+        ));
+        assert!(
+            output.contains("      - id: status\n        type: b4\n        enum: device_status\n")
+        );
+        assert!(output.contains("    0: reserved\n    1: up\n"));
+        assert!(output.contains("    15: reserved_15\n"));
+    }
+
+    #[test]
+    fn test_generate_kaitai_examples_parse() {
+        for input in [CAN_BUS, ECPRI] {
+            let output = generate_kaitai_from_string(input).unwrap();
+            let yaml: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+            assert!(yaml["meta"]["id"].is_string());
+            assert!(yaml["seq"].is_sequence());
+        }
+    }
+
+    #[test]
+    fn test_generate_kaitai_arrays_and_unions() {
+        let output = generate_kaitai_from_string(
+            r#"module Frames version 2;
+union Body { 0 => empty: void; 1..2 => values: int16[]; 3 => code: uint8; };
+struct Frame {
+    kind: uint8;
+    [discriminated_by=kind] body: Body;
+    [length_of=samples] count: uint16;
+    samples: float32[];
+    payload: byte[];
+    grid: uint8[2][3];
+    [bits=4] _padding: uint8;
+};"#,
+        )
+        .unwrap();
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["meta"]["id"], "frames");
+        assert!(output.contains(
+            r#"  - id: body
+    type:
+      switch-on: kind
+      cases:
+        1: body_values
+        2: body_values
+        3: u1
+"#
+        ));
+        assert!(
+            output.contains(
+                "  - id: samples\n    type: f4\n    repeat: expr\n    repeat-expr: count\n"
+            )
+        );
+        assert!(output.contains(
+            "  - id: payload_length\n    type: u4\n  - id: payload\n    type: u1\n    repeat: expr\n    repeat-expr: payload_length\n"
+        ));
+        assert!(output.contains("    repeat-expr: 2 * 3\n"));
+        assert!(output.contains("  - id: reserved_padding\n    type: b4\n"));
+        assert!(output.contains(
+            r#"  body_values:
+    seq:
+      - id: value
+        type: s2
+        repeat: eos
+"#
+        ));
+    }
+}
@@ -0,0 +1,768 @@
+use crate::Endianness;
+use crate::MeksmithError;
+use crate::ast::{
+    Definition, DefinitionLookup, EnumerationDefinition, EnumerationField, FlagsDefinition,
+    Protocol, StructureDefinition, TypeDefinition, TypeIdentifier, UnionDefinition, UnionField,
+    discriminated_by, field_size_in_bits_from_attributes, length_field, range_member_name,
+};
+
+/// Options controlling the output of the Kotlin smith.
+#[derive(Debug, Clone, Default)]
+pub struct KotlinCodegenOptions {
+    /// Byte order used by the generated codecs for multi-byte fields.
+    pub endianness: Endianness,
+}
+
+const KOTLIN_KEYWORDS: &[&str] = &[
+    "as",
+    "break",
+    "class",
+    "continue",
+    "do",
+    "else",
+    "false",
+    "for",
+    "fun",
+    "if",
+    "in",
+    "interface",
+    "is",
+    "null",
+    "object",
+    "package",
+    "return",
+    "super",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typealias",
+    "typeof",
+    "val",
+    "var",
+    "when",
+    "while",
+];
+
+/// Escapes identifiers colliding with Kotlin hard keywords using backticks.
+fn kotlin_identifier(name: &str) -> String {
+    if KOTLIN_KEYWORDS.contains(&name) {
+        format!("`{name}`")
+    } else {
+        name.to_string()
+    }
+}
+
+fn generate_type_identifier_code(type_identifier: &TypeIdentifier) -> String {
+    match type_identifier {
+        TypeIdentifier::Integer8 => "Byte".to_string(),
+        TypeIdentifier::Integer16 => "Short".to_string(),
+        TypeIdentifier::Integer32 => "Int".to_string(),
+        TypeIdentifier::Integer64 => "Long".to_string(),
+        TypeIdentifier::UnsignedInteger8 => "UByte".to_string(),
+        TypeIdentifier::UnsignedInteger16 => "UShort".to_string(),
+        TypeIdentifier::UnsignedInteger32 => "UInt".to_string(),
+        TypeIdentifier::UnsignedInteger64 => "ULong".to_string(),
+        TypeIdentifier::Float32 => "Float".to_string(),
+        TypeIdentifier::Float64 => "Double".to_string(),
+        TypeIdentifier::Bit => "Boolean".to_string(),
+        TypeIdentifier::Byte | TypeIdentifier::Char => "UByte".to_string(),
+        TypeIdentifier::Void => "Unit".to_string(),
+        TypeIdentifier::UserDefined(identifier) => identifier.name.clone(),
+        TypeIdentifier::StaticArray { r#type, .. } | TypeIdentifier::DynamicArray { r#type } => {
+            format!("List<{}>", generate_type_identifier_code(r#type))
+        }
+    }
+}
+
+/// Returns the Kotlin type of the `value` of an enumeration, which is `Long` only
+/// for enumerations whose values do not fit in an `Int`.
+fn enumeration_value_type(enumeration: &EnumerationDefinition) -> &'static str {
+    if enumeration.max_value() > i128::from(i32::MAX)
+        || enumeration.min_value() < i128::from(i32::MIN)
+    {
+        "Long"
+    } else {
+        "Int"
+    }
+}
+
+/// Returns the Kotlin literal of an enumeration value of the given value type. Values of
+/// `uint64` enumerations above `Long.MAX_VALUE` are written as their two's complement, as read
+/// by the decoders, and the minimum values are named, as their negated literals overflow.
+fn enumeration_value_literal(value: i128, value_type: &str) -> String {
+    if value_type == "Long" {
+        match value as i64 {
+            i64::MIN => "Long.MIN_VALUE".to_string(),
+            value => format!("{value}L"),
+        }
+    } else if value == i128::from(i32::MIN) {
+        "Int.MIN_VALUE".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn generate_enumeration_code(enumeration: &EnumerationDefinition) -> String {
+    let name = &enumeration.name.name;
+    let value_type = enumeration_value_type(enumeration);
+
+    let mut entries = Vec::new();
+    for field in &enumeration.fields {
+        match field {
+            EnumerationField::SingleValue {
+                name: field_name,
+                value,
+                ..
+            } => entries.push(format!(
+                "    {}({})",
+                kotlin_identifier(&field_name.name),
+                enumeration_value_literal(*value, value_type)
+            )),
+            EnumerationField::RangeOfValues {
+                name: field_name,
+                start,
+                end,
+                ..
+            } => {
+                if start == end {
+                    entries.push(format!(
+                        "    {}({})",
+                        kotlin_identifier(&field_name.name),
+                        enumeration_value_literal(*start, value_type)
+                    ));
+                } else {
+                    for i in *start..=*end {
+                        entries.push(format!(
+                            "    {}({})",
+                            range_member_name(&field_name.name, i),
+                            enumeration_value_literal(i, value_type)
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    format!(
+        "enum class {name}(val value: {value_type}) {{\n{};\n\n    \
+         companion object {{\n        \
+         fun fromValue(value: {value_type}): {name}? = entries.firstOrNull {{ it.value == value }}\n    \
+         }}\n}}\n\n",
+        entries.join(",\n")
+    )
+}
+
+fn generate_type_definition_code(type_definition: &TypeDefinition) -> String {
+    format!(
+        "typealias {} = {}\n\n",
+        type_definition.new_type.name,
+        generate_type_identifier_code(&type_definition.r#type)
+    )
+}
+
+/// Generates a typealias of the underlying integer type of the flags, and a constant
+/// with the mask of every flag.
+fn generate_flags_code(flags: &FlagsDefinition) -> String {
+    let name = &flags.name.name;
+    let mut code = format!(
+        "typealias {name} = {}\n",
+        generate_type_identifier_code(&flags.r#type)
+    );
+    let suffix = match flags.r#type {
+        TypeIdentifier::UnsignedInteger64 => "uL",
+        _ => "u",
+    };
+    for field in &flags.fields {
+        code.push_str(&format!(
+            "const val {name}_{}: {name} = 0x{:X}{suffix}\n",
+            field.name.name, field.value
+        ));
+    }
+    code.push('\n');
+    code
+}
+
+/// Returns statements writing `value` of the given type to `writer`. The `size_in_bits`
+/// overrides the natural size of scalar types, as requested by the `bits`/`bytes` attributes,
+/// while `length` is the `Long` expression of the field holding the length of a dynamic array,
+/// which then has no length prefix.
+fn generate_encode_statements(
+    lookup: &DefinitionLookup,
+    type_identifier: &TypeIdentifier,
+    value: &str,
+    size_in_bits: Option<u64>,
+    length: Option<&str>,
+    depth: usize,
+) -> String {
+    let indent = "    ".repeat(depth + 2);
+    let type_identifier = lookup.resolve(type_identifier);
+    let bits = size_in_bits
+        .or(type_identifier.builtin_size_in_bits())
+        .unwrap_or(64);
+
+    match type_identifier {
+        TypeIdentifier::Void => String::new(),
+        TypeIdentifier::Bit => {
+            format!("{indent}writeUnsigned(writer, if ({value}) 1L else 0L, {bits})\n")
+        }
+        TypeIdentifier::Float32 => {
+            format!("{indent}writeUnsigned(writer, {value}.toRawBits().toLong(), 32)\n")
+        }
+        TypeIdentifier::Float64 => {
+            format!("{indent}writeUnsigned(writer, {value}.toRawBits(), 64)\n")
+        }
+        TypeIdentifier::StaticArray { r#type, .. } => {
+            let element = format!("element{depth}");
+            format!(
+                "{indent}for ({element} in {value}) {{\n{}{indent}}}\n",
+                generate_encode_statements(lookup, r#type, &element, None, None, depth + 1)
+            )
+        }
+        TypeIdentifier::DynamicArray { r#type } => {
+            let element = format!("element{depth}");
+            let length = match length {
+                // The field is encoded before the array, so it must already hold its length
+                Some(length) => format!(
+                    "{indent}require({length} == {value}.size.toLong()) {{ \"Length of {value} does not match its length field\" }}\n"
+                ),
+                None => format!("{indent}writeUnsigned(writer, {value}.size.toLong(), 32)\n"),
+            };
+            format!(
+                "{length}{indent}for ({element} in {value}) {{\n{}{indent}}}\n",
+                generate_encode_statements(lookup, r#type, &element, None, None, depth + 1)
+            )
+        }
+        TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => format!(
+                "{indent}writeUnsigned(writer, {value}.value.toLong(), {})\n",
+                size_in_bits.unwrap_or(enumeration.size_in_bits())
+            ),
+            _ => format!("{indent}{value}.encodeBits(writer)\n"),
+        },
+        _ => format!("{indent}writeUnsigned(writer, {value}.toLong(), {bits})\n"),
+    }
+}
+
+/// Returns an expression converting the value of a field holding the discriminator of a union
+/// or the length of a dynamic array to `Long`.
+fn generate_linked_field_expression(
+    lookup: &DefinitionLookup,
+    structure: &StructureDefinition,
+    linked_field: &str,
+) -> String {
+    let field_type = structure
+        .fields
+        .iter()
+        .find(|field| field.name.name == linked_field)
+        .map(|field| lookup.resolve(&field.r#type));
+    let linked_field = kotlin_identifier(linked_field);
+
+    match field_type {
+        Some(TypeIdentifier::Bit) => format!("if ({linked_field}) 1L else 0L"),
+        Some(TypeIdentifier::UserDefined(identifier)) => match lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(_)) => format!("{linked_field}.value.toLong()"),
+            _ => format!("{linked_field}.toLong()"),
+        },
+        _ => format!("{linked_field}.toLong()"),
+    }
+}
+
+/// Returns an expression reading a value of the given type from `reader`. The `linked_value`
+/// is the `Long` expression selecting the active field of a union, or holding the length of
+/// a dynamic array, which then has no length prefix.
+fn generate_decode_expression(
+    lookup: &DefinitionLookup,
+    type_identifier: &TypeIdentifier,
+    size_in_bits: Option<u64>,
+    linked_value: Option<&str>,
+) -> String {
+    let type_identifier = lookup.resolve(type_identifier);
+    let bits = size_in_bits
+        .or(type_identifier.builtin_size_in_bits())
+        .unwrap_or(64);
+
+    match type_identifier {
+        TypeIdentifier::Void => "Unit".to_string(),
+        TypeIdentifier::Integer8 => format!("readUnsigned(reader, {bits}).toByte()"),
+        TypeIdentifier::Integer16 => format!("readUnsigned(reader, {bits}).toShort()"),
+        TypeIdentifier::Integer32 => format!("readUnsigned(reader, {bits}).toInt()"),
+        TypeIdentifier::Integer64 => format!("readUnsigned(reader, {bits})"),
+        TypeIdentifier::UnsignedInteger8 | TypeIdentifier::Byte | TypeIdentifier::Char => {
+            format!("readUnsigned(reader, {bits}).toUByte()")
+        }
+        TypeIdentifier::UnsignedInteger16 => format!("readUnsigned(reader, {bits}).toUShort()"),
+        TypeIdentifier::UnsignedInteger32 => format!("readUnsigned(reader, {bits}).toUInt()"),
+        TypeIdentifier::UnsignedInteger64 => format!("readUnsigned(reader, {bits}).toULong()"),
+        TypeIdentifier::Float32 => "Float.fromBits(readUnsigned(reader, 32).toInt())".to_string(),
+        TypeIdentifier::Float64 => "Double.fromBits(readUnsigned(reader, 64))".to_string(),
+        TypeIdentifier::Bit => format!("readUnsigned(reader, {bits}) != 0L"),
+        TypeIdentifier::StaticArray { r#type, size, .. } => format!(
+            "List({size}) {{ {} }}",
+            generate_decode_expression(lookup, r#type, None, None)
+        ),
+        TypeIdentifier::DynamicArray { r#type } => format!(
+            "List({}.toInt()) {{ {} }}",
+            linked_value.unwrap_or("readUnsigned(reader, 32)"),
+            generate_decode_expression(lookup, r#type, None, None)
+        ),
+        TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => {
+                let conversion = if enumeration_value_type(enumeration) == "Long" {
+                    ""
+                } else {
+                    ".toInt()"
+                };
+                let bits = size_in_bits.unwrap_or(enumeration.size_in_bits());
+                let value = if enumeration.is_signed() && bits < 64 {
+                    // Shifting the value up and back down extends its sign
+                    let shift = 64 - bits;
+                    format!("(readUnsigned(reader, {bits}) shl {shift} shr {shift})")
+                } else {
+                    format!("readUnsigned(reader, {bits})")
+                };
+                let name = &enumeration.name.name;
+                format!(
+                    "{name}.fromValue({value}{conversion}) \
+                     ?: throw IllegalArgumentException(\"Unknown value of {name}\")"
+                )
+            }
+            Some(Definition::Union(union)) => match linked_value {
+                Some(discriminator) => {
+                    format!("{}.decodeBits(reader, {discriminator})", union.name.name)
+                }
+                None => format!(
+                    "throw IllegalStateException(\"{} has no discriminator\")",
+                    union.name.name
+                ),
+            },
+            _ => format!("{}.decodeBits(reader)", identifier.name),
+        },
+    }
+}
+
+fn generate_structure_code(lookup: &DefinitionLookup, structure: &StructureDefinition) -> String {
+    let name = &structure.name.name;
+    let mut code = format!("data class {name}(\n");
+    for field in &structure.fields {
+        code.push_str(&format!(
+            "    val {}: {},\n",
+            kotlin_identifier(&field.name.name),
+            generate_type_identifier_code(&field.r#type)
+        ));
+    }
+    code.push_str(&format!(
+        ") {{\n{ENCODE_CODE}\n    internal fun encodeBits(writer: MeksmithWriter) {{\n"
+    ));
+    for field in &structure.fields {
+        let length = length_field(structure, field)
+            .map(|length| generate_linked_field_expression(lookup, structure, length));
+        code.push_str(&generate_encode_statements(
+            lookup,
+            &field.r#type,
+            &kotlin_identifier(&field.name.name),
+            field_size_in_bits_from_attributes(&field.attributes),
+            length.as_deref(),
+            0,
+        ));
+    }
+    code.push_str("    }\n\n    companion object {\n");
+    code.push_str(&format!(
+        "        fun decode(buf: ByteBuffer): {name} = decodeBits(MeksmithReader(buf))\n\n        \
+         internal fun decodeBits(reader: MeksmithReader): {name} {{\n"
+    ));
+    for field in &structure.fields {
+        let linked_value = discriminated_by(&field.attributes)
+            .or_else(|| length_field(structure, field))
+            .map(|linked_field| generate_linked_field_expression(lookup, structure, linked_field));
+        code.push_str(&format!(
+            "            val {} = {}\n",
+            kotlin_identifier(&field.name.name),
+            generate_decode_expression(
+                lookup,
+                &field.r#type,
+                field_size_in_bits_from_attributes(&field.attributes),
+                linked_value.as_deref(),
+            )
+        ));
+    }
+    let arguments: Vec<String> = structure
+        .fields
+        .iter()
+        .map(|field| kotlin_identifier(&field.name.name))
+        .collect();
+    code.push_str(&format!(
+        "            return {name}({})\n        }}\n    }}\n}}\n\n",
+        arguments.join(", ")
+    ));
+    code
+}
+
+fn generate_union_code(lookup: &DefinitionLookup, union: &UnionDefinition) -> String {
+    let name = &union.name.name;
+    let mut variants = String::new();
+    let mut encode_branches = String::new();
+    let mut decode_branches = String::new();
+
+    for field in &union.fields {
+        let (field_name, r#type, pattern, has_discriminator) = match field {
+            UnionField::SingleValue {
+                name,
+                r#type,
+                discriminator,
+            } => (name, r#type, format!("{discriminator}L"), false),
+            UnionField::RangeOfValues {
+                name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => (
+                name,
+                r#type,
+                format!("in {start_discriminator}L..{end_discriminator}L"),
+                true,
+            ),
+        };
+        let variant = kotlin_identifier(&field_name.name);
+        let value_type = generate_type_identifier_code(r#type);
+        let value = generate_decode_expression(lookup, r#type, None, None);
+
+        if has_discriminator {
+            variants.push_str(&format!(
+                "    data class {variant}(val discriminator: Long, val value: {value_type}) : {name}()\n"
+            ));
+            decode_branches.push_str(&format!(
+                "                {pattern} -> {variant}(discriminator, {value})\n"
+            ));
+        } else {
+            variants.push_str(&format!(
+                "    data class {variant}(val value: {value_type}) : {name}()\n"
+            ));
+            decode_branches.push_str(&format!(
+                "                {pattern} -> {variant}({value})\n"
+            ));
+        }
+        encode_branches.push_str(&format!(
+            "            is {variant} -> {{\n{}            }}\n",
+            generate_encode_statements(lookup, r#type, "value", None, None, 2)
+        ));
+    }
+
+    format!(
+        "sealed class {name} {{\n{variants}\n{ENCODE_CODE}\n    \
+         internal fun encodeBits(writer: MeksmithWriter) {{\n        \
+         when (this) {{\n{encode_branches}        }}\n    }}\n\n    \
+         companion object {{\n        \
+         fun decode(buf: ByteBuffer, discriminator: Long): {name} =\n            \
+         decodeBits(MeksmithReader(buf), discriminator)\n\n        \
+         internal fun decodeBits(reader: MeksmithReader, discriminator: Long): {name} =\n            \
+         when (discriminator) {{\n{decode_branches}                \
+         else -> throw IllegalArgumentException(\"Unknown discriminator $discriminator\")\n            \
+         }}\n    }}\n}}\n\n"
+    )
+}
+
+/// Public `encode` method of structures and unions, writing the bits of the value to the buffer
+/// and padding the last byte with zeros.
+const ENCODE_CODE: &str = "    fun encode(buf: ByteBuffer) {
+        val writer = MeksmithWriter(buf)
+        encodeBits(writer)
+        writer.flush()
+    }
+";
+
+/// Bit streams shared by all generated codecs, as fields sized with the `bits` attribute do
+/// not have to be byte-aligned. Bits are written and read most significant bit first.
+const BIT_STREAM_CODE: &str = "internal class MeksmithWriter(private val buf: ByteBuffer) {
+    private var byte = 0
+    private var bitCount = 0
+
+    fun writeBits(value: Long, bits: Int) {
+        for (i in bits - 1 downTo 0) {
+            byte = (byte shl 1) or ((value ushr i) and 1L).toInt()
+            bitCount++
+            if (bitCount == 8) {
+                buf.put(byte.toByte())
+                byte = 0
+                bitCount = 0
+            }
+        }
+    }
+
+    fun flush() {
+        if (bitCount != 0) {
+            buf.put((byte shl (8 - bitCount)).toByte())
+            byte = 0
+            bitCount = 0
+        }
+    }
+}
+
+internal class MeksmithReader(private val buf: ByteBuffer) {
+    private var byte = 0
+    private var bitCount = 0
+
+    fun readBits(bits: Int): Long {
+        var value = 0L
+        repeat(bits) {
+            if (bitCount == 0) {
+                byte = buf.get().toInt() and 0xFF
+                bitCount = 8
+            }
+            bitCount--
+            value = (value shl 1) or ((byte ushr bitCount) and 1).toLong()
+        }
+        return value
+    }
+}
+
+";
+
+/// Returns the helpers shared by all generated codecs. Little-endian values of whole bytes
+/// have their bytes reversed, and are then written and read like all others. Native values
+/// are little-endian when the JVM runs on a little-endian host.
+fn generate_codec_helpers_code(options: &KotlinCodegenOptions) -> String {
+    let mut code = BIT_STREAM_CODE.to_string();
+    let keep_bytes = match options.endianness {
+        Endianness::Native => {
+            "bits % 8 != 0 || java.nio.ByteOrder.nativeOrder() != java.nio.ByteOrder.LITTLE_ENDIAN"
+        }
+        Endianness::Big | Endianness::Little => "bits % 8 != 0",
+    };
+    match options.endianness {
+        Endianness::Big => code.push_str(
+            "private fun writeUnsigned(writer: MeksmithWriter, value: Long, bits: Int) {
+    writer.writeBits(value, bits)
+}
+
+private fun readUnsigned(reader: MeksmithReader, bits: Int): Long = reader.readBits(bits)
+
+",
+        ),
+        Endianness::Little | Endianness::Native => code.push_str(&format!(
+            "private fun swapBytes(value: Long, bits: Int): Long {{
+    if ({keep_bytes}) {{
+        return value
+    }}
+    var swapped = 0L
+    for (i in 0 until bits / 8) {{
+        swapped = (swapped shl 8) or ((value ushr (8 * i)) and 0xFF)
+    }}
+    return swapped
+}}
+
+private fun writeUnsigned(writer: MeksmithWriter, value: Long, bits: Int) {{
+    writer.writeBits(swapBytes(value, bits), bits)
+}}
+
+private fun readUnsigned(reader: MeksmithReader, bits: Int): Long =
+    swapBytes(reader.readBits(bits), bits)
+
+",
+        )),
+    }
+    code
+}
+
+/// Generates Kotlin data classes for the protocol, each with `encode`/`decode` methods working
+/// on a `java.nio.ByteBuffer`. Fields are packed bit by bit, like in the C codecs, so fields
+/// sized with the `bits` attribute occupy only their bits, and dynamic arrays are prefixed with
+/// a 32-bit length, unless they are linked to a field holding it with `length`/`length_of`.
+pub fn generate_kotlin_with_options(
+    protocol: &Protocol,
+    package: &str,
+    options: &KotlinCodegenOptions,
+) -> String {
+    let lookup = DefinitionLookup::new(protocol);
+    let mut code = String::new();
+    code.push_str(&format!(
+        "package {package}\n\nimport java.nio.ByteBuffer\n\n"
+    ));
+    code.push_str(&generate_codec_helpers_code(options));
+
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Enumeration(enumeration) => {
+                code.push_str(&generate_enumeration_code(enumeration));
+            }
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_code(&lookup, structure));
+            }
+            Definition::Type(type_definition) => {
+                code.push_str(&generate_type_definition_code(type_definition));
+            }
+            Definition::Flags(flags) => {
+                code.push_str(&generate_flags_code(flags));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_code(&lookup, union));
+            }
+            Definition::Constant(constant) => {
+                code.push_str(&format!(
+                    "const val {} = {}\n\n",
+                    constant.name.name, constant.value
+                ));
+            }
+        }
+    }
+    code
+}
+
+pub fn generate_kotlin(protocol: &Protocol, package: &str) -> String {
+    generate_kotlin_with_options(protocol, package, &KotlinCodegenOptions::default())
+}
+
+pub fn generate_kotlin_from_string(input: &str, package: &str) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok(generate_kotlin(&sorted, package))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    #[test]
+    fn test_generate_kotlin_with_data_class_per_structure() {
+        let output = generate_kotlin_from_string(PING_PONG, "com.example.pingpong").unwrap();
+        assert!(output.starts_with("package com.example.pingpong\n\nimport java.nio.ByteBuffer\n"));
+        for structure in ["Message", "Ping", "Pong"] {
+            assert!(
+                output.contains(&format!("data class {structure}(\n")),
+                "Missing data class for {structure}"
+            );
+            assert!(output.contains(&format!(
+                "        fun decode(buf: ByteBuffer): {structure} = decodeBits(MeksmithReader(buf))\n"
+            )));
+        }
+        assert!(output.contains("sealed class PingPong {\n"));
+        assert!(output.contains("typealias IpAddress = List<UByte>\n"));
+    }
+
+    #[test]
+    fn test_generate_kotlin_enumeration_value_mapping() {
+        let output = generate_kotlin_from_string(PING_PONG, "com.example").unwrap();
+        assert!(output.contains(
+            r#"enum class MessageType(val value: Int) {
+    ping(0),
+    pong(1);
+
+    companion object {
+        fun fromValue(value: Int): MessageType? = entries.firstOrNull { it.value == value }
+    }
+}
+"#
+        ));
+        assert!(output.contains("    reserved_5(5),\n"));
+        assert!(output.contains("    reserved_15(15);\n"));
+    }
+
+    #[rstest]
+    #[case::unsigned_64(
+        "enum Big : uint64 { small = 1; huge = 0xFFFFFFFFFFFFFFFF; };",
+        "    small(1L),\n    huge(-1L);\n"
+    )]
+    #[case::signed_64(
+        "enum Big : int64 { low = -0x8000000000000000; high = 0x7FFFFFFFFFFFFFFF; };",
+        "    low(Long.MIN_VALUE),\n    high(9223372036854775807L);\n"
+    )]
+    #[case::signed_32(
+        "enum Small : int32 { low = -0x80000000; high = 0x7FFFFFFF; };",
+        "    low(Int.MIN_VALUE),\n    high(2147483647);\n"
+    )]
+    fn test_generate_kotlin_enumeration_value_literals(#[case] input: &str, #[case] entries: &str) {
+        let output = generate_kotlin_from_string(input, "com.example").unwrap();
+        assert!(output.contains(entries), "{output}");
+    }
+
+    #[test]
+    fn test_generate_kotlin_structure_codec() {
+        let output = generate_kotlin_from_string(PING_PONG, "com.example").unwrap();
+        assert!(output.contains(
+            r#"data class Message(
+    val message_type: MessageType,
+    val message: PingPong,
+) {
+    fun encode(buf: ByteBuffer) {
+        val writer = MeksmithWriter(buf)
+        encodeBits(writer)
+        writer.flush()
+    }
+
+    internal fun encodeBits(writer: MeksmithWriter) {
+        writeUnsigned(writer, message_type.value.toLong(), 1)
+        message.encodeBits(writer)
+    }
+
+    companion object {
+        fun decode(buf: ByteBuffer): Message = decodeBits(MeksmithReader(buf))
+
+        internal fun decodeBits(reader: MeksmithReader): Message {
+            val message_type = MessageType.fromValue(readUnsigned(reader, 1).toInt()) ?: throw IllegalArgumentException("Unknown value of MessageType")
+            val message = PingPong.decodeBits(reader, message_type.value.toLong())
+            return Message(message_type, message)
+        }
+    }
+}
+"#
+        ));
+    }
+
+    #[test]
+    fn test_generate_kotlin_dynamic_array_with_length_prefix() {
+        let output =
+            generate_kotlin_from_string("struct Data { payload: byte[]; };", "com.example")
+                .unwrap();
+        assert!(output.contains("    val payload: List<UByte>,\n"));
+        assert!(output.contains(
+            "        writeUnsigned(writer, payload.size.toLong(), 32)\n        for (element0 in payload) {\n            writeUnsigned(writer, element0.toLong(), 8)\n        }\n"
+        ));
+        assert!(output.contains(
+            "            val payload = List(readUnsigned(reader, 32).toInt()) { readUnsigned(reader, 8).toUByte() }\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_kotlin_packs_fields_sized_in_bits() {
+        let output = generate_kotlin_from_string(
+            "struct Header { [bits=3] version: uint8; [bits=1] flag: bit; [bits=12] length: uint16; };",
+            "com.example",
+        )
+        .unwrap();
+        assert!(output.contains(
+            "        writeUnsigned(writer, version.toLong(), 3)\n        \
+             writeUnsigned(writer, if (flag) 1L else 0L, 1)\n        \
+             writeUnsigned(writer, length.toLong(), 12)\n"
+        ));
+        assert!(output.contains(
+            "            val version = readUnsigned(reader, 3).toUByte()\n            \
+             val flag = readUnsigned(reader, 1) != 0L\n            \
+             val length = readUnsigned(reader, 12).toUShort()\n"
+        ));
+        assert!(output.contains("internal class MeksmithWriter(private val buf: ByteBuffer) {\n"));
+        assert!(output.contains("internal class MeksmithReader(private val buf: ByteBuffer) {\n"));
+    }
+
+    #[rstest]
+    #[case(Endianness::Big, "    writer.writeBits(value, bits)\n")]
+    #[case(
+        Endianness::Little,
+        "    if (bits % 8 != 0) {\n        return value\n    }\n"
+    )]
+    #[case(
+        Endianness::Native,
+        "    if (bits % 8 != 0 || java.nio.ByteOrder.nativeOrder() != java.nio.ByteOrder.LITTLE_ENDIAN) {\n"
+    )]
+    fn test_generate_kotlin_uses_endianness(#[case] endianness: Endianness, #[case] write: &str) {
+        let protocol = crate::parse_protocol_to_ast(PING_PONG).unwrap();
+        let output = generate_kotlin_with_options(
+            &protocol,
+            "com.example",
+            &KotlinCodegenOptions { endianness },
+        );
+        assert!(output.contains(write));
+    }
+}
@@ -0,0 +1,444 @@
+use crate::MeksmithError;
+use crate::ast::{
+    Definition, DefinitionLookup, EnumerationDefinition, EnumerationField, Protocol,
+    StructureDefinition, TypeIdentifier, UnionDefinition, UnionField, range_member_name,
+};
+use crate::validation::Diagnostic;
+
+use std::collections::HashSet;
+
+/// Converts a name to `UPPER_SNAKE_CASE`, e.g. `MessageType` to `MESSAGE_TYPE`, which prefixes
+/// the values of proto3 enumerations as they share the scope of the enumeration.
+fn upper_snake_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase()
+            && previous
+                .is_some_and(|previous| previous.is_ascii_lowercase() || previous.is_ascii_digit())
+        {
+            result.push('_');
+        }
+        result.push(c.to_ascii_uppercase());
+        previous = Some(c);
+    }
+    result
+}
+
+/// Returns the type of a field holding a value of the given type. Arrays become `repeated`
+/// fields, except for arrays of bytes which become `bytes` and text which becomes `string`.
+/// As proto3 cannot nest `repeated`, elements of nested arrays are flattened into a single
+/// `repeated` field.
+fn generate_type_identifier_code(
+    lookup: &DefinitionLookup,
+    type_identifier: &TypeIdentifier,
+) -> String {
+    match lookup.resolve(type_identifier) {
+        TypeIdentifier::Integer8 | TypeIdentifier::Integer16 | TypeIdentifier::Integer32 => {
+            "int32".to_string()
+        }
+        TypeIdentifier::Integer64 => "int64".to_string(),
+        TypeIdentifier::UnsignedInteger8
+        | TypeIdentifier::UnsignedInteger16
+        | TypeIdentifier::UnsignedInteger32
+        | TypeIdentifier::Byte
+        | TypeIdentifier::Char => "uint32".to_string(),
+        TypeIdentifier::UnsignedInteger64 => "uint64".to_string(),
+        TypeIdentifier::Float32 => "float".to_string(),
+        TypeIdentifier::Float64 => "double".to_string(),
+        TypeIdentifier::Bit => "bool".to_string(),
+        TypeIdentifier::Void => "google.protobuf.Empty".to_string(),
+        TypeIdentifier::UserDefined(identifier) => identifier.name.clone(),
+        TypeIdentifier::StaticArray { r#type, .. } | TypeIdentifier::DynamicArray { r#type } => {
+            match lookup.resolve(r#type) {
+                TypeIdentifier::Byte => "bytes".to_string(),
+                TypeIdentifier::Char => "string".to_string(),
+                _ => {
+                    let element = generate_type_identifier_code(lookup, r#type);
+                    format!("repeated {}", element.trim_start_matches("repeated "))
+                }
+            }
+        }
+    }
+}
+
+fn uses_void(lookup: &DefinitionLookup, type_identifier: &TypeIdentifier) -> bool {
+    match lookup.resolve(type_identifier) {
+        TypeIdentifier::Void => true,
+        TypeIdentifier::StaticArray { r#type, .. } | TypeIdentifier::DynamicArray { r#type } => {
+            uses_void(lookup, r#type)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the names and values of the proto3 enumeration generated for the enumeration, in
+/// the order they are written. The first one must be 0, so zero values come first, followed
+/// by the others sorted by value, negative ones included.
+fn enumeration_values(enumeration: &EnumerationDefinition) -> Vec<(String, i128)> {
+    let mut values = Vec::new();
+    for field in &enumeration.fields {
+        match field {
+            EnumerationField::SingleValue { name, value, .. } => {
+                values.push((name.name.to_uppercase(), *value));
+            }
+            EnumerationField::RangeOfValues {
+                name, start, end, ..
+            } if start == end => {
+                values.push((name.name.to_uppercase(), *start));
+            }
+            EnumerationField::RangeOfValues {
+                name, start, end, ..
+            } => {
+                for i in *start..=*end {
+                    values.push((range_member_name(&name.name, i).to_uppercase(), i));
+                }
+            }
+        }
+    }
+    values.sort_by_key(|(_, value)| (*value != 0, *value));
+    values
+}
+
+/// Generates a proto3 enumeration with the values of [`enumeration_values`]. A
+/// `<NAME>_UNSPECIFIED` value is added first, with a warning, when the enumeration has no 0.
+fn generate_enumeration_code(
+    enumeration: &EnumerationDefinition,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let name = &enumeration.name.name;
+    let prefix = upper_snake_case(name);
+    let mut values = enumeration_values(enumeration);
+    if values.first().is_none_or(|(_, value)| *value != 0) {
+        diagnostics.push(Diagnostic::warning(format!(
+            "Enumeration {name} has no value 0, which proto3 requires, \
+             so {prefix}_UNSPECIFIED = 0 is added"
+        )));
+        values.insert(0, ("UNSPECIFIED".to_string(), 0));
+    }
+    if enumeration.max_value() > i128::from(i32::MAX) {
+        diagnostics.push(Diagnostic::warning(format!(
+            "Enumeration {name} has values above {}, which proto3 enumerations cannot hold",
+            i32::MAX
+        )));
+    }
+    if enumeration.min_value() < i128::from(i32::MIN) {
+        diagnostics.push(Diagnostic::warning(format!(
+            "Enumeration {name} has values below {}, which proto3 enumerations cannot hold",
+            i32::MIN
+        )));
+    }
+
+    let mut code = format!("enum {name} {{\n");
+    let mut seen = HashSet::new();
+    if !values.iter().all(|(_, value)| seen.insert(*value)) {
+        code.push_str("    option allow_alias = true;\n");
+    }
+    for (value_name, value) in values {
+        code.push_str(&format!("    {prefix}_{value_name} = {value};\n"));
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+/// Generates a message with a field per field of the structure, numbered sequentially from 1.
+fn generate_structure_code(lookup: &DefinitionLookup, structure: &StructureDefinition) -> String {
+    let mut code = format!("message {} {{\n", structure.name.name);
+    for (number, field) in (1..).zip(&structure.fields) {
+        code.push_str(&format!(
+            "    {} {} = {number};\n",
+            generate_type_identifier_code(lookup, &field.r#type),
+            field.name.name
+        ));
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+/// Generates a message holding the members of the union in a `oneof`. As a `oneof` cannot
+/// hold `repeated` fields, array members are wrapped in a nested `<member>_values` message.
+fn generate_union_code(lookup: &DefinitionLookup, union: &UnionDefinition) -> String {
+    let mut members = Vec::new();
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue { name, r#type, .. } => {
+                members.push((name.name.clone(), r#type))
+            }
+            UnionField::RangeOfValues {
+                name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => {
+                for i in *start_discriminator..=*end_discriminator {
+                    members.push((format!("{}_{i}", name.name), r#type));
+                }
+            }
+        }
+    }
+
+    let mut code = format!("message {} {{\n", union.name.name);
+    let mut oneof = String::from("    oneof value {\n");
+    for (number, (name, r#type)) in (1..).zip(members) {
+        let type_code = generate_type_identifier_code(lookup, r#type);
+        let type_code = if type_code.starts_with("repeated ") {
+            code.push_str(&format!(
+                "    message {name}_values {{\n        {type_code} values = 1;\n    }}\n\n"
+            ));
+            format!("{name}_values")
+        } else {
+            type_code
+        };
+        oneof.push_str(&format!("        {type_code} {name} = {number};\n"));
+    }
+    code.push_str(&oneof);
+    code.push_str("    }\n}\n\n");
+    code
+}
+
+/// Checks the preconditions of proto3 which [`generate_proto`] cannot meet without changing
+/// the protocol: the first value of every enumeration must be zero, and all of its values
+/// must fit in the `int32` backing proto3 enumerations.
+pub fn validate(protocol: &Protocol) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    for definition in &protocol.definitions {
+        let Definition::Enumeration(enumeration) = definition else {
+            continue;
+        };
+        let name = &enumeration.name.name;
+        if enumeration_values(enumeration)
+            .first()
+            .is_none_or(|(_, value)| *value != 0)
+        {
+            errors.push(format!(
+                "Enumeration {name} lacks a zero value, which proto3 requires as the first value"
+            ));
+        }
+        if enumeration.max_value() > i128::from(i32::MAX) {
+            errors.push(format!(
+                "Enumeration {name} has values above {}, which proto3 enumerations cannot hold",
+                i32::MAX
+            ));
+        }
+        if enumeration.min_value() < i128::from(i32::MIN) {
+            errors.push(format!(
+                "Enumeration {name} has values below {}, which proto3 enumerations cannot hold",
+                i32::MIN
+            ));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Same as [`generate_proto`], but also returns the warnings about parts of the protocol
+/// which proto3 cannot express as they are.
+pub fn generate_proto_with_diagnostics(protocol: &Protocol) -> (String, Vec<Diagnostic>) {
+    let lookup = DefinitionLookup::new(protocol);
+    let mut diagnostics = Vec::new();
+    let mut body = String::new();
+    let mut needs_empty = false;
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Enumeration(enumeration) => {
+                body.push_str(&generate_enumeration_code(enumeration, &mut diagnostics));
+            }
+            Definition::Structure(structure) => {
+                needs_empty |= structure
+                    .fields
+                    .iter()
+                    .any(|field| uses_void(&lookup, &field.r#type));
+                body.push_str(&generate_structure_code(&lookup, structure));
+            }
+            Definition::Union(union) => {
+                needs_empty |= union.fields.iter().any(|field| match field {
+                    UnionField::SingleValue { r#type, .. }
+                    | UnionField::RangeOfValues { r#type, .. } => uses_void(&lookup, r#type),
+                });
+                body.push_str(&generate_union_code(&lookup, union));
+            }
+            // Fields refer to the types aliased by type definitions and flags directly,
+            // while sizes given by constants are lost in repeated fields anyway
+            Definition::Type(_) | Definition::Flags(_) | Definition::Constant(_) => {}
+        }
+    }
+
+    let mut code = String::from("syntax = \"proto3\";\n\n");
+    if needs_empty {
+        code.push_str("import \"google/protobuf/empty.proto\";\n\n");
+    }
+    code.push_str(&body);
+    (code, diagnostics)
+}
+
+/// Generates a Protocol Buffers schema for the protocol: structures become messages,
+/// enumerations become enums, unions become messages holding a `oneof`, while type definitions
+/// and flags are replaced with the types they alias. The schema describes the values of the
+/// protocol, not its wire format.
+pub fn generate_proto(protocol: &Protocol) -> String {
+    generate_proto_with_diagnostics(protocol).0
+}
+
+/// Parses, validates and sorts the protocol, and generates its schema, failing if it does not
+/// meet the preconditions checked by [`validate`].
+pub fn generate_proto_from_string(input: &str) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    validate(&sorted).map_err(MeksmithError::Validation)?;
+    Ok(generate_proto(&sorted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    #[test]
+    fn test_generate_proto_messages() {
+        let output = generate_proto_from_string(PING_PONG).unwrap();
+        assert!(output.starts_with("syntax = \"proto3\";\n\n"));
+        assert!(output.contains(
+            r#"message Ping {
+    bytes device_ip = 1;
+    uint32 device_port = 2;
+    uint32 sequence_number = 3;
+}
+"#
+        ));
+        assert!(output.contains("    MessageType message_type = 1;\n    PingPong message = 2;\n"));
+
+        let output = generate_proto_from_string(
+            "using Row = int16[4]; struct Data { values: float64[]; rows: Row[]; payload: byte[]; name: string[8]; };",
+        )
+        .unwrap();
+        assert!(output.contains(
+            "    repeated double values = 1;\n    repeated int32 rows = 2;\n    bytes payload = 3;\n    string name = 4;\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_proto_enumerations() {
+        let output = generate_proto_from_string(PING_PONG).unwrap();
+        assert!(output.contains(
+            r#"enum MessageType {
+    MESSAGE_TYPE_PING = 0;
+    MESSAGE_TYPE_PONG = 1;
+}
+"#
+        ));
+        assert!(
+            output.contains(
+                "    DEVICE_STATUS_UNAVAILABLE = 4;\n    DEVICE_STATUS_RESERVED_5 = 5;\n"
+            )
+        );
+    }
+
+    #[rstest]
+    #[case::without_zero(
+        "enum Kind { a = 1; b = 2; };",
+        "enum Kind {\n    KIND_UNSPECIFIED = 0;\n    KIND_A = 1;\n    KIND_B = 2;\n}\n",
+        vec![Diagnostic::warning(
+            "Enumeration Kind has no value 0, which proto3 requires, so KIND_UNSPECIFIED = 0 is added"
+                .to_string()
+        )]
+    )]
+    #[case::with_zero_declared_last(
+        "enum Kind { a = 1; b = 0; };",
+        "enum Kind {\n    KIND_B = 0;\n    KIND_A = 1;\n}\n",
+        vec![]
+    )]
+    #[case::with_negative_values(
+        "enum Kind: int8 { below = -2..-1; zero = 0; above = 1; };",
+        "enum Kind {\n    KIND_ZERO = 0;\n    KIND_BELOW_MINUS_2 = -2;\n    KIND_BELOW_MINUS_1 = -1;\n    \
+         KIND_ABOVE = 1;\n}\n",
+        vec![]
+    )]
+    #[case::negative_without_zero(
+        "enum Kind: int8 { below = -1; above = 1; };",
+        "enum Kind {\n    KIND_UNSPECIFIED = 0;\n    KIND_BELOW = -1;\n    KIND_ABOVE = 1;\n}\n",
+        vec![Diagnostic::warning(
+            "Enumeration Kind has no value 0, which proto3 requires, so KIND_UNSPECIFIED = 0 is added"
+                .to_string()
+        )]
+    )]
+    fn test_generate_proto_enumeration_zero_value(
+        #[case] input: &str,
+        #[case] expected: &str,
+        #[case] expected_diagnostics: Vec<Diagnostic>,
+    ) {
+        let protocol = crate::parse_protocol_to_ast(input).unwrap();
+        let (output, diagnostics) = generate_proto_with_diagnostics(&protocol);
+        assert!(output.contains(expected), "{output}");
+        assert_eq!(diagnostics, expected_diagnostics);
+    }
+
+    #[test]
+    fn test_validate_conformant_protocol() {
+        let protocol = crate::parse_protocol_to_ast(PING_PONG).unwrap();
+        assert_eq!(validate(&protocol), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_enumerations() {
+        let input = "enum Kind { a = 1; }; enum Wide { a = 0; b = 0x80000000; };";
+        let protocol = crate::parse_protocol_to_ast(input).unwrap();
+        assert_eq!(
+            validate(&protocol),
+            Err(vec![
+                "Enumeration Kind lacks a zero value, which proto3 requires as the first value"
+                    .to_string(),
+                "Enumeration Wide has values above 2147483647, which proto3 enumerations \
+                 cannot hold"
+                    .to_string(),
+            ])
+        );
+        let Err(MeksmithError::Validation(errors)) = generate_proto_from_string(input) else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(Err(errors), validate(&protocol));
+    }
+
+    #[test]
+    fn test_generate_proto_unions() {
+        let output = generate_proto_from_string(PING_PONG).unwrap();
+        assert!(output.contains(
+            r#"message PingPong {
+    oneof value {
+        Ping ping = 1;
+        Pong pong = 2;
+    }
+}
+"#
+        ));
+
+        let output = generate_proto_from_string(
+            "struct Message { kind: uint8; [discriminated_by=kind] body: Body; };\n\
+             union Body { 0 => empty: void; 1..2 => values: uint16[]; };",
+        )
+        .unwrap();
+        assert!(output.contains("import \"google/protobuf/empty.proto\";\n\n"));
+        assert!(output.contains(
+            r#"message Body {
+    message values_1_values {
+        repeated uint32 values = 1;
+    }
+
+    message values_2_values {
+        repeated uint32 values = 1;
+    }
+
+    oneof value {
+        google.protobuf.Empty empty = 1;
+        values_1_values values_1 = 2;
+        values_2_values values_2 = 3;
+    }
+}
+"#
+        ));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::MeksmithError;
+use crate::ast::{
+    Definition, EnumerationDefinition, EnumerationField, FlagsDefinition, Protocol,
+    StructureDefinition, TypeDefinition, TypeIdentifier, UnionDefinition, UnionField,
+    range_member_name,
+};
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Escapes identifiers colliding with Python keywords with a trailing underscore.
+fn python_identifier(name: &str) -> String {
+    if PYTHON_KEYWORDS.contains(&name) {
+        format!("{name}_")
+    } else {
+        name.to_string()
+    }
+}
+
+fn generate_type_identifier_code(type_identifier: &TypeIdentifier) -> String {
+    match type_identifier {
+        TypeIdentifier::Integer8
+        | TypeIdentifier::Integer16
+        | TypeIdentifier::Integer32
+        | TypeIdentifier::Integer64
+        | TypeIdentifier::UnsignedInteger8
+        | TypeIdentifier::UnsignedInteger16
+        | TypeIdentifier::UnsignedInteger32
+        | TypeIdentifier::UnsignedInteger64
+        | TypeIdentifier::Byte
+        | TypeIdentifier::Char => "int".to_string(),
+        TypeIdentifier::Float32 | TypeIdentifier::Float64 => "float".to_string(),
+        TypeIdentifier::Bit => "bool".to_string(),
+        TypeIdentifier::Void => "None".to_string(),
+        TypeIdentifier::UserDefined(identifier) => identifier.name.clone(),
+        TypeIdentifier::StaticArray { r#type, .. } | TypeIdentifier::DynamicArray { r#type } => {
+            format!("list[{}]", generate_type_identifier_code(r#type))
+        }
+    }
+}
+
+/// Returns a trailing comment documenting the length of a static array, as Python lists
+/// do not carry their size in the type.
+fn generate_length_comment(type_identifier: &TypeIdentifier) -> String {
+    match type_identifier {
+        TypeIdentifier::StaticArray { size, .. } => format!("  # length: {size}"),
+        _ => String::new(),
+    }
+}
+
+fn generate_enumeration_code(enumeration: &EnumerationDefinition) -> String {
+    let mut code = format!("class {}(enum.IntEnum):\n", enumeration.name.name);
+    for field in &enumeration.fields {
+        match field {
+            EnumerationField::SingleValue { name, value, .. } => {
+                code.push_str(&format!(
+                    "    {} = {value}\n",
+                    python_identifier(&name.name)
+                ));
+            }
+            EnumerationField::RangeOfValues {
+                name, start, end, ..
+            } => {
+                if start == end {
+                    code.push_str(&format!(
+                        "    {} = {start}\n",
+                        python_identifier(&name.name)
+                    ));
+                } else {
+                    for i in *start..=*end {
+                        code.push_str(&format!("    {} = {i}\n", range_member_name(&name.name, i)));
+                    }
+                }
+            }
+        }
+    }
+    code
+}
+
+fn generate_structure_code(structure: &StructureDefinition) -> String {
+    let mut code = format!("@dataclass\nclass {}:\n", structure.name.name);
+    for field in &structure.fields {
+        code.push_str(&format!(
+            "    {}: {}{}\n",
+            python_identifier(&field.name.name),
+            generate_type_identifier_code(&field.r#type),
+            generate_length_comment(&field.r#type)
+        ));
+    }
+    code
+}
+
+/// Returns the name of the class generated for a single field of a union.
+fn union_variant_name(union_name: &str, field_name: &str) -> String {
+    format!("{union_name}_{field_name}")
+}
+
+/// Unions become an empty base class with one dataclass per field deriving from it. The
+/// discriminator of a field is a class variable, except for fields covering a range of
+/// discriminators, which carry it as an instance field, so it can be encoded back. Fields
+/// of type `void` carry no value.
+fn generate_union_code(union: &UnionDefinition) -> String {
+    let union_name = &union.name.name;
+    let mut classes = vec![format!("class {union_name}:\n    pass\n")];
+    for field in &union.fields {
+        let (name, r#type) = match field {
+            UnionField::SingleValue { name, r#type, .. }
+            | UnionField::RangeOfValues { name, r#type, .. } => (&name.name, r#type),
+        };
+        let mut code = format!(
+            "@dataclass\nclass {}({union_name}):\n",
+            union_variant_name(union_name, name)
+        );
+        match field {
+            UnionField::SingleValue { discriminator, .. } => {
+                code.push_str(&format!(
+                    "    DISCRIMINATOR: ClassVar[int] = {discriminator}\n"
+                ));
+            }
+            UnionField::RangeOfValues {
+                start_discriminator,
+                end_discriminator,
+                ..
+            } => {
+                code.push_str(&format!(
+                    "    DISCRIMINATORS: ClassVar[range] = range({start_discriminator}, {})\n    discriminator: int\n",
+                    end_discriminator + 1
+                ));
+            }
+        }
+        if *r#type != TypeIdentifier::Void {
+            code.push_str(&format!(
+                "    value: {}{}\n",
+                generate_type_identifier_code(r#type),
+                generate_length_comment(r#type)
+            ));
+        }
+        classes.push(code);
+    }
+    classes.join("\n\n")
+}
+
+/// Sets of flags become `enum.IntFlag`s, which can be combined with `|`.
+fn generate_flags_code(flags: &FlagsDefinition) -> String {
+    let mut code = format!("class {}(enum.IntFlag):\n", flags.name.name);
+    for field in &flags.fields {
+        code.push_str(&format!(
+            "    {} = 0x{:X}\n",
+            python_identifier(&field.name.name),
+            field.value
+        ));
+    }
+    code
+}
+
+fn generate_type_definition_code(type_definition: &TypeDefinition) -> String {
+    format!(
+        "{} = {}{}\n",
+        type_definition.new_type.name,
+        generate_type_identifier_code(&type_definition.r#type),
+        generate_length_comment(&type_definition.r#type)
+    )
+}
+
+/// Generates Python classes for the protocol: structures become dataclasses, enumerations
+/// become `enum.IntEnum`s, unions become a hierarchy of dataclasses tagged with their
+/// discriminators, flags become `enum.IntFlag`s and type definitions become type aliases.
+pub fn generate_python(protocol: &Protocol) -> String {
+    let mut blocks = vec![
+        "from __future__ import annotations\n\nimport enum\nfrom dataclasses import dataclass\nfrom typing import ClassVar\n"
+            .to_string(),
+    ];
+    for definition in &protocol.definitions {
+        blocks.push(match definition {
+            Definition::Enumeration(enumeration) => generate_enumeration_code(enumeration),
+            Definition::Structure(structure) => generate_structure_code(structure),
+            Definition::Type(type_definition) => generate_type_definition_code(type_definition),
+            Definition::Flags(flags) => generate_flags_code(flags),
+            Definition::Union(union) => generate_union_code(union),
+            Definition::Constant(constant) => {
+                format!("{} = {}\n", constant.name.name, constant.value)
+            }
+        });
+    }
+    blocks.join("\n\n")
+}
+
+pub fn generate_python_code_from_string(input: &str) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok(generate_python(&sorted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    #[test]
+    fn test_generate_python_structures() {
+        let output = generate_python_code_from_string(PING_PONG).unwrap();
+        assert!(output.starts_with("from __future__ import annotations\n\n"));
+        assert!(output.contains(
+            r#"@dataclass
+class Ping:
+    device_ip: IpAddress
+    device_port: int
+    sequence_number: int
+"#
+        ));
+        assert!(output.contains("IpAddress = list[int]  # length: 4\n"));
+    }
+
+    #[test]
+    fn test_generate_python_unions() {
+        let output = generate_python_code_from_string(PING_PONG).unwrap();
+        assert!(output.contains(
+            r#"class PingPong:
+    pass
+
+
+@dataclass
+class PingPong_ping(PingPong):
+    DISCRIMINATOR: ClassVar[int] = 0
+    value: Ping
+
+
+@dataclass
+class PingPong_pong(PingPong):
+    DISCRIMINATOR: ClassVar[int] = 1
+    value: Pong
+"#
+        ));
+
+        let output = generate_python_code_from_string(
+            "union Frame { 0 => empty: void; 1..7 => data: byte[4]; };",
+        )
+        .unwrap();
+        assert!(output.contains(
+            r#"@dataclass
+class Frame_empty(Frame):
+    DISCRIMINATOR: ClassVar[int] = 0
+"#
+        ));
+        assert!(output.contains(
+            r#"@dataclass
+class Frame_data(Frame):
+    DISCRIMINATORS: ClassVar[range] = range(1, 8)
+    discriminator: int
+    value: list[int]  # length: 4
+"#
+        ));
+    }
+
+    #[test]
+    fn test_generate_python_enumerations() {
+        let output = generate_python_code_from_string(PING_PONG).unwrap();
+        assert!(output.contains(
+            r#"class MessageType(enum.IntEnum):
+    ping = 0
+    pong = 1
+"#
+        ));
+        assert!(output.contains("    reserved = 0\n    up = 1\n"));
+        assert!(output.contains("    reserved_5 = 5\n"));
+        assert!(output.contains("    reserved_15 = 15\n"));
+    }
+
+    #[test]
+    fn test_generate_python_arrays_and_keywords() {
+        let output = generate_python_code_from_string(
+            "struct Data { payload: byte[]; samples: int32[8]; from: float64; };",
+        )
+        .unwrap();
+        assert!(output.contains("    payload: list[int]\n"));
+        assert!(output.contains("    samples: list[int]  # length: 8\n"));
+        assert!(output.contains("    from_: float\n"));
+    }
+}
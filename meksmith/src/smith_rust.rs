@@ -0,0 +1,889 @@
+use crate::Endianness;
+use crate::MeksmithError;
+use crate::ast::{
+    Definition, DefinitionLookup, EnumerationDefinition, EnumerationField, FlagsDefinition,
+    Protocol, Repr, StructureDefinition, StructureField, TypeDefinition, TypeIdentifier,
+    UnionDefinition, UnionField, assertion, discriminated_by, field_size_in_bits_from_attributes,
+    length_field, range_member_name, repr,
+};
+
+/// Combinator crate used by the parsers generated with [`RustCodegenOptions::emit_parsers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParserCrate {
+    #[default]
+    Nom,
+    Winnow,
+}
+
+/// Options controlling the output of the Rust smith.
+#[derive(Debug, Clone, Default)]
+pub struct RustCodegenOptions {
+    /// Emits `parse_*` functions decoding every structure from its binary representation.
+    pub emit_parsers: bool,
+    /// Combinator crate the generated parsers are written against.
+    pub parser_crate: ParserCrate,
+    /// Byte order used by the generated parsers for multi-byte fields.
+    pub endianness: Endianness,
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Keywords which cannot be used as raw identifiers, so they are suffixed instead.
+const RUST_NON_RAW_KEYWORDS: &[&str] = &["crate", "self", "Self", "super"];
+
+/// Escapes identifiers colliding with Rust keywords using the raw identifier syntax,
+/// or with a trailing underscore for the keywords that cannot be raw identifiers.
+fn rust_identifier(name: &str) -> String {
+    if RUST_NON_RAW_KEYWORDS.contains(&name) {
+        format!("{name}_")
+    } else if RUST_KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Converts a type name such as `PingPong` or `IQData` into `ping_pong` or `iq_data`,
+/// which is used to name the generated functions.
+pub(crate) fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake_case = String::new();
+
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let previous = if i > 0 { chars.get(i - 1) } else { None };
+            let next = chars.get(i + 1);
+            let starts_new_word = match previous {
+                Some(previous) if previous.is_lowercase() || previous.is_ascii_digit() => true,
+                Some(previous) if previous.is_uppercase() => next.is_some_and(|n| n.is_lowercase()),
+                _ => false,
+            };
+            if starts_new_word && !snake_case.ends_with('_') {
+                snake_case.push('_');
+            }
+            snake_case.extend(c.to_lowercase());
+        } else {
+            snake_case.push(*c);
+        }
+    }
+
+    snake_case
+}
+
+fn generate_type_identifier_code(type_identifier: &TypeIdentifier) -> String {
+    match type_identifier {
+        TypeIdentifier::Integer8 => "i8".to_string(),
+        TypeIdentifier::Integer16 => "i16".to_string(),
+        TypeIdentifier::Integer32 => "i32".to_string(),
+        TypeIdentifier::Integer64 => "i64".to_string(),
+        TypeIdentifier::UnsignedInteger8 => "u8".to_string(),
+        TypeIdentifier::UnsignedInteger16 => "u16".to_string(),
+        TypeIdentifier::UnsignedInteger32 => "u32".to_string(),
+        TypeIdentifier::UnsignedInteger64 => "u64".to_string(),
+        TypeIdentifier::Float32 => "f32".to_string(),
+        TypeIdentifier::Float64 => "f64".to_string(),
+        TypeIdentifier::Bit => "bool".to_string(),
+        TypeIdentifier::Byte | TypeIdentifier::Char => "u8".to_string(),
+        TypeIdentifier::Void => "()".to_string(),
+        TypeIdentifier::UserDefined(identifier) => identifier.name.clone(),
+        TypeIdentifier::StaticArray {
+            r#type,
+            size_constant: Some(constant),
+            ..
+        } => format!(
+            "[{}; {}]",
+            generate_type_identifier_code(r#type),
+            constant.name
+        ),
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            format!("[{}; {}]", generate_type_identifier_code(r#type), size)
+        }
+        TypeIdentifier::DynamicArray { r#type } => {
+            format!("Vec<{}>", generate_type_identifier_code(r#type))
+        }
+    }
+}
+
+fn generate_enumeration_code(enumeration: &EnumerationDefinition) -> String {
+    let mut code = String::new();
+    code.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n");
+    code.push_str(&format!(
+        "#[repr({}{})]\n",
+        if enumeration.is_signed() { "i" } else { "u" },
+        enumeration.size_in_bits()
+    ));
+    code.push_str(&format!("pub enum {} {{\n", enumeration.name.name));
+    for field in &enumeration.fields {
+        match field {
+            EnumerationField::SingleValue { name, value, .. } => {
+                code.push_str(&format!(
+                    "    {} = {},\n",
+                    rust_identifier(&name.name),
+                    value
+                ));
+            }
+            EnumerationField::RangeOfValues {
+                name, start, end, ..
+            } => {
+                if start == end {
+                    code.push_str(&format!(
+                        "    {} = {},\n",
+                        rust_identifier(&name.name),
+                        start
+                    ));
+                } else {
+                    for i in *start..=*end {
+                        code.push_str(&format!(
+                            "    {} = {},\n",
+                            range_member_name(&name.name, i),
+                            i
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+fn generate_type_definition_code(type_definition: &TypeDefinition) -> String {
+    format!(
+        "pub type {} = {};\n\n",
+        type_definition.new_type.name,
+        generate_type_identifier_code(&type_definition.r#type)
+    )
+}
+
+fn generate_flags_code(flags: &FlagsDefinition) -> String {
+    let name = &flags.name.name;
+    let mut code = format!(
+        "pub type {name} = {};\n",
+        generate_type_identifier_code(&flags.r#type)
+    );
+    for field in &flags.fields {
+        code.push_str(&format!(
+            "#[allow(non_upper_case_globals)]\npub const {name}_{}: {name} = 0x{:X};\n",
+            field.name.name, field.value
+        ));
+    }
+    code.push('\n');
+    code
+}
+
+fn generate_structure_code(structure: &StructureDefinition) -> String {
+    let mut code = String::new();
+    code.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+    match repr(&structure.attributes) {
+        Some(Repr::C) => code.push_str("#[repr(C)]\n"),
+        Some(Repr::CPacked) => code.push_str("#[repr(C, packed)]\n"),
+        Some(Repr::Transparent) => code.push_str("#[repr(transparent)]\n"),
+        None => {}
+    }
+    code.push_str(&format!("pub struct {} {{\n", structure.name.name));
+    for field in &structure.fields {
+        code.push_str(&format!(
+            "    pub {}: {},\n",
+            rust_identifier(&field.name.name),
+            generate_type_identifier_code(&field.r#type)
+        ));
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+fn generate_union_code(union: &UnionDefinition) -> String {
+    let mut code = String::new();
+    code.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+    code.push_str(&format!("pub enum {} {{\n", union.name.name));
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue { name, r#type, .. } => {
+                code.push_str(&format!(
+                    "    {}({}),\n",
+                    rust_identifier(&name.name),
+                    generate_type_identifier_code(r#type)
+                ));
+            }
+            UnionField::RangeOfValues { name, r#type, .. } => {
+                code.push_str(&format!(
+                    "    {} {{ discriminator: u64, value: {} }},\n",
+                    rust_identifier(&name.name),
+                    generate_type_identifier_code(r#type)
+                ));
+            }
+        }
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+/// Returns the helpers shared by all generated parsers. Every parser works on a bit
+/// stream, because fields annotated with `bits` do not have to be byte-aligned.
+fn generate_parser_helpers_code(options: &RustCodegenOptions) -> String {
+    let mut code = String::new();
+
+    match options.parser_crate {
+        ParserCrate::Nom => code.push_str(
+            r#"use nom::Parser;
+
+type BitInput<'a> = (&'a [u8], usize);
+type BitResult<'a, T> = Result<T, nom::Err<nom::error::Error<BitInput<'a>>>>;
+
+fn invalid_value(input: BitInput<'_>) -> nom::Err<nom::error::Error<BitInput<'_>>> {
+    nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify))
+}
+
+fn take_bits<'a>(input: &mut BitInput<'a>, bits: usize) -> BitResult<'a, u64> {
+    let (rest, value) = nom::bits::complete::take(bits)(*input)?;
+    *input = rest;
+    Ok(value)
+}
+
+"#,
+        ),
+        ParserCrate::Winnow => code.push_str(
+            r#"use winnow::Parser;
+
+type BitInput<'a> = (&'a [u8], usize);
+type BitResult<'a, T> = winnow::ModalResult<T>;
+
+fn invalid_value(_input: BitInput<'_>) -> winnow::error::ErrMode<winnow::error::ContextError> {
+    winnow::error::ErrMode::Backtrack(winnow::error::ContextError::new())
+}
+
+fn take_bits<'a>(input: &mut BitInput<'a>, bits: usize) -> BitResult<'a, u64> {
+    winnow::binary::bits::take(bits).parse_next(input)
+}
+
+"#,
+        ),
+    }
+
+    match options.endianness {
+        Endianness::Big => code.push_str(
+            r#"fn read_unsigned<'a>(input: &mut BitInput<'a>, bits: usize) -> BitResult<'a, u64> {
+    take_bits(input, bits)
+}
+
+"#,
+        ),
+        Endianness::Little => code.push_str(
+            r#"fn read_unsigned<'a>(input: &mut BitInput<'a>, bits: usize) -> BitResult<'a, u64> {
+    let value = take_bits(input, bits)?;
+    if bits % 8 == 0 {
+        Ok(value.swap_bytes() >> (64 - bits))
+    } else {
+        Ok(value)
+    }
+}
+
+"#,
+        ),
+        Endianness::Native => code.push_str(
+            r#"fn read_unsigned<'a>(input: &mut BitInput<'a>, bits: usize) -> BitResult<'a, u64> {
+    let value = take_bits(input, bits)?;
+    if cfg!(target_endian = "little") && bits % 8 == 0 {
+        Ok(value.swap_bytes() >> (64 - bits))
+    } else {
+        Ok(value)
+    }
+}
+
+"#,
+        ),
+    }
+
+    code.push_str(
+        r#"fn read_enum<'a, T: TryFrom<u64>>(input: &mut BitInput<'a>, bits: usize) -> BitResult<'a, T> {
+    let start = *input;
+    let value = read_unsigned(input, bits)?;
+    T::try_from(value).map_err(|_| invalid_value(start))
+}
+
+fn read_array<'a, T, const N: usize>(
+    input: &mut BitInput<'a>,
+    read_item: impl Fn(&mut BitInput<'a>) -> BitResult<'a, T>,
+) -> BitResult<'a, [T; N]> {
+    let mut items = Vec::with_capacity(N);
+    for _ in 0..N {
+        items.push(read_item(input)?);
+    }
+    Ok(items.try_into().unwrap_or_else(|_| unreachable!()))
+}
+
+fn read_vec<'a, T>(
+    input: &mut BitInput<'a>,
+    read_item: impl Fn(&mut BitInput<'a>) -> BitResult<'a, T>,
+) -> BitResult<'a, Vec<T>> {
+    let length = read_unsigned(input, 32)?;
+    read_vec_of_length(input, length, read_item)
+}
+
+fn read_vec_of_length<'a, T>(
+    input: &mut BitInput<'a>,
+    length: u64,
+    read_item: impl Fn(&mut BitInput<'a>) -> BitResult<'a, T>,
+) -> BitResult<'a, Vec<T>> {
+    (0..length).map(|_| read_item(input)).collect()
+}
+
+"#,
+    );
+
+    code
+}
+
+/// Returns the local variable a parser binds the value of a field to, prefixed so that it
+/// cannot shadow the `input` argument or the helpers called by the parser.
+fn field_variable(field_name: &str) -> String {
+    format!("field_{field_name}")
+}
+
+/// Returns an expression reading a value of the given type from `input`. The `size_in_bits`
+/// overrides the natural size of scalar types, as requested by the `bits`/`bytes` attributes.
+/// The `linked_field` is the field of the structure holding the discriminator of a union or
+/// the length of a dynamic array, which then has no length prefix.
+fn generate_read_expression(
+    lookup: &DefinitionLookup,
+    type_identifier: &TypeIdentifier,
+    size_in_bits: Option<u64>,
+    linked_field: Option<&str>,
+) -> String {
+    let type_identifier = lookup.resolve(type_identifier);
+    let bits = size_in_bits.or(type_identifier.builtin_size_in_bits());
+
+    match type_identifier {
+        TypeIdentifier::Void => "()".to_string(),
+        TypeIdentifier::Bit => format!("read_unsigned(input, {})? != 0", bits.unwrap_or(1)),
+        TypeIdentifier::Float32 => "f32::from_bits(read_unsigned(input, 32)? as u32)".to_string(),
+        TypeIdentifier::Float64 => "f64::from_bits(read_unsigned(input, 64)?)".to_string(),
+        TypeIdentifier::StaticArray { r#type, .. } => format!(
+            "read_array(input, |input| Ok({}))?",
+            generate_read_expression(lookup, r#type, None, None)
+        ),
+        TypeIdentifier::DynamicArray { r#type } => match linked_field {
+            Some(length) => format!(
+                "read_vec_of_length(input, {} as u64, |input| Ok({}))?",
+                field_variable(length),
+                generate_read_expression(lookup, r#type, None, None)
+            ),
+            None => format!(
+                "read_vec(input, |input| Ok({}))?",
+                generate_read_expression(lookup, r#type, None, None)
+            ),
+        },
+        TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => format!(
+                "read_enum::<{}>(input, {})?",
+                enumeration.name.name,
+                size_in_bits.unwrap_or(enumeration.size_in_bits())
+            ),
+            Some(Definition::Union(union)) => match linked_field {
+                Some(discriminator) => format!(
+                    "parse_{}_bits(input, {} as u64)?",
+                    to_snake_case(&union.name.name),
+                    field_variable(discriminator)
+                ),
+                None => "Err(invalid_value(*input))?".to_string(),
+            },
+            _ => format!("parse_{}_bits(input)?", to_snake_case(&identifier.name)),
+        },
+        _ => format!(
+            "read_unsigned(input, {})? as {}",
+            bits.unwrap_or(64),
+            generate_type_identifier_code(type_identifier)
+        ),
+    }
+}
+
+fn generate_structure_field_read_code(
+    lookup: &DefinitionLookup,
+    structure: &StructureDefinition,
+    field: &StructureField,
+) -> String {
+    let name = field_variable(&field.name.name);
+    let mut code = format!(
+        "    let {}: {} = {};\n",
+        name,
+        generate_type_identifier_code(&field.r#type),
+        generate_read_expression(
+            lookup,
+            &field.r#type,
+            field_size_in_bits_from_attributes(&field.attributes),
+            discriminated_by(&field.attributes).or_else(|| length_field(structure, field)),
+        )
+    );
+    if let Some(expression) = assertion(&field.attributes) {
+        code.push_str(&format!(
+            "    {{\n        let value = {name};\n        if !({expression}) {{\n            \
+             return Err(invalid_value(*input));\n        }}\n    }}\n"
+        ));
+    }
+    code
+}
+
+fn generate_structure_parser_code(
+    lookup: &DefinitionLookup,
+    structure: &StructureDefinition,
+    options: &RustCodegenOptions,
+) -> String {
+    let name = &structure.name.name;
+    let function_name = to_snake_case(name);
+    let mut code = String::new();
+
+    match options.parser_crate {
+        ParserCrate::Nom => code.push_str(&format!(
+            "pub fn parse_{function_name}<'a>(input: &'a [u8]) -> nom::IResult<&'a [u8], {name}> {{\n    \
+             nom::bits::bits(|mut input: BitInput<'a>| {{\n        \
+             let value = parse_{function_name}_bits(&mut input)?;\n        \
+             Ok((input, value))\n    \
+             }})\n    \
+             .parse(input)\n}}\n\n"
+        )),
+        ParserCrate::Winnow => code.push_str(&format!(
+            "pub fn parse_{function_name}(input: &mut &[u8]) -> winnow::ModalResult<{name}> {{\n    \
+             winnow::binary::bits::bits(parse_{function_name}_bits).parse_next(input)\n}}\n\n"
+        )),
+    }
+
+    code.push_str(&format!(
+        "fn parse_{function_name}_bits<'a>(input: &mut BitInput<'a>) -> BitResult<'a, {name}> {{\n"
+    ));
+    for field in &structure.fields {
+        code.push_str(&generate_structure_field_read_code(
+            lookup, structure, field,
+        ));
+    }
+    code.push_str(&format!("    Ok({name} {{\n"));
+    for field in &structure.fields {
+        code.push_str(&format!(
+            "        {}: {},\n",
+            rust_identifier(&field.name.name),
+            field_variable(&field.name.name)
+        ));
+    }
+    code.push_str("    })\n}\n\n");
+    code
+}
+
+fn generate_union_parser_code(lookup: &DefinitionLookup, union: &UnionDefinition) -> String {
+    let name = &union.name.name;
+    let function_name = to_snake_case(name);
+    let mut code = String::new();
+
+    code.push_str(&format!(
+        "fn parse_{function_name}_bits<'a>(input: &mut BitInput<'a>, discriminator: u64) -> BitResult<'a, {name}> {{\n"
+    ));
+    code.push_str("    match discriminator {\n");
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue {
+                name: field_name,
+                r#type,
+                discriminator,
+            } => code.push_str(&format!(
+                "        {} => Ok({}::{}({})),\n",
+                discriminator,
+                name,
+                rust_identifier(&field_name.name),
+                generate_read_expression(lookup, r#type, None, None)
+            )),
+            UnionField::RangeOfValues {
+                name: field_name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => code.push_str(&format!(
+                "        {}..={} => Ok({}::{} {{\n            discriminator,\n            value: {},\n        }}),\n",
+                start_discriminator,
+                end_discriminator,
+                name,
+                rust_identifier(&field_name.name),
+                generate_read_expression(lookup, r#type, None, None)
+            )),
+        }
+    }
+    code.push_str("        _ => Err(invalid_value(*input)),\n    }\n}\n\n");
+    code
+}
+
+fn generate_enumeration_try_from_code(enumeration: &EnumerationDefinition) -> String {
+    let name = &enumeration.name.name;
+    let mut code = String::new();
+    code.push_str(&format!("impl TryFrom<u64> for {name} {{\n"));
+    code.push_str("    type Error = u64;\n\n");
+    code.push_str("    fn try_from(value: u64) -> Result<Self, Self::Error> {\n");
+    if enumeration.is_signed() {
+        // The value holds the bits of a signed integer, as read for signed integer fields
+        code.push_str(&format!(
+            "        match value as i{} {{\n",
+            enumeration.size_in_bits()
+        ));
+    } else {
+        code.push_str("        match value {\n");
+    }
+    for field in &enumeration.fields {
+        match field {
+            EnumerationField::SingleValue {
+                name: field_name,
+                value,
+                ..
+            } => {
+                code.push_str(&format!(
+                    "            {} => Ok({}::{}),\n",
+                    value,
+                    name,
+                    rust_identifier(&field_name.name)
+                ));
+            }
+            EnumerationField::RangeOfValues {
+                name: field_name,
+                start,
+                end,
+                ..
+            } => {
+                if start == end {
+                    code.push_str(&format!(
+                        "            {} => Ok({}::{}),\n",
+                        start,
+                        name,
+                        rust_identifier(&field_name.name)
+                    ));
+                } else {
+                    for i in *start..=*end {
+                        code.push_str(&format!(
+                            "            {} => Ok({}::{}),\n",
+                            i,
+                            name,
+                            range_member_name(&field_name.name, i)
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    code.push_str("            _ => Err(value),\n        }\n    }\n}\n\n");
+    code
+}
+
+fn generate_parsers_code(protocol: &Protocol, options: &RustCodegenOptions) -> String {
+    let lookup = DefinitionLookup::new(protocol);
+    let mut code = generate_parser_helpers_code(options);
+
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Enumeration(enumeration) => {
+                code.push_str(&generate_enumeration_try_from_code(enumeration));
+            }
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_parser_code(&lookup, structure, options));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_parser_code(&lookup, union));
+            }
+            Definition::Type(_) | Definition::Flags(_) | Definition::Constant(_) => {}
+        }
+    }
+    code
+}
+
+pub fn generate_rust_code_with_options(
+    protocol: &Protocol,
+    options: &RustCodegenOptions,
+) -> String {
+    let mut code = String::new();
+    code.push_str("#![allow(non_camel_case_types, non_snake_case)]\n\n");
+
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Enumeration(enumeration) => {
+                code.push_str(&generate_enumeration_code(enumeration));
+            }
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_code(structure));
+            }
+            Definition::Type(type_definition) => {
+                code.push_str(&generate_type_definition_code(type_definition));
+            }
+            Definition::Flags(flags) => {
+                code.push_str(&generate_flags_code(flags));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_code(union));
+            }
+            Definition::Constant(constant) => {
+                code.push_str(&format!(
+                    "pub const {}: usize = {};\n\n",
+                    constant.name.name, constant.value
+                ));
+            }
+        }
+    }
+
+    if options.emit_parsers {
+        code.push_str(&generate_parsers_code(protocol, options));
+    }
+    code
+}
+
+pub fn generate_rust_code(protocol: &Protocol) -> String {
+    generate_rust_code_with_options(protocol, &RustCodegenOptions::default())
+}
+
+pub fn generate_rust_code_from_string_with_options(
+    input: &str,
+    options: &RustCodegenOptions,
+) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok(generate_rust_code_with_options(&sorted, options))
+}
+
+pub fn generate_rust_code_from_string(input: &str) -> Result<String, MeksmithError> {
+    generate_rust_code_from_string_with_options(input, &RustCodegenOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    static EXPECTED_PING_PONG_RUST_OUTPUT: &str = r#"#![allow(non_camel_case_types, non_snake_case)]
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum MessageType {
+    ping = 0,
+    pong = 1,
+}
+
+pub type IpAddress = [u8; 4];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ping {
+    pub device_ip: IpAddress,
+    pub device_port: u16,
+    pub sequence_number: u32,
+}
+
+pub type DeviceName = [u8; 32];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum DeviceStatus {
+    reserved = 0,
+    up = 1,
+    up_but_overloaded = 2,
+    up_but_network_issues = 3,
+    unavailable = 4,
+    reserved_5 = 5,
+    reserved_6 = 6,
+    reserved_7 = 7,
+    reserved_8 = 8,
+    reserved_9 = 9,
+    reserved_10 = 10,
+    reserved_11 = 11,
+    reserved_12 = 12,
+    reserved_13 = 13,
+    reserved_14 = 14,
+    reserved_15 = 15,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pong {
+    pub sequence_number: u32,
+    pub device_name: DeviceName,
+    pub uptime_in_seconds: u32,
+    pub status: DeviceStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PingPong {
+    ping(Ping),
+    pong(Pong),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub message_type: MessageType,
+    pub message: PingPong,
+}
+
+"#;
+
+    #[test]
+    fn test_generate_rust_code_from_string() {
+        let output = generate_rust_code_from_string(PING_PONG).unwrap();
+        assert_eq!(output, EXPECTED_PING_PONG_RUST_OUTPUT);
+    }
+
+    #[rstest]
+    #[case("PingPong", "ping_pong")]
+    #[case("IQData", "iq_data")]
+    #[case("CANFrame", "can_frame")]
+    #[case("IWFStartUp", "iwf_start_up")]
+    #[case("my_enum_alias_t", "my_enum_alias_t")]
+    #[case("Message2Type", "message2_type")]
+    fn test_to_snake_case(#[case] name: &str, #[case] expected: &str) {
+        assert_eq!(to_snake_case(name), expected);
+    }
+
+    #[rstest]
+    #[case("type", "r#type")]
+    #[case("match", "r#match")]
+    #[case("self", "self_")]
+    #[case("Self", "Self_")]
+    #[case("crate", "crate_")]
+    #[case("super", "super_")]
+    #[case("length", "length")]
+    fn test_rust_identifier(#[case] name: &str, #[case] expected: &str) {
+        assert_eq!(rust_identifier(name), expected);
+    }
+
+    #[rstest]
+    #[case::c_packed("[repr=c_packed]", "#[repr(C, packed)]\n")]
+    #[case::c("[repr=c]", "#[repr(C)]\n")]
+    #[case::transparent("[repr=transparent]", "#[repr(transparent)]\n")]
+    fn test_generate_rust_code_with_repr(#[case] attributes: &str, #[case] expected_repr: &str) {
+        let output = generate_rust_code_from_string(&format!(
+            "{attributes} struct Header {{ version: uint8; }};"
+        ))
+        .unwrap();
+        assert!(output.contains(&format!(
+            "#[derive(Debug, Clone, PartialEq)]\n{expected_repr}pub struct Header {{\n"
+        )));
+    }
+
+    #[test]
+    fn test_generate_rust_code_without_parsers() {
+        let output = generate_rust_code_from_string(PING_PONG).unwrap();
+        assert!(!output.contains("fn parse_"));
+        assert!(!output.contains("nom::"));
+    }
+
+    #[rstest]
+    #[case(
+        ParserCrate::Nom,
+        "pub fn parse_ping<'a>(input: &'a [u8]) -> nom::IResult<&'a [u8], Ping>"
+    )]
+    #[case(
+        ParserCrate::Winnow,
+        "pub fn parse_ping(input: &mut &[u8]) -> winnow::ModalResult<Ping>"
+    )]
+    fn test_generate_rust_code_with_parser_per_structure(
+        #[case] parser_crate: ParserCrate,
+        #[case] ping_parser_signature: &str,
+    ) {
+        let options = RustCodegenOptions {
+            emit_parsers: true,
+            parser_crate,
+            ..Default::default()
+        };
+        let output = generate_rust_code_from_string_with_options(PING_PONG, &options).unwrap();
+
+        assert!(output.contains(ping_parser_signature));
+        for structure in ["message", "ping", "pong"] {
+            assert!(
+                output.contains(&format!("pub fn parse_{structure}("))
+                    || output.contains(&format!("pub fn parse_{structure}<'a>(")),
+                "Missing parser for {structure}"
+            );
+        }
+        assert!(output.contains(
+            "fn parse_ping_pong_bits<'a>(input: &mut BitInput<'a>, discriminator: u64) -> BitResult<'a, PingPong>"
+        ));
+    }
+
+    #[test]
+    fn test_generate_rust_code_with_parsers_reads_fields_in_order() {
+        let options = RustCodegenOptions {
+            emit_parsers: true,
+            ..Default::default()
+        };
+        let output = generate_rust_code_from_string_with_options(PING_PONG, &options).unwrap();
+
+        assert!(output.contains(
+            "    let field_message_type: MessageType = read_enum::<MessageType>(input, 1)?;\n    \
+             let field_message: PingPong = parse_ping_pong_bits(input, field_message_type as u64)?;\n"
+        ));
+        assert!(output.contains(
+            "    let field_device_ip: IpAddress = read_array(input, |input| Ok(read_unsigned(input, 8)? as u8))?;\n    \
+             let field_device_port: u16 = read_unsigned(input, 16)? as u16;\n"
+        ));
+        assert!(output.contains("            0 => Ok(MessageType::ping),\n"));
+    }
+
+    #[test]
+    fn test_generate_rust_code_with_parsers_checks_assertions() {
+        let options = RustCodegenOptions {
+            emit_parsers: true,
+            ..Default::default()
+        };
+        let output = generate_rust_code_from_string_with_options(
+            r#"struct Reading { [assert="value <= 100"] percent: uint8; raw: uint16; };"#,
+            &options,
+        )
+        .unwrap();
+
+        assert!(output.contains(
+            "    let field_percent: u8 = read_unsigned(input, 8)? as u8;\n    \
+             {\n        \
+             let value = field_percent;\n        \
+             if !(value <= 100) {\n            \
+             return Err(invalid_value(*input));\n        \
+             }\n    \
+             }\n    \
+             let field_raw: u16 = read_unsigned(input, 16)? as u16;\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_rust_code_with_parsers_keeps_input_of_fields_named_input() {
+        let options = RustCodegenOptions {
+            emit_parsers: true,
+            ..Default::default()
+        };
+        let output = generate_rust_code_from_string_with_options(
+            "struct S { input: uint8; read_unsigned: uint8; value: uint16; };",
+            &options,
+        )
+        .unwrap();
+
+        assert!(output.contains(
+            "    let field_input: u8 = read_unsigned(input, 8)? as u8;\n    \
+             let field_read_unsigned: u8 = read_unsigned(input, 8)? as u8;\n    \
+             let field_value: u16 = read_unsigned(input, 16)? as u16;\n    \
+             Ok(S {\n        \
+             input: field_input,\n        \
+             read_unsigned: field_read_unsigned,\n        \
+             value: field_value,\n    \
+             })\n"
+        ));
+    }
+
+    #[rstest]
+    #[case(Endianness::Big, false)]
+    #[case(Endianness::Little, true)]
+    #[case(Endianness::Native, true)]
+    fn test_generate_rust_code_with_parsers_uses_endianness(
+        #[case] endianness: Endianness,
+        #[case] swaps_bytes: bool,
+    ) {
+        let options = RustCodegenOptions {
+            emit_parsers: true,
+            endianness,
+            ..Default::default()
+        };
+        let output = generate_rust_code_from_string_with_options(PING_PONG, &options).unwrap();
+        assert_eq!(output.contains("value.swap_bytes()"), swaps_bytes);
+    }
+}
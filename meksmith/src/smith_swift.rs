@@ -0,0 +1,319 @@
+use crate::MeksmithError;
+use crate::ast::{
+    Definition, EnumerationDefinition, EnumerationField, FlagsDefinition, Protocol,
+    StructureDefinition, TypeDefinition, TypeIdentifier, UnionDefinition, UnionField,
+    range_member_name,
+};
+
+const SWIFT_KEYWORDS: &[&str] = &[
+    "as",
+    "break",
+    "case",
+    "class",
+    "continue",
+    "default",
+    "defer",
+    "do",
+    "else",
+    "enum",
+    "extension",
+    "false",
+    "for",
+    "func",
+    "if",
+    "import",
+    "in",
+    "init",
+    "internal",
+    "is",
+    "let",
+    "nil",
+    "operator",
+    "private",
+    "protocol",
+    "public",
+    "repeat",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "subscript",
+    "super",
+    "switch",
+    "throw",
+    "true",
+    "try",
+    "typealias",
+    "var",
+    "where",
+    "while",
+];
+
+/// Escapes identifiers colliding with Swift keywords using backticks.
+fn swift_identifier(name: &str) -> String {
+    if SWIFT_KEYWORDS.contains(&name) {
+        format!("`{name}`")
+    } else {
+        name.to_string()
+    }
+}
+
+fn generate_type_identifier_code(type_identifier: &TypeIdentifier) -> String {
+    match type_identifier {
+        TypeIdentifier::Integer8 => "Int8".to_string(),
+        TypeIdentifier::Integer16 => "Int16".to_string(),
+        TypeIdentifier::Integer32 => "Int32".to_string(),
+        TypeIdentifier::Integer64 => "Int64".to_string(),
+        TypeIdentifier::UnsignedInteger8 => "UInt8".to_string(),
+        TypeIdentifier::UnsignedInteger16 => "UInt16".to_string(),
+        TypeIdentifier::UnsignedInteger32 => "UInt32".to_string(),
+        TypeIdentifier::UnsignedInteger64 => "UInt64".to_string(),
+        TypeIdentifier::Float32 => "Float".to_string(),
+        TypeIdentifier::Float64 => "Double".to_string(),
+        TypeIdentifier::Bit => "Bool".to_string(),
+        TypeIdentifier::Byte | TypeIdentifier::Char => "UInt8".to_string(),
+        TypeIdentifier::Void => "Void".to_string(),
+        TypeIdentifier::UserDefined(identifier) => identifier.name.clone(),
+        TypeIdentifier::StaticArray { r#type, .. } | TypeIdentifier::DynamicArray { r#type } => {
+            format!("[{}]", generate_type_identifier_code(r#type))
+        }
+    }
+}
+
+/// Returns a trailing comment documenting the length of a static array, as Swift arrays
+/// do not carry their size in the type.
+fn generate_length_comment(type_identifier: &TypeIdentifier) -> String {
+    match type_identifier {
+        TypeIdentifier::StaticArray { size, .. } => format!(" // length: {size}"),
+        _ => String::new(),
+    }
+}
+
+/// Returns the raw type of an enumeration, which is `UInt64` only for enumerations
+/// whose values do not fit in an `UInt32`. Enumerations with negative values get the
+/// signed `Int32` or `Int64` instead.
+fn enumeration_raw_type(enumeration: &EnumerationDefinition) -> &'static str {
+    match (enumeration.is_signed(), enumeration.size_in_bits()) {
+        (true, 64) => "Int64",
+        (true, _) => "Int32",
+        (false, 64) => "UInt64",
+        (false, _) => "UInt32",
+    }
+}
+
+fn generate_enumeration_code(enumeration: &EnumerationDefinition) -> String {
+    let mut code = format!(
+        "enum {}: {}, Codable {{\n",
+        enumeration.name.name,
+        enumeration_raw_type(enumeration)
+    );
+    for field in &enumeration.fields {
+        match field {
+            EnumerationField::SingleValue { name, value, .. } => {
+                code.push_str(&format!(
+                    "    case {} = {value}\n",
+                    swift_identifier(&name.name)
+                ));
+            }
+            EnumerationField::RangeOfValues {
+                name, start, end, ..
+            } => {
+                if start == end {
+                    code.push_str(&format!(
+                        "    case {} = {start}\n",
+                        swift_identifier(&name.name)
+                    ));
+                } else {
+                    for i in *start..=*end {
+                        code.push_str(&format!(
+                            "    case {} = {i}\n",
+                            range_member_name(&name.name, i)
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+fn generate_structure_code(structure: &StructureDefinition) -> String {
+    let mut code = format!("struct {}: Codable {{\n", structure.name.name);
+    for field in &structure.fields {
+        code.push_str(&format!(
+            "    var {}: {}{}\n",
+            swift_identifier(&field.name.name),
+            generate_type_identifier_code(&field.r#type),
+            generate_length_comment(&field.r#type)
+        ));
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+/// Unions become enumerations with associated values. Fields covering a range of
+/// discriminators also carry the discriminator, so it can be encoded back. Fields of type
+/// `void` carry no value, as `Void` is not `Codable`.
+fn generate_union_code(union: &UnionDefinition) -> String {
+    let mut code = format!("indirect enum {}: Codable {{\n", union.name.name);
+    for field in &union.fields {
+        match field {
+            UnionField::SingleValue {
+                name,
+                r#type: TypeIdentifier::Void,
+                ..
+            } => {
+                code.push_str(&format!("    case {}\n", swift_identifier(&name.name)));
+            }
+            UnionField::RangeOfValues {
+                name,
+                r#type: TypeIdentifier::Void,
+                ..
+            } => {
+                code.push_str(&format!(
+                    "    case {}(discriminator: UInt64)\n",
+                    swift_identifier(&name.name)
+                ));
+            }
+            UnionField::SingleValue { name, r#type, .. } => {
+                code.push_str(&format!(
+                    "    case {}({}){}\n",
+                    swift_identifier(&name.name),
+                    generate_type_identifier_code(r#type),
+                    generate_length_comment(r#type)
+                ));
+            }
+            UnionField::RangeOfValues { name, r#type, .. } => {
+                code.push_str(&format!(
+                    "    case {}(discriminator: UInt64, value: {}){}\n",
+                    swift_identifier(&name.name),
+                    generate_type_identifier_code(r#type),
+                    generate_length_comment(r#type)
+                ));
+            }
+        }
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+/// Sets of flags become `OptionSet`s backed by their underlying integer type.
+fn generate_flags_code(flags: &FlagsDefinition) -> String {
+    let name = &flags.name.name;
+    let mut code = format!(
+        "struct {name}: OptionSet, Codable {{\n    let rawValue: {}\n\n",
+        generate_type_identifier_code(&flags.r#type)
+    );
+    for field in &flags.fields {
+        code.push_str(&format!(
+            "    static let {} = {name}(rawValue: 0x{:X})\n",
+            swift_identifier(&field.name.name),
+            field.value
+        ));
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+fn generate_type_definition_code(type_definition: &TypeDefinition) -> String {
+    format!(
+        "typealias {} = {}{}\n\n",
+        type_definition.new_type.name,
+        generate_type_identifier_code(&type_definition.r#type),
+        generate_length_comment(&type_definition.r#type)
+    )
+}
+
+/// Generates Swift types conforming to `Codable` for the protocol: structures become
+/// structs, enumerations become enums backed by their raw value, unions become enums
+/// with associated values, flags become option sets and type definitions become typealiases.
+pub fn generate_swift(protocol: &Protocol) -> String {
+    let mut code = String::from("import Foundation\n\n");
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Enumeration(enumeration) => {
+                code.push_str(&generate_enumeration_code(enumeration));
+            }
+            Definition::Structure(structure) => {
+                code.push_str(&generate_structure_code(structure));
+            }
+            Definition::Type(type_definition) => {
+                code.push_str(&generate_type_definition_code(type_definition));
+            }
+            Definition::Flags(flags) => {
+                code.push_str(&generate_flags_code(flags));
+            }
+            Definition::Union(union) => {
+                code.push_str(&generate_union_code(union));
+            }
+            Definition::Constant(constant) => {
+                code.push_str(&format!(
+                    "let {} = {}\n\n",
+                    constant.name.name, constant.value
+                ));
+            }
+        }
+    }
+    code
+}
+
+pub fn generate_swift_from_string(input: &str) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok(generate_swift(&sorted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    #[test]
+    fn test_generate_swift_structures() {
+        let output = generate_swift_from_string(PING_PONG).unwrap();
+        assert!(output.starts_with("import Foundation\n\n"));
+        assert!(output.contains(
+            r#"struct Ping: Codable {
+    var device_ip: IpAddress
+    var device_port: UInt16
+    var sequence_number: UInt32
+}
+"#
+        ));
+        assert!(output.contains("typealias IpAddress = [UInt8] // length: 4\n"));
+        assert!(output.contains(
+            r#"indirect enum PingPong: Codable {
+    case ping(Ping)
+    case pong(Pong)
+}
+"#
+        ));
+    }
+
+    #[test]
+    fn test_generate_swift_enumerations() {
+        let output = generate_swift_from_string(PING_PONG).unwrap();
+        assert!(output.contains(
+            r#"enum MessageType: UInt32, Codable {
+    case ping = 0
+    case pong = 1
+}
+"#
+        ));
+        assert!(output.contains("    case reserved = 0\n    case up = 1\n"));
+        assert!(output.contains("    case reserved_15 = 15\n}\n"));
+    }
+
+    #[test]
+    fn test_generate_swift_arrays_and_keywords() {
+        let output = generate_swift_from_string(
+            "struct Data { payload: byte[]; samples: int32[8]; default: float64; };",
+        )
+        .unwrap();
+        assert!(output.contains("    var payload: [UInt8]\n"));
+        assert!(output.contains("    var samples: [Int32] // length: 8\n"));
+        assert!(output.contains("    var `default`: Double\n"));
+    }
+}
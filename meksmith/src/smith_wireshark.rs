@@ -0,0 +1,733 @@
+use crate::Endianness;
+use crate::MeksmithError;
+use crate::ast::{
+    Definition, DefinitionLookup, EnumerationDefinition, EnumerationField, Protocol,
+    StructureDefinition, StructureField, TypeIdentifier, UnionDefinition, UnionField,
+    discriminated_by, field_size_in_bits_from_attributes, length_field, range_member_name,
+    root_structure,
+};
+use crate::smith_rust::to_snake_case;
+
+use std::collections::HashSet;
+
+/// Options controlling the output of the Wireshark smith.
+#[derive(Debug, Clone, Default)]
+pub struct WiresharkCodegenOptions {
+    /// Byte order of the multi-byte values written by the encoders the dissector reads. A
+    /// dissector has a fixed byte order, so [`Endianness::Native`] is read as little-endian,
+    /// which is the byte order of nearly all hosts.
+    pub endianness: Endianness,
+}
+
+impl WiresharkCodegenOptions {
+    fn is_little_endian(&self) -> bool {
+        self.endianness != Endianness::Big
+    }
+}
+
+/// Helpers shared by the dissection functions, preceded by the `little_endian` flag. Values
+/// are read bit by bit, most significant bit first, as the encoders write them with their
+/// default bit order. When the flag is set, the bytes of values of whole bytes and of
+/// floating-point numbers are read in reverse order, as the encoders swap them.
+const HELPERS_CODE: &str = r#"local function add_subtree(tree, tvbuf, bit_offset, label)
+    return tree:add(tvbuf:range(math.floor(bit_offset / 8)), label)
+end
+
+local function close_subtree(subtree, start, bit_offset)
+    subtree:set_len(math.floor((bit_offset + 7) / 8) - math.floor(start / 8))
+end
+
+local function read_le_bits(range, bit_offset, bits)
+    local value = UInt64.new(0)
+    for i = bits / 8 - 1, 0, -1 do
+        value = value:lshift(8):bor(range:bitfield(bit_offset + 8 * i, 8))
+    end
+    if bits > 32 then
+        return value
+    end
+    return value:tonumber()
+end
+
+local function add_bits(tree, field, tvbuf, bit_offset, bits, signed)
+    local range = tvbuf:range(math.floor(bit_offset / 8), math.floor((bit_offset % 8 + bits + 7) / 8))
+    local value
+    if little_endian and bits > 8 and bits % 8 == 0 then
+        value = read_le_bits(range, bit_offset % 8, bits)
+    else
+        value = range:bitfield(bit_offset % 8, bits)
+    end
+    if signed then
+        if bits > 32 then
+            value = Int64.new(value:lower(), value:higher())
+        elseif value >= 2 ^ (bits - 1) then
+            value = value - 2 ^ bits
+        end
+    end
+    tree:add(field, range, value)
+    return bit_offset + bits, value
+end
+
+local function add_float(tree, field, tvbuf, bit_offset, bits)
+    local range = tvbuf:range(math.floor(bit_offset / 8), bits / 8)
+    if little_endian then
+        tree:add(field, range, range:le_float())
+    else
+        tree:add(field, range, range:float())
+    end
+    return bit_offset + bits
+end
+
+"#;
+
+const LUA_KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Returns the expression of the value of a field stored in the `values` table of a dissection
+/// function, indexing the table with a string for names colliding with Lua keywords.
+fn stored_value(name: &str) -> String {
+    if LUA_KEYWORDS.contains(&name) {
+        format!("values[\"{name}\"]")
+    } else {
+        format!("values.{name}")
+    }
+}
+
+/// Definitions and names shared by the generators of all functions of the dissector.
+struct DissectorContext<'a> {
+    lookup: DefinitionLookup<'a>,
+    /// Name of the Lua variable holding the `Proto`, which also prefixes the abbreviations of
+    /// all fields.
+    proto: String,
+}
+
+/// Returns the name of the Lua function dissecting a structure or a union.
+fn dissect_function_name(name: &str) -> String {
+    format!("dissect_{}", to_snake_case(name))
+}
+
+/// Returns the name of the table mapping the values of an enumeration to their names.
+fn value_names_table_name(enumeration_name: &str) -> String {
+    format!("{}_names", to_snake_case(enumeration_name))
+}
+
+/// Returns the base a value is displayed in, which is hexadecimal for bytes, characters and
+/// flags, and decimal for all other values.
+fn display_base(lookup: &DefinitionLookup, type_identifier: &TypeIdentifier) -> &'static str {
+    match type_identifier {
+        TypeIdentifier::Byte | TypeIdentifier::Char => "base.HEX",
+        TypeIdentifier::StaticArray { r#type, .. } | TypeIdentifier::DynamicArray { r#type } => {
+            display_base(lookup, r#type)
+        }
+        TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name) {
+            Some(Definition::Flags(_)) => "base.HEX",
+            Some(Definition::Type(type_definition)) => {
+                display_base(lookup, &type_definition.r#type)
+            }
+            _ => "base.DEC",
+        },
+        _ => "base.DEC",
+    }
+}
+
+/// Returns the innermost element type of a (possibly nested) array, or the type itself.
+fn element_type<'a>(
+    lookup: &DefinitionLookup<'a>,
+    type_identifier: &'a TypeIdentifier,
+) -> &'a TypeIdentifier {
+    match lookup.resolve(type_identifier) {
+        TypeIdentifier::StaticArray { r#type, .. } | TypeIdentifier::DynamicArray { r#type } => {
+            element_type(lookup, r#type)
+        }
+        type_identifier => type_identifier,
+    }
+}
+
+/// Returns the size in bits and signedness of the integers of a scalar type, or `None` for
+/// floating-point numbers and types dissected by their own functions. The `size_in_bits`
+/// overrides the natural size, as requested by the `bits`/`bytes` attributes.
+fn integer_layout(
+    lookup: &DefinitionLookup,
+    type_identifier: &TypeIdentifier,
+    size_in_bits: Option<u64>,
+) -> Option<(u64, bool)> {
+    match type_identifier {
+        TypeIdentifier::Integer8
+        | TypeIdentifier::Integer16
+        | TypeIdentifier::Integer32
+        | TypeIdentifier::Integer64 => Some((
+            size_in_bits.or(type_identifier.builtin_size_in_bits())?,
+            true,
+        )),
+        TypeIdentifier::UnsignedInteger8
+        | TypeIdentifier::UnsignedInteger16
+        | TypeIdentifier::UnsignedInteger32
+        | TypeIdentifier::UnsignedInteger64
+        | TypeIdentifier::Byte
+        | TypeIdentifier::Char
+        | TypeIdentifier::Bit => Some((
+            size_in_bits.or(type_identifier.builtin_size_in_bits())?,
+            false,
+        )),
+        TypeIdentifier::UserDefined(identifier) => match lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => Some((
+                size_in_bits.unwrap_or(enumeration.size_in_bits()),
+                enumeration.is_signed(),
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the declaration of the `ProtoField` showing the values of a field, or `None` for
+/// fields holding structures or unions, which get their own subtrees instead. Arrays are
+/// shown as one entry per element, so they are declared with their element type.
+fn generate_proto_field_code(
+    context: &DissectorContext,
+    abbreviation: &str,
+    label: &str,
+    type_identifier: &TypeIdentifier,
+    size_in_bits: Option<u64>,
+) -> Option<String> {
+    let abbreviation = format!("{}.{abbreviation}", context.proto);
+    let element = element_type(&context.lookup, type_identifier);
+    match element {
+        TypeIdentifier::Float32 => {
+            return Some(format!("ProtoField.float(\"{abbreviation}\", \"{label}\")"));
+        }
+        TypeIdentifier::Float64 => {
+            return Some(format!(
+                "ProtoField.double(\"{abbreviation}\", \"{label}\")"
+            ));
+        }
+        TypeIdentifier::Bit if size_in_bits.is_none_or(|size| size == 1) => {
+            return Some(format!("ProtoField.bool(\"{abbreviation}\", \"{label}\")"));
+        }
+        _ => {}
+    }
+    let (bits, is_signed) = integer_layout(&context.lookup, element, size_in_bits)?;
+    let width = match bits {
+        0..=8 => 8,
+        9..=16 => 16,
+        17..=24 => 24,
+        25..=32 => 32,
+        _ => 64,
+    };
+    let kind = if is_signed { "int" } else { "uint" };
+    let base = display_base(&context.lookup, type_identifier);
+    Some(match element {
+        TypeIdentifier::UserDefined(identifier) => format!(
+            "ProtoField.{kind}{width}(\"{abbreviation}\", \"{label}\", {base}, {})",
+            value_names_table_name(&identifier.name)
+        ),
+        _ => format!("ProtoField.{kind}{width}(\"{abbreviation}\", \"{label}\", {base})"),
+    })
+}
+
+/// Returns the statements adding a value of the given type to `subtree`, advancing
+/// `bit_offset` past it. The `field` is the key of its `ProtoField` in the `fields` table,
+/// `length` the expression holding the number of elements of a dynamic array and
+/// `discriminator` the one selecting the field of a union. The value is stored to `target`,
+/// e.g. `values.kind`, when other fields refer to it.
+#[allow(clippy::too_many_arguments)]
+fn generate_dissect_statements(
+    context: &DissectorContext,
+    field: &str,
+    label: &str,
+    type_identifier: &TypeIdentifier,
+    size_in_bits: Option<u64>,
+    length: Option<&str>,
+    discriminator: Option<&str>,
+    target: Option<&str>,
+    depth: usize,
+) -> String {
+    let indent = "    ".repeat(depth + 1);
+    let assignment = match target {
+        Some(target) => format!("bit_offset, {target}"),
+        None => "bit_offset".to_string(),
+    };
+    let type_identifier = context.lookup.resolve(type_identifier);
+    match type_identifier {
+        TypeIdentifier::Void => String::new(),
+        TypeIdentifier::Float32 | TypeIdentifier::Float64 => format!(
+            "{indent}bit_offset = add_float(subtree, fields.{field}, tvbuf, bit_offset, {})\n",
+            type_identifier.builtin_size_in_bits().unwrap_or(32)
+        ),
+        TypeIdentifier::StaticArray { r#type, size, .. } => {
+            let index = format!("i{depth}");
+            format!(
+                "{indent}for {index} = 1, {size} do\n{}{indent}end\n",
+                generate_dissect_statements(
+                    context,
+                    field,
+                    label,
+                    r#type,
+                    None,
+                    None,
+                    None,
+                    None,
+                    depth + 1
+                )
+            )
+        }
+        TypeIdentifier::DynamicArray { r#type } => match length {
+            Some(length) => {
+                let index = format!("i{depth}");
+                format!(
+                    "{indent}for {index} = 1, {length} do\n{}{indent}end\n",
+                    generate_dissect_statements(
+                        context,
+                        field,
+                        label,
+                        r#type,
+                        None,
+                        None,
+                        None,
+                        None,
+                        depth + 1
+                    )
+                )
+            }
+            None => format!("{indent}error(\"length of {label} is unknown\")\n"),
+        },
+        TypeIdentifier::UserDefined(identifier) => match context.lookup.get(&identifier.name) {
+            Some(Definition::Enumeration(enumeration)) => {
+                let (bits, is_signed) =
+                    integer_layout(&context.lookup, type_identifier, size_in_bits)
+                        .unwrap_or((enumeration.size_in_bits(), enumeration.is_signed()));
+                let signed = if is_signed { ", true" } else { "" };
+                format!(
+                    "{indent}{assignment} = add_bits(subtree, fields.{field}, tvbuf, bit_offset, {bits}{signed})\n"
+                )
+            }
+            Some(Definition::Union(union)) => match discriminator {
+                Some(discriminator) => format!(
+                    "{indent}bit_offset = {}(tvbuf, subtree, bit_offset, \"{label}\", {discriminator})\n",
+                    dissect_function_name(&union.name.name)
+                ),
+                None => format!("{indent}error(\"discriminator of {label} is unknown\")\n"),
+            },
+            _ => format!(
+                "{indent}bit_offset = {}(tvbuf, subtree, bit_offset, \"{label}\")\n",
+                dissect_function_name(&identifier.name)
+            ),
+        },
+        _ => {
+            let (bits, is_signed) = integer_layout(&context.lookup, type_identifier, size_in_bits)
+                .unwrap_or((64, false));
+            let signed = if is_signed { ", true" } else { "" };
+            format!(
+                "{indent}{assignment} = add_bits(subtree, fields.{field}, tvbuf, bit_offset, {bits}{signed})\n"
+            )
+        }
+    }
+}
+
+/// Returns the key of the `ProtoField` of a field of a structure or a union in the `fields`
+/// table, and its abbreviation without the protocol prefix.
+fn field_names(definition_name: &str, field_name: &str) -> (String, String) {
+    let definition_name = to_snake_case(definition_name);
+    (
+        format!("{definition_name}_{field_name}"),
+        format!("{definition_name}.{field_name}"),
+    )
+}
+
+/// Returns the names of the fields of a structure whose values other fields refer to, as
+/// discriminators of unions or lengths of dynamic arrays.
+fn referenced_fields(structure: &StructureDefinition) -> HashSet<&str> {
+    structure
+        .fields
+        .iter()
+        .flat_map(|field| {
+            discriminated_by(&field.attributes)
+                .into_iter()
+                .chain(length_field(structure, field))
+        })
+        .collect()
+}
+
+/// Returns whether a dynamic array field of a structure is preceded by a length prefix, which
+/// is the case unless it is linked to the field holding its length.
+fn has_length_prefix(
+    lookup: &DefinitionLookup,
+    structure: &StructureDefinition,
+    field: &StructureField,
+) -> bool {
+    matches!(
+        lookup.resolve(&field.r#type),
+        TypeIdentifier::DynamicArray { .. }
+    ) && length_field(structure, field).is_none()
+}
+
+/// Returns the declarations of the `ProtoField`s of a structure, as entries of the `fields`
+/// table. Dynamic arrays with a length prefix get a `<field>_length` entry for it.
+fn generate_structure_fields_code(
+    context: &DissectorContext,
+    structure: &StructureDefinition,
+) -> String {
+    let mut code = String::new();
+    for field in &structure.fields {
+        let field_name = &field.name.name;
+        let (key, abbreviation) = field_names(&structure.name.name, field_name);
+        if has_length_prefix(&context.lookup, structure, field) {
+            code.push_str(&format!(
+                "    {key}_length = ProtoField.uint32(\"{}.{abbreviation}_length\", \"{field_name}_length\", base.DEC),\n",
+                context.proto
+            ));
+        }
+        if let Some(proto_field) = generate_proto_field_code(
+            context,
+            &abbreviation,
+            field_name,
+            &field.r#type,
+            field_size_in_bits_from_attributes(&field.attributes),
+        ) {
+            code.push_str(&format!("    {key} = {proto_field},\n"));
+        }
+    }
+    code
+}
+
+/// Returns the declarations of the `ProtoField`s of the fields of a union holding values
+/// shown directly, as entries of the `fields` table.
+fn generate_union_fields_code(context: &DissectorContext, union: &UnionDefinition) -> String {
+    let mut code = String::new();
+    for field in &union.fields {
+        let (UnionField::SingleValue { name, r#type, .. }
+        | UnionField::RangeOfValues { name, r#type, .. }) = field;
+        let (key, abbreviation) = field_names(&union.name.name, &name.name);
+        if let Some(proto_field) =
+            generate_proto_field_code(context, &abbreviation, &name.name, r#type, None)
+        {
+            code.push_str(&format!("    {key} = {proto_field},\n"));
+        }
+    }
+    code
+}
+
+/// Returns the table mapping the values of an enumeration to their names, which is shown next
+/// to the values of the fields holding it.
+fn generate_value_names_code(enumeration: &EnumerationDefinition) -> String {
+    let mut code = format!(
+        "local {} = {{\n",
+        value_names_table_name(&enumeration.name.name)
+    );
+    let mut seen = HashSet::new();
+    for field in &enumeration.fields {
+        let (name, values) = match field {
+            EnumerationField::SingleValue { name, value, .. } => (name, *value..=*value),
+            EnumerationField::RangeOfValues {
+                name, start, end, ..
+            } => (name, *start..=*end),
+        };
+        let is_range = values.start() != values.end();
+        for value in values {
+            if !seen.insert(value) {
+                continue;
+            }
+            let member = if is_range {
+                range_member_name(&name.name, value)
+            } else {
+                name.name.clone()
+            };
+            code.push_str(&format!("    [{value}] = \"{member}\",\n"));
+        }
+    }
+    code.push_str("}\n\n");
+    code
+}
+
+/// Returns the function adding the fields of a structure to a subtree, in order.
+fn generate_structure_dissector_code(
+    context: &DissectorContext,
+    structure: &StructureDefinition,
+) -> String {
+    let referenced = referenced_fields(structure);
+    let mut code = format!(
+        "local function {}(tvbuf, tree, bit_offset, label)\n    \
+         local start = bit_offset\n    \
+         local subtree = add_subtree(tree, tvbuf, bit_offset, label)\n",
+        dissect_function_name(&structure.name.name)
+    );
+    if !referenced.is_empty()
+        || structure
+            .fields
+            .iter()
+            .any(|field| has_length_prefix(&context.lookup, structure, field))
+    {
+        code.push_str("    local values = {}\n");
+    }
+    for field in &structure.fields {
+        let field_name = &field.name.name;
+        let (key, _) = field_names(&structure.name.name, field_name);
+        let length = if has_length_prefix(&context.lookup, structure, field) {
+            let length = stored_value(&format!("{field_name}_length"));
+            code.push_str(&format!(
+                "    bit_offset, {length} = add_bits(subtree, fields.{key}_length, tvbuf, bit_offset, 32)\n"
+            ));
+            Some(length)
+        } else {
+            length_field(structure, field).map(stored_value)
+        };
+        let discriminator = discriminated_by(&field.attributes).map(stored_value);
+        let target = referenced
+            .contains(field_name.as_str())
+            .then(|| stored_value(field_name));
+        code.push_str(&generate_dissect_statements(
+            context,
+            &key,
+            field_name,
+            &field.r#type,
+            field_size_in_bits_from_attributes(&field.attributes),
+            length.as_deref(),
+            discriminator.as_deref(),
+            target.as_deref(),
+            0,
+        ));
+    }
+    code.push_str("    close_subtree(subtree, start, bit_offset)\n    return bit_offset\nend\n\n");
+    code
+}
+
+/// Returns the function adding the field of a union selected by the discriminator to a
+/// subtree, with one branch per field. Unknown discriminators are reported as malformed.
+fn generate_union_dissector_code(context: &DissectorContext, union: &UnionDefinition) -> String {
+    let mut code = format!(
+        "local function {}(tvbuf, tree, bit_offset, label, discriminator)\n    \
+         local start = bit_offset\n    \
+         local subtree = add_subtree(tree, tvbuf, bit_offset, label)\n",
+        dissect_function_name(&union.name.name)
+    );
+    for (i, field) in union.fields.iter().enumerate() {
+        let keyword = if i == 0 { "if" } else { "elseif" };
+        let (name, r#type, condition) = match field {
+            UnionField::SingleValue {
+                name,
+                r#type,
+                discriminator,
+            } => (name, r#type, format!("discriminator == {discriminator}")),
+            UnionField::RangeOfValues {
+                name,
+                r#type,
+                start_discriminator,
+                end_discriminator,
+            } => (
+                name,
+                r#type,
+                format!(
+                    "discriminator >= {start_discriminator} and discriminator <= {end_discriminator}"
+                ),
+            ),
+        };
+        let (key, _) = field_names(&union.name.name, &name.name);
+        code.push_str(&format!("    {keyword} {condition} then\n"));
+        code.push_str(&generate_dissect_statements(
+            context, &key, &name.name, r#type, None, None, None, None, 1,
+        ));
+    }
+    code.push_str(
+        "    else\n        \
+         subtree:add_expert_info(PI_MALFORMED, PI_ERROR, \"Unknown discriminator \" .. tostring(discriminator))\n    \
+         end\n    \
+         close_subtree(subtree, start, bit_offset)\n    \
+         return bit_offset\nend\n\n",
+    );
+    code
+}
+
+/// Generates a Wireshark dissector in Lua for the protocol, read from its root structure, which
+/// is the first one not used by any other definition. Every field of a structure, and every
+/// field of a union holding a value, is declared as a `ProtoField` abbreviated
+/// `<protocol>.<definition>.<field>`, where the protocol is named after the module or the root
+/// structure. Structures and unions get their own subtrees, and unions branch on the value of
+/// their discriminators. Values are read as written by the encoders with the byte order of
+/// [`WiresharkCodegenOptions::endianness`] and their other options left to default, while
+/// floating-point numbers must start on a byte boundary.
+///
+/// The dissector is registered for "Decode As" on UDP ports.
+pub fn generate_wireshark_with_options(
+    protocol: &Protocol,
+    options: &WiresharkCodegenOptions,
+) -> String {
+    let root = root_structure(protocol);
+    let title = match (&protocol.name, root) {
+        (Some(name), _) => name.clone(),
+        (None, Some(root)) => root.name.name.clone(),
+        (None, None) => "Protocol".to_string(),
+    };
+    let context = DissectorContext {
+        lookup: DefinitionLookup::new(protocol),
+        proto: to_snake_case(&title),
+    };
+    let proto = &context.proto;
+
+    let mut code = format!("local {proto} = Proto(\"{proto}\", \"{title}\")\n\n");
+    let mut fields = String::new();
+    let mut functions = String::new();
+    for definition in &protocol.definitions {
+        match definition {
+            Definition::Enumeration(enumeration) => {
+                code.push_str(&generate_value_names_code(enumeration));
+            }
+            Definition::Structure(structure) => {
+                fields.push_str(&generate_structure_fields_code(&context, structure));
+                functions.push_str(&generate_structure_dissector_code(&context, structure));
+            }
+            Definition::Union(union) => {
+                fields.push_str(&generate_union_fields_code(&context, union));
+                functions.push_str(&generate_union_dissector_code(&context, union));
+            }
+            Definition::Type(_) | Definition::Flags(_) | Definition::Constant(_) => {}
+        }
+    }
+    code.push_str(&format!(
+        "local fields = {{\n{fields}}}\n{proto}.fields = fields\n\n"
+    ));
+    code.push_str(&format!(
+        "local little_endian = {}\n\n",
+        options.is_little_endian()
+    ));
+    code.push_str(HELPERS_CODE);
+    code.push_str(&functions);
+
+    code.push_str(&format!(
+        "function {proto}.dissector(tvbuf, pinfo, tree)\n    \
+         pinfo.cols.protocol = {proto}.name\n    \
+         local subtree = tree:add({proto}, tvbuf:range())\n"
+    ));
+    if let Some(root) = root {
+        code.push_str(&format!(
+            "    {}(tvbuf, subtree, 0, \"{}\")\n",
+            dissect_function_name(&root.name.name),
+            root.name.name
+        ));
+    }
+    code.push_str(&format!(
+        "    return tvbuf:len()\nend\n\nDissectorTable.get(\"udp.port\"):add_for_decode_as({proto})\n"
+    ));
+    code
+}
+
+pub fn generate_wireshark(protocol: &Protocol) -> String {
+    generate_wireshark_with_options(protocol, &WiresharkCodegenOptions::default())
+}
+
+pub fn generate_wireshark_from_string_with_options(
+    input: &str,
+    options: &WiresharkCodegenOptions,
+) -> Result<String, MeksmithError> {
+    let protocol = crate::parse_protocol_to_ast(input)?;
+    let (sorted, _) = crate::ast::prepare_with_warnings(&protocol)?;
+    Ok(generate_wireshark_with_options(&sorted, options))
+}
+
+pub fn generate_wireshark_from_string(input: &str) -> Result<String, MeksmithError> {
+    generate_wireshark_from_string_with_options(input, &WiresharkCodegenOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    static PING_PONG: &str = include_str!("../examples/data/ping-pong.mek");
+
+    #[rstest]
+    #[case::native(Endianness::Native, "true")]
+    #[case::little(Endianness::Little, "true")]
+    #[case::big(Endianness::Big, "false")]
+    fn test_generate_wireshark_endianness(
+        #[case] endianness: Endianness,
+        #[case] little_endian: &str,
+    ) {
+        let output = generate_wireshark_from_string_with_options(
+            PING_PONG,
+            &WiresharkCodegenOptions { endianness },
+        )
+        .unwrap();
+        let flag = format!("local little_endian = {little_endian}\n\n");
+        let flag_position = output.find(&flag).unwrap();
+        assert!(flag_position < output.find("local function add_bits").unwrap());
+        assert!(output.contains(
+            "    if little_endian and bits > 8 and bits % 8 == 0 then\n        value = read_le_bits(range, bit_offset % 8, bits)\n"
+        ));
+        assert!(output.contains("        tree:add(field, range, range:le_float())\n"));
+    }
+
+    #[test]
+    fn test_generate_wireshark_field_abbreviations() {
+        let output = generate_wireshark_from_string(PING_PONG).unwrap();
+        assert!(output.starts_with("local message = Proto(\"message\", \"Message\")\n\n"));
+        for declaration in [
+            "message_message_type = ProtoField.uint8(\"message.message.message_type\", \"message_type\", base.DEC, message_type_names),",
+            "ping_device_ip = ProtoField.uint8(\"message.ping.device_ip\", \"device_ip\", base.HEX),",
+            "ping_device_port = ProtoField.uint16(\"message.ping.device_port\", \"device_port\", base.DEC),",
+            "pong_status = ProtoField.uint8(\"message.pong.status\", \"status\", base.DEC, device_status_names),",
+        ] {
+            assert!(output.contains(declaration), "{declaration}");
+        }
+        assert!(!output.contains("\"message.message.message\""));
+        assert!(output.contains("    [15] = \"reserved_15\",\n"));
+    }
+
+    #[test]
+    fn test_generate_wireshark_dissector() {
+        let output = generate_wireshark_from_string(PING_PONG).unwrap();
+        assert!(output.contains(
+            r#"local function dissect_message(tvbuf, tree, bit_offset, label)
+    local start = bit_offset
+    local subtree = add_subtree(tree, tvbuf, bit_offset, label)
+    local values = {}
+    bit_offset, values.message_type = add_bits(subtree, fields.message_message_type, tvbuf, bit_offset, 1)
+    bit_offset = dissect_ping_pong(tvbuf, subtree, bit_offset, "message", values.message_type)
+    close_subtree(subtree, start, bit_offset)
+    return bit_offset
+end
+"#
+        ));
+        assert!(output.contains(
+            r#"    if discriminator == 0 then
+        bit_offset = dissect_ping(tvbuf, subtree, bit_offset, "ping")
+    elseif discriminator == 1 then
+        bit_offset = dissect_pong(tvbuf, subtree, bit_offset, "pong")
+    else
+"#
+        ));
+        assert!(output.contains("    dissect_message(tvbuf, subtree, 0, \"Message\")\n"));
+    }
+
+    #[test]
+    fn test_generate_wireshark_arrays() {
+        let output = generate_wireshark_from_string(
+            r#"module Samples;
+struct Frame {
+    [length_of=values] count: uint8;
+    values: int16[];
+    payload: byte[];
+    level: float32;
+};"#,
+        )
+        .unwrap();
+        assert!(output.contains("    frame_payload_length = ProtoField.uint32(\"samples.frame.payload_length\", \"payload_length\", base.DEC),\n"));
+        assert!(output.contains(
+            "    frame_values = ProtoField.int16(\"samples.frame.values\", \"values\", base.DEC),\n"
+        ));
+        assert!(
+            output.contains(
+                "    frame_level = ProtoField.float(\"samples.frame.level\", \"level\"),\n"
+            )
+        );
+        assert!(output.contains(
+            r#"    bit_offset, values.count = add_bits(subtree, fields.frame_count, tvbuf, bit_offset, 8)
+    for i0 = 1, values.count do
+        bit_offset = add_bits(subtree, fields.frame_values, tvbuf, bit_offset, 16, true)
+    end
+    bit_offset, values.payload_length = add_bits(subtree, fields.frame_payload_length, tvbuf, bit_offset, 32)
+    for i0 = 1, values.payload_length do
+"#
+        ));
+    }
+}
@@ -0,0 +1,1786 @@
+use crate::ast::{
+    Attribute, Definition, DefinitionKind, EnumerationField, Protocol, StructureField,
+    TypeIdentifier, UnionField, discriminated_by, length_field,
+};
+
+use std::collections::{HashMap, HashSet};
+
+/// Describes how serious a [`Diagnostic`] is. Errors prevent code generation,
+/// while warnings are only reported next to the generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Represents a single problem found in a protocol.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(message: String) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    pub fn warning(message: String) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+/// Options relaxing the checks of [`collect_diagnostics_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ValidationOptions {
+    /// Reports values claimed by more than one field of an enumeration as warnings about the
+    /// later field shadowing the earlier one, instead of errors.
+    pub warn_shadowing: bool,
+    /// Requires every dynamic array field of a structure to be linked to the field holding its
+    /// length with `length` or `length_of`, and rejects dynamic arrays which cannot be linked,
+    /// i.e. those in unions or nested in other arrays.
+    pub strict: bool,
+}
+
+/// Describes the kind of a definition in a sentence, e.g. "as a structure".
+fn describe_kind(kind: DefinitionKind) -> &'static str {
+    match kind {
+        DefinitionKind::Enumeration => "as an enumeration",
+        DefinitionKind::Structure => "as a structure",
+        DefinitionKind::Union => "as a union",
+        DefinitionKind::Type => "as a type",
+        DefinitionKind::Flags => "as flags",
+        DefinitionKind::Constant => "as a constant",
+    }
+}
+
+/// Ensures that no two definitions share the same name, as the generated code would either
+/// define the type twice or silently drop one of them.
+fn validate_unique_definition_names(protocol: &Protocol) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut owners: HashMap<&str, DefinitionKind> = HashMap::new();
+
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        match owners.get(name.as_str()) {
+            Some(kind) => diagnostics.push(Diagnostic::error(format!(
+                "Definition {name} is defined more than once, {} and {}",
+                describe_kind(*kind),
+                describe_kind(definition.kind())
+            ))),
+            None => {
+                owners.insert(name, definition.kind());
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Ensures that no two definitions share the same value of the `id` attribute,
+/// as otherwise they could not be told apart on the wire.
+fn validate_unique_definition_ids(protocol: &Protocol) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut owners: HashMap<u64, &str> = HashMap::new();
+
+    for definition in &protocol.definitions {
+        let Some(id) = definition.id() else {
+            continue;
+        };
+        let name = &definition.name().name;
+        match owners.get(&id) {
+            Some(owner) => diagnostics.push(Diagnostic::error(format!(
+                "Id {id} is used by both {owner} and {name}"
+            ))),
+            None => {
+                owners.insert(id, name);
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Returns every pair of fields claiming some of the same values, given as the field name with
+/// the inclusive range of claimed values, together with the description of the shared values,
+/// e.g. "value 5" or "values 9..10". The earlier field of each pair comes first.
+fn overlapping_claims<'a, T: Ord + Copy + std::fmt::Display>(
+    claims: &[(&'a str, T, T)],
+    noun: &str,
+) -> Vec<(&'a str, &'a str, String)> {
+    let mut overlaps = Vec::new();
+    for (index, (later, later_start, later_end)) in claims.iter().enumerate() {
+        for (earlier, earlier_start, earlier_end) in &claims[..index] {
+            let start = *later_start.max(earlier_start);
+            let end = *later_end.min(earlier_end);
+            if start > end {
+                continue;
+            }
+            let values = if start == end {
+                format!("{noun} {start}")
+            } else {
+                format!("{noun}s {start}..{end}")
+            };
+            overlaps.push((*earlier, *later, values));
+        }
+    }
+    overlaps
+}
+
+/// Ensures that no value of an enumeration is claimed by more than one of its fields, as then
+/// a reader cannot tell which name applies. With [`ValidationOptions::warn_shadowing`], the
+/// later field is reported as shadowing the earlier one for the overlapping values instead.
+fn validate_enumeration_values_are_unique(
+    protocol: &Protocol,
+    options: &ValidationOptions,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for definition in &protocol.definitions {
+        let Definition::Enumeration(enumeration) = definition else {
+            continue;
+        };
+        let ranges: Vec<(&str, i128, i128)> = enumeration
+            .fields
+            .iter()
+            .map(|field| match field {
+                EnumerationField::SingleValue { name, value, .. } => {
+                    (name.name.as_str(), *value, *value)
+                }
+                EnumerationField::RangeOfValues {
+                    name, start, end, ..
+                } => (name.name.as_str(), *start, *end),
+            })
+            .collect();
+
+        let enumeration_name = &enumeration.name.name;
+        for (earlier, later, values) in overlapping_claims(&ranges, "value") {
+            diagnostics.push(if options.warn_shadowing {
+                Diagnostic::warning(format!(
+                    "Field {later} of {enumeration_name} shadows {earlier} for {values}"
+                ))
+            } else {
+                Diagnostic::error(format!(
+                    "Fields {earlier} and {later} of {enumeration_name} both claim {values}"
+                ))
+            });
+        }
+    }
+    diagnostics
+}
+
+/// Ensures that every range of enumeration values starts at or below its end, taking the sign
+/// of the values into account, as a reversed range would claim no value at all.
+fn validate_enumeration_ranges_are_ordered(protocol: &Protocol) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for definition in &protocol.definitions {
+        let Definition::Enumeration(enumeration) = definition else {
+            continue;
+        };
+        for field in &enumeration.fields {
+            if let EnumerationField::RangeOfValues {
+                name, start, end, ..
+            } = field
+                && start > end
+            {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Field {} of {} has range {start}..{end}, whose start is greater than its end",
+                    name.name, enumeration.name.name
+                )));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Ensures that the base type declared by an enumeration is an integer type able to hold all
+/// of its values, as otherwise some of them could not be stored on the wire. Without a base
+/// type, the values have to fit in a single 64-bit integer, either signed or unsigned.
+fn validate_enumeration_base_types(protocol: &Protocol) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for definition in &protocol.definitions {
+        let Definition::Enumeration(enumeration) = definition else {
+            continue;
+        };
+        let name = &enumeration.name.name;
+        let Some(base) = &enumeration.base else {
+            if enumeration.min_value() < 0 && enumeration.max_value() > i128::from(i64::MAX) {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Enumeration {name} has both negative values and values above {}, \
+                     which no 64-bit integer can hold",
+                    i64::MAX
+                )));
+            }
+            continue;
+        };
+        let keyword = crate::ast::builtin_type_keyword(base).unwrap_or("?");
+        let bits = enumeration.size_in_bits();
+        let (min, max): (i128, i128) = match base {
+            TypeIdentifier::Integer8
+            | TypeIdentifier::Integer16
+            | TypeIdentifier::Integer32
+            | TypeIdentifier::Integer64 => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+            TypeIdentifier::UnsignedInteger8
+            | TypeIdentifier::UnsignedInteger16
+            | TypeIdentifier::UnsignedInteger32
+            | TypeIdentifier::UnsignedInteger64
+            | TypeIdentifier::Byte => (0, (1 << bits) - 1),
+            _ => {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Base type {keyword} of enumeration {name} is not an integer type"
+                )));
+                continue;
+            }
+        };
+        let mut values = vec![enumeration.min_value(), enumeration.max_value()];
+        values.dedup();
+        for value in values {
+            if !(min..=max).contains(&value) {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Base type {keyword} of enumeration {name} cannot hold value {value}"
+                )));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Ensures that no discriminator of a union is claimed by more than one of its fields, as then
+/// a decoder cannot tell which member follows it.
+fn validate_union_discriminators_are_unique(protocol: &Protocol) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for definition in &protocol.definitions {
+        let Definition::Union(union) = definition else {
+            continue;
+        };
+        let ranges: Vec<(&str, u64, u64)> = union
+            .fields
+            .iter()
+            .map(|field| match field {
+                UnionField::SingleValue {
+                    name,
+                    discriminator,
+                    ..
+                } => (name.name.as_str(), *discriminator, *discriminator),
+                UnionField::RangeOfValues {
+                    name,
+                    start_discriminator,
+                    end_discriminator,
+                    ..
+                } => (name.name.as_str(), *start_discriminator, *end_discriminator),
+            })
+            .collect();
+
+        let union_name = &union.name.name;
+        for (earlier, later, discriminators) in overlapping_claims(&ranges, "discriminator") {
+            diagnostics.push(Diagnostic::error(format!(
+                "Fields {earlier} and {later} of {union_name} both claim {discriminators}"
+            )));
+        }
+    }
+    diagnostics
+}
+
+/// Returns whether the type is an integer, which can hold the length of a dynamic array.
+fn is_integer(lookup: &crate::ast::DefinitionLookup, r#type: &TypeIdentifier) -> bool {
+    matches!(
+        lookup.resolve(r#type),
+        TypeIdentifier::Integer8
+            | TypeIdentifier::Integer16
+            | TypeIdentifier::Integer32
+            | TypeIdentifier::Integer64
+            | TypeIdentifier::UnsignedInteger8
+            | TypeIdentifier::UnsignedInteger16
+            | TypeIdentifier::UnsignedInteger32
+            | TypeIdentifier::UnsignedInteger64
+    )
+}
+
+/// Returns why the attribute cannot be applied to the structure field, if it cannot.
+fn field_attribute_mismatch(
+    lookup: &crate::ast::DefinitionLookup,
+    attribute: &Attribute,
+    field: &StructureField,
+) -> Option<&'static str> {
+    match attribute {
+        Attribute::DiscriminatedBy { .. } => match lookup.resolve(&field.r#type) {
+            TypeIdentifier::UserDefined(identifier)
+                if matches!(lookup.get(&identifier.name), Some(Definition::Union(_))) =>
+            {
+                None
+            }
+            _ => Some("only unions have a discriminator"),
+        },
+        Attribute::Id { .. } | Attribute::Repr { .. } => Some("it applies to definitions"),
+        Attribute::LengthOf { .. } if is_integer(lookup, &field.r#type) => None,
+        Attribute::LengthOf { .. } => Some("only integers hold a length"),
+        Attribute::Length { .. } => match lookup.resolve(&field.r#type) {
+            TypeIdentifier::DynamicArray { .. } => None,
+            _ => Some("only dynamic arrays have a length"),
+        },
+        Attribute::BitsSize { .. }
+        | Attribute::BytesSize { .. }
+        | Attribute::CType { .. }
+        | Attribute::Assert { .. } => None,
+    }
+}
+
+/// Returns why the attribute cannot be applied to the definition, if it cannot.
+fn definition_attribute_mismatch(
+    attribute: &Attribute,
+    definition: &Definition,
+) -> Option<&'static str> {
+    match attribute {
+        Attribute::Id { .. } => None,
+        Attribute::Repr { .. } => match definition {
+            Definition::Structure(_) => None,
+            _ => Some("it applies to structures"),
+        },
+        Attribute::DiscriminatedBy { .. }
+        | Attribute::BitsSize { .. }
+        | Attribute::BytesSize { .. }
+        | Attribute::CType { .. }
+        | Attribute::Assert { .. }
+        | Attribute::LengthOf { .. }
+        | Attribute::Length { .. } => Some("it applies to structure fields"),
+    }
+}
+
+/// Ensures that every attribute is applied to a target it has a meaning for, e.g. that
+/// `discriminated_by` is only given to union fields and `repr` only to structures.
+fn validate_attributes_are_applicable(protocol: &Protocol) -> Vec<Diagnostic> {
+    let lookup = crate::ast::DefinitionLookup::new(protocol);
+    let mut diagnostics = Vec::new();
+
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        for attribute in definition.attributes() {
+            if let Some(reason) = definition_attribute_mismatch(attribute, definition) {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Attribute {} cannot be applied to {name}, as {reason}",
+                    attribute.name()
+                )));
+            }
+        }
+        let Definition::Structure(structure_def) = definition else {
+            continue;
+        };
+        for field in &structure_def.fields {
+            for attribute in &field.attributes {
+                if let Some(reason) = field_attribute_mismatch(&lookup, attribute, field) {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "Attribute {} cannot be applied to field {} in {name}, as {reason}",
+                        attribute.name(),
+                        field.name.name
+                    )));
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Ensures that every user-defined type referenced by a definition is defined in the protocol,
+/// and so is every constant giving the size of a static array. Constants are not types.
+fn validate_referenced_types_are_defined(protocol: &Protocol) -> Vec<Diagnostic> {
+    let (constants, types): (Vec<&Definition>, Vec<&Definition>) = protocol
+        .definitions
+        .iter()
+        .partition(|definition| matches!(definition, Definition::Constant(_)));
+    let names = |definitions: Vec<&Definition>| -> HashSet<String> {
+        definitions
+            .into_iter()
+            .map(|definition| definition.name().name.clone())
+            .collect()
+    };
+    let (defined_constants, defined_types) = (names(constants), names(types));
+    let is_undefined = |type_identifier: &TypeIdentifier| {
+        crate::ast::extract_custom_type_identifier_name(type_identifier)
+            .filter(|name| !defined_types.contains(name.as_str()))
+    };
+    let undefined_size_constant = |type_identifier: &TypeIdentifier| {
+        crate::ast::array_size_constants(type_identifier)
+            .into_iter()
+            .map(|constant| constant.name.clone())
+            .find(|name| !defined_constants.contains(name))
+    };
+
+    let mut diagnostics = Vec::new();
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        match definition {
+            Definition::Enumeration(_) | Definition::Flags(_) | Definition::Constant(_) => {}
+            Definition::Structure(structure_def) => {
+                for field in &structure_def.fields {
+                    if let Some(type_name) = is_undefined(&field.r#type) {
+                        diagnostics.push(Diagnostic::error(format!(
+                            "Type {type_name} of field {} in {name} is not defined",
+                            field.name.name
+                        )));
+                    }
+                    if let Some(constant_name) = undefined_size_constant(&field.r#type) {
+                        diagnostics.push(Diagnostic::error(format!(
+                            "Array size {constant_name} of field {} in {name} is not \
+                             a defined constant",
+                            field.name.name
+                        )));
+                    }
+                }
+            }
+            Definition::Union(union_def) => {
+                for field in &union_def.fields {
+                    let (UnionField::SingleValue {
+                        name: field_name,
+                        r#type,
+                        ..
+                    }
+                    | UnionField::RangeOfValues {
+                        name: field_name,
+                        r#type,
+                        ..
+                    }) = field;
+                    if let Some(type_name) = is_undefined(r#type) {
+                        diagnostics.push(Diagnostic::error(format!(
+                            "Type {type_name} of field {} in {name} is not defined",
+                            field_name.name
+                        )));
+                    }
+                    if let Some(constant_name) = undefined_size_constant(r#type) {
+                        diagnostics.push(Diagnostic::error(format!(
+                            "Array size {constant_name} of field {} in {name} is not \
+                             a defined constant",
+                            field_name.name
+                        )));
+                    }
+                }
+            }
+            Definition::Type(type_def) => {
+                if let Some(type_name) = is_undefined(&type_def.r#type) {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "Type {type_name} aliased by {name} is not defined"
+                    )));
+                }
+                if let Some(constant_name) = undefined_size_constant(&type_def.r#type) {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "Array size {constant_name} of the array aliased by {name} is not \
+                         a defined constant"
+                    )));
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Ensures that elements of static arrays have a size known up front, which is not the case
+/// for elements being (possibly through type aliases) dynamic arrays. Each array is reported
+/// where it is written, not where it is used through an alias.
+fn validate_static_array_elements_are_sized(protocol: &Protocol) -> Vec<Diagnostic> {
+    let lookup = crate::ast::DefinitionLookup::new(protocol);
+    let contains_dynamic_array = |type_identifier: &TypeIdentifier| {
+        let mut type_identifier = lookup.resolve(type_identifier);
+        loop {
+            match type_identifier {
+                TypeIdentifier::DynamicArray { .. } => return true,
+                TypeIdentifier::StaticArray { r#type, .. } => {
+                    type_identifier = lookup.resolve(r#type);
+                }
+                _ => return false,
+            }
+        }
+    };
+    let unsized_element_type = |type_identifier: &TypeIdentifier| match type_identifier {
+        TypeIdentifier::StaticArray { r#type, .. } if contains_dynamic_array(r#type) => {
+            Some(crate::ast::type_identifier_name(r#type))
+        }
+        _ => None,
+    };
+
+    let mut diagnostics = Vec::new();
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        let fields: Vec<(&str, &TypeIdentifier)> = match definition {
+            Definition::Structure(structure_def) => structure_def
+                .fields
+                .iter()
+                .map(|field| (field.name.name.as_str(), &field.r#type))
+                .collect(),
+            Definition::Union(union_def) => union_def
+                .fields
+                .iter()
+                .map(|field| match field {
+                    UnionField::SingleValue { name, r#type, .. }
+                    | UnionField::RangeOfValues { name, r#type, .. } => {
+                        (name.name.as_str(), r#type)
+                    }
+                })
+                .collect(),
+            Definition::Type(type_def) => {
+                if let Some(element_type) = unsized_element_type(&type_def.r#type) {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "Static array aliased by {name} has elements of type {element_type}, \
+                         which have a variable size"
+                    )));
+                }
+                continue;
+            }
+            Definition::Enumeration(_) | Definition::Flags(_) | Definition::Constant(_) => {
+                continue;
+            }
+        };
+        for (field_name, r#type) in fields {
+            if let Some(element_type) = unsized_element_type(r#type) {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Static array field {field_name} in {name} has elements of type \
+                     {element_type}, which have a variable size"
+                )));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Returns whether the type is `void` or an array of (arrays of) `void`.
+fn contains_void(type_identifier: &TypeIdentifier) -> bool {
+    match type_identifier {
+        TypeIdentifier::Void => true,
+        TypeIdentifier::StaticArray { r#type, .. } | TypeIdentifier::DynamicArray { r#type } => {
+            contains_void(r#type)
+        }
+        _ => false,
+    }
+}
+
+/// Ensures that `void` is only used directly as the type of a union field, marking a
+/// discriminator which carries no payload. Anywhere else it would describe a value without
+/// any representation.
+fn validate_void_is_only_used_by_union_fields(protocol: &Protocol) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        match definition {
+            Definition::Structure(structure_def) => {
+                for field in &structure_def.fields {
+                    if contains_void(&field.r#type) {
+                        diagnostics.push(Diagnostic::error(format!(
+                            "Field {} in {name} uses type void, which is only allowed as \
+                             the type of union fields",
+                            field.name.name
+                        )));
+                    }
+                }
+            }
+            Definition::Union(union_def) => {
+                for field in &union_def.fields {
+                    let (UnionField::SingleValue {
+                        name: field_name,
+                        r#type,
+                        ..
+                    }
+                    | UnionField::RangeOfValues {
+                        name: field_name,
+                        r#type,
+                        ..
+                    }) = field;
+                    if !matches!(r#type, TypeIdentifier::Void) && contains_void(r#type) {
+                        diagnostics.push(Diagnostic::error(format!(
+                            "Field {} in {name} uses an array of void, which is only allowed \
+                             as the type of union fields",
+                            field_name.name
+                        )));
+                    }
+                }
+            }
+            Definition::Type(type_def) => {
+                if contains_void(&type_def.r#type) {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "Type {name} aliases void, which is only allowed as the type of \
+                         union fields"
+                    )));
+                }
+            }
+            Definition::Enumeration(_) | Definition::Flags(_) | Definition::Constant(_) => {}
+        }
+    }
+    diagnostics
+}
+
+/// Ensures that the `bits` and `bytes` attributes do not widen a field of a built-in type past
+/// its natural size, as the remaining bits could not be represented by the field, and that
+/// they do not shrink a field to no bits at all.
+fn validate_bits_fit_field_types(protocol: &Protocol) -> Vec<Diagnostic> {
+    let lookup = crate::ast::DefinitionLookup::new(protocol);
+    let mut diagnostics = Vec::new();
+
+    for definition in &protocol.definitions {
+        let Definition::Structure(structure_def) = definition else {
+            continue;
+        };
+        for field in &structure_def.fields {
+            let Some((bits, size_text)) =
+                field
+                    .attributes
+                    .iter()
+                    .find_map(|attribute| match attribute {
+                        Attribute::BitsSize { size } => Some((*size, format!("{size} bits"))),
+                        Attribute::BytesSize { size } => {
+                            Some((size.saturating_mul(8), format!("{size} bytes")))
+                        }
+                        _ => None,
+                    })
+            else {
+                continue;
+            };
+            if bits == 0 {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Field {} in {} has {size_text}, so it cannot hold any value",
+                    field.name.name, structure_def.name.name
+                )));
+                continue;
+            }
+            let r#type = lookup.resolve(&field.r#type);
+            if let Some(size) = r#type.builtin_size_in_bits()
+                && bits > size
+            {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Field {} in {} has {size_text}, which exceeds the {size} bits of its type",
+                    field.name.name, structure_def.name.name
+                )));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Ensures that reserved fields hold integers or static arrays of them, whose encoding as zeros
+/// is well defined, and that no other field refers to them.
+fn validate_reserved_fields(protocol: &Protocol) -> Vec<Diagnostic> {
+    let lookup = crate::ast::DefinitionLookup::new(protocol);
+    let is_integer = |type_identifier: &TypeIdentifier| {
+        let mut type_identifier = lookup.resolve(type_identifier);
+        while let TypeIdentifier::StaticArray { r#type, .. } = type_identifier {
+            type_identifier = lookup.resolve(r#type);
+        }
+        !matches!(
+            type_identifier,
+            TypeIdentifier::Float32
+                | TypeIdentifier::Float64
+                | TypeIdentifier::Void
+                | TypeIdentifier::StaticArray { .. }
+                | TypeIdentifier::DynamicArray { .. }
+                | TypeIdentifier::UserDefined(_)
+        )
+    };
+    let mut diagnostics = Vec::new();
+
+    for definition in &protocol.definitions {
+        let Definition::Structure(structure_def) = definition else {
+            continue;
+        };
+        let reserved_fields: HashSet<&str> = structure_def
+            .fields
+            .iter()
+            .filter(|field| field.reserved)
+            .map(|field| field.name.name.as_str())
+            .collect();
+        for field in &structure_def.fields {
+            if field.reserved && !is_integer(&field.r#type) {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Reserved field {} in {} must be an integer or a static array of integers",
+                    field.name.name, structure_def.name.name
+                )));
+            }
+            for attribute in &field.attributes {
+                if let Attribute::DiscriminatedBy { field: target }
+                | Attribute::LengthOf { field: target }
+                | Attribute::Length { field: target } = attribute
+                    && reserved_fields.contains(target.name.as_str())
+                {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "Field {} in {} refers to {} in its {} attribute, which is reserved",
+                        field.name.name,
+                        structure_def.name.name,
+                        target.name,
+                        attribute.name()
+                    )));
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Ensures that every `length_of` attribute names a dynamic array field following the field
+/// holding its length in the same structure, that every `length` attribute names an integer
+/// field preceding the array, and that no array has two lengths.
+fn validate_length_links(protocol: &Protocol) -> Vec<Diagnostic> {
+    let lookup = crate::ast::DefinitionLookup::new(protocol);
+    let mut diagnostics = Vec::new();
+
+    for definition in &protocol.definitions {
+        let Definition::Structure(structure_def) = definition else {
+            continue;
+        };
+        let mut linked_fields = HashSet::new();
+        for (index, field) in structure_def.fields.iter().enumerate() {
+            for attribute in &field.attributes {
+                let linked_field = match attribute {
+                    Attribute::LengthOf { field: target } => {
+                        let is_following_dynamic_array =
+                            structure_def.fields[index + 1..].iter().any(|other| {
+                                other.name.name == target.name
+                                    && matches!(
+                                        lookup.resolve(&other.r#type),
+                                        TypeIdentifier::DynamicArray { .. }
+                                    )
+                            });
+                        if !is_following_dynamic_array {
+                            diagnostics.push(Diagnostic::error(format!(
+                                "Field {} in {} holds the length of {}, which is not a dynamic \
+                                 array field following it",
+                                field.name.name, structure_def.name.name, target.name
+                            )));
+                            continue;
+                        }
+                        target
+                    }
+                    Attribute::Length { field: source } => {
+                        let is_preceding_integer =
+                            structure_def.fields[..index].iter().any(|other| {
+                                other.name.name == source.name && is_integer(&lookup, &other.r#type)
+                            });
+                        if !is_preceding_integer {
+                            diagnostics.push(Diagnostic::error(format!(
+                                "Field {} in {} has its length held by {}, which is not an \
+                                 integer field preceding it",
+                                field.name.name, structure_def.name.name, source.name
+                            )));
+                            continue;
+                        }
+                        &field.name
+                    }
+                    _ => continue,
+                };
+                if !linked_fields.insert(linked_field.name.as_str()) {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "Field {} in {} has more than one field holding its length",
+                        linked_field.name, structure_def.name.name
+                    )));
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Ensures that every `discriminated_by` attribute names another field of the same structure,
+/// which holds the discriminator. That the attributed field is a union is ensured by
+/// [`validate_attributes_are_applicable`].
+fn validate_discriminator_links(protocol: &Protocol) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for definition in &protocol.definitions {
+        let Definition::Structure(structure_def) = definition else {
+            continue;
+        };
+        for field in &structure_def.fields {
+            for attribute in &field.attributes {
+                let Attribute::DiscriminatedBy { field: target } = attribute else {
+                    continue;
+                };
+                let is_sibling = structure_def
+                    .fields
+                    .iter()
+                    .any(|other| other.name.name == target.name && other.name != field.name);
+                if !is_sibling {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "Field {} in {} is discriminated by {}, which is not another field of {}",
+                        field.name.name,
+                        structure_def.name.name,
+                        target.name,
+                        structure_def.name.name
+                    )));
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+fn contains_dynamic_array(lookup: &crate::ast::DefinitionLookup, r#type: &TypeIdentifier) -> bool {
+    match lookup.resolve(r#type) {
+        TypeIdentifier::DynamicArray { .. } => true,
+        TypeIdentifier::StaticArray { r#type, .. } => contains_dynamic_array(lookup, r#type),
+        _ => false,
+    }
+}
+
+/// Strict mode check ensuring that the length of every dynamic array is held by a field
+/// linked to it with `length` or `length_of`, so that decoders never depend on an implicit
+/// length.
+fn validate_dynamic_arrays_have_length_links(protocol: &Protocol) -> Vec<Diagnostic> {
+    let lookup = crate::ast::DefinitionLookup::new(protocol);
+    let mut diagnostics = Vec::new();
+
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        match definition {
+            Definition::Structure(structure_def) => {
+                for field in &structure_def.fields {
+                    let reason = match lookup.resolve(&field.r#type) {
+                        TypeIdentifier::DynamicArray { r#type } => {
+                            if contains_dynamic_array(&lookup, r#type) {
+                                Some(
+                                    "has dynamic array elements, whose lengths cannot be held by fields",
+                                )
+                            } else if length_field(structure_def, field).is_none() {
+                                Some("has no field holding its length")
+                            } else {
+                                None
+                            }
+                        }
+                        TypeIdentifier::StaticArray { r#type, .. }
+                            if contains_dynamic_array(&lookup, r#type) =>
+                        {
+                            Some(
+                                "has dynamic array elements, whose lengths cannot be held by fields",
+                            )
+                        }
+                        _ => None,
+                    };
+                    if let Some(reason) = reason {
+                        diagnostics.push(Diagnostic::error(format!(
+                            "Field {} in {name} {reason}, as required by strict mode",
+                            field.name.name
+                        )));
+                    }
+                }
+            }
+            Definition::Union(union_def) => {
+                for field in &union_def.fields {
+                    let (UnionField::SingleValue {
+                        name: field_name,
+                        r#type,
+                        ..
+                    }
+                    | UnionField::RangeOfValues {
+                        name: field_name,
+                        r#type,
+                        ..
+                    }) = field;
+                    if contains_dynamic_array(&lookup, r#type) {
+                        diagnostics.push(Diagnostic::error(format!(
+                            "Field {} in {name} contains a dynamic array, whose length cannot \
+                             be held by a field of a union as required by strict mode",
+                            field_name.name
+                        )));
+                    }
+                }
+            }
+            Definition::Enumeration(_)
+            | Definition::Flags(_)
+            | Definition::Type(_)
+            | Definition::Constant(_) => {}
+        }
+    }
+    diagnostics
+}
+
+fn contains_union(lookup: &crate::ast::DefinitionLookup, r#type: &TypeIdentifier) -> bool {
+    match lookup.resolve(r#type) {
+        TypeIdentifier::DynamicArray { r#type } | TypeIdentifier::StaticArray { r#type, .. } => {
+            contains_union(lookup, r#type)
+        }
+        TypeIdentifier::UserDefined(identifier) => {
+            matches!(lookup.get(&identifier.name), Some(Definition::Union(_)))
+        }
+        _ => false,
+    }
+}
+
+/// Returns why the encoders and decoders cannot handle a field of the given type, i.e. when
+/// the length of a dynamic array or the discriminator of a union is not held by a field of
+/// the structure. Fields of unions (`in_union`) cannot hold any of them.
+fn unsupported_codec_reason(
+    lookup: &crate::ast::DefinitionLookup,
+    r#type: &TypeIdentifier,
+    in_union: bool,
+    is_discriminated: bool,
+) -> Option<&'static str> {
+    match lookup.resolve(r#type) {
+        TypeIdentifier::DynamicArray { .. } if in_union => {
+            Some("contains a dynamic array, whose length cannot be held by a field of a union")
+        }
+        TypeIdentifier::DynamicArray { r#type } | TypeIdentifier::StaticArray { r#type, .. } => {
+            if contains_dynamic_array(lookup, r#type) {
+                Some("has dynamic array elements, whose lengths cannot be held by fields")
+            } else if contains_union(lookup, r#type) {
+                Some("has union elements, whose discriminators cannot be held by fields")
+            } else {
+                None
+            }
+        }
+        TypeIdentifier::UserDefined(identifier)
+            if matches!(lookup.get(&identifier.name), Some(Definition::Union(_))) =>
+        {
+            if in_union {
+                Some("contains a union, whose discriminator cannot be held by a field of a union")
+            } else if !is_discriminated {
+                Some("holds a union without the discriminated_by attribute")
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Check of the smiths generating encoders and decoders, ensuring that they can handle every
+/// field instead of generating functions which always fail.
+fn validate_fields_have_codecs(protocol: &Protocol) -> Vec<Diagnostic> {
+    let lookup = crate::ast::DefinitionLookup::new(protocol);
+    let mut diagnostics = Vec::new();
+    let mut report = |field_name: &str, name: &str, reason: &str| {
+        diagnostics.push(Diagnostic::error(format!(
+            "Field {field_name} in {name} {reason}, so it cannot be encoded nor decoded"
+        )));
+    };
+
+    for definition in &protocol.definitions {
+        let name = &definition.name().name;
+        match definition {
+            Definition::Structure(structure_def) => {
+                for field in structure_def.fields.iter().filter(|field| !field.reserved) {
+                    let is_discriminated = discriminated_by(&field.attributes).is_some();
+                    if let Some(reason) =
+                        unsupported_codec_reason(&lookup, &field.r#type, false, is_discriminated)
+                    {
+                        report(&field.name.name, name, reason);
+                    }
+                }
+            }
+            Definition::Union(union_def) => {
+                for field in &union_def.fields {
+                    let (UnionField::SingleValue {
+                        name: field_name,
+                        r#type,
+                        ..
+                    }
+                    | UnionField::RangeOfValues {
+                        name: field_name,
+                        r#type,
+                        ..
+                    }) = field;
+                    if let Some(reason) = unsupported_codec_reason(&lookup, r#type, true, false) {
+                        report(&field_name.name, name, reason);
+                    }
+                }
+            }
+            Definition::Enumeration(_)
+            | Definition::Flags(_)
+            | Definition::Type(_)
+            | Definition::Constant(_) => {}
+        }
+    }
+    diagnostics
+}
+
+/// Warns about structure fields whose `c_type` attribute names a C type which is likely
+/// to have a different size than the type the field would get without the attribute.
+fn warn_about_c_type_size_mismatches(protocol: &Protocol) -> Vec<Diagnostic> {
+    let lookup = crate::ast::DefinitionLookup::new(protocol);
+    let mut diagnostics = Vec::new();
+
+    for definition in &protocol.definitions {
+        let Definition::Structure(structure_def) = definition else {
+            continue;
+        };
+        for field in &structure_def.fields {
+            let Some(c_type) = crate::ast::c_type_override(&field.attributes) else {
+                continue;
+            };
+            let element_type = match lookup.resolve(&field.r#type) {
+                TypeIdentifier::StaticArray { r#type, .. }
+                | TypeIdentifier::DynamicArray { r#type } => lookup.resolve(r#type),
+                type_identifier => type_identifier,
+            };
+            let default_c_type = crate::smith_c::generate_type_identifier_code(
+                element_type,
+                &crate::smith_c::DefaultCMapper,
+            );
+            if let (Some(size), Some(default_size)) = (
+                crate::smith_c::likely_c_type_size_in_bits(c_type),
+                crate::smith_c::likely_c_type_size_in_bits(&default_c_type),
+            ) && size != default_size
+            {
+                diagnostics.push(Diagnostic::warning(format!(
+                    "C type {c_type} of field {} in {} is likely {size} bits wide, \
+                     while {default_c_type} is {default_size} bits wide",
+                    field.name.name, structure_def.name.name
+                )));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Warns about enumerations, unions and type definitions that are not used by any other
+/// definition. Structures are not reported, as unused structures are top-level messages.
+fn warn_about_unused_definitions(protocol: &Protocol) -> Vec<Diagnostic> {
+    let used_types: HashSet<String> = protocol
+        .definitions
+        .iter()
+        .flat_map(crate::ast::extract_definition_subtypes)
+        .collect();
+
+    protocol
+        .definitions
+        .iter()
+        .filter(|definition| {
+            !matches!(definition, Definition::Structure(_))
+                && definition.id().is_none()
+                && !used_types.contains(&definition.name().name)
+        })
+        .map(|definition| {
+            Diagnostic::warning(format!(
+                "Definition {} is never used",
+                &definition.name().name
+            ))
+        })
+        .collect()
+}
+
+/// Runs all checks on the protocol and returns every problem found, both errors and warnings.
+pub fn collect_diagnostics(protocol: &Protocol) -> Vec<Diagnostic> {
+    collect_diagnostics_with_options(protocol, &ValidationOptions::default())
+}
+
+/// Same as [`collect_diagnostics`], with some errors turned into warnings by the options.
+pub fn collect_diagnostics_with_options(
+    protocol: &Protocol,
+    options: &ValidationOptions,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = validate_unique_definition_names(protocol);
+    diagnostics.extend(validate_unique_definition_ids(protocol));
+    diagnostics.extend(validate_enumeration_ranges_are_ordered(protocol));
+    diagnostics.extend(validate_enumeration_base_types(protocol));
+    diagnostics.extend(validate_enumeration_values_are_unique(protocol, options));
+    diagnostics.extend(validate_union_discriminators_are_unique(protocol));
+    diagnostics.extend(validate_referenced_types_are_defined(protocol));
+    diagnostics.extend(validate_attributes_are_applicable(protocol));
+    diagnostics.extend(validate_static_array_elements_are_sized(protocol));
+    diagnostics.extend(validate_void_is_only_used_by_union_fields(protocol));
+    diagnostics.extend(validate_bits_fit_field_types(protocol));
+    diagnostics.extend(validate_reserved_fields(protocol));
+    diagnostics.extend(validate_length_links(protocol));
+    diagnostics.extend(validate_discriminator_links(protocol));
+    if options.strict {
+        diagnostics.extend(validate_dynamic_arrays_have_length_links(protocol));
+    }
+    diagnostics.extend(warn_about_c_type_size_mismatches(protocol));
+    diagnostics.extend(warn_about_unused_definitions(protocol));
+    diagnostics
+}
+
+/// Runs all semantic checks on the protocol that cannot be expressed in the grammar.
+/// Returns all found errors at once, so they can be fixed together. Warnings are ignored,
+/// use [`collect_diagnostics`] to get them as well.
+pub fn validate_protocol(protocol: &Protocol) -> Result<(), Vec<String>> {
+    let errors: Vec<String> = collect_diagnostics(protocol)
+        .into_iter()
+        .filter(Diagnostic::is_error)
+        .map(|diagnostic| diagnostic.message)
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks that the encoders and decoders generated for the protocol can handle all fields,
+/// which they cannot when the length of a dynamic array or the discriminator of a union is not
+/// held by a field of the structure. Returns all found errors at once.
+pub fn validate_protocol_codecs(protocol: &Protocol) -> Result<(), Vec<String>> {
+    let errors: Vec<String> = validate_fields_have_codecs(protocol)
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Formats errors returned by [`validate_protocol`] into a single message
+/// used by the `generate_*_from_string` functions.
+pub(crate) fn format_validation_errors(errors: &[String]) -> String {
+    format!("Validation failed. Errors: {}", errors.join(", "))
+}
+
+/// Collects diagnostics of the protocol, failing with [`crate::MeksmithError::Validation`]
+/// holding the messages of all errors if there are any. On success, only warnings are returned.
+pub(crate) fn collect_warnings_or_errors(
+    protocol: &Protocol,
+    options: &ValidationOptions,
+) -> Result<Vec<Diagnostic>, crate::MeksmithError> {
+    let (errors, warnings): (Vec<Diagnostic>, Vec<Diagnostic>) =
+        collect_diagnostics_with_options(protocol, options)
+            .into_iter()
+            .partition(Diagnostic::is_error);
+
+    if errors.is_empty() {
+        Ok(warnings)
+    } else {
+        Err(crate::MeksmithError::Validation(
+            errors.into_iter().map(|error| error.message).collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_protocol_to_ast;
+    use rstest::rstest;
+
+    #[test]
+    fn test_validate_protocol_with_unique_ids() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+[id=0x10] struct Ping { sequence_number: uint32; };
+[id=0x11] struct Pong { sequence_number: uint32; };
+struct Other { field: uint8; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(validate_protocol(&protocol), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_protocol_with_duplicated_names() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+enum Status { up = 1; };
+enum Status { down = 0; };
+struct Message { status: Status; };
+using Message = uint8;
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Definition Status is defined more than once, as an enumeration and as an \
+                 enumeration"
+                    .to_string(),
+                "Definition Message is defined more than once, as a structure and as a type"
+                    .to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_duplicated_ids() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+[id=0x10] struct Ping { sequence_number: uint32; };
+[id=16] enum Status { up = 1; };
+[id=0x11] struct Pong { sequence_number: uint32; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec!["Id 16 is used by both Ping and Status".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_undefined_types() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+using Alias = Missing[4];
+union Payload { 0 => first: uint8; 1..2 => second: Unknown; };
+struct Message { payload: Payload; data: Absent[]; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Type Missing aliased by Alias is not defined".to_string(),
+                "Type Unknown of field second in Payload is not defined".to_string(),
+                "Type Absent of field data in Message is not defined".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_undefined_array_size_constants() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+const MAX_LEN = 8;
+using Alias = byte[MISSING];
+union Payload { 0 => first: uint8[Message]; };
+struct Message { data: byte[MAX_LEN]; len: MAX_LEN; payload: Payload; alias: Alias; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Array size MISSING of the array aliased by Alias is not a defined constant"
+                    .to_string(),
+                "Array size Message of field first in Payload is not a defined constant"
+                    .to_string(),
+                "Type MAX_LEN of field len in Message is not defined".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_static_array_of_dynamic_arrays() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+using Bytes = byte[];
+using Packets = Bytes[4];
+using NestedBytes = Bytes[2];
+union Payload { 0 => first: NestedBytes[3]; };
+struct Message { packets: Packets; chunks: Bytes[8]; payload: Payload; rows: uint16[2][]; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Static array aliased by Packets has elements of type Bytes, which have a variable size".to_string(),
+                "Static array aliased by NestedBytes has elements of type Bytes, which have a variable size".to_string(),
+                "Static array field first in Payload has elements of type NestedBytes, which have a variable size".to_string(),
+                "Static array field chunks in Message has elements of type Bytes, which have a variable size".to_string(),
+                "Static array field rows in Message has elements of type uint16[], which have a variable size".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_void_outside_of_union_fields() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+using Nothing = void;
+union Payload { 0 => none: void; 1 => nones: void[2]; };
+struct Message { kind: uint8; empty: void; [discriminated_by=kind] payload: Payload; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Type Nothing aliases void, which is only allowed as the type of union fields"
+                    .to_string(),
+                "Field nones in Payload uses an array of void, which is only allowed as the type of union fields".to_string(),
+                "Field empty in Message uses type void, which is only allowed as the type of union fields".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_sizes_exceeding_field_types() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+using Counter = uint16;
+struct Message {
+    [bits=8] small: uint8;
+    [bits=9] wide: uint8;
+    [bits=17] count: Counter;
+    [bits=0] empty: int8;
+    [bytes=8] timestamp: uint64;
+    [bytes=9] huge: uint8;
+};
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Field wide in Message has 9 bits, which exceeds the 8 bits of its type"
+                    .to_string(),
+                "Field count in Message has 17 bits, which exceeds the 16 bits of its type"
+                    .to_string(),
+                "Field empty in Message has 0 bits, so it cannot hold any value".to_string(),
+                "Field huge in Message has 9 bytes, which exceeds the 8 bits of its type"
+                    .to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_static_array_of_structures() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+struct Point { x: int32; y: int32; };
+using Triangle = Point[3];
+struct Shape { triangles: Triangle[2]; points: Point[4]; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(validate_protocol(&protocol), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_protocol_with_examples() {
+        for example in [
+            include_str!("../examples/data/ping-pong.mek"),
+            include_str!("../examples/data/can-bus.mek"),
+            include_str!("../examples/data/ecpri.mek"),
+        ] {
+            let protocol = parse_protocol_to_ast(example).unwrap();
+            assert_eq!(validate_protocol(&protocol), Ok(()));
+            assert_eq!(validate_protocol_codecs(&protocol), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_collect_diagnostics_with_unused_definitions() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+enum Unused { a = 1; };
+enum Used { a = 1; };
+[id=1] enum Identified { a = 1; };
+struct Message { field: Used; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            collect_diagnostics(&protocol),
+            vec![Diagnostic::warning(
+                "Definition Unused is never used".to_string()
+            )]
+        );
+        assert_eq!(validate_protocol(&protocol), Ok(()));
+    }
+
+    #[test]
+    fn test_collect_diagnostics_with_ping_pong_example() {
+        let ping_pong = include_str!("../examples/data/ping-pong.mek");
+        let protocol = parse_protocol_to_ast(ping_pong).unwrap();
+        assert!(collect_diagnostics(&protocol).is_empty());
+    }
+
+    #[test]
+    fn test_collect_diagnostics_with_c_type_size_mismatch() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+using Length = uint32;
+struct Buffer {
+    [c_type="size_t"] capacity: Length;
+    [c_type="uint32_t"] length: uint32;
+    [c_type="char"] data: byte[];
+    [c_type="my_type_t"] custom: uint16;
+};
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            collect_diagnostics(&protocol),
+            vec![Diagnostic::warning(
+                "C type size_t of field capacity in Buffer is likely 64 bits wide, while uint32_t is 32 bits wide"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_overlapping_enumeration_values() {
+        let protocol = parse_protocol_to_ast(
+            "enum Code { a = 5; b = 1..10; c = 9..12; }; struct Message { code: Code; };",
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Fields a and b of Code both claim value 5".to_string(),
+                "Fields b and c of Code both claim values 9..10".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_invalid_enumeration_base_types() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+enum Fits : uint8 { a = 0; b = 255; };
+enum Large : uint8 { a = 0; b = 256; };
+enum Negative : uint16 { a = -1; b = 1; };
+enum Narrow : int8 { a = -129..128; };
+enum Floating : float32 { a = 1; };
+enum Unsigned64 : uint64 { a = 0; b = 0xFFFFFFFFFFFFFFFF; };
+enum Signed64 : int64 { a = 0x8000000000000000; };
+enum Mixed { a = -1; b = 0xFFFFFFFFFFFFFFFF; };
+struct Message {
+    fits: Fits;
+    large: Large;
+    negative: Negative;
+    narrow: Narrow;
+    floating: Floating;
+    unsigned64: Unsigned64;
+    signed64: Signed64;
+    mixed: Mixed;
+};
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Base type uint8 of enumeration Large cannot hold value 256".to_string(),
+                "Base type uint16 of enumeration Negative cannot hold value -1".to_string(),
+                "Base type int8 of enumeration Narrow cannot hold value -129".to_string(),
+                "Base type int8 of enumeration Narrow cannot hold value 128".to_string(),
+                "Base type float32 of enumeration Floating is not an integer type".to_string(),
+                "Base type int64 of enumeration Signed64 cannot hold value 9223372036854775808"
+                    .to_string(),
+                "Enumeration Mixed has both negative values and values above \
+                 9223372036854775807, which no 64-bit integer can hold"
+                    .to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_reversed_enumeration_ranges() {
+        let protocol = parse_protocol_to_ast(
+            "enum Code { a = -1..-3; b = 5..2; c = -2..2; }; struct Message { code: Code; };",
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Field a of Code has range -1..-3, whose start is greater than its end".to_string(),
+                "Field b of Code has range 5..2, whose start is greater than its end".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_overlapping_union_discriminators() {
+        let protocol = parse_protocol_to_ast(
+            "union Body { 3 => a: uint8; 1..5 => b: uint16; 5..6 => c: void; 7 => d: uint8; }; \
+             struct Message { kind: uint8; [discriminated_by=kind] body: Body; };",
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Fields a and b of Body both claim discriminator 3".to_string(),
+                "Fields b and c of Body both claim discriminator 5".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_collect_diagnostics_with_shadowing_warnings() {
+        let protocol = parse_protocol_to_ast(
+            "enum Code { a = 5; b = 1..10; }; struct Message { code: Code; };",
+        )
+        .unwrap();
+        let options = ValidationOptions {
+            warn_shadowing: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            collect_diagnostics_with_options(&protocol, &options),
+            vec![Diagnostic::warning(
+                "Field b of Code shadows a for value 5".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_invalid_length_links() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+struct Message {
+    before: byte[];
+    [length_of=before] late: uint8;
+    [length_of=fixed] wrong: uint8;
+    [length_of=data] count: uint16;
+    [length_of=data] again: uint16;
+    fixed: byte[4];
+    data: byte[];
+};
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Field late in Message holds the length of before, which is not a dynamic array field following it".to_string(),
+                "Field wrong in Message holds the length of fixed, which is not a dynamic array field following it".to_string(),
+                "Field data in Message has more than one field holding its length".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_invalid_length_attributes() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+struct Message {
+    [length=later] early: byte[];
+    [length=ratio] scaled: byte[];
+    ratio: float32;
+    later: uint8;
+    count: uint16;
+    [length=count] fixed: byte[4];
+    [length_of=data] size: uint16;
+    [length=count] data: byte[];
+};
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Attribute length cannot be applied to field fixed in Message, as only dynamic arrays have a length".to_string(),
+                "Field early in Message has its length held by later, which is not an integer field preceding it".to_string(),
+                "Field scaled in Message has its length held by ratio, which is not an integer field preceding it".to_string(),
+                "Field data in Message has more than one field holding its length".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_invalid_discriminator_links() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+union Body { 0 => a: uint8; };
+struct Message {
+    kind: uint8;
+    [discriminated_by=knd] typo: Body;
+    [discriminated_by=itself] itself: Body;
+    [discriminated_by=kind] scalar: uint8;
+};
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![
+                "Attribute discriminated_by cannot be applied to field scalar in Message, as only unions have a discriminator".to_string(),
+                "Field typo in Message is discriminated by knd, which is not another field of Message".to_string(),
+                "Field itself in Message is discriminated by itself, which is not another field of Message".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_codecs() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+union Inner { 0 => a: uint8; };
+union Payload { 0 => raw: byte[]; 1 => inner: Inner; 2 => fixed: byte[4]; };
+struct Message {
+    kind: uint8;
+    [discriminated_by=kind] payload: Payload;
+    loose: Inner;
+    many: Inner[2];
+    rows: uint8[][];
+    [length=kind] data: uint8[];
+};
+"#,
+        )
+        .unwrap();
+        assert_eq!(validate_protocol(&protocol), Ok(()));
+        assert_eq!(
+            validate_protocol_codecs(&protocol),
+            Err(vec![
+                "Field raw in Payload contains a dynamic array, whose length cannot be held by a field of a union, so it cannot be encoded nor decoded".to_string(),
+                "Field inner in Payload contains a union, whose discriminator cannot be held by a field of a union, so it cannot be encoded nor decoded".to_string(),
+                "Field loose in Message holds a union without the discriminated_by attribute, so it cannot be encoded nor decoded".to_string(),
+                "Field many in Message has union elements, whose discriminators cannot be held by fields, so it cannot be encoded nor decoded".to_string(),
+                "Field rows in Message has dynamic array elements, whose lengths cannot be held by fields, so it cannot be encoded nor decoded".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_collect_diagnostics_in_strict_mode() {
+        let options = ValidationOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let linked = parse_protocol_to_ast(
+            "using Bytes = byte[]; struct Message { [length_of=data] count: uint16; data: Bytes; };",
+        )
+        .unwrap();
+        assert_eq!(collect_diagnostics_with_options(&linked, &options), vec![]);
+        assert_eq!(collect_diagnostics(&linked), vec![]);
+
+        let linked_from_array = parse_protocol_to_ast(
+            "struct Message { count: uint16; [length=count] data: byte[]; };",
+        )
+        .unwrap();
+        assert_eq!(
+            collect_diagnostics_with_options(&linked_from_array, &options),
+            vec![]
+        );
+
+        let unlinked = parse_protocol_to_ast(
+            r#"
+using Bytes = byte[];
+union Payload { 0 => raw: byte[]; };
+struct Message {
+    data: byte[];
+    [length_of=rows] count: uint16;
+    rows: Bytes[];
+    kind: uint8;
+    [discriminated_by=kind] payload: Payload;
+};
+"#,
+        )
+        .unwrap();
+        assert_eq!(collect_diagnostics(&unlinked), vec![]);
+        assert_eq!(
+            collect_diagnostics_with_options(&unlinked, &options),
+            vec![
+                Diagnostic::error(
+                    "Field raw in Payload contains a dynamic array, whose length cannot be held by a field of a union as required by strict mode".to_string()
+                ),
+                Diagnostic::error(
+                    "Field data in Message has no field holding its length, as required by strict mode".to_string()
+                ),
+                Diagnostic::error(
+                    "Field rows in Message has dynamic array elements, whose lengths cannot be held by fields, as required by strict mode".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_applicable_attributes() {
+        let protocol = parse_protocol_to_ast(
+            r#"
+[id=1, repr=c_packed] struct Message {
+    [bits=1] kind: uint8;
+    [discriminated_by=kind] payload: Payload;
+    [c_type="char", assert="value != 0"] tag: byte;
+};
+using Payload = Body;
+[id=2] union Body { 0 => a: uint8; 1 => b: uint16; };
+"#,
+        )
+        .unwrap();
+        assert_eq!(validate_protocol(&protocol), Ok(()));
+    }
+
+    #[rstest]
+    #[case::discriminated_by_on_scalar(
+        "struct Message { kind: uint8; [discriminated_by=kind] payload: uint32; };",
+        "Attribute discriminated_by cannot be applied to field payload in Message, as only unions have a discriminator"
+    )]
+    #[case::id_on_field(
+        "struct Message { [id=1] kind: uint8; };",
+        "Attribute id cannot be applied to field kind in Message, as it applies to definitions"
+    )]
+    #[case::repr_on_field(
+        "struct Message { [repr=c] kind: uint8; };",
+        "Attribute repr cannot be applied to field kind in Message, as it applies to definitions"
+    )]
+    #[case::repr_on_enumeration(
+        "[repr=c] enum Kind { a = 0; }; struct Message { kind: Kind; };",
+        "Attribute repr cannot be applied to Kind, as it applies to structures"
+    )]
+    #[case::bits_on_definition(
+        "[bits=3] struct Message { kind: uint8; };",
+        "Attribute bits cannot be applied to Message, as it applies to structure fields"
+    )]
+    #[case::c_type_on_definition(
+        "[c_type=\"int\"] using Kind = uint8; struct Message { kind: Kind; };",
+        "Attribute c_type cannot be applied to Kind, as it applies to structure fields"
+    )]
+    #[case::assert_on_definition(
+        "[assert=\"value > 0\"] struct Message { kind: uint8; };",
+        "Attribute assert cannot be applied to Message, as it applies to structure fields"
+    )]
+    #[case::length_of_on_float(
+        "struct Message { [length_of=data] count: float32; data: byte[]; };",
+        "Attribute length_of cannot be applied to field count in Message, as only integers hold a length"
+    )]
+    fn test_validate_protocol_with_inapplicable_attribute(
+        #[case] input: &str,
+        #[case] expected_error: &str,
+    ) {
+        let protocol = parse_protocol_to_ast(input).unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![expected_error.to_string()])
+        );
+    }
+
+    #[test]
+    fn test_validate_protocol_with_reserved_fields() {
+        let protocol = parse_protocol_to_ast(
+            "struct Header { version: uint8; _reserved: uint8[3]; [bits=4] _padding: uint8; [bits=4] flags: uint8; };",
+        )
+        .unwrap();
+        assert_eq!(validate_protocol(&protocol), Ok(()));
+    }
+
+    #[rstest]
+    #[case::float(
+        "struct Message { _reserved: float32; };",
+        "Reserved field _reserved in Message must be an integer or a static array of integers"
+    )]
+    #[case::structure(
+        "struct Inner { a: uint8; }; struct Message { _reserved: Inner[2]; };",
+        "Reserved field _reserved in Message must be an integer or a static array of integers"
+    )]
+    #[case::discriminator(
+        "union Body { 0 => a: uint8; 1 => b: uint16; }; struct Message { _kind: uint8; [discriminated_by=_kind] body: Body; };",
+        "Field body in Message refers to _kind in its discriminated_by attribute, which is reserved"
+    )]
+    fn test_validate_protocol_with_invalid_reserved_field(
+        #[case] input: &str,
+        #[case] expected_error: &str,
+    ) {
+        let protocol = parse_protocol_to_ast(input).unwrap();
+        assert_eq!(
+            validate_protocol(&protocol),
+            Err(vec![expected_error.to_string()])
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_display() {
+        assert_eq!(
+            Diagnostic::warning("Definition A is never used".to_string()).to_string(),
+            "warning: Definition A is never used"
+        );
+        assert_eq!(
+            Diagnostic::error("Id 1 is used by both A and B".to_string()).to_string(),
+            "error: Id 1 is used by both A and B"
+        );
+    }
+}
@@ -3,21 +3,41 @@ use crate::utils::static_regex::static_regex;
 
 use leptos::prelude::*;
 use regex_lite::Regex;
+use std::time::Duration;
+use web_sys::js_sys;
+use web_sys::wasm_bindgen::{JsCast, JsValue};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub(crate) enum CodeEditorLanguage {
     #[allow(dead_code)]
     PlainText,
     Meklang,
     C,
+    Rust,
+    Kotlin,
+    Swift,
 }
 
-static_regex!(MEKLANG_KEYWORDS_REGEX, r"\b(enum|struct|union|using)\b");
+/// Languages the code written in meklang can be generated in, in the order they are offered.
+pub(crate) const OUTPUT_LANGUAGES: &[CodeEditorLanguage] = &[
+    CodeEditorLanguage::C,
+    CodeEditorLanguage::Rust,
+    CodeEditorLanguage::Kotlin,
+    CodeEditorLanguage::Swift,
+];
+
+static_regex!(
+    MEKLANG_KEYWORDS_REGEX,
+    r"\b(enum|struct|union|oneof|flags|const|using|module|version)\b"
+);
 static_regex!(
     MEKLANG_BUILTIN_TYPES_REGEX,
-    r"\b(uint8|uint16|uint32|uint64|int8|int16|int32|int64|float32|float64|bit|byte)\b"
+    r"\b(uint8|uint16|uint32|uint64|int8|int16|int32|int64|float32|float64|bit|byte|char|string|void)\b"
 );
-static_regex!(MEKLANG_COMMENT_REGEX, r"#.*");
+static_regex!(MEKLANG_NUMBER_REGEX, r"\b(0x[0-9A-Fa-f]+|0b[01]+|[0-9]+)\b");
+// Matched after HTML escaping, so `=>` is `=&gt;`
+static_regex!(MEKLANG_OPERATOR_REGEX, r"=&gt;|\.\.");
+static_regex!(MEKLANG_COMMENT_REGEX, r"#.*|(?s:/\*.*?\*/)");
 
 static_regex!(C_KEYWORDS_REGEX, r"\b(enum|struct|union|typedef|static)\b");
 static_regex!(
@@ -25,10 +45,74 @@ static_regex!(
     r"\b(int|unsigned|long|uint8_t|uint16_t|uint32_t|uint64_t|int8_t|int16_t|int32_t|int64_t|float|double|bool|char)\b"
 );
 
+static_regex!(
+    RUST_KEYWORDS_REGEX,
+    r"\b(as|const|enum|fn|for|if|else|impl|in|let|match|mod|mut|pub|return|self|Self|struct|type|use|where|while)\b"
+);
+static_regex!(
+    RUST_BUILTIN_TYPES_REGEX,
+    r"\b(u8|u16|u32|u64|i8|i16|i32|i64|f32|f64|bool|char|usize|Vec|String|Option|Result)\b"
+);
+
+static_regex!(
+    KOTLIN_KEYWORDS_REGEX,
+    r"\b(package|import|class|data|enum|sealed|object|companion|fun|val|var|override|return|if|else|when|is|for|in|typealias|const|private|internal)\b"
+);
+static_regex!(
+    KOTLIN_BUILTIN_TYPES_REGEX,
+    r"\b(Byte|Short|Int|Long|UByte|UShort|UInt|ULong|Float|Double|Boolean|Char|ByteArray|List|Array|String)\b"
+);
+
+static_regex!(
+    SWIFT_KEYWORDS_REGEX,
+    r"\b(import|struct|enum|indirect|case|let|var|func|init|typealias|extension|public|static|return|if|else|switch|for|in|self)\b"
+);
+static_regex!(
+    SWIFT_BUILTIN_TYPES_REGEX,
+    r"\b(Int8|Int16|Int32|Int64|UInt8|UInt16|UInt32|UInt64|Float|Double|Bool|Character|String|Array|Codable)\b"
+);
+
 impl CodeEditorLanguage {
+    /// Returns the name of the language shown to users.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            CodeEditorLanguage::PlainText => "plain text",
+            CodeEditorLanguage::Meklang => "meklang",
+            CodeEditorLanguage::C => "C",
+            CodeEditorLanguage::Rust => "Rust",
+            CodeEditorLanguage::Kotlin => "Kotlin",
+            CodeEditorLanguage::Swift => "Swift",
+        }
+    }
+
+    /// Returns the extension of files holding code in the language.
+    fn file_extension(&self) -> &'static str {
+        match self {
+            CodeEditorLanguage::PlainText => "txt",
+            CodeEditorLanguage::Meklang => "mek",
+            CodeEditorLanguage::C => "c",
+            CodeEditorLanguage::Rust => "rs",
+            CodeEditorLanguage::Kotlin => "kt",
+            CodeEditorLanguage::Swift => "swift",
+        }
+    }
+
+    /// Returns the backend generating code in the language, if there is one.
+    fn backend(&self) -> Option<meksmith::pipeline::Backend> {
+        match self {
+            CodeEditorLanguage::PlainText | CodeEditorLanguage::Meklang => None,
+            CodeEditorLanguage::C => Some(meksmith::pipeline::Backend::C),
+            CodeEditorLanguage::Rust => Some(meksmith::pipeline::Backend::Rust),
+            CodeEditorLanguage::Kotlin => Some(meksmith::pipeline::Backend::Kotlin),
+            CodeEditorLanguage::Swift => Some(meksmith::pipeline::Backend::Swift),
+        }
+    }
+
     fn get_highlighter(&self) -> LanguageHighlighter {
         const KEYWORD_CLASS: &str = "code-editor-highlight-keyword";
         const BUILTIN_TYPE_CLASS: &str = "code-editor-highlight-builtin-type";
+        const NUMBER_CLASS: &str = "code-editor-highlight-number";
+        const OPERATOR_CLASS: &str = "code-editor-highlight-operator";
         const COMMENT_CLASS: &str = "code-editor-highlight-comment";
 
         match self {
@@ -37,6 +121,8 @@ impl CodeEditorLanguage {
                 rules: vec![
                     (KEYWORD_CLASS, &MEKLANG_KEYWORDS_REGEX),
                     (BUILTIN_TYPE_CLASS, &MEKLANG_BUILTIN_TYPES_REGEX),
+                    (NUMBER_CLASS, &MEKLANG_NUMBER_REGEX),
+                    (OPERATOR_CLASS, &MEKLANG_OPERATOR_REGEX),
                     (COMMENT_CLASS, &MEKLANG_COMMENT_REGEX),
                 ],
             },
@@ -46,6 +132,24 @@ impl CodeEditorLanguage {
                     (BUILTIN_TYPE_CLASS, &C_BUILTIN_TYPES_REGEX),
                 ],
             },
+            CodeEditorLanguage::Rust => LanguageHighlighter {
+                rules: vec![
+                    (KEYWORD_CLASS, &RUST_KEYWORDS_REGEX),
+                    (BUILTIN_TYPE_CLASS, &RUST_BUILTIN_TYPES_REGEX),
+                ],
+            },
+            CodeEditorLanguage::Kotlin => LanguageHighlighter {
+                rules: vec![
+                    (KEYWORD_CLASS, &KOTLIN_KEYWORDS_REGEX),
+                    (BUILTIN_TYPE_CLASS, &KOTLIN_BUILTIN_TYPES_REGEX),
+                ],
+            },
+            CodeEditorLanguage::Swift => LanguageHighlighter {
+                rules: vec![
+                    (KEYWORD_CLASS, &SWIFT_KEYWORDS_REGEX),
+                    (BUILTIN_TYPE_CLASS, &SWIFT_BUILTIN_TYPES_REGEX),
+                ],
+            },
         }
     }
 }
@@ -77,18 +181,11 @@ struct LanguageHighlighter {
 
 impl LanguageHighlighter {
     fn highlight(&self, code: &str) -> String {
-        let mut highlighted_code = code
+        let escaped_code = code
             .replace('&', "&amp;")
             .replace('<', "&lt;")
             .replace('>', "&gt;");
-
-        for (css_class, regex) in &self.rules {
-            highlighted_code = regex
-                .replace_all(&highlighted_code, |caps: &regex_lite::Captures| {
-                    format!(r#"<span class="{}">{}</span>"#, css_class, &caps[0])
-                })
-                .into_owned();
-        }
+        let mut highlighted_code = self.highlight_escaped(&escaped_code);
 
         if highlighted_code.ends_with('\n') {
             highlighted_code.push(' ');
@@ -96,6 +193,49 @@ impl LanguageHighlighter {
 
         highlighted_code
     }
+
+    /// Wraps the matches of the rules in spans in a single pass over the escaped code, so
+    /// text matched by one rule, e.g. a number in a comment, is never matched by another.
+    /// The match starting first wins, and of matches starting at once, the earlier rule.
+    fn highlight_escaped(&self, code: &str) -> String {
+        let mut highlighted_code = String::with_capacity(code.len());
+        let mut next_matches: Vec<_> = self
+            .rules
+            .iter()
+            .map(|(_, regex)| regex.find(code))
+            .collect();
+        let mut position = 0;
+
+        loop {
+            for (next_match, (_, regex)) in next_matches.iter_mut().zip(&self.rules) {
+                if next_match.is_some_and(|found| found.start() < position) {
+                    *next_match = regex.find_at(code, position);
+                }
+            }
+            let Some((css_class, found)) = next_matches
+                .iter()
+                .zip(&self.rules)
+                .filter_map(|(next_match, (css_class, _))| Some((*css_class, (*next_match)?)))
+                .min_by_key(|(_, found)| found.start())
+            else {
+                break;
+            };
+            if found.is_empty() {
+                break;
+            }
+
+            highlighted_code.push_str(&code[position..found.start()]);
+            highlighted_code.push_str(&format!(
+                r#"<span class="{}">{}</span>"#,
+                css_class,
+                found.as_str()
+            ));
+            position = found.end();
+        }
+
+        highlighted_code.push_str(&code[position..]);
+        highlighted_code
+    }
 }
 
 #[component]
@@ -103,8 +243,12 @@ pub fn CodeEditor(
     code_editor_options: CodeEditorOptions,
     #[prop(into)] code: ReadSignal<String>,
     #[prop(into)] set_code: WriteSignal<String>,
+    /// Numbers of the lines with errors, starting from 1, which are marked next to the code.
+    #[prop(optional, into)]
+    error_lines: Option<Signal<Vec<usize>>>,
 ) -> impl IntoView {
     let language_highlighter = code_editor_options.language.get_highlighter();
+    let error_lines = error_lines.unwrap_or_else(|| Signal::stored(Vec::new()));
 
     let textarea_code_ref: NodeRef<leptos::html::Textarea> = NodeRef::new();
     let code_editor_options_for_textarea = code_editor_options.clone();
@@ -133,7 +277,10 @@ pub fn CodeEditor(
     pre_line_numbers_ref.on_load(move |pre| {
         pre.set_class_name("code-editor-line-numbers");
         pre.set_scroll_top(textarea_code_ref.get().unwrap().scroll_top());
-        pre.set_text_content(Some(&get_line_numbers(&code.get())));
+        pre.set_inner_html(&get_line_numbers_with_errors(
+            &code.get(),
+            &error_lines.get(),
+        ));
     });
 
     let language_highlighter_for_input_sync = language_highlighter.clone();
@@ -148,8 +295,10 @@ pub fn CodeEditor(
                 .highlight(&textarea.value())
                 .as_str(),
         );
-        pre_line_numbers
-            .set_text_content(Some(get_line_numbers(textarea.value().as_str()).as_str()));
+        pre_line_numbers.set_inner_html(&get_line_numbers_with_errors(
+            &textarea.value(),
+            &error_lines.get(),
+        ));
 
         let scroll_top = textarea.scroll_top();
         let scroll_left = textarea.scroll_left();
@@ -174,8 +323,26 @@ pub fn CodeEditor(
         pre_line_numbers.set_scroll_left(scroll_left);
     };
 
+    let history = StoredValue::new(EditHistory::default());
+
+    let record_input = move |event: web_sys::InputEvent| {
+        let textarea = textarea_code_ref.get().unwrap();
+        history.update_value(|history| {
+            history.record(
+                EditorSnapshot::from_textarea(&textarea),
+                Some(&event.input_type()),
+                js_sys::Date::now(),
+            )
+        });
+    };
+
     let keydown = move |event: web_sys::KeyboardEvent| {
-        CodeEditorShortcut::from(event.clone()).handle_event(event, &textarea_code_ref, &set_code);
+        CodeEditorShortcut::from(event.clone()).handle_event(
+            event,
+            &textarea_code_ref,
+            &set_code,
+            &history,
+        );
     };
 
     let language_highlighter_for_effect = language_highlighter.clone();
@@ -192,7 +359,10 @@ pub fn CodeEditor(
             }
 
             if let Some(pre) = pre_line_numbers_ref.get() {
-                pre.set_text_content(Some(get_line_numbers(&code.get()).as_str()));
+                pre.set_inner_html(&get_line_numbers_with_errors(
+                    &code.get(),
+                    &error_lines.get(),
+                ));
             }
         }
     });
@@ -204,6 +374,7 @@ pub fn CodeEditor(
             <textarea node_ref=textarea_code_ref
                 on:input=input_sync
                 on:scroll=scroll_sync
+                on:beforeinput=record_input
                 on:keydown=keydown
                 aria-label="Code editor"
             ></textarea>
@@ -211,6 +382,9 @@ pub fn CodeEditor(
     }
 }
 
+/// Editor of code in meklang next to the code generated from it in one of [`OUTPUT_LANGUAGES`].
+/// The output language is the language of `output_code_editor_options`, unless it is selected
+/// with `output_language`.
 #[component]
 pub fn CodeEditorWithOutput(
     input_code_editor_options: CodeEditorOptions,
@@ -218,25 +392,74 @@ pub fn CodeEditorWithOutput(
     extra_section_classes: &'static str,
     #[prop(into)] code: ReadSignal<String>,
     #[prop(into)] set_code: WriteSignal<String>,
+    #[prop(optional, into)] output_language: Option<Signal<CodeEditorLanguage>>,
 ) -> impl IntoView {
+    let output_language =
+        output_language.unwrap_or_else(|| Signal::stored(output_code_editor_options.language));
     let (parsed_code, set_parsed_code) = signal(String::new());
     let (parsing_error, set_parsing_error) = signal(String::new());
+    let (error_lines, set_error_lines) = signal(Vec::<usize>::new());
+    let (module_name, set_module_name) = signal(None::<String>);
+    let (copied, set_copied) = signal(false);
+
+    let copy_to_clipboard = move |_| {
+        let promise = window()
+            .navigator()
+            .clipboard()
+            .write_text(&parsed_code.get());
+        wasm_bindgen_futures::spawn_local(async move {
+            match wasm_bindgen_futures::JsFuture::from(promise).await {
+                Ok(_) => {
+                    set_copied.set(true);
+                    set_timeout(move || set_copied.set(false), Duration::from_secs(2));
+                }
+                Err(e) => {
+                    leptos::logging::error!("Failed to copy the generated code: {e:?}");
+                }
+            }
+        });
+    };
 
     Effect::new(move |_| {
-        match meksmith::smith_c::generate_c_code_from_string(code.get().as_str()) {
-            Ok(c_code) => {
-                set_parsed_code.set(c_code);
+        let Some(backend) = output_language.get().backend() else {
+            set_parsing_error.set(format!(
+                "Code cannot be generated in {}",
+                output_language.get().name()
+            ));
+            return;
+        };
+        match meksmith::pipeline::compile(
+            code.get().as_str(),
+            backend,
+            &meksmith::pipeline::CompileOptions::default(),
+        ) {
+            Ok(output) => {
+                set_parsed_code.set(output.code);
                 set_parsing_error.set(String::new());
+                set_error_lines.set(Vec::new());
+                set_module_name.set(output.module_name);
+            }
+            Err(e) => {
+                set_error_lines.set(get_error_lines(&e));
+                set_parsing_error.set(e.to_string());
             }
-            Err(e) => set_parsing_error.set(e),
         }
     });
 
+    let download = move |_| {
+        if let Err(e) = download_file(
+            &get_output_file_name(module_name.get().as_deref(), output_language.get()),
+            &parsed_code.get(),
+        ) {
+            leptos::logging::error!("Failed to download the generated code: {e:?}");
+        }
+    };
+
     view! {
         <section class={extra_section_classes.to_string() + " flex-container flex-row"}>
             <div class="flex-1">
                 <h3>"Input in " <TextWithAnimatedGradient text="meklang" /> </h3>
-                <CodeEditor code_editor_options=input_code_editor_options.clone() code=code set_code=set_code />
+                <CodeEditor code_editor_options=input_code_editor_options.clone() code=code set_code=set_code error_lines />
                 <Show
                     when=move || !parsing_error.get().is_empty()
                 >
@@ -246,13 +469,107 @@ pub fn CodeEditorWithOutput(
                 </Show>
             </div>
             <div class="flex-1">
-                <h3>"Generated output in C"</h3>
-                <CodeEditor code_editor_options=output_code_editor_options.clone() code=parsed_code set_code=set_parsed_code />
+                <div class="code-editor-header">
+                    <h3>{move || format!("Generated output in {}", output_language.get().name())}</h3>
+                    <div>
+                        <button
+                            class="common-button"
+                            disabled=move || !parsing_error.get().is_empty()
+                            on:click=copy_to_clipboard
+                        >
+                            {move || if copied.get() { "Copied!" } else { "Copy" }}
+                        </button>
+                        " "
+                        <button
+                            class="common-button"
+                            disabled=move || !parsing_error.get().is_empty()
+                            on:click=download
+                        >
+                            "Download"
+                        </button>
+                    </div>
+                </div>
+                {move || {
+                    let code_editor_options = CodeEditorOptions {
+                        language: output_language.get(),
+                        ..output_code_editor_options.clone()
+                    };
+                    view! {
+                        <CodeEditor code_editor_options code=parsed_code set_code=set_parsed_code />
+                    }
+                }}
             </div>
         </section>
     }
 }
 
+/// Returns the name of the file the code generated in the language is downloaded as, which
+/// is the name of the module of the protocol if it declares one.
+fn get_output_file_name(module_name: Option<&str>, language: CodeEditorLanguage) -> String {
+    format!(
+        "{}.{}",
+        module_name.unwrap_or("protocol"),
+        language.file_extension()
+    )
+}
+
+/// Makes the browser download the content as a file with the given name, by clicking
+/// a temporary `<a download>` element pointing to a blob holding the content. The URL of
+/// the blob is revoked only after the browser had a chance to start the download.
+fn download_file(file_name: &str, content: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(content));
+    let blob_options = web_sys::BlobPropertyBag::new();
+    blob_options.set_type("text/plain");
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &blob_options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let body = document()
+        .body()
+        .ok_or_else(|| JsValue::from_str("document has no body"))?;
+    let anchor = document()
+        .create_element("a")?
+        .unchecked_into::<web_sys::HtmlAnchorElement>();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    body.append_child(&anchor)?;
+    anchor.click();
+    anchor.remove();
+    set_timeout(
+        move || {
+            let _ = web_sys::Url::revoke_object_url(&url);
+        },
+        Duration::ZERO,
+    );
+    Ok(())
+}
+
+/// Returns the numbers of the lines at which errors of parsing the code begin, in ascending
+/// order and without duplicates. Errors found after parsing, e.g. by validation, are not located in the code.
+fn get_error_lines(error: &meksmith::MeksmithError) -> Vec<usize> {
+    let meksmith::MeksmithError::Parse(errors) = error else {
+        return Vec::new();
+    };
+    let mut lines: Vec<usize> = errors.iter().map(|error| error.line).collect();
+    lines.sort_unstable();
+    lines.dedup();
+    lines
+}
+
+/// Same as [`get_line_numbers`], but as HTML marking the numbers of the lines with errors.
+fn get_line_numbers_with_errors(code: &str, error_lines: &[usize]) -> String {
+    get_line_numbers(code)
+        .lines()
+        .enumerate()
+        .map(|(index, number)| {
+            if error_lines.contains(&(index + 1)) {
+                format!(r#"<span class="code-editor-error-line-number">{number}</span>"#) + "\n"
+            } else {
+                number.to_string() + "\n"
+            }
+        })
+        .collect()
+}
+
 /// Returns all line numbers separated by a newline in the given code string.
 /// The number of lines is determined by counting the number of newline characters
 /// in the code, supporting also multiple empty lines. Numbering starts from 1
@@ -279,6 +596,9 @@ enum CodeEditorShortcut {
     CtrlX,
     AltDownArrow,
     AltUpArrow,
+    CtrlZ,
+    CtrlShiftZ,
+    CtrlD,
     Other,
 }
 
@@ -288,7 +608,19 @@ impl CodeEditorShortcut {
         event: web_sys::KeyboardEvent,
         textarea_ref: &NodeRef<leptos::html::Textarea>,
         set_code: &WriteSignal<String>,
+        history: &StoredValue<EditHistory>,
     ) {
+        if self.edits_code() {
+            let textarea = textarea_ref.get().unwrap();
+            history.update_value(|history| {
+                history.record(
+                    EditorSnapshot::from_textarea(&textarea),
+                    None,
+                    js_sys::Date::now(),
+                )
+            });
+        }
+
         match self {
             CodeEditorShortcut::Tab => self.tab(event, textarea_ref, set_code),
             CodeEditorShortcut::ShiftTab => self.outdent(event, textarea_ref, set_code),
@@ -297,10 +629,57 @@ impl CodeEditorShortcut {
             CodeEditorShortcut::CtrlX => self.cut_or_remove_line(event, textarea_ref, set_code),
             CodeEditorShortcut::AltDownArrow => self.move_line_down(event, textarea_ref, set_code),
             CodeEditorShortcut::AltUpArrow => self.move_line_up(event, textarea_ref, set_code),
+            CodeEditorShortcut::CtrlZ => self.undo(event, textarea_ref, set_code, history),
+            CodeEditorShortcut::CtrlShiftZ => self.redo(event, textarea_ref, set_code, history),
+            CodeEditorShortcut::CtrlD => self.duplicate_line(event, textarea_ref, set_code),
             CodeEditorShortcut::Other => {}
         }
     }
 
+    /// Returns whether the shortcut changes the code, so that it can be undone.
+    fn edits_code(&self) -> bool {
+        !matches!(
+            self,
+            CodeEditorShortcut::CtrlZ | CodeEditorShortcut::CtrlShiftZ | CodeEditorShortcut::Other
+        )
+    }
+
+    fn undo(
+        &self,
+        event: web_sys::KeyboardEvent,
+        textarea_code_ref: &NodeRef<leptos::html::Textarea>,
+        set_code: &WriteSignal<String>,
+        history: &StoredValue<EditHistory>,
+    ) {
+        event.prevent_default();
+        let textarea = textarea_code_ref.get().unwrap();
+        let current = EditorSnapshot::from_textarea(&textarea);
+        if let Some(snapshot) = history
+            .try_update_value(|history| history.undo(current))
+            .flatten()
+        {
+            snapshot.restore(&textarea, set_code);
+        }
+    }
+
+    fn redo(
+        &self,
+        event: web_sys::KeyboardEvent,
+        textarea_code_ref: &NodeRef<leptos::html::Textarea>,
+        set_code: &WriteSignal<String>,
+        history: &StoredValue<EditHistory>,
+    ) {
+        event.prevent_default();
+        let textarea = textarea_code_ref.get().unwrap();
+        let current = EditorSnapshot::from_textarea(&textarea);
+        if let Some(snapshot) = history
+            .try_update_value(|history| history.redo(current))
+            .flatten()
+        {
+            snapshot.restore(&textarea, set_code);
+        }
+    }
+
     fn tab(
         &self,
         event: web_sys::KeyboardEvent,
@@ -454,6 +833,24 @@ impl CodeEditorShortcut {
         });
     }
 
+    fn duplicate_line(
+        &self,
+        event: web_sys::KeyboardEvent,
+        textarea_code_ref: &NodeRef<leptos::html::Textarea>,
+        set_code: &WriteSignal<String>,
+    ) {
+        event.prevent_default();
+        with_textarea(textarea_code_ref, |textarea, start, end, value| {
+            let (new_value, new_start, new_end) = duplicate_lines(&value, start, end);
+
+            set_code.set(new_value.clone());
+            textarea.set_value(&new_value);
+            textarea
+                .set_selection_range(new_start as u32, new_end as u32)
+                .unwrap();
+        });
+    }
+
     fn move_line_up(
         &self,
         event: web_sys::KeyboardEvent,
@@ -515,11 +912,147 @@ impl From<web_sys::KeyboardEvent> for CodeEditorShortcut {
             (CTRL, NO_ALT, NO_SHIFT, "x") => CodeEditorShortcut::CtrlX,
             (NO_CTRL, ALT, NO_SHIFT, "ArrowDown") => CodeEditorShortcut::AltDownArrow,
             (NO_CTRL, ALT, NO_SHIFT, "ArrowUp") => CodeEditorShortcut::AltUpArrow,
+            (CTRL, NO_ALT, NO_SHIFT, "z") => CodeEditorShortcut::CtrlZ,
+            (CTRL, NO_ALT, SHIFT, "z" | "Z") => CodeEditorShortcut::CtrlShiftZ,
+            (CTRL, NO_ALT, _, "d" | "D") => CodeEditorShortcut::CtrlD,
             _ => CodeEditorShortcut::Other,
         }
     }
 }
 
+/// Duplicates the lines covered by the selection from `start` to `end` below them, returning
+/// the new code and the selection moved onto the copy. A selection ending at the start of
+/// a line does not cover it. Offsets are in UTF-16 code units, as in the selection of
+/// a textarea.
+fn duplicate_lines(value: &str, start: usize, end: usize) -> (String, usize, usize) {
+    let start = utf16_to_byte_offset(value, start);
+    let end = utf16_to_byte_offset(value, end);
+    let last = if end > start && value[..end].ends_with('\n') {
+        end - 1
+    } else {
+        end
+    };
+    let line_start = value[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = value[last..].find('\n').map_or(value.len(), |i| last + i);
+    let lines = &value[line_start..line_end];
+
+    let mut new_value = value.to_string();
+    new_value.insert_str(line_end, &format!("\n{lines}"));
+
+    let offset = lines.len() + 1;
+    let new_start = byte_to_utf16_offset(&new_value, start + offset);
+    let new_end = byte_to_utf16_offset(&new_value, end + offset);
+    (new_value, new_start, new_end)
+}
+
+/// Returns the offset in bytes of the character at the offset in UTF-16 code units, clamped
+/// to the length of the value.
+fn utf16_to_byte_offset(value: &str, offset: usize) -> usize {
+    let mut utf16_offset = 0;
+    for (byte_offset, character) in value.char_indices() {
+        if utf16_offset >= offset {
+            return byte_offset;
+        }
+        utf16_offset += character.len_utf16();
+    }
+    value.len()
+}
+
+/// Returns the offset in UTF-16 code units of the character at the offset in bytes.
+fn byte_to_utf16_offset(value: &str, offset: usize) -> usize {
+    value[..offset].encode_utf16().count()
+}
+
+/// Code and selection of the editor at some moment, restored by undoing and redoing edits.
+#[derive(Clone, Debug, PartialEq)]
+struct EditorSnapshot {
+    code: String,
+    selection_start: usize,
+    selection_end: usize,
+}
+
+impl EditorSnapshot {
+    fn from_textarea(textarea: &web_sys::HtmlTextAreaElement) -> Self {
+        EditorSnapshot {
+            code: textarea.value(),
+            selection_start: textarea.selection_start().unwrap_or(Some(0)).unwrap_or(0) as usize,
+            selection_end: textarea.selection_end().unwrap_or(Some(0)).unwrap_or(0) as usize,
+        }
+    }
+
+    fn restore(&self, textarea: &web_sys::HtmlTextAreaElement, set_code: &WriteSignal<String>) {
+        set_code.set(self.code.clone());
+        textarea.set_value(&self.code);
+        textarea
+            .set_selection_range(self.selection_start as u32, self.selection_end as u32)
+            .unwrap();
+    }
+}
+
+/// Undo and redo stacks of the editor, holding snapshots taken before edits. The editor keeps
+/// its own history, as the native one of the browser does not know of the edits made by
+/// the shortcuts. Edits of the same kind made in quick succession, e.g. typing a word, are
+/// coalesced into a single step.
+#[derive(Clone, Debug, Default)]
+struct EditHistory {
+    undo_stack: Vec<EditorSnapshot>,
+    redo_stack: Vec<EditorSnapshot>,
+    /// Kind and time of the last edit, if it can be coalesced with the next one.
+    last_edit: Option<(String, f64)>,
+}
+
+impl EditHistory {
+    /// Longest pause in milliseconds between edits coalesced into a single step.
+    const COALESCING_INTERVAL_MS: f64 = 1000.0;
+    /// Number of steps which can be undone, older steps are forgotten.
+    const MAX_STEPS: usize = 200;
+
+    /// Records the snapshot taken before an edit made at the time in milliseconds. The kind of
+    /// the edit, e.g. the input type of its event, is `None` for edits never coalesced.
+    fn record(&mut self, before: EditorSnapshot, kind: Option<&str>, time: f64) {
+        let coalesced = match (&self.last_edit, kind) {
+            (Some((last_kind, last_time)), Some(kind)) => {
+                last_kind == kind && time - last_time <= Self::COALESCING_INTERVAL_MS
+            }
+            _ => false,
+        };
+        if !coalesced {
+            self.undo_stack.push(before);
+            if self.undo_stack.len() > Self::MAX_STEPS {
+                self.undo_stack.remove(0);
+            }
+        }
+        self.redo_stack.clear();
+        self.last_edit = kind.map(|kind| (kind.to_string(), time));
+    }
+
+    /// Returns the snapshot to restore to undo the last step, if there is one. Snapshots of
+    /// edits which did not change the code, e.g. moving the last line down, are skipped.
+    fn undo(&mut self, current: EditorSnapshot) -> Option<EditorSnapshot> {
+        let snapshot = Self::pop_changed(&mut self.undo_stack, &current.code)?;
+        self.redo_stack.push(current);
+        self.last_edit = None;
+        Some(snapshot)
+    }
+
+    /// Returns the snapshot to restore to redo the last undone step, if there is one.
+    fn redo(&mut self, current: EditorSnapshot) -> Option<EditorSnapshot> {
+        let snapshot = Self::pop_changed(&mut self.redo_stack, &current.code)?;
+        self.undo_stack.push(current);
+        self.last_edit = None;
+        Some(snapshot)
+    }
+
+    fn pop_changed(stack: &mut Vec<EditorSnapshot>, code: &str) -> Option<EditorSnapshot> {
+        while let Some(snapshot) = stack.pop() {
+            if snapshot.code != code {
+                return Some(snapshot);
+            }
+        }
+        None
+    }
+}
+
 fn with_textarea<Function: FnOnce(web_sys::HtmlTextAreaElement, usize, usize, String)>(
     textarea_ref: &NodeRef<leptos::html::Textarea>,
     function: Function,
@@ -547,6 +1080,186 @@ mod tests {
         assert_eq!(options.get_formatted_size(), "width: 800px; height: 600px;");
     }
 
+    #[test]
+    fn test_highlight_meklang_numbers_and_operators() {
+        let highlighter = CodeEditorLanguage::Meklang.get_highlighter();
+        assert_eq!(
+            highlighter.highlight("0x1A => value: uint8;"),
+            concat!(
+                r#"<span class="code-editor-highlight-number">0x1A</span> "#,
+                r#"<span class="code-editor-highlight-operator">=&gt;</span> value: "#,
+                r#"<span class="code-editor-highlight-builtin-type">uint8</span>;"#,
+            )
+        );
+        assert_eq!(
+            highlighter.highlight("a = 0b101..42;"),
+            concat!(
+                r#"a = <span class="code-editor-highlight-number">0b101</span>"#,
+                r#"<span class="code-editor-highlight-operator">..</span>"#,
+                r#"<span class="code-editor-highlight-number">42</span>;"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_highlight_meklang_comments_without_nested_spans() {
+        let highlighter = CodeEditorLanguage::Meklang.get_highlighter();
+        assert_eq!(
+            highlighter.highlight("value: uint8; # 0..255 => struct"),
+            concat!(
+                r#"value: <span class="code-editor-highlight-builtin-type">uint8</span>; "#,
+                r#"<span class="code-editor-highlight-comment"># 0..255 =&gt; struct</span>"#,
+            )
+        );
+        assert_eq!(
+            highlighter.highlight("/* 42 */ 7"),
+            concat!(
+                r#"<span class="code-editor-highlight-comment">/* 42 */</span> "#,
+                r#"<span class="code-editor-highlight-number">7</span>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_output_file_name() {
+        assert_eq!(
+            get_output_file_name(None, CodeEditorLanguage::C),
+            "protocol.c"
+        );
+        assert_eq!(
+            get_output_file_name(Some("Telemetry"), CodeEditorLanguage::C),
+            "Telemetry.c"
+        );
+        assert_eq!(
+            get_output_file_name(Some("Telemetry"), CodeEditorLanguage::Swift),
+            "Telemetry.swift"
+        );
+    }
+
+    #[test]
+    fn test_output_languages_generate_code() {
+        let input = "struct Message { field: uint8; };";
+        for language in OUTPUT_LANGUAGES {
+            let backend = language
+                .backend()
+                .unwrap_or_else(|| panic!("{} has no backend", language.name()));
+            let output = meksmith::pipeline::compile(
+                input,
+                backend,
+                &meksmith::pipeline::CompileOptions::default(),
+            )
+            .unwrap();
+            assert!(output.code.contains("Message"));
+        }
+        assert_eq!(CodeEditorLanguage::Meklang.backend(), None);
+    }
+
+    fn snapshot(code: &str) -> EditorSnapshot {
+        EditorSnapshot {
+            code: code.to_string(),
+            selection_start: code.len(),
+            selection_end: code.len(),
+        }
+    }
+
+    #[test]
+    fn test_edit_history_coalesces_typing() {
+        let mut history = EditHistory::default();
+        history.record(snapshot(""), Some("insertText"), 0.0);
+        history.record(snapshot("a"), Some("insertText"), 200.0);
+        history.record(snapshot("ab"), Some("insertText"), 400.0);
+        history.record(snapshot("abc"), Some("insertLineBreak"), 600.0);
+        history.record(snapshot("abc\n"), Some("insertText"), 3000.0);
+
+        assert_eq!(history.undo(snapshot("abc\nd")), Some(snapshot("abc\n")));
+        assert_eq!(history.undo(snapshot("abc\n")), Some(snapshot("abc")));
+        assert_eq!(history.undo(snapshot("abc")), Some(snapshot("")));
+        assert_eq!(history.undo(snapshot("")), None);
+    }
+
+    #[test]
+    fn test_edit_history_never_coalesces_shortcuts() {
+        let mut history = EditHistory::default();
+        history.record(snapshot("a"), None, 0.0);
+        history.record(snapshot("\ta"), None, 100.0);
+
+        assert_eq!(history.undo(snapshot("\t\ta")), Some(snapshot("\ta")));
+        assert_eq!(history.undo(snapshot("\ta")), Some(snapshot("a")));
+    }
+
+    #[test]
+    fn test_edit_history_redo() {
+        let mut history = EditHistory::default();
+        history.record(snapshot("a"), None, 0.0);
+        history.record(snapshot("ab"), None, 100.0);
+
+        assert_eq!(history.undo(snapshot("abc")), Some(snapshot("ab")));
+        assert_eq!(history.undo(snapshot("ab")), Some(snapshot("a")));
+        assert_eq!(history.redo(snapshot("a")), Some(snapshot("ab")));
+        assert_eq!(history.redo(snapshot("ab")), Some(snapshot("abc")));
+        assert_eq!(history.redo(snapshot("abc")), None);
+
+        history.undo(snapshot("abc"));
+        history.record(snapshot("ab"), Some("insertText"), 200.0);
+        assert_eq!(history.redo(snapshot("abx")), None);
+    }
+
+    #[test]
+    fn test_edit_history_skips_unchanged_code() {
+        let mut history = EditHistory::default();
+        history.record(snapshot("a"), None, 0.0);
+        history.record(snapshot("ab"), None, 100.0);
+
+        assert_eq!(history.undo(snapshot("ab")), Some(snapshot("a")));
+    }
+
+    #[test]
+    fn test_duplicate_middle_line() {
+        let code = "first\nsecond\nthird";
+        let cursor = code.find("cond").unwrap();
+        let (new_code, start, end) = duplicate_lines(code, cursor, cursor);
+        assert_eq!(new_code, "first\nsecond\nsecond\nthird");
+        assert_eq!((start, end), (cursor + 7, cursor + 7));
+        assert_eq!(&new_code[start..], "cond\nthird");
+    }
+
+    #[test]
+    fn test_duplicate_lines() {
+        let cases = [
+            // Code, selection, and the expected code and selection after duplicating
+            ("a\nb", (0, 0), "a\na\nb", (2, 2)),
+            ("a\nbc", (3, 3), "a\nbc\nbc", (6, 6)),
+            ("abc\nd", (1, 2), "abc\nabc\nd", (5, 6)),
+            ("a\nb\nc", (0, 3), "a\nb\na\nb\nc", (4, 7)),
+            ("a\nb\nc", (0, 2), "a\na\nb\nc", (2, 4)),
+            ("", (0, 0), "\n", (1, 1)),
+        ];
+        for (code, (start, end), expected_code, (expected_start, expected_end)) in cases {
+            assert_eq!(
+                duplicate_lines(code, start, end),
+                (expected_code.to_string(), expected_start, expected_end),
+                "duplicating {start}..{end} of {code:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_duplicate_lines_with_multi_byte_characters() {
+        let cases = [
+            // Code, selection, and the expected code and selection after duplicating
+            ("zażółć\nb", (3, 3), "zażółć\nzażółć\nb", (10, 10)),
+            ("a\n😀b", (4, 4), "a\n😀b\n😀b", (8, 8)),
+            ("😀\nb\nc", (0, 4), "😀\nb\n😀\nb\nc", (5, 9)),
+        ];
+        for (code, (start, end), expected_code, (expected_start, expected_end)) in cases {
+            assert_eq!(
+                duplicate_lines(code, start, end),
+                (expected_code.to_string(), expected_start, expected_end),
+                "duplicating {start}..{end} of {code:?}"
+            );
+        }
+    }
+
     #[test]
     fn test_get_line_numbers() {
         assert_eq!(get_line_numbers(""), "1\n");
@@ -559,6 +1272,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_error_lines() {
+        let compile = |code: &str| {
+            meksmith::pipeline::compile(
+                code,
+                meksmith::pipeline::Backend::C,
+                &meksmith::pipeline::CompileOptions::default(),
+            )
+            .unwrap_err()
+        };
+        assert_eq!(
+            get_error_lines(&compile("struct A { a: Missing; };")),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            get_error_lines(&compile(
+                "struct A {\n    a: uint8\n};\nenum B { x = ; };\n"
+            )),
+            vec![3, 4]
+        );
+        let parse_error = |line: usize| meksmith::ParseError {
+            message: "unexpected token".to_string(),
+            line,
+            column: 1,
+            span: 0..1,
+            source_name: None,
+        };
+        assert_eq!(
+            get_error_lines(&meksmith::MeksmithError::Parse(vec![
+                parse_error(4),
+                parse_error(2),
+                parse_error(4),
+                parse_error(2),
+            ])),
+            vec![2, 4]
+        );
+    }
+
+    #[test]
+    fn test_get_line_numbers_with_errors() {
+        assert_eq!(get_line_numbers_with_errors("a\nb", &[]), "1\n2\n");
+        assert_eq!(
+            get_line_numbers_with_errors("a\nb\nc", &[2]),
+            "1\n<span class=\"code-editor-error-line-number\">2</span>\n3\n"
+        );
+    }
+
     #[test]
     fn test_get_line_numbers_with_multiple_empty_lines() {
         assert_eq!(get_line_numbers("\n\n\n\n\n"), "1\n2\n3\n4\n5\n6\n");
@@ -3,53 +3,72 @@ use leptos::prelude::*;
 use crate::components::code_editor::{CodeEditor, CodeEditorLanguage, CodeEditorOptions};
 use crate::components::text::TextWithAnimatedGradient;
 
-const MEKLANG_BNF_GRAMMAR: &str = r#"<protocol> ::= (<definition> | <comment>)+
-<comment> ::= '#' <text> '\n'
+const MEKLANG_BNF_GRAMMAR: &str = r#"<protocol> ::= <comment>* [<module_header>] (<definition> | <comment>)+
+<comment> ::= '#' <text> '\n' | '/*' <text> '*/'
+<module_header> ::= 'module' <identifier> ['version' <unsigned_integer>] <semicolon>
 <definition> ::=
       <enumeration_definition>
     | <structure_definition>
     | <union_definition>
+    | <oneof_definition>
     | <type_definition>
+    | <flags_definition>
+    | <constant_definition>
 
-<enumeration_definition> ::= 'enum' <identifier> <left_brace> <enumeration_field>+ <right_brace> <semicolon>
+<enumeration_definition> ::= [<attributes>] 'enum' <identifier> [<colon> <builtin_type>] <left_brace> <enumeration_field>+ <right_brace> <semicolon>
 <enumeration_field> ::= <identifier> <equal> (<unsigned_integer> | <range>) <semicolon>
 
-<structure_definition> ::= 'struct' <identifier> <left_brace> <structure_field>+ <right_brace> <semicolon>
+<structure_definition> ::= [<attributes>] 'struct' <identifier> <left_brace> <structure_field>+ <right_brace> <semicolon>
 <structure_field> ::= [<attributes>] <identifier> <colon> <type_identifier> <semicolon>
 
-<union_definition> ::= 'union' <identifier> <left_brace> <union_field>+ <right_brace> <semicolon>
-<union_field> ::= (<unsigned_integer> | <range>) <maps_to> <identifier> <colon> <type_identifier> <semicolon>
+<union_definition> ::= [<attributes>] 'union' <identifier> <left_brace> <union_field>+ <right_brace> <semicolon>
+<union_field> ::= <comment>* (<unsigned_integer> | <range>) <maps_to> <identifier> <colon> <type_identifier> <semicolon>
+
+<oneof_definition> ::= [<attributes>] 'oneof' <identifier> <left_brace> <oneof_field>+ <right_brace> <semicolon>
+<oneof_field> ::= <comment>* <identifier> <colon> <type_identifier> <semicolon>
 
 <attribute> ::=
       'discriminated_by' <equal> <identifier>
     | 'bits' <equal> <unsigned_integer>
     | 'bytes' <equal> <unsigned_integer>
+    | 'id' <equal> <unsigned_integer>
+    | 'c_type' <equal> <string_literal>
+    | 'repr' <equal> <repr>
+    | 'assert' <equal> <string_literal>
+    | 'length_of' <equal> <identifier>
+    | 'length' <equal> <identifier>
+<repr> ::= 'c' | 'c_packed' | 'transparent'
 <attribute_tail> ::= <comma> <attribute>
 <attributes> ::= <left_bracket> <attribute> <attribute_tail>* <right_bracket>
 
-<type_definition> ::= 'using' <identifier> <equal> <type_identifier> <semicolon>
+<type_definition> ::= [<attributes>] 'using' <identifier> <equal> <type_identifier> <semicolon>
+
+<flags_definition> ::= [<attributes>] 'flags' <identifier> <colon> <unsigned_integer_type> <left_brace> <flags_field>+ <right_brace> <semicolon>
+<flags_field> ::= <comment>* <identifier> <equal> <unsigned_integer> <semicolon>
+
+<constant_definition> ::= 'const' <identifier> <equal> <unsigned_integer> <semicolon>
 
 <type_identifier> ::=
-      <builtin_type>
+      <string_type>
+    | <builtin_type>
     | <user_defined_type>
-    | <static_array_type>
-    | <dynamic_array_type>
+    | <array_type>
 
 <builtin_type> ::=
       'int8' | 'int16' | 'int32' | 'int64'
     | 'uint8' | 'uint16' | 'uint32' | 'uint64'
     | 'float32' | 'float64'
-    | 'bit' | 'byte'
+    | 'bit' | 'byte' | 'char' | 'void'
+<unsigned_integer_type> ::= 'uint8' | 'uint16' | 'uint32' | 'uint64'
+<string_type> ::= 'string' [<left_bracket> (<unsigned_integer> | <identifier>) <right_bracket>]
 <user_defined_type> ::= <identifier>
-<static_array_type> ::=
-      <builtin_type> <left_bracket> <unsigned_integer> <right_bracket>
-    | <user_defined_type> <left_bracket> <unsigned_integer> <right_bracket>
-<dynamic_array_type> ::=
-      <builtin_type> <left_bracket> <right_bracket>
-    | <user_defined_type> <left_bracket> <right_bracket>
+<array_type> ::= (<builtin_type> | <user_defined_type>) <array_dimension>+
+<array_dimension> ::= <left_bracket> [<array_size>] <right_bracket>
+<array_size> ::= <unsigned_integer> | <identifier> | 'bytes' <colon> <unsigned_integer>
 
 <range> ::= <unsigned_integer> <double_dot> <unsigned_integer>
 <identifier> ::= [a-zA-Z_][a-zA-Z0-9_]*
+<string_literal> ::= '"' [^"\n]* '"'
 
 <unsigned_integer> ::= <hexadecimal> | <binary> | <decimal>
 <hexadecimal> ::= "0x" [0-9a-fA-F]+
@@ -69,33 +88,70 @@ const MEKLANG_BNF_GRAMMAR: &str = r#"<protocol> ::= (<definition> | <comment>)+
 <comma> ::= ','
 <double_dot> ::= '..'"#;
 
-const MEKLANG_BUILTIN_TYPES: &str = r#"int8, int16, int32, int64,
-uint8, uint16, uint32, uint64,
-float32, float64,
-bit, byte"#;
-
 const MEKLANG_STRUCTURE_EXAMPLE: &str = r#"struct StructureName {
     first_field: uint8;
     second_field: int16;
     third_field: bit;
     fourth_field: int64[2];
+    fifth_field: uint8[4][4];
+    sixth_field: byte[];
 };"#;
 
 const MEKLANG_ENUMERATION_EXAMPLE: &str = r#"enum EnumerationName {
     single_value = 1;
     another_single_value = 2;
     range_of_values = 3..10;
+};
+
+enum EnumerationWithBaseType : uint16 {
+    single_value = 1;
 };"#;
 
 const MEKLANG_UNION_EXAMPLE: &str = r#"union UnionName {
     0 => first_field: uint8;
     1 => second_field: int16;
     2 => third_field: bit;
+};
+
+oneof OneofName {
+    first_field: uint8;
+    second_field: int16;
+};"#;
+
+const MEKLANG_FLAGS_EXAMPLE: &str = r#"flags FlagsName : uint8 {
+    first_flag = 0x01;
+    second_flag = 0x02;
+    third_flag = 0x04;
+};"#;
+
+const MEKLANG_CONSTANT_EXAMPLE: &str = r#"const MAX_LEN = 256;
+
+struct StructureName {
+    data: byte[MAX_LEN];
+};"#;
+
+const MEKLANG_TEXT_EXAMPLE: &str = r#"struct Person {
+    initial: char;
+    name: string[16];
+    biography: string;
+};"#;
+
+const MEKLANG_RESERVED_EXAMPLE: &str = r#"struct Header {
+    version: uint8;
+    _reserved: uint8[3];
+    [bits=4] _padding: uint8;
+    [bits=4] flags: uint8;
 };"#;
 
 const MEKLANG_ATTRIBUTES_EXAMPLE: &str = r#"[discriminated_by=name_of_field]
 [bits=size_in_bits]
-[bytes=size_in_bytes]"#;
+[bytes=size_in_bytes]
+[id=wire_identifier]
+[c_type="c_type_name"]
+[repr=c_packed]
+[assert="value <= 100"]
+[length_of=name_of_dynamic_array_field]
+[length=name_of_integer_field]"#;
 
 const MEKLANG_DISCRIMINATED_BY_ATTRIBUTE_EXAMPLE: &str = r#"structure StructureName {
     my_field: uint8;
@@ -123,33 +179,49 @@ pub fn Cheatsheet() -> impl IntoView {
             </div>
             <div class="flex-1">
                 <div class="documentation-grid">
-                    <CheatsheetBoxWithCode
-                        title="built-in types"
-                        description="There are a few supported built-in types, which are appropriately mapped to built-in types of various languages by smiths."
-                        code_example=MEKLANG_BUILTIN_TYPES
-                    />
+                    <CheatsheetBuiltinTypesBox />
                     <CheatsheetBox
                         title="smiths"
-                        description="\"smiths\" are the code generators that produce code in a specific language. Currently, only C is supported, but more languages are planned to be added in the future, such as Rust, Python, C++, Go, and possibly even Wireshark dissectors."
+                        description="\"smiths\" are the code generators that produce code in a specific language. Currently, C, Rust, Kotlin, Swift and Python are supported, along with Protocol Buffers schemas, Kaitai Struct specifications, Wireshark dissectors in Lua and AsciiDoc documentation. More languages are planned to be added in the future, such as C++ and Go."
                     />
                     <CheatsheetBoxWithCode
                         title="structures"
-                        description="Simple structure containing a few fields with different types."
+                        description="Simple structure containing a few fields with different types. Arrays can have a fixed size, be dynamic (prefixed by their length on the wire, unless a preceding field given by the length attribute holds it) or have several dimensions, the first one being the outermost."
                         code_example=MEKLANG_STRUCTURE_EXAMPLE
                     />
                     <CheatsheetBoxWithCode
                         title="enumerations"
-                        description="Enumerations can be defined in a similar way to C language, but they also support ranges of values."
+                        description="Enumerations can be defined in a similar way to C language, but they also support ranges of values. The integer type holding the values on the wire can be given after a colon."
                         code_example=MEKLANG_ENUMERATION_EXAMPLE
                     />
                     <CheatsheetBoxWithCode
                         title="(discriminated) unions"
-                        description="Unions allow you to define a field that can hold different types, similar to C unions. The value before => is the discriminator."
+                        description="Unions allow you to define a field that can hold different types, similar to C unions. The value before => is the discriminator. A oneof is a union with discriminators assigned in order, starting from 0."
                         code_example=MEKLANG_UNION_EXAMPLE
                     />
+                    <CheatsheetBoxWithCode
+                        title="flags"
+                        description="Flags are unsigned integers whose bits are given names. Unlike values of enumerations, the masks of flags can be combined with each other."
+                        code_example=MEKLANG_FLAGS_EXAMPLE
+                    />
+                    <CheatsheetBoxWithCode
+                        title="constants"
+                        description="Constants name unsigned integers, which can be used as sizes of static arrays instead of repeating the same number."
+                        code_example=MEKLANG_CONSTANT_EXAMPLE
+                    />
+                    <CheatsheetBoxWithCode
+                        title="text"
+                        description="Text is an array of char. A string with a size becomes a fixed C array of characters, while a string without one is prefixed by its length on the wire and decoded into a null-terminated char*."
+                        code_example=MEKLANG_TEXT_EXAMPLE
+                    />
+                    <CheatsheetBoxWithCode
+                        title="reserved fields"
+                        description="Fields whose names start with _ are reserved. They are present on the wire, but always encoded as zeros, skipped when decoding and ignored when comparing. The bits and bytes attributes set the width of sub-byte padding."
+                        code_example=MEKLANG_RESERVED_EXAMPLE
+                    />
                     <CheatsheetBoxWithCode
                         title="attributes"
-                        description="Structure fields can contain attributes that specify additional properties or behaviors in encoding/decoding."
+                        description="Structure fields and definitions can contain attributes that specify additional properties or behaviors in encoding/decoding."
                         code_example=MEKLANG_ATTRIBUTES_EXAMPLE
                     />
                     <CheatsheetBoxWithCode
@@ -197,6 +269,35 @@ fn CheatsheetBoxWithCode(
     }
 }
 
+#[component]
+fn CheatsheetBuiltinTypesBox() -> impl IntoView {
+    view! {
+        <div class="documentation-box">
+            <h2 class="documentation-box-title">"built-in types"</h2>
+            <p>
+                "There are a few supported built-in types, which are appropriately mapped to built-in types of various languages by smiths."
+            </p>
+            <table class="builtin-types-table">
+                <tr>
+                    <th>"type"</th>
+                    <th>"size in bits"</th>
+                </tr>
+                {meksmith::ast::builtin_types_table()
+                    .into_iter()
+                    .map(|(name, size_in_bits)| {
+                        view! {
+                            <tr>
+                                <td>{name}</td>
+                                <td>{size_in_bits}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </table>
+        </div>
+    }
+}
+
 #[component]
 fn CheatsheetBox(title: &'static str, description: &'static str) -> impl IntoView {
     view! {
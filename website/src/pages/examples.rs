@@ -1,7 +1,9 @@
 use leptos::prelude::*;
 use web_sys::wasm_bindgen::JsCast;
 
-use crate::components::code_editor::{CodeEditorLanguage, CodeEditorOptions, CodeEditorWithOutput};
+use crate::components::code_editor::{
+    CodeEditorLanguage, CodeEditorOptions, CodeEditorWithOutput, OUTPUT_LANGUAGES,
+};
 use crate::components::text::TextWithAnimatedGradient;
 
 /// [`include_example`] macro extracts the contents of a file from the `meksmith/examples`
@@ -38,6 +40,7 @@ static EXAMPLES: &[Example] = &[
 pub fn Examples() -> impl IntoView {
     let (selected_example, set_selected_example) = signal(EXAMPLES[0].clone());
     let (code, set_code) = signal(String::from(selected_example.get().example_code));
+    let (output_language, set_output_language) = signal(OUTPUT_LANGUAGES[0]);
 
     view! {
         <div class="center">
@@ -58,6 +61,7 @@ pub fn Examples() -> impl IntoView {
                 extra_section_classes="w-1600"
                 code
                 set_code
+                output_language
             />
             <div class="flex-container flex-row w-1600">
                 <div class="flex-1">
@@ -76,8 +80,15 @@ pub fn Examples() -> impl IntoView {
                 </div>
                 <div class="flex-1">
                     <label for="language-select" class="common-label">"Output language: "</label>
-                    <select class="common-select" id="language-select" disabled=true>
-                        <option value="c" selected="selected" disabled>"C"</option>
+                    <select class="common-select" id="language-select" on:change=move |event| {
+                        let selected_value = event.target().unwrap().unchecked_into::<web_sys::HtmlSelectElement>().value();
+                        if let Some(language) = OUTPUT_LANGUAGES.iter().find(|l| l.name() == selected_value) {
+                            set_output_language.set(*language);
+                        }
+                    }>
+                        { OUTPUT_LANGUAGES.iter().map(|language| view! {
+                            <option value=language.name()>{ language.name() }</option>
+                        }).collect_view() }
                     </select>
                 </div>
             </div>